@@ -0,0 +1,500 @@
+//<***************Arbitrary precision*****************>//
+//For --precision big:BITS, zooms past where even double-doubles (see
+//double.rs) run out. Every number is a num::BigInt n standing for the fixed
+//point value n / 2^BITS : the points the iteration goes through all lie
+//within |z| <= 2 until they escape, so a fixed point keeps BITS bits after
+//the binary point all the way, which is what a float of that many bits
+//would keep there anyway.
+//
+//The view's corners can't be f64 at such depths (at a width of 1e-30 they
+//would be the same number), so under --precision big the view is laid out
+//around 0,0 and its corners are offsets from the center, which is kept on
+//its own as a Point. --center is read digit by digit straight into it, a
+//decimal of 60 significant digits keeping all of them. The offset of a
+//pixel is an f64 (a fraction of the width, however narrow the width is),
+//and a point is the center plus that offset, exactly.
+//
+//It is slow : each iteration is three BigInt multiplications, each a
+//hundred or more times as long as an f64 one, and nothing but the plain
+//iteration is done this way. --perturbation only iterates the center in
+//BigInts and the pixels as f64 differences from it, see perturbation.rs.
+//
+//Built with --features astro-float the iteration goes through
+//astro-float's BigFloats instead, of BITS + 2 bits so that they keep at
+//least the BITS bits after the binary point the fixed point does out to
+//|z| = 4, and more the closer z is to 0. The points are still read and laid
+//out in fixed point, which holds them exactly, and turned into BigFloats
+//as they are, so only the iteration rounds any differently : the counts
+//are the same but by the edge of the set. Z (below) is the one part that
+//differs between the two.
+
+use num::{BigInt, Complex, Float, One, Signed, ToPrimitive};
+#[cfg(not(feature = "astro-float"))]
+use num::Zero;
+use std::ops::ControlFlow::{Break, Continue};
+#[cfg(not(feature = "astro-float"))]
+use std::str::FromStr;
+#[cfg(feature = "astro-float")]
+use astro_float::{BigFloat, RoundingMode, Sign, Word, WORD_BIT_SIZE};
+
+use crate::config::View;
+use crate::{in_stretches, normalize, pixel_to_point, render_bands_within, rows_of, split_pair,
+            Budget, Renderer};
+
+//A point in fixed point, `re` and `im` over 2^bits
+#[derive(Clone, Debug, PartialEq)]
+pub struct Point {
+    pub re : BigInt,
+    pub im : BigInt,
+    pub bits : usize
+}
+
+impl Point {
+    //`text` as --center takes it if it is a pair of decimals, such as
+    //-1.7400623825793399052208441670658,0.0281762003157540, read to `bits`
+    //bits without going through f64. None for any other form of point.
+    pub fn parse(text : &str, bits : usize) -> Option<Point> {
+        let (re, im) = split_pair(&normalize(text), ',').ok()?;
+        Some(Point { re : decimal(&re, bits)?, im : decimal(&im, bits)?, bits })
+    }
+
+    //A point given as f64, which it holds exactly
+    pub fn of(point : Complex<f64>, bits : usize) -> Point {
+        Point { re : fixed(point.re, bits), im : fixed(point.im, bits), bits }
+    }
+
+    //This point moved by `offset`, exactly but for what falls below 2^-bits
+    pub fn offset_by(&self, offset : Complex<f64>) -> Point {
+        let offset = Point::of(offset, self.bits);
+        Point { re : offset.re + &self.re, im : offset.im + &self.im, ..offset }
+    }
+
+    //To the nearest f64, for the log and the checks on the view
+    pub fn rounded(&self) -> Complex<f64> {
+        Complex { re : rounded(&self.re, self.bits), im : rounded(&self.im, self.bits) }
+    }
+
+    //As many decimal places as the bits are worth, for the log
+    pub fn show(&self) -> String {
+        format!("{},{}", show(&self.re, self.bits), show(&self.im, self.bits))
+    }
+}
+
+//A decimal such as -0.74364388703715870475e-2 to the nearest multiple of
+//2^-bits, None if `text` isn't one
+fn decimal(text : &str, bits : usize) -> Option<BigInt> {
+    let (mantissa, exponent) = match text.find(['e', 'E']) {
+        Some(index) => (&text[..index], text[index + 1..].parse::<i64>().ok()?),
+        None => (text, 0)
+    };
+    let (negative, mantissa) = match mantissa.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, mantissa.strip_prefix('+').unwrap_or(mantissa))
+    };
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = format!("{}{}", whole, fraction);
+    if digits.is_empty() || !digits.bytes().all(|digit| digit.is_ascii_digit()) {
+        return None;
+    }
+    //digits * 10^exponent * 2^bits, rounded to the nearest whole number
+    let exponent = exponent - fraction.len() as i64;
+    if exponent.unsigned_abs() > 100_000 {
+        return None;
+    }
+    let mut value = BigInt::parse_bytes(digits.as_bytes(), 10)? << bits;
+    let power = num::pow(BigInt::from(10), exponent.unsigned_abs() as usize);
+    value = match exponent >= 0 {
+        true => value * power,
+        false => (value * 2 + &power) / (power * 2)
+    };
+    Some(if negative { -value } else { value })
+}
+
+//`value` * 2^bits, exactly (an f64 being a whole number times a power of
+//two) but for what falls below 2^-bits
+fn fixed(value : f64, bits : usize) -> BigInt {
+    let (mantissa, exponent, sign) = value.integer_decode();
+    let mantissa = BigInt::from(mantissa as i64 * sign as i64);
+    let shift = exponent as i64 + bits as i64;
+    match shift >= 0 {
+        true => mantissa << shift as usize,
+        false => mantissa >> (-shift) as usize
+    }
+}
+
+fn rounded(value : &BigInt, bits : usize) -> f64 {
+    //Down to the top 64 bits first, a BigInt of thousands of bits being
+    //out of f64's range
+    let spare = value.bits().saturating_sub(64);
+    let top = (value >> spare).to_f64().unwrap_or(0.0);
+    top * 2f64.powi(spare as i32 - bits as i32)
+}
+
+fn show(value : &BigInt, bits : usize) -> String {
+    let places = (bits as f64 * 2f64.log10()).floor() as usize;
+    let half = BigInt::one() << (bits - 1);
+    let scaled = (value.abs() * num::pow(BigInt::from(10), places) + half) >> bits;
+    let digits = format!("{:0>width$}", scaled.to_str_radix(10), width = places + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - places);
+    let fraction = fraction.trim_end_matches('0');
+    let sign = if value.is_negative() { "-" } else { "" };
+    match fraction.is_empty() {
+        true => format!("{}{}", sign, whole),
+        false => format!("{}{}.{}", sign, whole, fraction)
+    }
+}
+
+//What big:BITS iterates in, for the log
+#[cfg(not(feature = "astro-float"))]
+pub const ARITHMETIC : &str = "BigInt fixed point";
+#[cfg(feature = "astro-float")]
+pub const ARITHMETIC : &str = "astro-float BigFloats";
+
+//Where the orbit of a point has got to : Z, its squares, which are kept
+//from one iteration to the next where they go into the new Z after having
+//been checked against the radius, and C, every number over 2^bits
+#[cfg(not(feature = "astro-float"))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Z {
+    c : Point,
+    re : BigInt,
+    im : BigInt,
+    square_re : BigInt,
+    square_im : BigInt,
+    four : BigInt
+}
+
+#[cfg(not(feature = "astro-float"))]
+impl Z {
+    //Z(0) = 0 on the way to the orbit of `c`
+    fn start(c : &Point) -> Z {
+        Z::at(c, BigInt::zero(), BigInt::zero())
+    }
+
+    fn at(c : &Point, re : BigInt, im : BigInt) -> Z {
+        let bits = c.bits;
+        Z {
+            square_re : (&re * &re) >> bits,
+            square_im : (&im * &im) >> bits,
+            c : c.clone(),
+            re,
+            im,
+            four : BigInt::from(4) << bits
+        }
+    }
+
+    //On to the next Z, true if it has escaped
+    fn step(&mut self) -> bool {
+        let bits = self.c.bits;
+        let twice_re_im = (&self.re * &self.im) >> (bits - 1);
+        self.re = std::mem::replace(&mut self.square_re, BigInt::zero()) - &self.square_im
+            + &self.c.re;
+        self.im = twice_re_im + &self.c.im;
+        self.square_re = (&self.re * &self.re) >> bits;
+        self.square_im = (&self.im * &self.im) >> bits;
+        &self.square_re + &self.square_im > self.four
+    }
+
+    //To the nearest f64
+    fn point(&self) -> Complex<f64> {
+        Complex { re : rounded(&self.re, self.c.bits), im : rounded(&self.im, self.c.bits) }
+    }
+
+    //The parts of Z as the orbit cache writes them (see orbits.rs), and Z
+    //from them again on the way to the orbit of `c`
+    pub fn parts(&self) -> (String, String) {
+        (self.re.to_string(), self.im.to_string())
+    }
+
+    pub fn from_parts(c : &Point, re : &str, im : &str) -> Option<Z> {
+        Some(Z::at(c, BigInt::from_str(re).ok()?, BigInt::from_str(im).ok()?))
+    }
+}
+
+//The same in BigFloats of precision(bits) bits
+#[cfg(feature = "astro-float")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Z {
+    c : (BigFloat, BigFloat),
+    re : BigFloat,
+    im : BigFloat,
+    square_re : BigFloat,
+    square_im : BigFloat,
+    four : BigFloat,
+    precision : usize
+}
+
+#[cfg(feature = "astro-float")]
+const ROUNDING : RoundingMode = RoundingMode::ToEven;
+
+//The bits of the BigFloats big:`bits` iterates in
+#[cfg(feature = "astro-float")]
+fn precision(bits : usize) -> usize {
+    bits + 2
+}
+
+#[cfg(feature = "astro-float")]
+impl Z {
+    fn start(c : &Point) -> Z {
+        let zero = BigFloat::from_word(0, precision(c.bits));
+        Z::at(c, zero.clone(), zero)
+    }
+
+    fn at(c : &Point, re : BigFloat, im : BigFloat) -> Z {
+        let precision = precision(c.bits);
+        Z {
+            c : (float(&c.re, c.bits), float(&c.im, c.bits)),
+            square_re : re.mul(&re, precision, ROUNDING),
+            square_im : im.mul(&im, precision, ROUNDING),
+            re,
+            im,
+            four : BigFloat::from_word(4, precision),
+            precision
+        }
+    }
+
+    fn step(&mut self) -> bool {
+        let precision = self.precision;
+        let re_im = self.re.mul(&self.im, precision, ROUNDING);
+        self.re = self.square_re.sub(&self.square_im, precision, ROUNDING)
+            .add(&self.c.0, precision, ROUNDING);
+        self.im = re_im.add(&re_im, precision, ROUNDING).add(&self.c.1, precision, ROUNDING);
+        self.square_re = self.re.mul(&self.re, precision, ROUNDING);
+        self.square_im = self.im.mul(&self.im, precision, ROUNDING);
+        let radius = self.square_re.add(&self.square_im, precision, ROUNDING);
+        radius.cmp(&self.four).is_some_and(|order| order > 0)
+    }
+
+    fn point(&self) -> Complex<f64> {
+        Complex { re : float_rounded(&self.re), im : float_rounded(&self.im) }
+    }
+
+    //Each part as its sign, its mantissa's words in hex from the lowest and
+    //its exponent, -8000000000000000_0000000000000000p2, which holds it
+    //exactly
+    pub fn parts(&self) -> (String, String) {
+        let part = |value : &BigFloat| {
+            let (words, _, sign, exponent, _) = value.as_raw_parts().unwrap();
+            let words = words.iter()
+                .map(|word| format!("{:0width$x}", word, width = WORD_BIT_SIZE / 4))
+                .collect::<Vec<_>>();
+            let sign = if sign == Sign::Neg { "-" } else { "" };
+            format!("{}{}p{}", sign, words.join("_"), exponent)
+        };
+        (part(&self.re), part(&self.im))
+    }
+
+    pub fn from_parts(c : &Point, re : &str, im : &str) -> Option<Z> {
+        let part = |text : &str| {
+            let (sign, text) = match text.strip_prefix('-') {
+                Some(rest) => (Sign::Neg, rest),
+                None => (Sign::Pos, text)
+            };
+            let (words, exponent) = text.split_once('p')?;
+            let words = words.split('_')
+                .filter(|word| !word.is_empty())
+                .map(|word| Word::from_str_radix(word, 16).ok())
+                .collect::<Option<Vec<_>>>()?;
+            let value = BigFloat::from_words(&words, sign, exponent.parse().ok()?);
+            (!value.is_nan()).then_some(value)
+        };
+        Some(Z::at(c, part(re)?, part(im)?))
+    }
+}
+
+//`value` over 2^bits as a BigFloat, exactly
+#[cfg(feature = "astro-float")]
+fn float(value : &BigInt, bits : usize) -> BigFloat {
+    let (sign, bytes) = value.to_bytes_le();
+    let words = bytes.chunks(WORD_BIT_SIZE / 8)
+        .map(|chunk| chunk.iter().rev().fold(0, |word : Word, &byte| word << 8 | byte as Word))
+        .collect::<Vec<_>>();
+    let sign = if sign == num::bigint::Sign::Minus { Sign::Neg } else { Sign::Pos };
+    //The words are a fraction below 1 times 2^(their bits)
+    BigFloat::from_words(&words, sign, (words.len() * WORD_BIT_SIZE) as i32 - bits as i32)
+}
+
+//To the nearest f64, from the top word of the mantissa
+#[cfg(feature = "astro-float")]
+fn float_rounded(value : &BigFloat) -> f64 {
+    let (words, _, sign, exponent, _) = value.as_raw_parts().unwrap();
+    let top = words.last().map_or(0.0, |&word| word as f64);
+    let magnitude = top * 2f64.powi(exponent - WORD_BIT_SIZE as i32);
+    if sign == Sign::Neg { -magnitude } else { magnitude }
+}
+
+//The same iteration as escape_time(), in ARITHMETIC
+fn escape_time(c : &Point, limit : u32) -> Option<u32> {
+    in_stretches(0..limit, Z::start(c), |mut z, stretch| {
+        for i in stretch {
+            if z.step() {
+                return Break(i);
+            }
+        }
+        Continue(z)
+    }).break_value()
+}
+
+//The orbit escape_time() takes `c` through, Z(0) = 0 up to the limit or up
+//to where it escaped, each Z to the nearest f64, for --perturbation
+pub fn orbit(c : &Point, limit : u32) -> Vec<Complex<f64>> {
+    let mut orbit = Orbit::new(c);
+    orbit.extend(limit);
+    orbit.points
+}
+
+//An orbit as orbit() works it out, with the Z it has got to in full, so
+//that it can be taken on to a higher limit later (see orbits.rs)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Orbit {
+    pub c : Point,
+    //Z(0) = 0 on, each to the nearest f64
+    pub points : Vec<Complex<f64>>,
+    //The last of them in full
+    pub z : Z,
+    pub escaped : bool
+}
+
+impl Orbit {
+    pub fn new(c : &Point) -> Orbit {
+        Orbit {
+            c : c.clone(),
+            points : vec![Complex { re : 0.0, im : 0.0 }],
+            z : Z::start(c),
+            escaped : false
+        }
+    }
+
+    //Taken on to `limit` iterations, unless it has escaped before then
+    pub fn extend(&mut self, limit : u32) {
+        let from = self.points.len() as u32 - 1;
+        if self.escaped || from >= limit {
+            return;
+        }
+        let points = std::mem::take(&mut self.points);
+        let iterated = in_stretches(from..limit, (self.z.clone(), points),
+                                    |(mut z, mut points), stretch| {
+            for _ in stretch {
+                let escaped = z.step();
+                points.push(z.point());
+                if escaped {
+                    return Break((z, points));
+                }
+            }
+            Continue((z, points))
+        });
+        let ((z, points), escaped) = match iterated {
+            Break(iterated) => (iterated, true),
+            Continue(iterated) => (iterated, false)
+        };
+        self.z = z;
+        self.points = points;
+        self.escaped = escaped;
+    }
+
+    //The orbit up to `limit`, what orbit() gives for it as far as this one
+    //goes
+    pub fn to(&self, limit : u32) -> &[Complex<f64>] {
+        &self.points[..self.points.len().min(limit as usize + 1)]
+    }
+}
+
+//The escape counts of the pixels of `view`, whose corners are offsets from
+//`center`
+pub fn counts(renderer : Option<&Renderer>, budget : Option<&Budget>, view : &View,
+              center : &Point, limit : u32)
+    -> Vec<Option<u32>>
+{
+    let bounds = view.bounds;
+    let mut counts = vec![None;bounds.0 * bounds.1];
+    render_bands_within(renderer, budget, &mut counts, bounds, |band, top| {
+        for row in 0..rows_of(band.len(), top, bounds) {
+            for column in 0..bounds.0 {
+                let point = center.offset_by(pixel_to_point(view, (column, top + row)));
+                band[column + bounds.0 * row] = escape_time(&point, limit);
+            }
+        }
+    });
+    counts
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::cli::{Options, Precision};
+    use crate::config::{self, Config};
+    use crate::render_counts;
+
+    //The Misiurewicz point M(4,1), whose orbit lands on a fixed point after 4
+    //steps, to 60 decimal places. The set branches around it the same way at
+    //every width, 1e-30 as much as 1e-3, so a render that far in has to come
+    //out structured.
+    pub const MISIUREWICZ : &str =
+        "-0.101096363845622161025785445738622565463805442826253483876931,\
+         0.956286510809141500771096057729977435809833336510529170034314";
+    pub const SIZE : (usize, usize) = (64, 48);
+    //Only as far as f64 can tell, on the edge of the set a few pixels go
+    //either way
+    pub const OFF : f64 = 0.01;
+    //Fewer counts than this over the SIZE pixels is no structure
+    pub const STRUCTURED : usize = 20;
+
+    //The view `width` across around MISIUREWICZ, in `precision`
+    pub fn centered(precision : Precision, width : f64, perturbation : bool) -> Config {
+        config::build(&Options {
+            size : Some(SIZE),
+            center : crate::parse_complex(MISIUREWICZ).ok(),
+            center_text : Some(MISIUREWICZ.to_string()),
+            width : Some(width),
+            iterations : 2000,
+            precision,
+            perturbation,
+            ..Options::default()
+        }).unwrap()
+    }
+
+    pub fn differing(a : &[Option<u32>], b : &[Option<u32>]) -> usize {
+        a.iter().zip(b).filter(|(a, b)| a != b).count()
+    }
+
+    //How many different counts there are among `counts`
+    pub fn distinct(counts : &[Option<u32>]) -> usize {
+        let mut counts = counts.to_vec();
+        counts.sort();
+        counts.dedup();
+        counts.len()
+    }
+
+    #[test]
+    fn every_digit_of_the_center_is_read() {
+        let center = Point::parse(MISIUREWICZ, 256).map(|center| center.show());
+        assert_eq!(center.as_deref(), Some(MISIUREWICZ));
+    }
+
+    #[test]
+    fn big_agrees_with_f64_but_for_the_edge_of_the_set() {
+        let whole = Options {
+            size : Some(SIZE),
+            center : Some(Complex { re : -0.5, im : 0.0 }),
+            width : Some(3.0),
+            ..Options::default()
+        };
+        let plain = render_counts(&config::build(&whole).unwrap(), None);
+        let big = render_counts(&config::build(&Options { precision : Precision::Big(160),
+                                                          ..whole }).unwrap(), None);
+        let off = differing(&big, &plain);
+        assert!(off as f64 <= OFF * plain.len() as f64, "{} of {} differ", off, plain.len());
+    }
+
+    #[test]
+    fn the_same_branches_show_1e_30_across_in_any_bits() {
+        let counts = [160, 256].map(|bits| {
+            render_counts(&centered(Precision::Big(bits), 1e-30, false), None)
+        });
+        let off = differing(&counts[0], &counts[1]);
+        assert!(distinct(&counts[0]) >= STRUCTURED, "{} different counts",
+                distinct(&counts[0]));
+        assert!(off as f64 <= OFF * counts[0].len() as f64, "{} of {} differ", off,
+                counts[0].len());
+    }
+}
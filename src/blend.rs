@@ -0,0 +1,186 @@
+//<***************Blended colourings*****************>//
+//For --blend : each pixel's colour mixed from two colourings with a weight
+//each, smooth iteration counts for the glow outside the set and an orbit
+//trap for the filaments inside it and along its edge :
+//
+//    --blend smooth:0.7,orbit-trap:0.3
+//
+//A source left out has no weight, and the weights are divided by their sum
+//so only their ratio counts (smooth:1 is the smooth colouring on its own).
+//The two colours are mixed in linear light, through the sRGB transfer
+//function of palette.rs, and encoded again, which keeps a half and half
+//mix of black and white at the gray the eye sees halfway rather than the
+//darker 128.
+//
+//One pass of the iteration gives both, see escape_blend() : it goes on
+//past the escape radius of 2 to SMOOTH_RADIUS for the smooth count
+//
+//    n + 1 - log2(ln |z|)
+//
+//which runs on from one whole count to the next without steps, and keeps
+//how close z came to the trap, the real and imaginary axes, up to where it
+//escaped. Each source goes through --palette if there is one (the smooth
+//count between the colours of the counts either side of it, the trap
+//from the start of the palette where an orbit touched an axis to its end
+//TRAP_REACH away) or to a gray : the smooth count light to dark like the
+//plain render, the trap bright where an orbit came close. Interior points
+//have no smooth count and are black in that source, but the trap colours
+//them as well.
+
+use num::Complex;
+use std::ops::ControlFlow::{Break, Continue};
+
+use crate::config::View;
+use crate::palette::{self, Palette};
+use crate::{in_stretches, pixel_to_point, rows_of};
+
+//How far z goes before the smooth count is taken, far enough out that the
+//count no longer shows the steps of the radius 2 circle
+const SMOOTH_RADIUS : f64 = 256.0;
+
+//How far from an axis an orbit may keep and still be coloured by the trap
+const TRAP_REACH : f64 = 0.25;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Blend {
+    pub smooth : f64,
+    pub trap : f64
+}
+
+impl Blend {
+    //`text` as --blend takes it, SOURCE:WEIGHT pairs separated by commas
+    pub fn parse(text : &str) -> Result<Blend, String> {
+        let mut blend = Blend { smooth : 0.0, trap : 0.0 };
+        let mut given = Vec::new();
+        for part in text.split(',').map(str::trim) {
+            let (source, weight) = part.split_once(':')
+                .ok_or_else(|| format!("\"{}\" isn't SOURCE:WEIGHT", part))?;
+            let weight = weight.trim().parse::<f64>().ok()
+                .filter(|weight| weight.is_finite() && *weight >= 0.0)
+                .ok_or_else(|| format!("the weight of {} has to be a number of at least 0, \
+                                        not \"{}\"", source, weight))?;
+            if given.contains(&source) {
+                return Err(format!("{} is given twice", source));
+            }
+            given.push(source);
+            match source {
+                "smooth" => blend.smooth = weight,
+                "orbit-trap" => blend.trap = weight,
+                _ => return Err(format!("no colouring called \"{}\" (smooth or orbit-trap)",
+                                        source))
+            }
+        }
+        if blend.smooth + blend.trap <= 0.0 {
+            return Err("at least one weight has to be above 0".to_string());
+        }
+        Ok(blend)
+    }
+
+    pub fn name(&self) -> String {
+        format!("smooth:{},orbit-trap:{}", self.smooth, self.trap)
+    }
+}
+
+//The same iteration as escape_time(), going on to SMOOTH_RADIUS : the
+//smooth count (None for a point that doesn't escape) and the least
+//distance from z to an axis while |z| <= 2
+pub fn escape_blend(c : Complex<f64>, limit : u32) -> (Option<f64>, f64) {
+    let z = Complex { re : 0.0, im : 0.0 };
+    let start = (z, f64::INFINITY, false);
+    let iterated = in_stretches(0..limit, start, move |(mut z, mut trap, mut escaped), stretch| {
+        for i in stretch {
+            z = z * z + c;
+            if z.norm_sqr() > SMOOTH_RADIUS * SMOOTH_RADIUS {
+                let count = i as f64 + 1.0 - z.norm().ln().log2();
+                return Break((count.max(0.0), trap));
+            }
+            escaped = escaped || z.norm_sqr() > 4.0;
+            if !escaped {
+                trap = trap.min(z.re.abs().min(z.im.abs()));
+            }
+        }
+        Continue((z, trap, escaped))
+    });
+    let (trap, escaped) = match iterated {
+        Break((count, trap)) => return (Some(count), trap),
+        Continue((_, trap, escaped)) => (trap, escaped)
+    };
+    //Past the limit on the way out to SMOOTH_RADIUS, it escaped at the last
+    let count = match escaped {
+        true => Some(limit as f64),
+        false => None
+    };
+    (count, trap)
+}
+
+//The rows of `pixels` from `top` down, coloured by `blend`
+pub fn render(pixels : &mut [[u8; 3]], top : usize, view : &View, limit : u32, blend : Blend,
+              palette : Option<&Palette>)
+{
+    let bounds = view.bounds;
+    let total = blend.smooth + blend.trap;
+    for row in 0..rows_of(pixels.len(), top, bounds) {
+        for column in 0..bounds.0 {
+            let point = pixel_to_point(view, (column, top + row));
+            let (count, trap) = escape_blend(point, limit);
+            let trap = (trap / TRAP_REACH).min(1.0);
+            let smooth = match (count, palette) {
+                (None, _) => [0, 0, 0],
+                (Some(count), Some(palette)) => palette.between(count),
+                (Some(count), None) => gray(1.0 - count / limit as f64)
+            };
+            let trapped = match palette {
+                Some(palette) => palette.at(trap),
+                None => gray(1.0 - trap)
+            };
+            let mut color = [0; 3];
+            for (channel, value) in color.iter_mut().enumerate() {
+                let light = palette::to_linear(smooth[channel]) * blend.smooth
+                    + palette::to_linear(trapped[channel]) * blend.trap;
+                *value = palette::from_linear(light / total);
+            }
+            pixels[column + bounds.0 * row] = color;
+        }
+    }
+}
+
+//A gray `brightness` (0 to 1) of the way from black to white in its 8-bit
+//value, as the plain gray render has it
+fn gray(brightness : f64) -> [u8; 3] {
+    [(brightness.clamp(0.0, 1.0) * 255.0).round() as u8; 3]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::escape_time;
+
+    #[test]
+    fn smooth_counts_stay_within_a_few_iterations_of_the_escape_counts() {
+        //The smooth count goes on past radius 2 for a few iterations and
+        //takes a little off again
+        const OFF : f64 = 3.0;
+        let (upper_left, lower_right) = (Complex { re : -2.0, im : 1.2 },
+                                         Complex { re : 1.0, im : -1.2 });
+        for i in 0..3000 {
+            let point = upper_left + (lower_right - upper_left) * (i as f64 / 3000.0);
+            match (escape_blend(point, 255).0, escape_time(point, 255)) {
+                (Some(smooth), Some(count)) =>
+                    assert!((smooth - count as f64).abs() <= OFF, "{} for {} at {}", smooth,
+                            count, point),
+                (None, None) => {}
+                (smooth, count) => panic!("{:?} for {:?} at {}", smooth, count, point)
+            }
+        }
+    }
+
+    #[test]
+    fn weights_are_read_and_anything_else_refused() {
+        assert_eq!(Blend::parse("smooth:0.7,orbit-trap:0.3"),
+                   Ok(Blend { smooth : 0.7, trap : 0.3 }));
+        assert_eq!(Blend::parse("orbit-trap:2"), Ok(Blend { smooth : 0.0, trap : 2.0 }));
+        for text in ["smooth:0", "smooth:1,smooth:2", "glow:1", "smooth", ""] {
+            assert!(Blend::parse(text).is_err(), "{:?}", text);
+        }
+    }
+}
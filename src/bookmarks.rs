@@ -0,0 +1,239 @@
+//<***************Bookmarks*****************>//
+//Views saved while exploring, to come back to with --bookmark NAME.
+//--save-bookmark NAME works out the view the options describe and adds it
+//to ~/.local/share/mandelbrot/bookmarks.toml (or under $XDG_DATA_HOME),
+//one [section] per bookmark :
+//
+//    [spiral]
+//    center = "-0.7746806106,0.1374168856"
+//    width = 0.0001
+//    iterations = 2000
+//    mode = "gray"
+//    palette = "fire"
+//    palette-wrap = false
+//    linear-blend = false
+//    gamma = 1
+//    interior-value = 0
+//    exterior = "light-to-dark"
+//
+//The keys are the options of the same names and take the same values, so a
+//bookmark reads like a config file and wins over a preset the same way.
+//center, width, iterations, mode and the gray mapping are always written,
+//rotate for a turned view, band-width for --mode bands, julia for a Julia
+//set and palette when there is one. The format only ever gains keys, so a
+//bookmarks file can be handed to someone else as it is.
+
+use std::path::PathBuf;
+
+use crate::toml::{self, Entry};
+
+pub struct Bookmark {
+    pub name : String,
+    //Keys without their [section]
+    pub entries : Vec<Entry>
+}
+
+fn file() -> Result<PathBuf, String> {
+    let data = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME")
+                 .map(|home| PathBuf::from(home).join(".local").join("share")))
+        .ok_or_else(|| "can't find the bookmarks, neither $XDG_DATA_HOME nor $HOME \
+                        is set".to_string())?;
+    Ok(data.join("mandelbrot").join("bookmarks.toml"))
+}
+
+//The saved bookmarks in the order they were saved, with the file they are in
+pub fn all() -> Result<(PathBuf, Vec<Bookmark>), String> {
+    read(file()?)
+}
+
+fn read(path : PathBuf) -> Result<(PathBuf, Vec<Bookmark>), String> {
+    if !path.exists() {
+        return Ok((path, Vec::new()));
+    }
+    let shown = path.display();
+    let text = std::fs::read_to_string(&path)
+        .map_err(|error| format!("error reading bookmarks {} : {}", shown, error))?;
+    let entries = toml::parse(&text).map_err(|message| format!("{} : {}", shown, message))?;
+
+    let mut bookmarks : Vec<Bookmark> = Vec::new();
+    for entry in entries {
+        let name = entry.section.clone().ok_or_else(|| {
+            format!("{} line {} : '{}' has to be under the [name] of a bookmark",
+                    shown, entry.line, entry.key)
+        })?;
+        let index = match bookmarks.iter().position(|bookmark| bookmark.name == name) {
+            Some(index) => index,
+            None => {
+                bookmarks.push(Bookmark { name, entries : Vec::new() });
+                bookmarks.len() - 1
+            }
+        };
+        bookmarks[index].entries.push(Entry { section : None, ..entry });
+    }
+    Ok((path, bookmarks))
+}
+
+//The entries of the bookmark `name`, with the file they came from
+pub fn find(name : &str) -> Result<(PathBuf, Vec<Entry>), String> {
+    let (path, bookmarks) = all()?;
+    if bookmarks.is_empty() {
+        return Err(format!("unknown bookmark '{}', none have been saved yet \
+                            (see --save-bookmark)", name));
+    }
+    let names = bookmarks.iter().map(|bookmark| bookmark.name.clone()).collect::<Vec<_>>();
+    bookmarks.into_iter()
+        .find(|bookmark| bookmark.name == name)
+        .map(|bookmark| (path, bookmark.entries))
+        .ok_or_else(|| format!("unknown bookmark '{}', expected one of {} \
+                                (see --list-bookmarks)", name, names.join(", ")))
+}
+
+//Adds the `key = value` lines `entries` as the bookmark `name`. A bookmark of
+//that name is only replaced with `force`.
+pub fn save(name : &str, entries : &str, force : bool) -> Result<PathBuf, String> {
+    save_in(file()?, name, entries, force)
+}
+
+fn save_in(path : PathBuf, name : &str, entries : &str, force : bool)
+    -> Result<PathBuf, String>
+{
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric()
+                                                || c == '-' || c == '_') {
+        return Err(format!("bookmark names may only hold letters, digits, '-' and \
+                            '_', got '{}'", name));
+    }
+    let (path, bookmarks) = read(path)?;
+    let shown = path.display();
+    let mut text = if path.exists() {
+        std::fs::read_to_string(&path)
+            .map_err(|error| format!("error reading bookmarks {} : {}", shown, error))?
+    } else {
+        String::new()
+    };
+    if bookmarks.iter().any(|bookmark| bookmark.name == name) {
+        if !force {
+            return Err(format!("there is already a bookmark '{}' in {}, use --force \
+                                to replace it", name, shown));
+        }
+        text = without_section(&text, name);
+    }
+
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+    if !text.is_empty() {
+        text.push('\n');
+    }
+    text.push_str(&format!("[{}]\n{}", name, entries));
+
+    if let Some(directory) = path.parent() {
+        std::fs::create_dir_all(directory)
+            .map_err(|error| format!("error creating {} : {}", directory.display(),
+                                     error))?;
+    }
+    std::fs::write(&path, text)
+        .map_err(|error| format!("error writing bookmarks {} : {}", shown, error))?;
+    Ok(path)
+}
+
+//`text` with the [name] section and its lines taken out, everything else
+//(comments included) left as it was
+fn without_section(text : &str, name : &str) -> String {
+    let header = format!("[{}]", name);
+    let mut inside = false;
+    let mut kept = Vec::new();
+    for line in text.lines() {
+        let content = line.split('#').next().unwrap_or("").trim();
+        if content.starts_with('[') {
+            inside = content == header;
+        }
+        if !inside {
+            kept.push(line);
+        }
+    }
+    let mut kept = kept.join("\n").trim_end().to_string();
+    if !kept.is_empty() {
+        kept.push('\n');
+    }
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::toml::Value;
+
+    //A bookmarks file of its own for each test, under the temp directory
+    fn scratch(test : &str) -> PathBuf {
+        let path = std::env::temp_dir()
+            .join(format!("mandelbrot-bookmarks-{}-{}", std::process::id(), test))
+            .join("bookmarks.toml");
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn keys(bookmark : &Bookmark) -> Vec<(String, Value)> {
+        bookmark.entries.iter().map(|entry| (entry.key.clone(), entry.value.clone())).collect()
+    }
+
+    #[test]
+    fn saved_bookmarks_read_back_in_order() {
+        let path = scratch("order");
+        save_in(path.clone(), "spiral", "center = \"-0.7746806106,0.1374168856\"\n\
+                                         width = 0.0001\niterations = 2000\n", false).unwrap();
+        save_in(path.clone(), "seahorse", "center = \"-0.75,0.1\"\nwidth = 0.05\n",
+                false).unwrap();
+
+        let (read_from, bookmarks) = read(path.clone()).unwrap();
+        assert_eq!(read_from, path);
+        let names = bookmarks.iter().map(|bookmark| bookmark.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["spiral", "seahorse"]);
+        assert_eq!(keys(&bookmarks[0]), [
+            ("center".to_string(), Value::String("-0.7746806106,0.1374168856".to_string())),
+            ("width".to_string(), Value::Number("0.0001".to_string())),
+            ("iterations".to_string(), Value::Number("2000".to_string()))
+        ]);
+        assert!(bookmarks.iter().flat_map(|bookmark| &bookmark.entries)
+                .all(|entry| entry.section.is_none()));
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn a_bookmark_is_only_replaced_with_force() {
+        let path = scratch("force");
+        save_in(path.clone(), "a", "width = 1\n", false).unwrap();
+        save_in(path.clone(), "b", "width = 2\n", false).unwrap();
+        assert!(save_in(path.clone(), "a", "width = 3\n", false).is_err());
+        assert_eq!(keys(&read(path.clone()).unwrap().1[0])[0].1,
+                   Value::Number("1".to_string()));
+
+        save_in(path.clone(), "a", "width = 3\n", true).unwrap();
+        let (_, bookmarks) = read(path.clone()).unwrap();
+        let widths = bookmarks.iter()
+            .map(|bookmark| (bookmark.name.as_str(), keys(bookmark)[0].1.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(widths, [("b", Value::Number("2".to_string())),
+                            ("a", Value::Number("3".to_string()))]);
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn bad_names_are_refused_before_anything_is_written() {
+        let path = scratch("names");
+        for name in ["", "has space", "dot.ted", "[x]"] {
+            assert!(save_in(path.clone(), name, "width = 1\n", false).is_err(), "{:?}", name);
+        }
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn without_section_keeps_the_rest_and_its_comments() {
+        let text = "# mine\n[a]\nwidth = 1 # wide\n\n[b]\nwidth = 2\n[c]\nwidth = 3\n";
+        assert_eq!(without_section(text, "b"),
+                   "# mine\n[a]\nwidth = 1 # wide\n\n[c]\nwidth = 3\n");
+        assert_eq!(without_section(text, "missing"), text);
+        assert_eq!(without_section("[only]\nwidth = 1\n", "only"), "");
+    }
+}
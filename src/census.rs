@@ -0,0 +1,206 @@
+//<***************Finding mini-Mandelbrots*****************>//
+//The set holds infinitely many small copies of itself, each hanging off a
+//filament too thin to show up at the resolution of a render. That makes
+//them easy to spot in a grid of escape counts, in one of two ways :
+//
+//  - an island : a patch of interior pixels not connected to any other
+//    interior pixel, the copy being big enough to cover a few pixels
+//  - a peak : a copy smaller than a pixel still holds the points near it
+//    for much longer than their surroundings, so it shows up as a pixel
+//    whose count is higher than all its neighbours' and at least
+//    `threshold` times the median count of the ring of pixels around those
+//
+//Islands touching the edge of the view are left out, they may be part of
+//something bigger that carries on outside it (the main cardioid, most of
+//the time). So are interior pixels in a line only one pixel wide, which is
+//what a filament looks like once it is there at all : the real axis from -2
+//to 1/4 is all in the set, and a row of pixels along it would otherwise tie
+//every copy on it to the edge. Neither test is proof, a peak can also be the tip of a spiral,
+//but zooming in on what they find is a quick way to interesting places.
+
+use num::Complex;
+
+use crate::config::View;
+
+pub enum Kind {
+    Island,
+    Peak
+}
+
+pub struct Candidate {
+    pub kind : Kind,
+    pub center : Complex<f64>,
+    //Across the island in the plane, a pixel for a peak
+    pub size : f64,
+    //Pixels the island covers, 1 for a peak
+    pub pixels : usize,
+    //Where it is in the grid, and how many pixels across
+    pub pixel : (usize, usize),
+    pub extent : usize
+}
+
+//The candidates among the escape counts `counts` of `view`, islands first
+//and then peaks, each from the biggest down
+pub fn find(counts : &[Option<u32>], view : &View, threshold : f64) -> Vec<Candidate> {
+    let mut candidates = islands(counts, view);
+    candidates.sort_by_key(|candidate| std::cmp::Reverse(candidate.pixels));
+    let mut peaks = peaks(counts, view, threshold);
+    peaks.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+    candidates.extend(peaks.into_iter().map(|(candidate, _)| candidate));
+    candidates
+}
+
+//Where the fractional pixel `pixel` is, the same way pixel_to_point()
+//places whole pixels (at the point each one's count was taken at)
+fn point_at(view : &View, pixel : (f64, f64)) -> Complex<f64> {
+    let View { bounds, upper_left, lower_right, .. } = *view;
+    view.rotated(Complex {
+        re : upper_left.re + pixel.0 / bounds.0 as f64 * (lower_right.re - upper_left.re),
+        im : upper_left.im - pixel.1 / bounds.1 as f64 * (upper_left.im - lower_right.im)
+    })
+}
+
+fn pixel_width(view : &View) -> f64 {
+    (view.lower_right.re - view.upper_left.re) / view.bounds.0 as f64
+}
+
+//The 4-connected patches of interior pixels that don't touch the edge
+fn islands(counts : &[Option<u32>], view : &View) -> Vec<Candidate> {
+    let bounds = view.bounds;
+    let interior = |x : usize, y : usize| counts[x + y * bounds.0].is_none();
+    //In the set with in-set neighbours one way (across or down) and not the
+    //other, i.e. in a line
+    let land = |index : usize| {
+        let (x, y) = (index % bounds.0, index / bounds.0);
+        let across = (x > 0 && interior(x - 1, y)) || (x + 1 < bounds.0 && interior(x + 1, y));
+        let down = (y > 0 && interior(x, y - 1)) || (y + 1 < bounds.1 && interior(x, y + 1));
+        counts[index].is_none() && across == down
+    };
+    let mut seen = vec![false; counts.len()];
+    let mut islands = Vec::new();
+    for start in 0..counts.len() {
+        if seen[start] || !land(start) {
+            continue;
+        }
+        seen[start] = true;
+        let mut stack = vec![start];
+        let (mut sum, mut pixels, mut edge) = ((0.0, 0.0), 0, false);
+        let (mut low, mut high) = ((bounds.0, bounds.1), (0, 0));
+        while let Some(index) = stack.pop() {
+            let (x, y) = (index % bounds.0, index / bounds.0);
+            sum = (sum.0 + x as f64, sum.1 + y as f64);
+            pixels += 1;
+            low = (low.0.min(x), low.1.min(y));
+            high = (high.0.max(x), high.1.max(y));
+            edge |= x == 0 || y == 0 || x + 1 == bounds.0 || y + 1 == bounds.1;
+
+            let neighbours = [(x > 0, index.wrapping_sub(1)),
+                              (x + 1 < bounds.0, index + 1),
+                              (y > 0, index.wrapping_sub(bounds.0)),
+                              (y + 1 < bounds.1, index + bounds.0)];
+            for &(inside, next) in &neighbours {
+                if inside && !seen[next] && land(next) {
+                    seen[next] = true;
+                    stack.push(next);
+                }
+            }
+        }
+        if edge {
+            continue;
+        }
+        let centroid = (sum.0 / pixels as f64, sum.1 / pixels as f64);
+        let extent = (high.0 - low.0).max(high.1 - low.1) + 1;
+        islands.push(Candidate {
+            kind : Kind::Island,
+            center : point_at(view, centroid),
+            size : extent as f64 * pixel_width(view),
+            pixels,
+            pixel : (centroid.0.round() as usize, centroid.1.round() as usize),
+            extent
+        });
+    }
+    islands
+}
+
+//Escaping pixels whose count stands out from their surroundings, with
+//their counts. Interior pixels count as the highest count there is.
+fn peaks(counts : &[Option<u32>], view : &View, threshold : f64) -> Vec<(Candidate, u32)> {
+    let bounds = view.bounds;
+    let count_at = |x : usize, y : usize| counts[x + y * bounds.0];
+    let mut peaks = Vec::new();
+    if bounds.0 < 5 || bounds.1 < 5 {
+        return peaks;
+    }
+    for y in 2..bounds.1 - 2 {
+        for x in 2..bounds.0 - 2 {
+            let count = match count_at(x, y) {
+                Some(count) => count,
+                None => continue
+            };
+            //Higher than the pixels around it, or as high as those after it
+            //in the scan so that a flat top is found once. A neighbour in
+            //the set makes this the shore of an island instead.
+            let mut highest = true;
+            for dy in 0..3 {
+                for dx in 0..3 {
+                    if (dx, dy) == (1, 1) {
+                        continue;
+                    }
+                    let earlier = dy == 0 || (dy == 1 && dx == 0);
+                    highest &= match count_at(x + dx - 1, y + dy - 1) {
+                        None => false,
+                        Some(other) if earlier => other < count,
+                        Some(other) => other <= count
+                    };
+                }
+            }
+            if !highest {
+                continue;
+            }
+
+            let mut ring = Vec::with_capacity(16);
+            for dy in 0..5 {
+                for dx in 0..5 {
+                    if dx == 0 || dx == 4 || dy == 0 || dy == 4 {
+                        ring.push(count_at(x + dx - 2, y + dy - 2).unwrap_or(u32::MAX));
+                    }
+                }
+            }
+            ring.sort_unstable();
+            let median = (ring[7] as f64 + ring[8] as f64) / 2.0;
+            if (count as f64) < threshold * median.max(1.0) {
+                continue;
+            }
+            peaks.push((Candidate {
+                kind : Kind::Peak,
+                center : point_at(view, (x as f64, y as f64)),
+                size : pixel_width(view),
+                pixels : 1,
+                pixel : (x, y),
+                extent : 1
+            }, count));
+        }
+    }
+    peaks
+}
+
+//The pixels of a square around each of `candidates`, a few pixels clear of
+//it, for marking them in an image of `bounds`
+pub fn outlines(candidates : &[Candidate], bounds : (usize, usize)) -> Vec<usize> {
+    let mut indices = Vec::new();
+    for candidate in candidates {
+        let half = candidate.extent / 2 + 3;
+        let (x, y) = candidate.pixel;
+        let (left, top) = (x.saturating_sub(half), y.saturating_sub(half));
+        let (right, bottom) = ((x + half).min(bounds.0 - 1), (y + half).min(bounds.1 - 1));
+        for column in left..=right {
+            indices.push(column + top * bounds.0);
+            indices.push(column + bottom * bounds.0);
+        }
+        for row in top..=bottom {
+            indices.push(left + row * bounds.0);
+            indices.push(right + row * bounds.0);
+        }
+    }
+    indices
+}
@@ -0,0 +1,3167 @@
+//<***************Command line handling*****************>//
+//Every option the program understands is listed once in the OPTIONS
+//table below: its name, the group it belongs to, what its value looks
+//like, a line of help and the function that stores the parsed value into
+//Options. The parser, the --help text and --config files are all driven
+//by that table, so adding a feature is a matter of adding a field to
+//Options and a row to OPTIONS.
+//
+//The program is split into subcommands (render, animate, ...). Each one
+//accepts the option groups listed in Command::groups(), so the view,
+//iteration and colouring options are shared by everything that renders.
+//
+//A --config file holds the same options as TOML, `iterations = 500` for
+//--iterations 500, optionally under a [section] named after the option's
+//group. Defaults are overridden by the environment variables in
+//ENVIRONMENT, then by a --preset, then by the config file, then by the
+//command line. --print-config writes out the options a run ends up with
+//in that same format, with a comment saying where each value came from.
+
+use num::Complex;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::blend::Blend;
+use crate::bookmarks;
+use crate::config::JuliaPath;
+use crate::fixed;
+use crate::json;
+use crate::log::Level;
+use crate::palette::{self, Palette};
+use crate::permalink;
+use crate::presets;
+use crate::text::Caption;
+use crate::toml::{self, Entry, Value};
+use crate::{parse_complex, parse_pair, Fit};
+
+//The subcommands, `render` is the default when none is named
+#[derive(Clone, Copy, PartialEq)]
+pub enum Command {
+    Render,
+    Animate,
+    Serve,
+    Probe,
+    Area,
+    Stitch,
+    VerifyManifest,
+    Verify,
+    Census,
+    ContactSheet,
+    Measure
+}
+
+const COMMANDS : &[(&str, Command, &str)] = &[
+    ("render", Command::Render, "render one view to an image (the default)"),
+    ("animate", Command::Animate, "render a zoom into the view as numbered frames"),
+    ("serve", Command::Serve, "serve rendered PNGs over HTTP"),
+    ("probe", Command::Probe, "report how a single point escapes"),
+    ("area", Command::Area, "estimate the area of the set from random points"),
+    ("stitch", Command::Stitch, "lay several PNG images out in a grid"),
+    ("verify-manifest", Command::VerifyManifest,
+     "check animation frames against their manifest"),
+    ("verify", Command::Verify, "check this build against known answers"),
+    ("census", Command::Census, "look for mini-Mandelbrots in a view"),
+    ("contact-sheet", Command::ContactSheet,
+     "render every preset as a labelled thumbnail in one grid image"),
+    ("measure", Command::Measure, "measure the complex plane between two pixels of a view")
+];
+
+//Which part of the program an option belongs to
+#[derive(Clone, Copy, PartialEq)]
+enum Group {
+    General,
+    Output,
+    Viewer,
+    Threads,
+    View,
+    Iteration,
+    Colouring,
+    Animation,
+    Serve,
+    Probe,
+    Area,
+    Stitch,
+    Plan,
+    Jobs,
+    Swatch,
+    Pyramid,
+    Census,
+    ContactSheet,
+    Measure,
+    Caption
+}
+
+impl Group {
+    //The [section] of a config file holding the group's options
+    fn name(self) -> &'static str {
+        match self {
+            Group::General => "general",
+            Group::Output => "output",
+            Group::Viewer => "viewer",
+            Group::Threads => "threads",
+            Group::View => "view",
+            Group::Iteration => "iteration",
+            Group::Colouring => "colouring",
+            Group::Animation => "animation",
+            Group::Serve => "serve",
+            Group::Probe => "probe",
+            Group::Area => "area",
+            Group::Stitch => "stitch",
+            Group::Plan => "plan",
+            Group::Jobs => "jobs",
+            Group::Swatch => "swatch",
+            Group::Pyramid => "pyramid",
+            Group::Census => "census",
+            Group::ContactSheet => "contact-sheet",
+            Group::Measure => "measure",
+            Group::Caption => "caption"
+        }
+    }
+}
+
+impl Command {
+    pub fn name(self) -> &'static str {
+        COMMANDS.iter().find(|&&(_, command, _)| command == self).unwrap().0
+    }
+
+    fn groups(self) -> &'static [Group] {
+        match self {
+            Command::Render => &[Group::General, Group::Output, Group::Viewer,
+                                 Group::Threads, Group::View, Group::Iteration,
+                                 Group::Colouring, Group::Caption, Group::Plan,
+                                 Group::Jobs, Group::Swatch, Group::Pyramid],
+            Command::Animate => &[Group::General, Group::Output, Group::Threads,
+                                  Group::View, Group::Iteration, Group::Colouring,
+                                  Group::Caption, Group::Animation, Group::Plan],
+            //The view and colouring of every image served come from the
+            //query string of its request, see parse_named()
+            Command::Serve => &[Group::General, Group::Threads, Group::Serve],
+            Command::Probe => &[Group::General, Group::Iteration, Group::Probe],
+            Command::Area => &[Group::General, Group::Iteration, Group::Area],
+            Command::Stitch => &[Group::General, Group::Output, Group::Viewer,
+                                 Group::Stitch],
+            Command::VerifyManifest => &[Group::General],
+            Command::Verify => &[Group::General],
+            //The output is the optional image the finds are marked on
+            Command::Census => &[Group::General, Group::Output, Group::Threads,
+                                 Group::View, Group::Iteration, Group::Census],
+            //The views and their limits are the presets', --columns is
+            //stitch's
+            Command::ContactSheet => &[Group::General, Group::Output, Group::Threads,
+                                       Group::Colouring, Group::Stitch,
+                                       Group::ContactSheet],
+            //--threads for --precision, the pixels' points under a deep
+            //precision being worked out as render does
+            Command::Measure => &[Group::General, Group::Threads, Group::View,
+                                  Group::Measure]
+        }
+    }
+
+    fn accepts(self, group : Group) -> bool {
+        self.groups().contains(&group)
+    }
+}
+
+//How the escape counts get turned into colours
+#[derive(Clone, Copy, PartialEq)]
+pub enum Mode {
+    Gray,
+    Angle,
+    //Escape counts as in gray, worked out from a reference orbit
+    Perturbation,
+    //Escape counts modulo --band-width, in rings of the same colours
+    Bands
+}
+
+//Which way the gray ramp of escaping points runs, from the quickest to
+//escape to the slowest
+#[derive(Clone, Copy, PartialEq)]
+pub enum Exterior {
+    LightToDark,
+    DarkToLight
+}
+
+//How the rows of a window are shared out between the threads
+#[derive(Clone, Copy, PartialEq)]
+pub enum Schedule {
+    //Bands of consecutive rows, a few per thread, see optimal_bands()
+    Bands,
+    //Thread k of T gets rows k, k + T, k + 2T and so on
+    Interleaved,
+    //Chunks of --chunk-rows rows go to whichever thread is free next
+    Dynamic,
+    //A band per thread, of about the same cost by a quick pre-pass, see
+    //row_costs()
+    CostBalanced
+}
+
+impl Mode {
+    //As --mode takes it
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Gray => "gray",
+            Mode::Angle => "angle",
+            Mode::Perturbation => "perturbation",
+            Mode::Bands => "bands"
+        }
+    }
+}
+
+//Escape counts to a ring of --mode bands
+pub const DEFAULT_BAND_WIDTH : u32 = 8;
+
+//The most pixels a render takes unless --max-pixels (or
+//MANDELBROT_MAX_PIXELS) says otherwise, a 40000x25000 image or so : far
+//beyond anything wanted by accident, but short of the gigabytes a typo in
+//--size can ask for
+pub const DEFAULT_MAX_PIXELS : usize = 1_000_000_000;
+
+//What serve holds each request to unless --max-pixels is given, 2000x2000 :
+//plenty for a page's tiles and previews, and one request can't keep the
+//server busy for long
+#[cfg(feature = "serve")]
+pub const DEFAULT_SERVE_MAX_PIXELS : usize = 4_000_000;
+
+//The most iterations a pixel serve lets a request ask for unless
+//--max-request-iterations is given, 64 times the default --iterations
+pub const DEFAULT_SERVE_MAX_ITERATIONS : u32 = 255 * 64;
+
+impl Schedule {
+    //As --schedule takes it
+    pub fn name(self) -> &'static str {
+        match self {
+            Schedule::Bands => "bands",
+            Schedule::Interleaved => "interleaved",
+            Schedule::Dynamic => "dynamic",
+            Schedule::CostBalanced => "cost-balanced"
+        }
+    }
+}
+
+//The floating point type the points are worked out and iterated in
+#[derive(Clone, Copy, PartialEq)]
+pub enum Precision {
+    F64,
+    F32,
+    //A pair of f64s, see double.rs
+    DoubleDouble,
+    //Fixed point of 124 bits after the binary point in an i128, see fixed.rs
+    Fixed128,
+    //Fixed point of this many bits after the binary point, see big.rs
+    Big(usize),
+    //Whichever of the others the pixels need, settled by config::build().
+    //`fixed` is false where the fixed point ones don't apply.
+    Auto { fixed : bool }
+}
+
+//The bits --precision big may have : fewer than a double-double's are no
+//use, and past a few thousand a single iteration takes milliseconds
+pub const BIG_BITS : (usize, usize) = (64, 8192);
+
+impl Precision {
+    //As --precision takes it
+    pub fn name(self) -> String {
+        match self {
+            Precision::F64 => "f64".to_string(),
+            Precision::F32 => "f32".to_string(),
+            Precision::DoubleDouble => "dd".to_string(),
+            Precision::Fixed128 => "fixed128".to_string(),
+            Precision::Big(bits) => format!("big:{}", bits),
+            Precision::Auto { .. } => "auto".to_string()
+        }
+    }
+
+    //The bits after the binary point of the fixed point precisions, whose
+    //views are laid out around a center of their own (see big.rs)
+    pub fn fixed_bits(self) -> Option<usize> {
+        match self {
+            Precision::Fixed128 => Some(fixed::BITS),
+            Precision::Big(bits) => Some(bits),
+            _ => None
+        }
+    }
+}
+
+//What kind of file gets written
+#[derive(Clone, Copy, PartialEq)]
+pub enum Format {
+    Png,
+    Csv
+}
+
+//How hard the PNG encoder tries, see encode_png()
+#[derive(Clone, Copy, PartialEq)]
+pub enum PngCompression {
+    Fast,
+    Default,
+    Best
+}
+
+//Everything the command line can ask for, with the defaults filled in
+#[derive(Clone)]
+pub struct Options {
+    pub output : Option<PathBuf>,
+    pub output_dir : Option<PathBuf>,
+    pub size : Option<(usize, usize)>,
+    //The size as a number of pixels and a shape instead
+    pub megapixels : Option<f64>,
+    pub aspect : Option<(f64, f64)>,
+    pub upper_left : Option<Complex<f64>>,
+    pub lower_right : Option<Complex<f64>>,
+    pub center : Option<Complex<f64>>,
+    //--center as it was given, for --precision big to read every digit of
+    pub center_text : Option<String>,
+    pub width : Option<f64>,
+    pub zoom : Option<f64>,
+    pub fit : Option<Fit>,
+    //Fractions of the view's width and height to move it by
+    pub pan : Option<Complex<f64>>,
+    pub zoom_by : Option<f64>,
+    //Degrees counterclockwise, --rotate-by's on top of --rotate's
+    pub rotate : f64,
+    pub rotate_by : f64,
+    pub swap_corners : bool,
+    pub preset : Option<String>,
+    pub list_presets : bool,
+    pub bookmark : Option<String>,
+    pub save_bookmark : Option<String>,
+    pub list_bookmarks : bool,
+    pub permalink : bool,
+    pub from_permalink : Option<String>,
+    pub mode : Mode,
+    pub band_width : u32,
+    pub iterations : u32,
+    pub limit_center : Option<u32>,
+    pub limit_edge : Option<u32>,
+    pub diff_limits : Option<(u32, u32)>,
+    pub adaptive : bool,
+    pub adaptive_threshold : f64,
+    pub max_iterations : u32,
+    pub chromatic : Option<u32>,
+    pub format : Format,
+    pub png_compression : PngCompression,
+    pub csv_interior : String,
+    pub open : bool,
+    pub watch : bool,
+    pub no_clobber : bool,
+    pub skip_existing : bool,
+    pub allow_uniform : bool,
+    pub single_threaded : bool,
+    //0 for as many as there are CPUs, see thread_count()
+    pub threads : usize,
+    pub schedule : Schedule,
+    pub chunk_rows : usize,
+    pub time_budget : Option<Duration>,
+    //Runs to time, not counting the warm-up
+    pub bench : Option<usize>,
+    pub bench_log : Option<PathBuf>,
+    //Thread counts to render with one after the other
+    pub scaling_sweep : Option<Vec<usize>>,
+    pub report_iterations : bool,
+    pub fast_math : bool,
+    pub simd : bool,
+    pub precision : Precision,
+    pub perturbation : bool,
+    //None for no limit at all
+    pub max_pixels : Option<usize>,
+    //In bytes, None for no limit but the machine's
+    pub max_memory : Option<usize>,
+    pub preview_stride : usize,
+    pub force : bool,
+    pub palette : Option<Palette>,
+    pub palette_wrap : bool,
+    pub linear_blend : bool,
+    pub blend : Option<Blend>,
+    pub show_glitches : bool,
+    pub heatmap : bool,
+    pub cost_map : bool,
+    pub gamma : f64,
+    pub interior_value : u8,
+    pub exterior : Exterior,
+    pub auto_exposure : bool,
+    //None for the default percentiles, see config::DEFAULT_EXPOSURE_CLIP
+    pub exposure_clip : Option<(f64, f64)>,
+    pub title : Option<String>,
+    pub title_coordinates : bool,
+    pub border_color : [u8; 3],
+    //None to scale the text with the width of the image
+    pub font_size : Option<usize>,
+    pub frames : usize,
+    //Frames animate renders at the same time
+    pub parallel_frames : usize,
+    //None for the default, which depends on the kind of animation
+    pub zoom_per_frame : Option<f64>,
+    //(columns, rows) each frame moves right and down from the last
+    pub pan_per_frame : Option<(f64, f64)>,
+    //How fast the iterations grow with the zoom, see suggest_iterations()
+    pub iteration_scale : f64,
+    pub julia : Option<Complex<f64>>,
+    pub julia_path : Option<JuliaPath>,
+    pub manifest : Option<PathBuf>,
+    //Where the reference orbits of --perturbation are kept between runs
+    pub orbit_cache : Option<PathBuf>,
+    pub listen : String,
+    pub max_request_iterations : u32,
+    pub point : Option<Complex<f64>>,
+    pub line_to : Option<Complex<f64>>,
+    pub steps : usize,
+    pub orbit : bool,
+    pub samples : usize,
+    pub seed : u64,
+    pub census_threshold : f64,
+    pub from_pixel : Option<(usize, usize)>,
+    pub to_pixel : Option<(usize, usize)>,
+    pub columns : usize,
+    pub inputs : Vec<PathBuf>,
+    pub cell_size : (usize, usize),
+    pub config : Option<PathBuf>,
+    pub print_config : bool,
+    pub jobs : Option<PathBuf>,
+    pub preview_palette : Option<Palette>,
+    pub swatch_size : (usize, usize),
+    pub pyramid : Option<PathBuf>,
+    pub levels : usize,
+    pub jobs_stdin : bool,
+    pub parallel_jobs : usize,
+    pub only : Option<String>,
+    pub dry_run : bool,
+    pub estimate : bool,
+    pub json : bool,
+    pub help : bool,
+    pub version : bool,
+    //--progress json, and where progress goes if not stderr
+    pub progress_json : bool,
+    pub progress_file : Option<PathBuf>,
+    pub stats : Option<PathBuf>,
+    //Points on the command line written as -1,2;0,35
+    pub decimal_comma : bool,
+    //How much goes to stderr, from --quiet and -v/-vv
+    pub verbosity : Level,
+    //(option name, where its value came from) for the options that aren't
+    //at their defaults, for --print-config
+    pub sources : Vec<(&'static str, String)>
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            output : None,
+            output_dir : None,
+            size : None,
+            megapixels : None,
+            aspect : None,
+            upper_left : None,
+            lower_right : None,
+            center : None,
+            center_text : None,
+            width : None,
+            zoom : None,
+            fit : None,
+            pan : None,
+            zoom_by : None,
+            rotate : 0.0,
+            rotate_by : 0.0,
+            swap_corners : false,
+            preset : None,
+            list_presets : false,
+            bookmark : None,
+            save_bookmark : None,
+            list_bookmarks : false,
+            permalink : false,
+            from_permalink : None,
+            mode : Mode::Gray,
+            band_width : DEFAULT_BAND_WIDTH,
+            iterations : 255,
+            limit_center : None,
+            limit_edge : None,
+            diff_limits : None,
+            adaptive : false,
+            adaptive_threshold : 0.001,
+            max_iterations : 255 * 64,
+            chromatic : None,
+            format : Format::Png,
+            png_compression : PngCompression::Default,
+            csv_interior : String::new(),
+            open : false,
+            watch : false,
+            no_clobber : false,
+            skip_existing : false,
+            allow_uniform : false,
+            single_threaded : false,
+            threads : 0,
+            schedule : Schedule::Bands,
+            chunk_rows : 4,
+            time_budget : None,
+            bench : None,
+            bench_log : None,
+            scaling_sweep : None,
+            report_iterations : false,
+            fast_math : false,
+            simd : false,
+            precision : Precision::F64,
+            perturbation : false,
+            max_pixels : Some(DEFAULT_MAX_PIXELS),
+            max_memory : None,
+            preview_stride : 1,
+            force : false,
+            palette : None,
+            palette_wrap : false,
+            linear_blend : false,
+            blend : None,
+            show_glitches : false,
+            heatmap : false,
+            cost_map : false,
+            gamma : 1.0,
+            interior_value : 0,
+            exterior : Exterior::LightToDark,
+            auto_exposure : false,
+            exposure_clip : None,
+            title : None,
+            title_coordinates : false,
+            border_color : [0, 0, 0],
+            font_size : None,
+            frames : 30,
+            parallel_frames : 1,
+            zoom_per_frame : None,
+            pan_per_frame : None,
+            iteration_scale : 0.0,
+            julia : None,
+            julia_path : None,
+            manifest : None,
+            orbit_cache : None,
+            listen : "127.0.0.1:8080".to_string(),
+            max_request_iterations : DEFAULT_SERVE_MAX_ITERATIONS,
+            point : None,
+            line_to : None,
+            steps : 100,
+            orbit : false,
+            samples : 1_000_000,
+            seed : 1,
+            census_threshold : 2.0,
+            from_pixel : None,
+            to_pixel : None,
+            columns : 2,
+            inputs : Vec::new(),
+            cell_size : (240, 180),
+            config : None,
+            print_config : false,
+            jobs : None,
+            preview_palette : None,
+            swatch_size : (512, 64),
+            pyramid : None,
+            levels : 6,
+            jobs_stdin : false,
+            parallel_jobs : 1,
+            only : None,
+            dry_run : false,
+            estimate : false,
+            json : false,
+            help : false,
+            version : false,
+            progress_json : false,
+            progress_file : None,
+            stats : None,
+            decimal_comma : false,
+            verbosity : Level::Normal,
+            sources : Vec::new()
+        }
+    }
+}
+
+impl Options {
+    //A zoom goes in 1.1 times deeper every frame, a Julia movie or a pan
+    //keeps to the scale of the view it was given
+    pub fn frame_zoom(&self) -> f64 {
+        let moving = self.julia_path.is_some() || self.pan_per_frame.is_some();
+        self.zoom_per_frame.unwrap_or(if moving { 1.0 } else { 1.1 })
+    }
+
+    //How much narrower frame `frame` of an animation is than the first
+    pub fn zoom_at(&self, frame : usize) -> f64 {
+        self.frame_zoom().powi(frame as i32)
+    }
+
+    //The threads a render is split between, one per CPU unless --threads
+    //(or --single-threaded) says otherwise
+    pub fn thread_count(&self) -> usize {
+        match self.threads {
+            _ if self.single_threaded => 1,
+            0 => num_cpus::get(),
+            threads => threads
+        }
+    }
+
+    //What --title and --title-coordinates put under the image, if anything
+    pub fn caption(&self) -> Option<Caption> {
+        if self.title.is_none() && !self.title_coordinates {
+            return None;
+        }
+        Some(Caption {
+            title : self.title.clone(),
+            coordinates : self.title_coordinates,
+            border : self.border_color,
+            font_size : self.font_size
+        })
+    }
+}
+
+//One row of the option table. Flags that take no value have `value` set
+//to None and get handed an empty string. `show` gives the option's current
+//value as TOML for --print-config, or None when it isn't set.
+struct OptionSpec {
+    name : &'static str,
+    group : Group,
+    value : Option<&'static str>,
+    help : &'static str,
+    show : fn(&Options) -> Option<String>,
+    apply : fn(&mut Options, &str) -> Result<(), String>
+}
+
+const OPTIONS : &[OptionSpec] = &[
+    OptionSpec {
+        name : "--output", group : Group::Output,
+        value : Some("FILE"),
+        help : "file to write, - for stdout, auto for a name made up from the \
+                view (animate : pattern with {} for the frame number)",
+        show : |options| options.output.as_ref().map(|output| quote_path(output)),
+        apply : |options, value| { options.output = Some(PathBuf::from(value)); Ok(()) }
+    },
+    OptionSpec {
+        name : "--output-dir", group : Group::Output,
+        value : Some("DIR"),
+        help : "directory the files named by --output auto go in (default the \
+                current one)",
+        show : |options| options.output_dir.as_ref().map(|dir| quote_path(dir)),
+        apply : |options, value| { options.output_dir = Some(PathBuf::from(value)); Ok(()) }
+    },
+    OptionSpec {
+        name : "--single-threaded", group : Group::Threads,
+        value : None,
+        help : "render everything on the main thread, for debugging (the same as \
+                --threads 1)",
+        show : |options| Some(options.single_threaded.to_string()),
+        apply : |options, _| { options.single_threaded = true; Ok(()) }
+    },
+    OptionSpec {
+        name : "--threads", group : Group::Threads,
+        value : Some("N|auto"),
+        help : "threads to render on, auto (or 0) for one per CPU (default auto)",
+        show : |options| Some(match options.threads {
+            0 => "\"auto\"".to_string(),
+            threads => threads.to_string()
+        }),
+        apply : |options, value| {
+            options.threads = match value {
+                "auto" => 0,
+                _ => usize::from_str(value)
+                    .map_err(|_| expected("a whole number or auto", value))?
+            };
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--schedule", group : Group::Threads,
+        value : Some("bands|interleaved|dynamic|cost-balanced"),
+        help : "hand out bands of rows, a few per thread, every Nth row of the \
+                window to each thread so that the slow rows of the set get spread \
+                out, the next few rows to a thread whenever it is free, or a band \
+                per thread sized by a quick pre-pass to take as long as the others \
+                (default bands)",
+        show : |options| Some(toml::quote(options.schedule.name())),
+        apply : |options, value| {
+            options.schedule = match value {
+                "bands" => Schedule::Bands,
+                "interleaved" => Schedule::Interleaved,
+                "dynamic" => Schedule::Dynamic,
+                "cost-balanced" => Schedule::CostBalanced,
+                _ => return Err(expected("bands, interleaved, dynamic or cost-balanced",
+                                         value))
+            };
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--chunk-rows", group : Group::Threads,
+        value : Some("N"),
+        help : "rows a thread takes at a time with --schedule dynamic (default 4)",
+        show : |options| Some(options.chunk_rows.to_string()),
+        apply : |options, value| {
+            options.chunk_rows = parse_positive_u32(value)? as usize;
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--preview-stride", group : Group::Threads,
+        value : Some("N"),
+        help : "render only every Nth row and repeat it down, a coarse preview N \
+                times as fast, not for the final image (default 1, every row)",
+        show : |options| Some(options.preview_stride.to_string()),
+        apply : |options, value| {
+            options.preview_stride = parse_positive_u32(value)? as usize;
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--fast-math", group : Group::Threads,
+        value : None,
+        help : "iterate on plain f64s instead of num::Complex, giving the very \
+                same picture (whether that is any quicker depends on the compiler \
+                and the CPU, time both with --bench)",
+        show : |options| Some(options.fast_math.to_string()),
+        apply : |options, _| { options.fast_math = true; Ok(()) }
+    },
+    OptionSpec {
+        name : "--simd", group : Group::Threads,
+        value : None,
+        help : "iterate the points of a row several at a time with AVX2 or NEON \
+                where the CPU has them, giving the very same picture",
+        show : |options| Some(options.simd.to_string()),
+        apply : |options, _| { options.simd = true; Ok(()) }
+    },
+    OptionSpec {
+        name : "--precision", group : Group::Threads,
+        value : Some("auto|f64|f32|dd|fixed128|big:BITS"),
+        help : "work out and iterate the points in f64, in f32 for quicker \
+                previews of views that aren't zoomed in far, twice as quick under \
+                --simd but with the edges of the set a little off, in \
+                double-double (two f64s) for zooms past 1e13 where f64 turns \
+                blocky, about ten times slower, in 128-bit fixed point for zooms \
+                to 1e35, or with BITS bits after the binary point (64 to 8192) \
+                for zooms past that, very slow; the last two take the view as \
+                --center, every digit of it kept; auto picks the quickest of \
+                them that keeps the pixels apart (default f64)",
+        show : |options| Some(toml::quote(&options.precision.name())),
+        apply : |options, value| {
+            let bits = |bits : &str| match bits.parse::<usize>() {
+                Ok(bits) if bits >= BIG_BITS.0 && bits <= BIG_BITS.1 => Ok(bits),
+                _ => Err(expected(&format!("big:BITS with BITS from {} to {}", BIG_BITS.0,
+                                           BIG_BITS.1), value))
+            };
+            options.precision = match value {
+                "f64" => Precision::F64,
+                "f32" => Precision::F32,
+                "dd" => Precision::DoubleDouble,
+                "fixed128" => Precision::Fixed128,
+                "auto" => Precision::Auto { fixed : true },
+                _ => match value.strip_prefix("big:") {
+                    Some(count) => Precision::Big(bits(count)?),
+                    None => return Err(expected("auto, f64, f32, dd, fixed128 or big:BITS",
+                                                value))
+                }
+            };
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--perturbation", group : Group::Threads,
+        value : None,
+        help : "under --precision fixed128 or big:BITS, iterate only the center in \
+                that precision and every pixel as an f64 difference from its orbit, \
+                from the iteration a series approximation holds to, hundreds of \
+                times quicker for zooms to 1e300 (auto turns it on where it picks \
+                big:BITS)",
+        show : |options| Some(options.perturbation.to_string()),
+        apply : |options, _| { options.perturbation = true; Ok(()) }
+    },
+    OptionSpec {
+        name : "--max-pixels", group : Group::Threads,
+        value : Some("N|none"),
+        help : "refuse to render an image of more than N pixels, none for no \
+                limit (default 1000000000, for serve 4000000 a request)",
+        show : |options| Some(match options.max_pixels {
+            Some(pixels) => pixels.to_string(),
+            None => "\"none\"".to_string()
+        }),
+        apply : |options, value| {
+            options.max_pixels = match value {
+                "none" => None,
+                _ => Some(usize::from_str(value).ok()
+                          .filter(|&pixels| pixels > 0)
+                          .ok_or_else(|| expected("a positive whole number or none", value))?)
+            };
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--max-memory", group : Group::Threads,
+        value : Some("BYTES|none"),
+        help : "refuse to render an image whose buffers would take up more than \
+                BYTES, e.g. 512M or 2G, none for no limit but the machine's \
+                (the default)",
+        show : |options| options.max_memory.map(|bytes| bytes.to_string()),
+        apply : |options, value| {
+            options.max_memory = match value {
+                "none" => None,
+                _ => Some(parse_bytes(value)?)
+            };
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--time-budget", group : Group::Threads,
+        value : Some("TIME"),
+        help : "stop rendering an image after e.g. 5s or 500ms, leaving the \
+                rows not reached or cut short black (the tighter the budget, the \
+                more of the picture is missing)",
+        show : |options| options.time_budget
+            .map(|budget| toml::quote(&format!("{}ms", budget.as_millis()))),
+        apply : |options, value| {
+            options.time_budget = Some(parse_duration(value)?);
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--bench", group : Group::Threads,
+        value : Some("N"),
+        help : "time N renders of the view after one to warm up, instead of \
+                writing the image, and print the mean, median, standard \
+                deviation and pixels a second",
+        show : |options| options.bench.map(|runs| runs.to_string()),
+        apply : |options, value| {
+            options.bench = Some(parse_positive_u32(value)? as usize);
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--bench-log", group : Group::Threads,
+        value : Some("FILE"),
+        help : "also add a CSV line of the --bench results to FILE, along with \
+                the version, threads, scheduler and a hash of the view",
+        show : |options| options.bench_log.as_ref().map(|file| quote_path(file)),
+        apply : |options, value| {
+            options.bench_log = Some(PathBuf::from(value));
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--scaling-sweep", group : Group::Threads,
+        value : Some("N,N,..."),
+        help : "render the view once with each number of threads instead of \
+                writing the image, and print the time, speedup and efficiency of \
+                each against one thread (--json for JSON)",
+        show : |options| options.scaling_sweep.as_ref().map(|counts| toml::quote(
+            &counts.iter().map(|threads| threads.to_string()).collect::<Vec<_>>().join(","))),
+        apply : |options, value| {
+            let counts = value.split(',')
+                .map(|count| parse_positive_u32(count.trim()).map(|count| count as usize))
+                .collect::<Result<Vec<_>, _>>()?;
+            options.scaling_sweep = Some(counts);
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--report-iterations", group : Group::Threads,
+        value : None,
+        help : "count the iterations the renders go through and print the total \
+                at the end, for seeing what a change to the iteration saves",
+        show : |options| Some(options.report_iterations.to_string()),
+        apply : |options, _| { options.report_iterations = true; Ok(()) }
+    },
+    OptionSpec {
+        name : "--size", group : Group::View,
+        value : Some("WIDTHxHEIGHT"),
+        help : "image dimensions in pixels, e.g. 1000x750",
+        show : |options| options.size
+            .map(|(width, height)| toml::quote(&format!("{}x{}", width, height))),
+        apply : |options, value| {
+            options.size = Some(parse_size(value)?);
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--megapixels", group : Group::View,
+        value : Some("MP"),
+        help : "instead of --size, the size with about MP million pixels in the \
+                shape of --aspect",
+        show : |options| options.megapixels.map(|megapixels| megapixels.to_string()),
+        apply : |options, value| {
+            options.megapixels = Some(parse_positive_f64(value)?);
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--aspect", group : Group::View,
+        value : Some("W:H"),
+        help : "the width to height of a --megapixels image, e.g. 16:9 (default 4:3)",
+        show : |options| options.aspect
+            .map(|(width, height)| toml::quote(&format!("{}:{}", width, height))),
+        apply : |options, value| {
+            let aspect = parse_pair::<f64>(value, ':')
+                .map_err(|error| error.describe("an aspect ratio", ["width", "height"],
+                                                "16:9"))?;
+            if !(aspect.0 > 0.0 && aspect.1 > 0.0 && (aspect.0 / aspect.1).is_finite()) {
+                return Err(expected("two positive numbers such as 16:9", value));
+            }
+            options.aspect = Some(aspect);
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--upper-left", group : Group::View,
+        value : Some("RE,IM"),
+        help : "upper left corner of the view on the complex plane",
+        show : |options| options.upper_left.map(show_point),
+        apply : |options, value| {
+            options.upper_left = Some(parse_point(value)?);
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--lower-right", group : Group::View,
+        value : Some("RE,IM"),
+        help : "lower right corner of the view on the complex plane",
+        show : |options| options.lower_right.map(show_point),
+        apply : |options, value| {
+            options.lower_right = Some(parse_point(value)?);
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--center", group : Group::View,
+        value : Some("RE,IM"),
+        help : "center of the view, instead of giving the corners",
+        show : |options| options.center.map(show_point),
+        apply : |options, value| {
+            options.center = Some(parse_point(value)?);
+            options.center_text = Some(value.to_string());
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--width", group : Group::View,
+        value : Some("W"),
+        help : "complex-plane width of the view around --center",
+        show : |options| options.width.map(|width| width.to_string()),
+        apply : |options, value| {
+            options.width = Some(parse_positive_f64(value)?);
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--zoom", group : Group::View,
+        value : Some("Z"),
+        help : "magnification around --center, instead of --width (1 is 4 wide)",
+        show : |options| options.zoom.map(|zoom| zoom.to_string()),
+        apply : |options, value| {
+            options.zoom = Some(parse_positive_f64(value)?);
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--pan", group : Group::View,
+        value : Some("X,Y"),
+        help : "move the view by these fractions of its width and height (right \
+                and up), after everything else has placed it",
+        show : |options| options.pan.map(show_point),
+        apply : |options, value| {
+            options.pan = Some(parse_point(value)?);
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--zoom-by", group : Group::View,
+        value : Some("FACTOR"),
+        help : "magnify the view by FACTOR around its center, after any --pan",
+        show : |options| options.zoom_by.map(|factor| factor.to_string()),
+        apply : |options, value| {
+            options.zoom_by = Some(parse_positive_f64(value)?);
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--rotate", group : Group::View,
+        value : Some("DEGREES"),
+        help : "turn the sampling grid counterclockwise around the view's center, \
+                the picture keeps its size (default 0)",
+        show : |options| Some(options.rotate).filter(|&degrees| degrees != 0.0)
+            .map(|degrees| degrees.to_string()),
+        apply : |options, value| {
+            options.rotate = f64::from_str(value).ok()
+                .filter(|degrees| degrees.is_finite())
+                .ok_or_else(|| expected("an angle in degrees", value))?;
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--rotate-by", group : Group::View,
+        value : Some("DEGREES"),
+        help : "turn the view further counterclockwise around its center, after any \
+                --pan and --zoom-by, on top of the --rotate of a bookmark or config",
+        show : |options| Some(options.rotate_by).filter(|&degrees| degrees != 0.0)
+            .map(|degrees| degrees.to_string()),
+        apply : |options, value| {
+            options.rotate_by = f64::from_str(value).ok()
+                .filter(|degrees| degrees.is_finite())
+                .ok_or_else(|| expected("an angle in degrees", value))?;
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--fit", group : Group::View,
+        value : Some("expand|crop|stretch"),
+        help : "fix up corners whose aspect ratio doesn't match the image",
+        show : |options| options.fit.map(|fit| toml::quote(match fit {
+            Fit::Expand => "expand",
+            Fit::Crop => "crop",
+            Fit::Stretch => "stretch"
+        })),
+        apply : |options, value| {
+            options.fit = Some(match value {
+                "expand" => Fit::Expand,
+                "crop" => Fit::Crop,
+                "stretch" => Fit::Stretch,
+                _ => return Err(expected("one of expand, crop or stretch", value))
+            });
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--julia", group : Group::View,
+        value : Some("RE,IM"),
+        help : "render the Julia set of this constant instead of the Mandelbrot set",
+        show : |options| options.julia.map(show_point),
+        apply : |options, value| {
+            options.julia = Some(parse_point(value)?);
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--swap-corners", group : Group::View,
+        value : None,
+        help : "put corners given the wrong way round right instead of failing",
+        show : |options| Some(options.swap_corners.to_string()),
+        apply : |options, _| { options.swap_corners = true; Ok(()) }
+    },
+    OptionSpec {
+        name : "--preset", group : Group::View,
+        value : Some("NAME"),
+        help : "center, width and iterations of a well known place",
+        //What the preset set is shown instead
+        show : |_| None,
+        apply : |options, value| { options.preset = Some(value.to_string()); Ok(()) }
+    },
+    OptionSpec {
+        name : "--list-presets", group : Group::View,
+        value : None,
+        help : "list the presets and where they are",
+        show : |_| None,
+        apply : |options, _| { options.list_presets = true; Ok(()) }
+    },
+    OptionSpec {
+        name : "--bookmark", group : Group::View,
+        value : Some("NAME"),
+        help : "start from a view saved with --save-bookmark, over any preset",
+        show : |_| None,
+        apply : |options, value| { options.bookmark = Some(value.to_string()); Ok(()) }
+    },
+    OptionSpec {
+        name : "--save-bookmark", group : Group::View,
+        value : Some("NAME"),
+        help : "save the view, its iterations and colouring as a bookmark instead \
+                of running (--force replaces one of the same name)",
+        show : |_| None,
+        apply : |options, value| {
+            options.save_bookmark = Some(value.to_string());
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--list-bookmarks", group : Group::View,
+        value : None,
+        help : "list the saved bookmarks",
+        show : |_| None,
+        apply : |options, _| { options.list_bookmarks = true; Ok(()) }
+    },
+    OptionSpec {
+        name : "--permalink", group : Group::View,
+        value : None,
+        help : "print the view, its iterations and colouring as one line of text \
+                to share instead of running",
+        show : |_| None,
+        apply : |options, _| { options.permalink = true; Ok(()) }
+    },
+    OptionSpec {
+        name : "--from-permalink", group : Group::View,
+        value : Some("PERMALINK"),
+        help : "start from a view printed by --permalink, over any preset or bookmark",
+        show : |_| None,
+        apply : |options, value| {
+            options.from_permalink = Some(value.to_string());
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--mode", group : Group::Colouring,
+        value : Some("gray|angle|perturbation|bands"),
+        help : "colour by escape count (gray) or by escape angle (angle), \
+                perturbation colours like gray but works the counts out from a \
+                reference orbit, for zooms deeper than a width of about 1e-13, \
+                and bands colours the count modulo --band-width in rings that \
+                repeat the same ramp (or --palette)",
+        show : |options| Some(toml::quote(options.mode.name())),
+        apply : |options, value| {
+            options.mode = match value {
+                "gray" => Mode::Gray,
+                "angle" => Mode::Angle,
+                "perturbation" => Mode::Perturbation,
+                "bands" => Mode::Bands,
+                _ => return Err(expected("gray, angle, perturbation or bands", value))
+            };
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--band-width", group : Group::Colouring,
+        value : Some("N"),
+        help : "escape counts to a ring with --mode bands, at least 2 (default 8)",
+        show : |options| match options.mode {
+            Mode::Bands => Some(options.band_width.to_string()),
+            _ => None
+        },
+        apply : |options, value| {
+            options.band_width = u32::from_str(value).ok()
+                .filter(|&width| width >= 2)
+                .ok_or_else(|| expected("a whole number of at least 2", value))?;
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--iterations", group : Group::Iteration,
+        value : Some("N"),
+        help : "iteration limit (default 255)",
+        show : |options| Some(options.iterations.to_string()),
+        apply : |options, value| {
+            options.iterations = parse_positive_u32(value)?;
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--limit-center", group : Group::Iteration,
+        value : Some("N"),
+        help : "iteration limit in the middle of the view (with --limit-edge)",
+        show : |options| options.limit_center.map(|limit| limit.to_string()),
+        apply : |options, value| {
+            options.limit_center = Some(parse_positive_u32(value)?);
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--diff-limits", group : Group::Iteration,
+        value : Some("LOW,HIGH"),
+        help : "render at both limits and show which pixels only escape with the \
+                higher one, instead of the usual picture",
+        show : |options| options.diff_limits
+            .map(|(low, high)| toml::quote(&format!("{},{}", low, high))),
+        apply : |options, value| {
+            let limits = parse_pair::<u32>(value, ',').ok()
+                .filter(|&(low, high)| low > 0 && low < high)
+                .ok_or_else(|| expected("two limits LOW,HIGH with 0 < LOW < HIGH",
+                                        value))?;
+            options.diff_limits = Some(limits);
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--limit-edge", group : Group::Iteration,
+        value : Some("N"),
+        help : "iteration limit at the corners of the view (with --limit-center)",
+        show : |options| options.limit_edge.map(|limit| limit.to_string()),
+        apply : |options, value| {
+            options.limit_edge = Some(parse_positive_u32(value)?);
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--adaptive-iterations", group : Group::Iteration,
+        value : None,
+        help : "keep doubling the iteration limit until the detail stabilizes",
+        show : |options| Some(options.adaptive.to_string()),
+        apply : |options, _| { options.adaptive = true; Ok(()) }
+    },
+    OptionSpec {
+        name : "--adaptive-threshold", group : Group::Iteration,
+        value : Some("F"),
+        help : "stop escalating below this fraction of newly resolved pixels",
+        show : |options| Some(options.adaptive_threshold.to_string()),
+        apply : |options, value| {
+            options.adaptive_threshold = f64::from_str(value)
+                .map_err(|_| expected("a fraction such as 0.001", value))?;
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--max-iterations", group : Group::Iteration,
+        value : Some("N"),
+        help : "cap for --adaptive-iterations (default 16320)",
+        show : |options| Some(options.max_iterations.to_string()),
+        apply : |options, value| {
+            options.max_iterations = parse_positive_u32(value)?;
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--chromatic", group : Group::Colouring,
+        value : Some("N"),
+        help : "render R, G, B at limits N apart (about 3x slower)",
+        show : |options| options.chromatic.map(|offset| offset.to_string()),
+        apply : |options, value| {
+            options.chromatic = Some(u32::from_str(value)
+                .map_err(|_| expected("a whole number offset", value))?);
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--palette", group : Group::Colouring,
+        value : Some("NAME|#rrggbb,..."),
+        help : "colour the escape counts (--mode gray or perturbation) through a \
+                built-in or custom palette",
+        show : |options| options.palette.as_ref().map(|palette| toml::quote(palette.spec())),
+        apply : |options, value| {
+            options.palette = Some(Palette::parse(value)?);
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--palette-wrap", group : Group::Colouring,
+        value : None,
+        help : "cycle through the palette for counts past its end \
+                (default clamps to the last colour)",
+        show : |options| Some(options.palette_wrap.to_string()),
+        apply : |options, _| { options.palette_wrap = true; Ok(()) }
+    },
+    OptionSpec {
+        name : "--linear-blend", group : Group::Colouring,
+        value : None,
+        help : "blend a built-in palette's colours in linear light, for \
+                cleaner midtones than blending the sRGB values",
+        show : |options| Some(options.linear_blend.to_string()),
+        apply : |options, _| { options.linear_blend = true; Ok(()) }
+    },
+    OptionSpec {
+        name : "--blend", group : Group::Colouring,
+        value : Some("smooth:W,orbit-trap:W"),
+        help : "mix two colourings in linear light by their weights W, smooth \
+                iteration counts (the glow outside the set) and an orbit trap on \
+                the axes (filaments inside and along its edge), in gray or through \
+                --palette; only the ratio of the weights counts, a source left out \
+                has none",
+        show : |options| options.blend.map(|blend| toml::quote(&blend.name())),
+        apply : |options, value| {
+            options.blend = Some(Blend::parse(value)?);
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--heatmap", group : Group::Colouring,
+        value : None,
+        help : "colour the escape counts evenly from the lowest in the view to the \
+                highest through --palette (default viridis, magma is the other \
+                scientific one) and put a colorbar of the counts beside the image",
+        show : |options| Some(options.heatmap.to_string()),
+        apply : |options, _| { options.heatmap = true; Ok(()) }
+    },
+    OptionSpec {
+        name : "--cost-map", group : Group::Colouring,
+        value : None,
+        help : "show what each pixel cost instead, the iterations run there from \
+                none to the most any pixel took, through --palette (default magma) \
+                with a colorbar",
+        show : |options| Some(options.cost_map.to_string()),
+        apply : |options, _| { options.cost_map = true; Ok(()) }
+    },
+    OptionSpec {
+        name : "--show-glitches", group : Group::Colouring,
+        value : None,
+        help : "paint the pixels --mode perturbation had to iterate again against \
+                another reference orbit in magenta",
+        show : |options| Some(options.show_glitches.to_string()),
+        apply : |options, _| { options.show_glitches = true; Ok(()) }
+    },
+    OptionSpec {
+        name : "--gamma", group : Group::Colouring,
+        value : Some("G"),
+        help : "raise the gray brightness to the power G, 2.2 for the curve of most \
+                displays : above 1 darkens the midtones, below 1 lightens them (default 1)",
+        show : |options| Some(options.gamma.to_string()),
+        apply : |options, value| {
+            options.gamma = parse_positive_f64(value)?;
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--interior-value", group : Group::Colouring,
+        value : Some("0-255"),
+        help : "the gray of points inside the set (default 0, black)",
+        show : |options| Some(options.interior_value.to_string()),
+        apply : |options, value| {
+            options.interior_value = u8::from_str(value)
+                .map_err(|_| expected("a gray from 0 to 255", value))?;
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--exterior", group : Group::Colouring,
+        value : Some("light-to-dark|dark-to-light"),
+        help : "which way the gray runs outside the set, from the quickest points to \
+                escape to the slowest (default light-to-dark)",
+        show : |options| Some(toml::quote(match options.exterior {
+            Exterior::LightToDark => "light-to-dark",
+            Exterior::DarkToLight => "dark-to-light"
+        })),
+        apply : |options, value| {
+            options.exterior = match value {
+                "light-to-dark" => Exterior::LightToDark,
+                "dark-to-light" => Exterior::DarkToLight,
+                _ => return Err(expected("light-to-dark or dark-to-light", value))
+            };
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--auto-exposure", group : Group::Colouring,
+        value : None,
+        help : "stretch the gray between the counts of the darkest and brightest \
+                percent of the escaping points, clipping the rest",
+        show : |options| Some(options.auto_exposure.to_string()),
+        apply : |options, _| { options.auto_exposure = true; Ok(()) }
+    },
+    OptionSpec {
+        name : "--exposure-clip", group : Group::Colouring,
+        value : Some("LOW,HIGH"),
+        help : "the percentiles of the escape counts --auto-exposure stretches \
+                between (default 1,99)",
+        show : |options| options.exposure_clip
+            .map(|(low, high)| toml::quote(&format!("{},{}", low, high))),
+        apply : |options, value| {
+            let clip = parse_pair::<f64>(value, ',').ok()
+                .filter(|&(low, high)| 0.0 <= low && low < high && high <= 100.0)
+                .ok_or_else(|| expected("two percentiles LOW,HIGH with \
+                                         0 <= LOW < HIGH <= 100", value))?;
+            options.exposure_clip = Some(clip);
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--format", group : Group::Output,
+        value : Some("png|csv"),
+        help : "write a PNG image or a CSV grid of escape counts",
+        show : |options| Some(toml::quote(match options.format {
+            Format::Png => "png",
+            Format::Csv => "csv"
+        })),
+        apply : |options, value| {
+            options.format = match value {
+                "png" => Format::Png,
+                "csv" => Format::Csv,
+                _ => return Err(expected("either png or csv", value))
+            };
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--png-compression", group : Group::Output,
+        value : Some("fast|default|best"),
+        help : "encode PNGs in about half the time for much the same size, or in \
+                10 to 20 times the time for a file 10 to 20% smaller (default \
+                default)",
+        show : |options| Some(toml::quote(match options.png_compression {
+            PngCompression::Fast => "fast",
+            PngCompression::Default => "default",
+            PngCompression::Best => "best"
+        })),
+        apply : |options, value| {
+            options.png_compression = match value {
+                "fast" => PngCompression::Fast,
+                "default" => PngCompression::Default,
+                "best" => PngCompression::Best,
+                _ => return Err(expected("fast, default or best", value))
+            };
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--csv-interior", group : Group::Output,
+        value : Some("TEXT"),
+        help : "what to write in the CSV for interior points (default blank)",
+        show : |options| Some(toml::quote(&options.csv_interior)),
+        apply : |options, value| {
+            options.csv_interior = value.to_string();
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--no-clobber", group : Group::Output,
+        value : None,
+        help : "refuse to overwrite existing files (what happens anyway now, kept so \
+                older scripts still run)",
+        show : |options| Some(options.no_clobber.to_string()),
+        apply : |options, _| { options.no_clobber = true; Ok(()) }
+    },
+    OptionSpec {
+        name : "--force", group : Group::Output,
+        value : None,
+        help : "overwrite existing files instead of refusing to",
+        show : |options| Some(options.force.to_string()),
+        apply : |options, _| { options.force = true; Ok(()) }
+    },
+    OptionSpec {
+        name : "--skip-existing", group : Group::Output,
+        value : None,
+        help : "leave files that are already there as they are and carry on, so an \
+                interrupted animate or --pyramid picks up where it stopped",
+        show : |options| Some(options.skip_existing.to_string()),
+        apply : |options, _| { options.skip_existing = true; Ok(()) }
+    },
+    OptionSpec {
+        name : "--allow-uniform", group : Group::Output,
+        value : None,
+        help : "don't warn about a render that comes out nearly all one colour",
+        show : |options| Some(options.allow_uniform.to_string()),
+        apply : |options, _| { options.allow_uniform = true; Ok(()) }
+    },
+    OptionSpec {
+        name : "--open", group : Group::Viewer,
+        value : None,
+        help : "open the written image in the system's image viewer",
+        show : |options| Some(options.open.to_string()),
+        apply : |options, _| { options.open = true; Ok(()) }
+    },
+    OptionSpec {
+        name : "--watch", group : Group::Viewer,
+        value : None,
+        help : "keep running and render again whenever the --config file (or the \
+                user presets) change",
+        show : |_| None,
+        apply : |options, _| { options.watch = true; Ok(()) }
+    },
+    OptionSpec {
+        name : "--frames", group : Group::Animation,
+        value : Some("N"),
+        help : "number of frames to render (default 30)",
+        show : |options| Some(options.frames.to_string()),
+        apply : |options, value| {
+            options.frames = parse_positive_u32(value)? as usize;
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--parallel-frames", group : Group::Animation,
+        value : Some("N"),
+        help : "render up to N frames at the same time, each on its share of --threads, \
+                for animations of frames too small to keep every thread busy (default 1)",
+        show : |options| Some(options.parallel_frames.to_string()),
+        apply : |options, value| {
+            options.parallel_frames = parse_positive_u32(value)? as usize;
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--zoom-per-frame", group : Group::Animation,
+        value : Some("F"),
+        help : "how much narrower each frame is than the last (default 1.1, \
+                or 1 with --julia-path or --pan-per-frame)",
+        show : |options| options.zoom_per_frame.map(|zoom| zoom.to_string()),
+        apply : |options, value| {
+            options.zoom_per_frame = Some(parse_positive_f64(value)?);
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--pan-per-frame", group : Group::Animation,
+        value : Some("COLUMNS,ROWS"),
+        help : "move each frame this many pixels right and down from the last (default \
+                0,0), frames that move by whole pixels without zooming only render the \
+                strip they move into",
+        show : |options| options.pan_per_frame.map(|(columns, rows)| {
+            toml::quote(&format!("{},{}", columns, rows))
+        }),
+        apply : |options, value| {
+            options.pan_per_frame = Some(parse_pan(value)?);
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--iteration-scale", group : Group::Animation,
+        value : Some("S"),
+        help : "grow the iterations by S times --iterations for every tenfold zoom, \
+                so deep frames keep their detail (default 0, the same every frame)",
+        show : |options| Some(options.iteration_scale.to_string()),
+        apply : |options, value| {
+            options.iteration_scale = f64::from_str(value).ok()
+                .filter(|&scale| scale >= 0.0 && scale.is_finite())
+                .ok_or_else(|| expected("a number 0 or more", value))?;
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--julia-path", group : Group::Animation,
+        value : Some("circle:RE,IM,RADIUS"),
+        help : "animate the Julia set of a constant going once around a circle",
+        show : |options| options.julia_path.map(|path| match path {
+            JuliaPath::Circle { center, radius } =>
+                toml::quote(&format!("circle:{},{},{}", center.re, center.im, radius))
+        }),
+        apply : |options, value| {
+            options.julia_path = Some(parse_julia_path(value)?);
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--manifest", group : Group::Animation,
+        value : Some("FILE"),
+        help : "where to list the frames (default manifest.txt next to them)",
+        show : |options| options.manifest.as_ref().map(|manifest| quote_path(manifest)),
+        apply : |options, value| {
+            options.manifest = Some(PathBuf::from(value));
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--orbit-cache", group : Group::Animation,
+        value : Some("FILE"),
+        help : "keep the reference orbits of --perturbation in FILE from one run to the \
+                next, read before the first frame and written after the last",
+        show : |options| options.orbit_cache.as_ref().map(|path| quote_path(path)),
+        apply : |options, value| {
+            options.orbit_cache = Some(PathBuf::from(value));
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--listen", group : Group::Serve,
+        value : Some("ADDRESS"),
+        help : "address to serve on (default 127.0.0.1:8080)",
+        show : |options| Some(toml::quote(&options.listen)),
+        apply : |options, value| { options.listen = value.to_string(); Ok(()) }
+    },
+    OptionSpec {
+        name : "--max-request-iterations", group : Group::Serve,
+        value : Some("N"),
+        help : "answer requests for more than N iterations a pixel (with --iterations, \
+                --max-iterations, --limit-center, --limit-edge or --diff-limits) with 400 \
+                Bad Request (default 16320)",
+        show : |options| Some(options.max_request_iterations.to_string()),
+        apply : |options, value| {
+            options.max_request_iterations = parse_positive_u32(value)?;
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--point", group : Group::Probe,
+        value : Some("RE,IM"),
+        help : "the point on the complex plane to probe (RE,IM or a+bi)",
+        show : |options| options.point.map(show_point),
+        apply : |options, value| {
+            options.point = Some(parse_point(value)?);
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--to", group : Group::Probe,
+        value : Some("RE,IM"),
+        help : "probe points along the line from --point to here instead",
+        show : |options| options.line_to.map(show_point),
+        apply : |options, value| {
+            options.line_to = Some(parse_point(value)?);
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--steps", group : Group::Probe,
+        value : Some("N"),
+        help : "number of points along the --to line (default 100)",
+        show : |options| Some(options.steps.to_string()),
+        apply : |options, value| {
+            options.steps = parse_positive_u32(value)? as usize;
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--orbit", group : Group::Probe,
+        value : None,
+        help : "print every z the point's orbit goes through until it escapes, as \
+                N,RE,IM lines",
+        show : |options| Some(options.orbit.to_string()),
+        apply : |options, _| { options.orbit = true; Ok(()) }
+    },
+    OptionSpec {
+        name : "--samples", group : Group::Area,
+        value : Some("N"),
+        help : "number of random points to sample (default 1000000)",
+        show : |options| Some(options.samples.to_string()),
+        apply : |options, value| {
+            options.samples = parse_positive_u32(value)? as usize;
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--seed", group : Group::Area,
+        value : Some("N"),
+        help : "seed of the random points, the same seed gives the same estimate",
+        show : |options| Some(options.seed.to_string()),
+        apply : |options, value| {
+            options.seed = u64::from_str(value)
+                .map_err(|_| expected("a whole number", value))?;
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--title", group : Group::Caption,
+        value : Some("TEXT"),
+        help : "add rows under the image with TEXT on them, the image itself keeps \
+                its size",
+        show : |options| options.title.as_deref().map(toml::quote),
+        apply : |options, value| { options.title = Some(value.to_string()); Ok(()) }
+    },
+    OptionSpec {
+        name : "--title-coordinates", group : Group::Caption,
+        value : None,
+        help : "add a line with the center and width of the view under the image \
+                (under the --title if there is one)",
+        show : |options| Some(options.title_coordinates.to_string()),
+        apply : |options, _| { options.title_coordinates = true; Ok(()) }
+    },
+    OptionSpec {
+        name : "--border-color", group : Group::Caption,
+        value : Some("#RRGGBB"),
+        help : "colour of the rows the caption is on (default #000000), the text \
+                is black or white whichever shows up better",
+        show : |options| {
+            let [r, g, b] = options.border_color;
+            Some(toml::quote(&format!("#{:02x}{:02x}{:02x}", r, g, b)))
+        },
+        apply : |options, value| {
+            options.border_color = palette::parse_hex_color(value)?;
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--font-size", group : Group::Caption,
+        value : Some("PIXELS"),
+        help : "height of the caption's capitals, rounded down to a multiple of 5 \
+                (default a 40th of the image's width)",
+        show : |options| options.font_size.map(|size| size.to_string()),
+        apply : |options, value| {
+            options.font_size = usize::from_str(value).ok()
+                .filter(|&size| size >= 5)
+                .map(Some)
+                .ok_or_else(|| expected("a size of at least 5 pixels", value))?;
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--census-threshold", group : Group::Census,
+        value : Some("RATIO"),
+        help : "how many times the median count around it a pixel's count has to be \
+                to count as a mini-Mandelbrot too small to see (default 2)",
+        show : |options| Some(options.census_threshold.to_string()),
+        apply : |options, value| {
+            options.census_threshold = f64::from_str(value).ok()
+                .filter(|&threshold| threshold > 1.0)
+                .ok_or_else(|| expected("a number above 1", value))?;
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--from-pixel", group : Group::Measure,
+        value : Some("X,Y"),
+        help : "the pixel to measure from, 0,0 being the upper left corner of the \
+                view and WIDTH,HEIGHT its lower right",
+        show : |options| options.from_pixel.map(show_pixel),
+        apply : |options, value| {
+            options.from_pixel = Some(parse_pixel(value)?);
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--to-pixel", group : Group::Measure,
+        value : Some("X,Y"),
+        help : "the pixel to measure to",
+        show : |options| options.to_pixel.map(show_pixel),
+        apply : |options, value| {
+            options.to_pixel = Some(parse_pixel(value)?);
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--columns", group : Group::Stitch,
+        value : Some("N"),
+        help : "number of images per row (default 2, 4 for contact-sheet)",
+        show : |options| Some(options.columns.to_string()),
+        apply : |options, value| {
+            options.columns = parse_positive_u32(value)? as usize;
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--cell-size", group : Group::ContactSheet,
+        value : Some("WxH"),
+        help : "the size of each preset's picture, the name goes under it \
+                (default 240x180)",
+        show : |options| Some(toml::quote(&format!("{}x{}", options.cell_size.0,
+                                                   options.cell_size.1))),
+        apply : |options, value| {
+            options.cell_size = parse_size(value)?;
+            if options.cell_size.0 == 0 || options.cell_size.1 == 0 {
+                return Err(expected("a size of at least 1x1", value));
+            }
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--dry-run", group : Group::Plan,
+        value : None,
+        help : "print what would be rendered (view, limits, threads, memory) \
+                and stop before computing anything",
+        show : |_| None,
+        apply : |options, _| { options.dry_run = true; Ok(()) }
+    },
+    OptionSpec {
+        name : "--estimate", group : Group::Plan,
+        value : None,
+        help : "time a sparse sample of the pixels first and print roughly how \
+                long the run will take (part of the plan with --dry-run)",
+        show : |_| None,
+        apply : |options, _| { options.estimate = true; Ok(()) }
+    },
+    OptionSpec {
+        name : "--json", group : Group::Plan,
+        value : None,
+        help : "print the --dry-run plan or the --scaling-sweep report as JSON",
+        show : |_| None,
+        apply : |options, _| { options.json = true; Ok(()) }
+    },
+    OptionSpec {
+        name : "--preview-palette", group : Group::Swatch,
+        value : Some("NAME|#rrggbb,..."),
+        help : "instead of rendering, write a swatch of the palette's colours \
+                for counts 0 to --iterations",
+        show : |_| None,
+        apply : |options, value| {
+            options.preview_palette = Some(Palette::parse(value)?);
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--swatch-size", group : Group::Swatch,
+        value : Some("WIDTHxHEIGHT"),
+        help : "dimensions of the --preview-palette swatch (default 512x64)",
+        show : |options| Some(toml::quote(&format!("{}x{}", options.swatch_size.0,
+                                                   options.swatch_size.1))),
+        apply : |options, value| {
+            let size = parse_size(value)?;
+            if size.0 == 0 || size.1 == 0 {
+                return Err(expected("a size of at least 1x1", value));
+            }
+            options.swatch_size = size;
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--pyramid", group : Group::Pyramid,
+        value : Some("DIR"),
+        help : "instead of one image, write the view as 256x256 tiles \
+                DIR/z/x/y.png for web map viewers, 2^z by 2^z tiles at level z",
+        show : |_| None,
+        apply : |options, value| { options.pyramid = Some(PathBuf::from(value)); Ok(()) }
+    },
+    OptionSpec {
+        name : "--levels", group : Group::Pyramid,
+        value : Some("N"),
+        help : "zoom levels of the --pyramid, 0 to N-1 (default 6)",
+        show : |options| Some(options.levels.to_string()),
+        apply : |options, value| {
+            let levels = parse_positive_u32(value)?;
+            if levels > MAX_LEVELS {
+                return Err(format!("at most {} levels, {} would be {} tiles", MAX_LEVELS,
+                                   levels, (4u64.pow(levels) - 1) / 3));
+            }
+            options.levels = levels as usize;
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--jobs", group : Group::Jobs,
+        value : Some("FILE"),
+        help : "render every [[job]] table of a TOML file (keys as in --config, \
+                shared ones under [defaults], {name} and {center} in the output), \
+                --force and --skip-existing go for every job",
+        show : |_| None,
+        apply : |options, value| { options.jobs = Some(PathBuf::from(value)); Ok(()) }
+    },
+    OptionSpec {
+        name : "--jobs-stdin", group : Group::Jobs,
+        value : None,
+        help : "render a job per line of JSON on stdin as it arrives (keys as \
+                in --config), answering with a line of JSON each",
+        show : |_| None,
+        apply : |options, _| { options.jobs_stdin = true; Ok(()) }
+    },
+    OptionSpec {
+        name : "--parallel-jobs", group : Group::Jobs,
+        value : Some("N"),
+        help : "render up to N jobs at the same time (default 1)",
+        show : |_| None,
+        apply : |options, value| {
+            options.parallel_jobs = parse_positive_u32(value)? as usize;
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--only", group : Group::Jobs,
+        value : Some("NAME"),
+        help : "render only the job with this name",
+        show : |_| None,
+        apply : |options, value| { options.only = Some(value.to_string()); Ok(()) }
+    },
+    OptionSpec {
+        name : "--config", group : Group::General,
+        value : Some("FILE"),
+        help : "read options from a TOML file, the command line wins",
+        show : |_| None,
+        apply : |options, value| { options.config = Some(PathBuf::from(value)); Ok(()) }
+    },
+    OptionSpec {
+        name : "--print-config", group : Group::General,
+        value : None,
+        help : "print the options as a TOML config file instead of running",
+        show : |_| None,
+        apply : |options, _| { options.print_config = true; Ok(()) }
+    },
+    OptionSpec {
+        name : "--locale-decimal", group : Group::General,
+        value : Some("dot|comma"),
+        help : "with comma, points on the command line are written -1,2;0,35 : ';' \
+                between the parts and ',' as the decimal point (default dot)",
+        show : |_| None,
+        apply : |options, value| {
+            options.decimal_comma = match value {
+                "dot" => false,
+                "comma" => true,
+                _ => return Err(expected("dot or comma", value))
+            };
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--quiet", group : Group::General,
+        value : None,
+        help : "print nothing but errors",
+        show : |_| None,
+        apply : |options, _| {
+            if options.verbosity > Level::Normal {
+                return Err("can't be given with --verbose".to_string());
+            }
+            options.verbosity = Level::Quiet;
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--verbose", group : Group::General,
+        value : None,
+        help : "-v, also print the resolved configuration and how long each stage \
+                took, given twice (-vv) how long each band took too",
+        show : |_| None,
+        apply : |options, _| {
+            options.verbosity = match options.verbosity {
+                Level::Quiet => return Err("can't be given with --quiet".to_string()),
+                Level::Normal => Level::Verbose,
+                Level::Verbose | Level::Debug => Level::Debug
+            };
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--help", group : Group::General,
+        value : None,
+        help : "print this help",
+        show : |_| None,
+        apply : |options, _| { options.help = true; Ok(()) }
+    },
+    OptionSpec {
+        name : "--version", group : Group::General,
+        value : None,
+        help : "print the version and which threads it renders on",
+        show : |_| None,
+        apply : |options, _| { options.version = true; Ok(()) }
+    },
+    OptionSpec {
+        name : "--progress", group : Group::General,
+        value : Some("human|json"),
+        help : "report a long render's progress as a line every second, or as a \
+                JSON object a line for programs watching the run (default human)",
+        show : |options| {
+            Some(toml::quote(if options.progress_json { "json" } else { "human" }))
+        },
+        apply : |options, value| {
+            options.progress_json = match value {
+                "human" => false,
+                "json" => true,
+                _ => return Err(expected("either human or json", value))
+            };
+            Ok(())
+        }
+    },
+    OptionSpec {
+        name : "--progress-file", group : Group::General,
+        value : Some("FILE"),
+        help : "write progress to FILE instead of stderr",
+        show : |options| options.progress_file.as_ref().map(|file| quote_path(file)),
+        apply : |options, value| { options.progress_file = Some(PathBuf::from(value)); Ok(()) }
+    },
+    OptionSpec {
+        name : "--stats", group : Group::General,
+        value : Some("FILE"),
+        help : "write how long every band and every stage of the run took to FILE, \
+                as JSON (-v prints a summary)",
+        show : |options| options.stats.as_ref().map(|file| quote_path(file)),
+        apply : |options, value| { options.stats = Some(PathBuf::from(value)); Ok(()) }
+    }
+];
+
+//The --output that asks for a file name made up from the view
+pub const AUTO_OUTPUT : &str = "auto";
+
+type SetPath = fn(&mut Options, PathBuf);
+
+//The options that name a file or a directory. On the command line they are
+//set through these instead of their apply(), which takes text, so that the
+//name doesn't have to be UTF-8; everything else does.
+const PATH_OPTIONS : &[(&str, SetPath)] = &[
+    ("--output", |options, path| options.output = Some(path)),
+    ("--output-dir", |options, path| options.output_dir = Some(path)),
+    ("--manifest", |options, path| options.manifest = Some(path)),
+    ("--orbit-cache", |options, path| options.orbit_cache = Some(path)),
+    ("--pyramid", |options, path| options.pyramid = Some(path)),
+    ("--jobs", |options, path| options.jobs = Some(path)),
+    ("--config", |options, path| options.config = Some(path)),
+    ("--progress-file", |options, path| options.progress_file = Some(path)),
+    ("--stats", |options, path| options.stats = Some(path)),
+    ("--bench-log", |options, path| options.bench_log = Some(path))
+];
+
+//The options that take a point, which --locale-decimal comma applies to
+const POINT_OPTIONS : &[&str] = &["--upper-left", "--lower-right", "--center", "--pan",
+                                  "--julia", "--point", "--to"];
+
+//Applies `value` from the command line to the option of `spec`
+fn apply_arg(options : &mut Options, spec : &OptionSpec, value : &OsStr)
+    -> Result<(), String>
+{
+    match PATH_OPTIONS.iter().find(|&&(name, _)| name == spec.name) {
+        Some(&(_, set)) => { set(options, PathBuf::from(value)); Ok(()) }
+        None if POINT_OPTIONS.contains(&spec.name) => {
+            let point = command_line_point(options.decimal_comma, text(value)?)?;
+            (spec.apply)(options, &point)
+        }
+        None => (spec.apply)(options, text(value)?)
+    }
+}
+
+//A point as written on the command line, turned into the form parse_point()
+//reads. With --locale-decimal comma that means -1,2;0,35 becomes
+//-1.2,0.35, and -0,74+0,13i becomes -0.74+0.13i. Either way of writing
+//points rules the other out, so nothing is read two ways : without the
+//flag a ';' is an error as it always was, with it a '.' is, and so is a
+//comma that could be a decimal point or the separator (-1,2 on its own).
+//Config files, presets and bookmarks always use dots.
+fn command_line_point(decimal_comma : bool, value : &str) -> Result<String, String> {
+    if !decimal_comma {
+        return Ok(value.to_string());
+    }
+    let hint = "with --locale-decimal comma a point is written -1,2;0,35";
+    if value.contains('.') {
+        return Err(format!("'{}' has a '.', {}", value, hint));
+    }
+    if value.contains(',') && !value.contains(';') && !value.trim_end().ends_with('i') {
+        return Err(format!("'{}' is ambiguous, {} (or -1,2;0 for a point on the \
+                            real axis)", value, hint));
+    }
+    Ok(value.replace(',', ".").replace(';', ","))
+}
+
+//A command line argument that has to be text
+fn text(arg : &OsStr) -> Result<&str, String> {
+    arg.to_str().ok_or_else(|| format!("'{}' isn't valid UTF-8", arg.to_string_lossy()))
+}
+
+//A path as a TOML string. TOML has to be UTF-8, so a name that isn't comes
+//out with U+FFFD in place of the bytes that aren't.
+fn quote_path(path : &Path) -> String {
+    toml::quote(&path.to_string_lossy())
+}
+
+//Level 15 alone is over a billion tiles
+const MAX_LEVELS : u32 = 16;
+
+//The built-in presets make three rows of them
+const CONTACT_SHEET_COLUMNS : usize = 4;
+
+fn expected(what : &str, value : &str) -> String {
+    format!("expected {}, got '{}'", what, value)
+}
+
+fn parse_size(value : &str) -> Result<(usize, usize), String> {
+    parse_pair(value, 'x')
+        .map_err(|error| error.describe("an image size", ["width", "height"], "1000x750"))
+}
+
+fn parse_point(value : &str) -> Result<Complex<f64>, String> {
+    let parts = if value.trim_start().starts_with("polar:") {
+        ["modulus", "angle"]
+    } else {
+        ["real part", "imaginary part"]
+    };
+    parse_complex(value)
+        .map_err(|error| error.describe("a complex point", parts,
+                                        "-1.20,0.35, -pi/4,e/10, -0.74+0.13i \
+                                         or polar:0.7,45deg"))
+}
+
+//circle:RE,IM,RADIUS, the center being any form of point parse_point()
+//takes
+fn parse_julia_path(value : &str) -> Result<JuliaPath, String> {
+    let circle = value.strip_prefix("circle:")
+        .ok_or_else(|| expected("a path like circle:-0.75,0,0.1", value))?;
+    let (center, radius) = circle.rsplit_once(',')
+        .ok_or_else(|| expected("a path like circle:-0.75,0,0.1", value))?;
+    Ok(JuliaPath::Circle {
+        center : parse_point(center)?,
+        radius : parse_positive_f64(radius)?
+    })
+}
+
+//Pixels to move by, any number of them either way
+fn parse_pan(value : &str) -> Result<(f64, f64), String> {
+    let (columns, rows) = parse_pair::<f64>(value, ',')
+        .map_err(|error| error.describe("a move in pixels", ["columns", "rows"], "4,-2"))?;
+    match columns.is_finite() && rows.is_finite() {
+        true => Ok((columns, rows)),
+        false => Err(expected("a move in pixels such as 4,-2", value))
+    }
+}
+
+fn parse_positive_u32(value : &str) -> Result<u32, String> {
+    u32::from_str(value).ok()
+        .filter(|&value| value > 0)
+        .ok_or_else(|| expected("a positive whole number", value))
+}
+
+fn parse_positive_f64(value : &str) -> Result<f64, String> {
+    f64::from_str(value).ok()
+        .filter(|&value| value > 0.0 && value.is_finite())
+        .ok_or_else(|| expected("a positive number", value))
+}
+
+//A length of time such as 5s, 500ms or 2m, plain numbers are seconds
+fn parse_duration(value : &str) -> Result<Duration, String> {
+    let (number, unit) = match value.find(|c : char| c.is_ascii_alphabetic()) {
+        Some(index) => value.split_at(index),
+        None => (value, "s")
+    };
+    let seconds = match unit {
+        "ms" => 0.001,
+        "s" => 1.0,
+        "m" => 60.0,
+        _ => return Err(expected("a time such as 5s, 500ms or 2m", value))
+    };
+    f64::from_str(number).ok()
+        .filter(|&number| number > 0.0 && number.is_finite())
+        .map(|number| Duration::from_secs_f64(number * seconds))
+        .ok_or_else(|| expected("a time such as 5s, 500ms or 2m", value))
+}
+
+//A number of bytes, or of KiB, MiB or GiB with a K, M or G after it
+fn parse_bytes(value : &str) -> Result<usize, String> {
+    let (number, unit) = match value.find(|c : char| c.is_ascii_alphabetic()) {
+        Some(index) => value.split_at(index),
+        None => (value, "")
+    };
+    let unit : usize = match unit {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        _ => return Err(expected("a size such as 512M or 2G", value))
+    };
+    usize::from_str(number).ok()
+        .and_then(|number| number.checked_mul(unit))
+        .filter(|&bytes| bytes > 0)
+        .ok_or_else(|| expected("a size such as 512M or 2G", value))
+}
+
+fn show_point(point : Complex<f64>) -> String {
+    toml::quote(&format!("{},{}", point.re, point.im))
+}
+
+fn parse_pixel(value : &str) -> Result<(usize, usize), String> {
+    parse_pair(value, ',').map_err(|error| error.describe("a pixel", ["x", "y"], "120,45"))
+}
+
+fn show_pixel(pixel : (usize, usize)) -> String {
+    toml::quote(&format!("{},{}", pixel.0, pixel.1))
+}
+
+//Before a layer of options (config file, command line) goes over the ones
+//below it : corners in the new layer replace a center/width from below, and
+//a center or width replaces corners, rather than the two clashing
+fn make_room_for_view(options : &mut Options, names : &[&str]) {
+    let given = |name| names.contains(&name);
+    if given("--upper-left") || given("--lower-right") {
+        options.center = None;
+        options.center_text = None;
+        options.width = None;
+        options.zoom = None;
+    }
+    if given("--center") || given("--width") || given("--zoom") {
+        options.upper_left = None;
+        options.lower_right = None;
+    }
+    //So are --size and --megapixels
+    if given("--size") {
+        options.megapixels = None;
+        options.aspect = None;
+    }
+    if given("--megapixels") {
+        options.size = None;
+    }
+    //--width and --zoom are two ways of saying the same thing
+    if given("--width") {
+        options.zoom = None;
+    }
+    if given("--zoom") {
+        options.width = None;
+    }
+}
+
+fn apply_preset(options : &mut Options, name : &str) -> Result<(), String> {
+    let preset = presets::find(name)?;
+    options.center = Some(preset.center);
+    options.center_text = None;
+    options.width = Some(preset.width);
+    options.iterations = preset.iterations;
+    for option in ["--center", "--width", "--iterations"] {
+        set_source(options, option, format!("preset {}", name));
+    }
+    Ok(())
+}
+
+//The bookmark's keys go in the way a config file's do
+fn apply_bookmark(command : Command, options : &mut Options, name : &str)
+    -> Result<(), String>
+{
+    let (path, entries) = bookmarks::find(name)?;
+    let names = entries.iter().map(|entry| format!("--{}", entry.key)).collect::<Vec<_>>();
+    make_room_for_view(options, &names.iter().map(|name| name.as_str()).collect::<Vec<_>>());
+    apply_config_entries(command, options, &format!("{} [{}]", path.display(), name),
+                         &entries)
+}
+
+//The options a bookmark keeps, written as --save-bookmark saves them
+const BOOKMARKED : &[&str] = &["--center", "--width", "--rotate", "--iterations", "--mode",
+                               "--band-width", "--julia", "--palette", "--palette-wrap",
+                               "--linear-blend", "--gamma", "--interior-value",
+                               "--exterior"];
+
+//The `key = value` lines of `options` that make up a bookmark
+pub fn bookmark_toml(options : &Options) -> String {
+    BOOKMARKED.iter()
+        .filter_map(|&name| OPTIONS.iter().find(|spec| spec.name == name))
+        .filter_map(|spec| (spec.show)(options)
+                    .map(|value| format!("{} = {}\n", &spec.name[2..], value)))
+        .collect()
+}
+
+//The `key=value` lines of `options` that go in a permalink, the bookmarked
+//options that aren't at their defaults
+pub fn permalink_lines(options : &Options) -> Vec<String> {
+    let defaults = Options::default();
+    BOOKMARKED.iter()
+        .filter_map(|&name| OPTIONS.iter().find(|spec| spec.name == name))
+        .filter_map(|spec| (spec.show)(options)
+                    .filter(|value| Some(value) != (spec.show)(&defaults).as_ref())
+                    .map(|value| format!("{}={}", &spec.name[2..], value)))
+        .collect()
+}
+
+//The keys of a permalink go in the way a bookmark's do. Only the keys a
+//bookmark keeps are taken, so that a permalink from someone else can't say
+//where files are written.
+fn apply_permalink(command : Command, options : &mut Options, text : &str)
+    -> Result<(), String>
+{
+    let entries = permalink::decode(text)
+        .map_err(|message| format!("--from-permalink : {}", message))?;
+    let names = entries.iter().map(|entry| format!("--{}", entry.key)).collect::<Vec<_>>();
+    if let Some(name) = names.iter().find(|name| !BOOKMARKED.contains(&name.as_str())) {
+        return Err(format!("--from-permalink : '{}' isn't a key a permalink holds",
+                           &name[2..]));
+    }
+    make_room_for_view(options, &names.iter().map(|name| name.as_str()).collect::<Vec<_>>());
+    apply_config_entries(command, options, "--from-permalink", &entries)
+}
+
+fn set_source(options : &mut Options, name : &'static str, source : String) {
+    options.sources.retain(|&(option, _)| option != name);
+    options.sources.push((name, source));
+}
+
+//The environment variables that stand in for options, for setups where
+//changing the environment is easier than changing the command line.
+//They go underneath everything but the defaults.
+const ENVIRONMENT : &[(&str, &str)] = &[
+    ("MANDELBROT_LIMIT", "--iterations"),
+    ("MANDELBROT_THREADS", "--threads"),
+    ("MANDELBROT_OUTPUT_DIR", "--output-dir"),
+    ("MANDELBROT_MAX_PIXELS", "--max-pixels"),
+    ("MANDELBROT_MAX_MEMORY", "--max-memory")
+];
+
+fn apply_environment(command : Command, options : &mut Options) -> Result<(), String> {
+    for &(variable, name) in ENVIRONMENT {
+        let spec = match OPTIONS.iter()
+            .find(|spec| spec.name == name && command.accepts(spec.group))
+        {
+            Some(spec) => spec,
+            None => continue
+        };
+        //As on the command line, a directory doesn't have to be UTF-8
+        let value = match std::env::var_os(variable) {
+            Some(value) => value,
+            None => continue
+        };
+        apply_arg(options, spec, &value)
+            .map_err(|message| format!("{} : {}", variable, message))?;
+        set_source(options, spec.name, variable.to_string());
+    }
+    Ok(())
+}
+
+//Parses the arguments (without the program name) into the subcommand
+//and its Options.
+//
+//If the first argument isn't the name of a subcommand everything is handed
+//to `render`. Anything not starting with "--" is positional. For backward
+//compatibility render still takes the old `File Pixels Upperleft Lowerright`
+//form when exactly those four positional arguments are given (and
+//`File Pixels` together with --center/--width).
+pub fn parse(args : &[OsString]) -> Result<(Command, Options), String> {
+    let (command, args) = match args.first()
+        .and_then(|first| COMMANDS.iter().find(|&&(name, _, _)| name == first))
+    {
+        Some(&(_, command, _)) => (command, &args[1..]),
+        None => (Command::Render, args)
+    };
+
+    let mut options = Options::default();
+    if command == Command::ContactSheet {
+        options.columns = CONTACT_SHEET_COLUMNS;
+    }
+    let mut positional = Vec::new();
+    let mut given = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        //The only short options, -vv being --verbose twice
+        let verbose = match arg.to_str() {
+            Some("-v") => 1,
+            Some("-vv") => 2,
+            _ => 0
+        };
+        if verbose > 0 {
+            let spec = OPTIONS.iter().find(|spec| spec.name == "--verbose").unwrap();
+            given.extend(std::iter::repeat_n((spec, OsStr::new("")), verbose));
+            continue;
+        }
+        if !arg.as_encoded_bytes().starts_with(b"--") {
+            positional.push(arg);
+            continue;
+        }
+        let arg = text(arg)?;
+        let spec = OPTIONS.iter()
+            .find(|spec| spec.name == arg && command.accepts(spec.group))
+            .ok_or_else(|| format!("unknown option '{}' for {} (see {} --help)",
+                                   arg, command.name(), command.name()))?;
+        let value = match spec.value {
+            None => OsStr::new(""),
+            Some(format) => args.next()
+                .ok_or_else(|| format!("{} needs a value : {}", spec.name, format))?
+        };
+        given.push((spec, value));
+    }
+
+    //The general options first, they say whether there is a config file to
+    //put underneath the rest of the command line
+    for &(spec, value) in given.iter().filter(|(spec, _)| spec.group == Group::General) {
+        apply_arg(&mut options, spec, value)
+            .map_err(|message| format!("{} : {}", spec.name, message))?;
+    }
+    if options.help || options.version {
+        return Ok((command, options));
+    }
+    let config = match options.config {
+        Some(ref path) => Some((path.clone(), read_config_file(path)?)),
+        None => None
+    };
+
+    //Then the layers from the bottom up : environment, preset, config file,
+    //command line
+    apply_environment(command, &mut options)?;
+    let preset = given.iter()
+        .find(|(spec, _)| spec.name == "--preset")
+        .map(|&(_, value)| value.to_string_lossy().into_owned())
+        .or_else(|| config.as_ref().and_then(|(_, entries)| config_preset(entries)));
+    if let Some(preset) = preset {
+        apply_preset(&mut options, &preset)?;
+    }
+    if let Some(&(_, name)) = given.iter().find(|(spec, _)| spec.name == "--bookmark") {
+        apply_bookmark(command, &mut options, &name.to_string_lossy())?;
+    }
+    if let Some(&(_, text)) = given.iter().find(|(spec, _)| spec.name == "--from-permalink") {
+        apply_permalink(command, &mut options, &text.to_string_lossy())?;
+    }
+    if let Some((path, entries)) = config {
+        let names = entries.iter().map(|entry| format!("--{}", entry.key)).collect::<Vec<_>>();
+        make_room_for_view(&mut options,
+                           &names.iter().map(|name| name.as_str()).collect::<Vec<_>>());
+        apply_config_entries(command, &mut options, &path.display().to_string(), &entries)?;
+    }
+    let named = given.iter().map(|(spec, _)| spec.name).collect::<Vec<_>>();
+    if command == Command::Render {
+        //The legacy positional Pixels and corners
+        match positional.len() {
+            2 => make_room_for_view(&mut options, &["--size"]),
+            4 => make_room_for_view(&mut options, &["--size", "--upper-left"]),
+            _ => ()
+        }
+    }
+    make_room_for_view(&mut options, &named);
+    for &(spec, value) in given.iter().filter(|(spec, _)| spec.group != Group::General) {
+        apply_arg(&mut options, spec, value)
+            .map_err(|message| format!("{} : {}", spec.name, message))?;
+        set_source(&mut options, spec.name, "command line".to_string());
+    }
+    if options.list_presets || options.list_bookmarks {
+        return Ok((command, options));
+    }
+    for (name, timing) in [("--bench", options.bench.is_some()),
+                           ("--scaling-sweep", options.scaling_sweep.is_some())] {
+        if !timing {
+            continue;
+        }
+        if command != Command::Render {
+            return Err(format!("{} only applies to render", name));
+        }
+        if options.watch || options.dry_run || options.pyramid.is_some()
+            || options.preview_palette.is_some() || options.format != Format::Png
+        {
+            return Err(format!("{} can't be combined with --watch, --dry-run, --pyramid, \
+                                --preview-palette or --format csv", name));
+        }
+    }
+    if options.report_iterations && command != Command::Render && command != Command::Animate {
+        return Err("--report-iterations only applies to render and animate".to_string());
+    }
+    if options.bench.is_none() && options.bench_log.is_some() {
+        return Err("--bench-log only applies with --bench".to_string());
+    }
+    if options.scaling_sweep.is_some() {
+        if options.bench.is_some() {
+            return Err("give either --bench or --scaling-sweep, not both".to_string());
+        }
+        if let Some(&(spec, _)) = given.iter()
+            .find(|(spec, _)| spec.name == "--threads" || spec.name == "--single-threaded")
+        {
+            return Err(format!("{} doesn't apply to --scaling-sweep, which sets the \
+                                threads of each run", spec.name));
+        }
+    }
+    if options.preview_palette.is_some() {
+        if let Some(unexpected) = positional.first() {
+            return Err(format!("--preview-palette takes no positional arguments, \
+                                got '{}'", unexpected.to_string_lossy()));
+        }
+        if options.output.is_none() {
+            return Err("no output file given for the swatch (--output FILE)".to_string());
+        }
+        if options.watch {
+            return Err("--watch doesn't apply to --preview-palette".to_string());
+        }
+        return Ok((command, options));
+    }
+    if options.pyramid.is_some() {
+        if let Some(unexpected) = positional.first() {
+            return Err(format!("--pyramid takes no positional arguments, got '{}'",
+                               unexpected.to_string_lossy()));
+        }
+        if options.output.is_some() || options.size.is_some() || options.megapixels.is_some() {
+            return Err("--pyramid writes DIR/z/x/y.png tiles of 256x256 pixels, it \
+                        takes no --output, --size or --megapixels".to_string());
+        }
+        if options.watch || options.dry_run || options.format != Format::Png
+            || options.caption().is_some()
+        {
+            return Err("--pyramid can't be combined with --watch, --dry-run, \
+                        --format csv or a caption".to_string());
+        }
+        return Ok((command, options));
+    }
+    if options.jobs.is_some() && options.jobs_stdin {
+        return Err("give either --jobs or --jobs-stdin, not both".to_string());
+    }
+    if options.jobs_stdin {
+        if let Some(&(spec, _)) = given.iter()
+            .find(|(spec, _)| spec.name == "--parallel-jobs" || spec.name == "--only")
+        {
+            return Err(format!("{} only applies with --jobs", spec.name));
+        }
+    }
+    if options.jobs.is_some() || options.jobs_stdin {
+        //Everything else about the renders is in the jobs
+        if let Some(&(spec, _)) = given.iter()
+            .find(|(spec, _)| spec.group != Group::General && spec.group != Group::Jobs
+                  && !EVERY_JOB.contains(&spec.name))
+        {
+            return Err(format!("{} can't be given with jobs, put it in the jobs \
+                                themselves", spec.name));
+        }
+        if options.config.is_some() {
+            return Err("--config can't be given with jobs, put the shared options \
+                        under [defaults] in a job file".to_string());
+        }
+        if let Some(unexpected) = positional.first() {
+            return Err(format!("jobs take no positional arguments, got '{}'",
+                               unexpected.to_string_lossy()));
+        }
+        return Ok((command, options));
+    }
+    if let Some(&(spec, _)) = given.iter()
+        .find(|(spec, _)| spec.name == "--parallel-jobs" || spec.name == "--only")
+    {
+        return Err(format!("{} only applies with --jobs", spec.name));
+    }
+    if options.watch {
+        if options.config.is_none() {
+            return Err("--watch needs a --config FILE to watch".to_string());
+        }
+        if options.dry_run {
+            return Err("--watch can't be given with --dry-run".to_string());
+        }
+    }
+
+    match command {
+        Command::Render => parse_legacy_positional(&mut options, &positional, &named)?,
+        Command::Stitch => {
+            if positional.is_empty() {
+                return Err("stitch needs the images to put together".to_string());
+            }
+            options.inputs = positional.into_iter().map(PathBuf::from).collect();
+        }
+        Command::VerifyManifest => {
+            if positional.len() != 1 {
+                return Err("verify-manifest needs exactly one manifest file"
+                           .to_string());
+            }
+            options.manifest = Some(PathBuf::from(positional[0]));
+        }
+        _ => {
+            if let Some(unexpected) = positional.first() {
+                return Err(format!("{} takes no positional arguments, got '{}'",
+                                   command.name(), unexpected.to_string_lossy()));
+            }
+        }
+    }
+
+    if options.schedule != Schedule::Dynamic {
+        if let Some(&(spec, _)) = given.iter().find(|(spec, _)| spec.name == "--chunk-rows") {
+            return Err(format!("{} only applies with --schedule dynamic", spec.name));
+        }
+    }
+    if options.caption().is_none() {
+        if let Some(&(spec, _)) = given.iter()
+            .find(|(spec, _)| spec.name == "--border-color" || spec.name == "--font-size")
+        {
+            return Err(format!("{} only applies with --title or --title-coordinates",
+                               spec.name));
+        }
+    }
+
+    //MANDELBROT_OUTPUT_DIR is only a default for --output auto, it doesn't
+    //get in the way of naming the file
+    let dir_from_environment = options.sources.iter()
+        .any(|(name, source)| *name == "--output-dir" && source == "MANDELBROT_OUTPUT_DIR");
+    if options.output_dir.is_some() && !dir_from_environment
+        && (command != Command::Render
+            || options.output.as_deref() != Some(Path::new(AUTO_OUTPUT)))
+    {
+        return Err("--output-dir only applies to render --output auto".to_string());
+    }
+
+    match command {
+        Command::Render | Command::Animate
+            if options.save_bookmark.is_some() || options.permalink
+               || options.bench.is_some() || options.scaling_sweep.is_some() =>
+        {
+            Ok((command, options))
+        }
+        Command::Render | Command::Animate | Command::Stitch | Command::ContactSheet
+            if options.output.is_none() =>
+        {
+            Err("no output file given (--output FILE)".to_string())
+        }
+        Command::Probe if options.point.is_none() => {
+            Err("probe needs a --point".to_string())
+        }
+        Command::Probe if options.orbit && options.line_to.is_some() => {
+            Err("give either --orbit or --to, not both".to_string())
+        }
+        Command::Measure if options.from_pixel.is_none() || options.to_pixel.is_none() => {
+            Err("measure needs a --from-pixel and a --to-pixel".to_string())
+        }
+        _ => Ok((command, options))
+    }
+}
+
+//Fills in the Options given in the old `File Pixels [Upperleft Lowerright]`
+//positional form. `named` are the options given on the command line, which
+//the positional ones can't be mixed with (though they do override a
+//config file).
+fn parse_legacy_positional(options : &mut Options, positional : &[&OsString],
+                           named : &[&str])
+    -> Result<(), String>
+{
+    match positional.len() {
+        0 => Ok(()),
+        2 | 4 => {
+            if named.contains(&"--output") || named.contains(&"--size") {
+                return Err("give File and Pixels either as positional \
+                            arguments or as --output/--size, not both".to_string());
+            }
+            for name in ["--output", "--size", "--upper-left", "--lower-right"]
+                .iter().take(positional.len())
+            {
+                set_source(options, name, "command line".to_string());
+            }
+            options.output = Some(PathBuf::from(positional[0]));
+            options.size = Some(text(positional[1]).and_then(parse_size)
+                .map_err(|message| format!("Pixels : {}", message))?);
+            if positional.len() == 4 {
+                if named.contains(&"--upper-left") || named.contains(&"--lower-right") {
+                    return Err("give the corners either as positional \
+                                arguments or as --upper-left/--lower-right, \
+                                not both".to_string());
+                }
+                let decimal_comma = options.decimal_comma;
+                let corner = |arg| text(arg)
+                    .and_then(|corner| command_line_point(decimal_comma, corner))
+                    .and_then(|corner| parse_point(&corner));
+                options.upper_left = Some(corner(positional[2])
+                    .map_err(|message| format!("Upperleft : {}", message))?);
+                options.lower_right = Some(corner(positional[3])
+                    .map_err(|message| format!("Lowerright : {}", message))?);
+            }
+            Ok(())
+        }
+        //A word first that isn't a file name is more likely a mistyped command
+        _ => match positional[0].to_str().filter(|first| !first.contains(['.', '/'])) {
+            Some(first) => Err(format!("unknown command '{}', expected one of {}", first,
+                                       COMMANDS.iter()
+                                           .map(|&(name, _, _)| name)
+                                           .collect::<Vec<_>>()
+                                           .join(", "))),
+            None => Err(format!("expected 4 positional arguments \
+                                 (File Pixels Upperleft Lowerright), got {}",
+                                positional.len()))
+        }
+    }
+}
+
+//Parses `name=value` pairs that come from somewhere other than the command
+//line (the query string of an HTTP request) as the options `--name value`
+//of `command`
+#[cfg(feature = "serve")]
+pub fn parse_named(command : Command, pairs : &[(String, String)])
+    -> Result<Options, String>
+{
+    let mut options = Options::default();
+    for (name, value) in pairs {
+        let flag = format!("--{}", name);
+        let spec = OPTIONS.iter()
+            .find(|spec| spec.name == flag && command.accepts(spec.group))
+            .ok_or_else(|| format!("unknown parameter '{}'", name))?;
+        let value = if spec.value.is_some() { value.as_str() } else { "" };
+        (spec.apply)(&mut options, value)
+            .map_err(|message| format!("{} : {}", name, message))?;
+    }
+    Ok(options)
+}
+
+fn read_config_file(path : &Path) -> Result<Vec<Entry>, String> {
+    let shown = path.display();
+    let text = std::fs::read_to_string(path)
+        .map_err(|error| format!("error reading config file {} : {}", shown, error))?;
+    toml::parse(&text).map_err(|message| format!("{} : {}", shown, message))
+}
+
+//The preset a config file names, which goes underneath the rest of it
+fn config_preset(entries : &[Entry]) -> Option<String> {
+    entries.iter()
+        .filter(|entry| entry.key == "preset")
+        .filter(|entry| entry.section.as_ref().is_none_or(|section| section == "view"))
+        .find_map(|entry| match entry.value {
+            Value::String(ref name) => Some(name.clone()),
+            _ => None
+        })
+}
+
+//Sets the options read from the TOML file at `path`. Every key has to be
+//an option of `command`, and a key under a [section] has to belong to the
+//group of that name.
+fn apply_config_entries(command : Command, options : &mut Options, path : &str,
+                        entries : &[Entry])
+    -> Result<(), String>
+{
+    for entry in entries {
+        let flag = format!("--{}", entry.key);
+        let spec = OPTIONS.iter()
+            .filter(|spec| spec.group != Group::General && command.accepts(spec.group))
+            .filter(|spec| entry.section.as_ref()
+                    .is_none_or(|section| section == spec.group.name()))
+            .find(|spec| spec.name == flag)
+            .ok_or_else(|| match entry.section {
+                Some(ref section) => format!("{} line {} : unknown key '{}' in [{}] \
+                                              for {}", path, entry.line, entry.key,
+                                             section, command.name()),
+                None => format!("{} line {} : unknown key '{}' for {}", path,
+                                entry.line, entry.key, command.name())
+            })?;
+
+        let value = match (spec.value, &entry.value) {
+            (None, &Value::Boolean(true)) => "",
+            (None, &Value::Boolean(false)) => continue,
+            (None, _) => return Err(format!("{} line {} : {} : expected true or false",
+                                            path, entry.line, entry.key)),
+            (Some(format), &Value::Boolean(_)) =>
+                return Err(format!("{} line {} : {} : expected {}", path,
+                                   entry.line, entry.key, format)),
+            (Some(_), &Value::String(ref value)) | (Some(_), &Value::Number(ref value)) =>
+                value.as_str()
+        };
+        (spec.apply)(options, value)
+            .map_err(|message| format!("{} line {} : {} : {}", path, entry.line,
+                                       entry.key, message))?;
+        set_source(options, spec.name, format!("{} line {}", path, entry.line));
+    }
+    Ok(())
+}
+
+//<********Job files*******************>//
+//A --jobs file has a [[job]] table per render, each holding the same keys
+//as a render config file (without [sections]) plus an optional name.
+//Keys under [defaults] go underneath those of every job. The output of a
+//job may use {name} and {center}, which are filled in before it renders.
+//
+//    [defaults]
+//    size = "1920x1080"
+//    iterations = 1000
+//
+//    [[job]]
+//    name = "seahorse"
+//    preset = "seahorse-valley"
+//    output = "{name}.png"
+pub struct Job {
+    pub name : String,
+    //Why the job can't run, when its options don't add up
+    pub options : Result<Options, String>
+}
+
+//The options that can be given alongside --jobs or --jobs-stdin, which go
+//for every job on top of what the job itself says (a job can say force =
+//true on its own)
+pub const EVERY_JOB : &[&str] = &["--force", "--skip-existing"];
+
+//The jobs of the file at `path`. Anything wrong with the file as a whole
+//is an error, while a job with bad options only fails on its own.
+pub fn read_jobs(path : &Path) -> Result<Vec<Job>, String> {
+    let entries = read_config_file(path)?;
+    let path = &path.display().to_string();
+    let mut defaults = Vec::new();
+    let mut tables : Vec<Vec<Entry>> = Vec::new();
+    for entry in entries {
+        match (entry.section.as_deref(), entry.item) {
+            (Some("defaults"), None) => defaults.push(Entry { section : None, ..entry }),
+            (Some("job"), Some(item)) => {
+                if tables.len() <= item {
+                    tables.push(Vec::new());
+                }
+                tables[item].push(Entry { section : None, item : None, ..entry });
+            }
+            _ => return Err(format!("{} line {} : '{}' has to be under [defaults] or \
+                                     a [[job]]", path, entry.line, entry.key))
+        }
+    }
+
+    let mut jobs : Vec<Job> = Vec::new();
+    for (index, table) in tables.into_iter().enumerate() {
+        let (name, table) = split_name(path, table)?;
+        let name = name.unwrap_or_else(|| format!("job{}", index + 1));
+        if jobs.iter().any(|job| job.name == name) {
+            return Err(format!("{} : there are two jobs named '{}'", path, name));
+        }
+        let options = job_options(path, &defaults, &table);
+        jobs.push(Job { name, options });
+    }
+    if jobs.is_empty() {
+        return Err(format!("{} : no [[job]] to render", path));
+    }
+    Ok(jobs)
+}
+
+//The job on line `line` of --jobs-stdin, named after the line unless it
+//has a name of its own
+pub fn read_json_job(text : &str, line : usize) -> Job {
+    let path = "stdin";
+    let entries = json::parse_options(text, line)
+        .map_err(|message| format!("{} line {} : {}", path, line, message))
+        .and_then(|entries| split_name(path, entries));
+    match entries {
+        Ok((name, entries)) => Job {
+            name : name.unwrap_or_else(|| format!("line{}", line)),
+            options : job_options(path, &[], &entries)
+        },
+        Err(message) => Job { name : format!("line{}", line), options : Err(message) }
+    }
+}
+
+//Takes the name of a job out of its entries
+fn split_name(path : &str, entries : Vec<Entry>)
+    -> Result<(Option<String>, Vec<Entry>), String>
+{
+    let (name, entries) : (Vec<Entry>, Vec<Entry>) = entries.into_iter()
+        .partition(|entry| entry.key == "name" && entry.section.is_none());
+    match name.first() {
+        Some(&Entry { value : Value::String(ref name), .. }) =>
+            Ok((Some(name.clone()), entries)),
+        Some(entry) => Err(format!("{} line {} : name : expected a string", path,
+                                   entry.line)),
+        None => Ok((None, entries))
+    }
+}
+
+//The layers of a job from the bottom up : environment, preset, defaults,
+//the job's own keys
+fn job_options(path : &str, defaults : &[Entry], job : &[Entry]) -> Result<Options, String> {
+    let command = Command::Render;
+    let mut options = Options::default();
+    apply_environment(command, &mut options)?;
+    if let Some(preset) = config_preset(job).or_else(|| config_preset(defaults)) {
+        apply_preset(&mut options, &preset)?;
+    }
+    for layer in [defaults, job] {
+        let names = layer.iter().map(|entry| format!("--{}", entry.key)).collect::<Vec<_>>();
+        make_room_for_view(&mut options,
+                           &names.iter().map(|name| name.as_str()).collect::<Vec<_>>());
+        apply_config_entries(command, &mut options, path, layer)?;
+    }
+    if options.jobs.is_some() {
+        return Err("a job can't run a job file of its own".to_string());
+    }
+    if options.output.is_none() {
+        return Err("no output given".to_string());
+    }
+    Ok(options)
+}
+
+//The options of `command` as a config file that --config reads back
+//into the same Options, for --print-config
+pub fn to_toml(command : Command, options : &Options) -> String {
+    let mut text = format!("# {} options\n", command.name());
+    for &group in command.groups().iter().filter(|&&group| group != Group::General) {
+        let lines = OPTIONS.iter()
+            .filter(|spec| spec.group == group)
+            .filter_map(|spec| (spec.show)(options).map(|value| {
+                let source = options.sources.iter()
+                    .find(|&&(name, _)| name == spec.name)
+                    .map_or("default", |(_, source)| source.as_str());
+                format!("{} = {}  # {}\n", &spec.name[2..], value, source)
+            }))
+            .collect::<Vec<_>>();
+        if !lines.is_empty() {
+            text.push_str(&format!("\n[{}]\n", group.name()));
+            text.push_str(&lines.concat());
+        }
+    }
+    text
+}
+
+//The --help columns : the help of a flag starts at HELP_COLUMN, or on the
+//next line if the flag leaves it fewer than two spaces, and is wrapped to
+//stay within HELP_WIDTH
+const HELP_COLUMN : usize = 38;
+const HELP_WIDTH : usize = 80;
+
+//One entry of the --help text, `name` and its `help` in their columns
+fn help_entry(name : &str, help : &str) -> String {
+    let mut text = format!("  {}", name);
+    match text.len() + 2 <= HELP_COLUMN {
+        true => text.push_str(&" ".repeat(HELP_COLUMN - text.len())),
+        false => text.push_str(&format!("\n{}", " ".repeat(HELP_COLUMN)))
+    }
+    let mut column = HELP_COLUMN;
+    for (index, word) in help.split_whitespace().enumerate() {
+        if index > 0 && column + 1 + word.len() > HELP_WIDTH {
+            text.push_str(&format!("\n{}", " ".repeat(HELP_COLUMN)));
+            column = HELP_COLUMN;
+        } else if index > 0 {
+            text.push(' ');
+            column += 1;
+        }
+        text.push_str(word);
+        column += word.len();
+    }
+    text.push('\n');
+    text
+}
+
+//The --help text, generated from the option table
+pub fn usage(program : &str, command : Command) -> String {
+    let mut text = match command {
+        Command::Render => format!("Usage : {} [COMMAND] [File Pixels Upperleft Lowerright] \
+                                    [OPTIONS]\n\nCommands :\n", program),
+        Command::Stitch => format!("Usage : {} stitch [OPTIONS] IMAGE...\n", program),
+        Command::VerifyManifest => format!("Usage : {} verify-manifest MANIFEST\n",
+                                           program),
+        _ => format!("Usage : {} {} [OPTIONS]\n", program, command.name())
+    };
+    if command == Command::Render {
+        for &(name, _, help) in COMMANDS {
+            text.push_str(&help_entry(name, help));
+        }
+        text.push_str(&format!("\nRun {} COMMAND --help for the options of \
+                                each command.\n", program));
+    }
+
+    text.push_str("\nOptions :\n");
+    for spec in OPTIONS.iter().filter(|spec| command.accepts(spec.group)) {
+        let flag = match spec.value {
+            None => spec.name.to_string(),
+            Some(value) => format!("{} {}", spec.name, value)
+        };
+        text.push_str(&help_entry(&flag, spec.help));
+    }
+    let variables = ENVIRONMENT.iter()
+        .filter(|&&(_, name)| OPTIONS.iter()
+                .any(|spec| spec.name == name && command.accepts(spec.group)))
+        .collect::<Vec<_>>();
+    if !variables.is_empty() {
+        text.push_str("\nEnvironment (below --preset, --config and the command line) :\n");
+        for &&(variable, name) in &variables {
+            text.push_str(&help_entry(variable, &format!("same as {}", name)));
+        }
+    }
+    match command {
+        Command::Render => text.push_str(&format!(
+            "\nExample : {} mandelbrot.png 1000x750 -1.20,0.35 -1,0.20\n\
+             Example : {} --output mandelbrot.png --size 1920x1080 \
+             --center -0.743643887,0.131825904 --width 0.00002\n\
+             Example : {} --output deep.png --size 320x240 --precision big:160 \
+             --center -0.1010963638456221610257854457386225654638,\
+             0.9562865108091415007710960577299774358098 --width 1e-30\n",
+            program, program, program)),
+        Command::Animate => text.push_str(&format!(
+            "\nExample : {} animate --output zoom_{{}}.png --size 640x360 \
+             --center -0.743643887,0.131825904 --width 3 --frames 60\n",
+            program)),
+        Command::Area => text.push_str(&format!(
+            "\nExample : {} area --samples 10000000 --iterations 5000\n\
+             Higher iteration limits count fewer outside points as inside, \
+             the estimate approaches the known area of about 1.506.\n",
+            program)),
+        Command::Census => text.push_str(&format!(
+            "\nExample : {} census --size 800x600 --center -1.75,0 --width 0.5 \
+             --iterations 1000 --output census.png\n\
+             Each find can be zoomed in on with its CENTER as --center and a few \
+             times its SIZE as --width.\n",
+            program)),
+        Command::ContactSheet => text.push_str(&format!(
+            "\nExample : {} contact-sheet --output presets.png --columns 6 \
+             --palette fire\n",
+            program)),
+        Command::Measure => text.push_str(&format!(
+            "\nExample : {} measure --size 800x600 --center -0.75,0 --width 3 \
+             --from-pixel 100,250 --to-pixel 420,300\n\
+             Prints the points both pixels are at (their upper left corners), the \
+             spans between them along each axis and the distance across.\n",
+            program)),
+        Command::Serve => text.push_str(
+            "\nEndpoint : GET /render?size=400x300&center=-0.75,0&width=3&iterations=500\n\
+             The query parameters are render's options without the leading -- \
+             (size, center, width, zoom, corners, iterations, palette, preset, ...), \
+             %XX-escaped where need be, a flag such as smooth given with no value. \
+             The answer is 200 with the PNG as image/png, or a text/plain message \
+             with 400 for options that don't make a view (or ask for more pixels \
+             than --max-pixels, 4000000 unless given to serve), 404 for any other \
+             path, 405 for any other method. Requests are answered one at a time.\n"),
+        _ => {}
+    }
+    text.push_str("\nExit status : 0 done, 2 bad command line, 3 invalid view or parameters, \
+                   4 error reading or writing, 5 too big to render, 130 interrupted\n");
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config;
+
+    #[test]
+    fn parse_bytes_takes_plain_numbers_and_binary_units() {
+        assert_eq!(parse_bytes("4096"), Ok(4096));
+        assert_eq!(parse_bytes("1K"), Ok(1 << 10));
+        assert_eq!(parse_bytes("512M"), Ok(512 << 20));
+        assert_eq!(parse_bytes("2G"), Ok(2 << 30));
+        for bad in &["", "0", "0M", "K", "12k", "1.5G", "1T", "-1", "12 M"] {
+            assert!(parse_bytes(bad).is_err(), "{:?} parsed", bad);
+        }
+    }
+
+    #[test]
+    fn every_usage_example_parses() {
+        for &(_, command, _) in COMMANDS {
+            let text = usage("mandelbrot_plot", command);
+            for example in text.lines().filter_map(|line| {
+                line.strip_prefix("Example : mandelbrot_plot ")
+            }) {
+                let args = example.split_whitespace().map(OsString::from).collect::<Vec<_>>();
+                let (parsed, options) = parse(&args)
+                    .unwrap_or_else(|message| panic!("{} : {}", example, message));
+                assert!(parsed == command, "{} isn't {}", example, command.name());
+                if command == Command::Render {
+                    assert!(config::build(&options).is_ok(), "{} doesn't build", example);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn help_entries_keep_their_columns() {
+        assert_eq!(help_entry("--size WxH", "image size"),
+                   format!("  --size WxH{}image size\n", " ".repeat(HELP_COLUMN - 12)));
+        //Too long a flag has its help on the next line
+        let flag = format!("--{}", "a".repeat(HELP_COLUMN - 5));
+        assert_eq!(help_entry(&flag, "help"),
+                   format!("  {}\n{}help\n", flag, " ".repeat(HELP_COLUMN)));
+        let long = "word ".repeat(30);
+        let entry = help_entry("--flag", &long);
+        assert!(entry.lines().count() > 1);
+        for line in entry.lines() {
+            assert!(line.len() <= HELP_WIDTH, "{:?} is too long", line);
+        }
+        for line in entry.lines().skip(1) {
+            assert!(line.starts_with(&" ".repeat(HELP_COLUMN)) && line.len() > HELP_COLUMN);
+        }
+        assert_eq!(entry.split_whitespace().skip(1).collect::<Vec<_>>(),
+                   long.split_whitespace().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn every_flag_is_apart_from_its_help() {
+        let indent = " ".repeat(HELP_COLUMN);
+        for &(_, command, _) in COMMANDS {
+            let text = usage("mandelbrot_plot", command);
+            let lines = text.lines().collect::<Vec<_>>();
+            for (index, line) in lines.iter().enumerate()
+                .filter(|(_, line)| line.starts_with("  ") && !line.starts_with("   "))
+            {
+                //Either the help is in its column, or on the next line
+                match line.get(HELP_COLUMN - 2..HELP_COLUMN) == Some("  ") {
+                    true => assert!(!line[HELP_COLUMN..].starts_with(' ')),
+                    false => assert!(!line[2..].contains("  ")
+                                     && lines[index + 1].starts_with(&indent),
+                                     "{:?} runs into its help", line)
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn locale_decimal_reads_points_one_way_or_the_other() {
+        //The point it reads as, or a piece of the error it gives
+        type Reading = Result<(f64, f64), &'static str>;
+        //The mode, the point --center is given and what it reads as
+        let table : &[(&str, &str, Reading)] = &[
+            ("comma", "-1,2;0,35", Ok((-1.2, 0.35))),
+            ("comma", "-1;0", Ok((-1.0, 0.0))),
+            ("comma", "-1,2;0", Ok((-1.2, 0.0))),
+            ("comma", "-0,74+0,13i", Ok((-0.74, 0.13))),
+            ("comma", " -1,25 ; 0,5 ", Ok((-1.25, 0.5))),
+            ("dot", "-1.2,0.35", Ok((-1.2, 0.35))),
+            ("dot", "-0.74+0.13i", Ok((-0.74, 0.13))),
+            ("dot", "-1,2;0,35", Err("unexpected ';'")),
+            ("dot", "-1.2;0.35", Err("unexpected ';'")),
+            //A '.' in comma mode, whether or not the rest is in the comma form
+            ("comma", "-1.2;0.35", Err("has a '.'")),
+            ("comma", "-1.2,0.35", Err("has a '.'")),
+            ("comma", "-1,2;0.35", Err("has a '.'")),
+            //A comma that could be the decimal point or the separator
+            ("comma", "-1,2", Err("ambiguous")),
+            ("comma", ",", Err("ambiguous")),
+            ("comma", "-1,2,0,35", Err("ambiguous")),
+            ("comma", "-1,2;", Err("the imaginary part '' isn't valid")),
+            ("comma", ";0,35", Err("the real part '' isn't valid"))
+        ];
+        for &(mode, point, ref expected) in table {
+            let args = ["--output", "x.png", "--size", "4x3", "--locale-decimal", mode,
+                        "--center", point, "--width", "1"]
+                .iter().map(OsString::from).collect::<Vec<_>>();
+            let parsed = parse(&args).map(|(_, options)| options.center.unwrap());
+            match (parsed, expected) {
+                (Ok(center), &Ok((re, im))) => {
+                    assert!((center - Complex { re, im }).norm() < 1e-15,
+                            "{} {} read as {}", mode, point, center);
+                }
+                (Err(message), &Err(piece)) => {
+                    assert!(message.contains(piece), "{} {} : {}", mode, point, message);
+                }
+                (parsed, expected) => {
+                    panic!("{} {} gave {:?}, not {:?}", mode, point,
+                           parsed.map(|center| (center.re, center.im)), expected);
+                }
+            }
+        }
+    }
+
+    fn bookmarked(options : &Options) -> Options {
+        let text = bookmark_toml(options);
+        let entries = toml::parse(&text).unwrap();
+        let mut loaded = Options::default();
+        apply_config_entries(Command::Render, &mut loaded, "bookmark", &entries).unwrap();
+        loaded
+    }
+
+    #[test]
+    fn a_bookmark_reads_back_as_the_options_it_was_saved_from() {
+        let options = Options {
+            center : Some(Complex { re : -0.7746806106, im : 0.1374168856 }),
+            width : Some(1.0e-4),
+            rotate : 30.0,
+            iterations : 2000,
+            mode : Mode::Bands,
+            band_width : 12,
+            julia : Some(Complex { re : -0.8, im : 0.156 }),
+            palette : Some(Palette::parse("#000000,#ff8000,#ffffff").unwrap()),
+            palette_wrap : true,
+            gamma : 2.2,
+            interior_value : 128,
+            ..Options::default()
+        };
+        let loaded = bookmarked(&options);
+        assert_eq!(loaded.center, options.center);
+        assert_eq!(loaded.width, options.width);
+        assert_eq!(loaded.rotate, options.rotate);
+        assert_eq!(loaded.iterations, options.iterations);
+        assert!(loaded.mode == options.mode);
+        assert_eq!(loaded.band_width, options.band_width);
+        assert_eq!(loaded.julia, options.julia);
+        assert_eq!(loaded.palette.as_ref().map(Palette::spec),
+                   options.palette.as_ref().map(Palette::spec));
+        assert_eq!(loaded.palette_wrap, options.palette_wrap);
+        assert_eq!(loaded.gamma, options.gamma);
+        assert_eq!(loaded.interior_value, options.interior_value);
+        assert_eq!(bookmark_toml(&loaded), bookmark_toml(&options));
+    }
+
+    #[test]
+    fn rotating_by_turns_a_bookmarked_view_further() {
+        let loaded = bookmarked(&Options {
+            center : Some(Complex { re : -0.5, im : 0.25 }),
+            width : Some(4.0),
+            rotate : 30.0,
+            ..Options::default()
+        });
+        let build = |options : Options| config::build(&Options {
+            size : Some((40, 30)),
+            ..options
+        }).unwrap().view;
+        let view = build(Options {
+            pan : Some(Complex { re : 0.25, im : 0.0 }),
+            zoom_by : Some(2.0),
+            rotate_by : 15.0,
+            ..loaded.clone()
+        });
+        let rotation = view.rotation.unwrap();
+        assert_eq!(rotation.degrees, 45.0);
+        //Around the center the pan and zoom left, not the bookmark's
+        assert_eq!(rotation.pivot, Complex { re : 0.5, im : 0.25 });
+        assert_eq!(view.center(), rotation.pivot);
+        assert_eq!(view.width(), 2.0);
+        //Turning by as much the other way undoes the bookmark's rotation
+        let upright = build(Options { rotate_by : -30.0, ..loaded });
+        assert!(upright.rotation.is_none());
+    }
+
+    #[test]
+    fn a_bookmark_keeps_awkward_floats_exactly() {
+        for &(re, im, width) in &[(0.1 + 0.2, -1.0 / 3.0, 1.0e-300),
+                                  (-1.74006238257934, 0.0281150647220774, 5e-324),
+                                  (-0.0, f64::MIN_POSITIVE, 4.0)] {
+            let options = Options {
+                center : Some(Complex { re, im }),
+                width : Some(width),
+                ..Options::default()
+            };
+            let loaded = bookmarked(&options);
+            let center = loaded.center.unwrap();
+            assert_eq!((center.re.to_bits(), center.im.to_bits()), (re.to_bits(), im.to_bits()));
+            assert_eq!(loaded.width.map(f64::to_bits), Some(width.to_bits()));
+        }
+    }
+
+    //<********Paths that aren't UTF-8*********>//
+    #[cfg(unix)]
+    fn not_utf8(name : &[u8]) -> OsString {
+        use std::os::unix::ffi::OsStrExt;
+        OsStr::from_bytes(name).to_os_string()
+    }
+
+    #[test]
+    fn every_path_option_is_an_option() {
+        for &(name, _) in PATH_OPTIONS {
+            assert!(OPTIONS.iter().any(|spec| spec.name == name), "{}", name);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn path_options_take_names_that_arent_utf8() {
+        let path = not_utf8(b"/tmp/caf\xe9/out");
+        for &(name, _) in PATH_OPTIONS {
+            let spec = OPTIONS.iter().find(|spec| spec.name == name).unwrap();
+            let mut options = Options::default();
+            assert_eq!(apply_arg(&mut options, spec, &path), Ok(()), "{}", name);
+        }
+        //Anything else has to be text
+        let spec = OPTIONS.iter().find(|spec| spec.name == "--title").unwrap();
+        let error = apply_arg(&mut Options::default(), spec, &not_utf8(b"caf\xe9"));
+        assert!(error.err().is_some_and(|error| error.contains("isn't valid UTF-8")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn the_command_line_keeps_the_bytes_of_a_path() {
+        let output = not_utf8(b"caf\xe9.png");
+        let args = ["render", "--output"].iter().map(OsString::from)
+            .chain([output.clone()])
+            .chain(["--size", "40x30", "--center", "-0.5,0", "--width", "3",
+                    "--stats"].iter().map(OsString::from))
+            .chain([not_utf8(b"/tmp/\xff/stats.json")])
+            .collect::<Vec<_>>();
+        let (command, options) = parse(&args).unwrap();
+        assert!(command == Command::Render);
+        assert_eq!(options.output.as_deref(), Some(Path::new(&output)));
+        assert_eq!(options.stats.map(PathBuf::into_os_string),
+                   Some(not_utf8(b"/tmp/\xff/stats.json")));
+    }
+}
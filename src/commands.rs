@@ -0,0 +1,2004 @@
+//<***************The subcommands*****************>//
+//One function per subcommand, each taking the parsed Options and
+//reporting failure as a message for main() to print.
+
+use image::ColorType;
+use num::Complex;
+use std::ffi::OsString;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, Read, Write};
+#[cfg(feature = "serve")]
+use std::io::BufReader;
+#[cfg(feature = "serve")]
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::bookmarks;
+use crate::census;
+use crate::cli::{self, Format, Options, Precision};
+#[cfg(feature = "serve")]
+use crate::cli::Command;
+#[cfg(feature = "serve")]
+use crate::encode_png;
+use crate::config::{self, suggest_iterations, View};
+use crate::exit::{self, Failure, Kind};
+use crate::heatmap;
+use crate::Fit;
+use crate::rng::Rng;
+use crate::{colour_counts, counts_move_along, create_output, escape_time, escape_z,
+            estimate_seconds, estimated_memory, is_stdout, marked, moved_counts, orbit,
+            pan_canvas, pixel_to_point, render_counts, render_image,
+            render_image_or_partial, renders_repeatedly, rows_to_render, sample_line,
+            window_counts, write_csv, write_image, write_image_whole, Image, Renderer,
+            CSV_MAX_PIXELS, SCHEDULER};
+use crate::json::Json;
+use crate::log::{self, Level};
+use crate::orbits::{self, Cache};
+use crate::permalink;
+use crate::plan;
+use crate::presets;
+use crate::progress::{self, Progress};
+use crate::reorder::Reorder;
+use crate::stats;
+use crate::text;
+
+fn new_renderer(options : &Options) -> Renderer {
+    renderer_of(options, options.thread_count())
+}
+
+//A Renderer of `threads` threads, scheduled the way `options` says
+fn renderer_of(options : &Options, threads : usize) -> Renderer {
+    match threads {
+        1 => Renderer::single_threaded(),
+        threads => Renderer::new(threads).with_schedule(options.schedule, options.chunk_rows)
+    }
+}
+
+//<********render********************>//
+//A Ctrl-C stops the rows where they are, and what there is goes to
+//OUTPUT.partial.png
+pub fn render(options : &Options) -> Result<(), Failure> {
+    //A heatmap is rendered whole, the colorbar needs all of its counts
+    if options.format == Format::Png && !options.heatmap && !options.cost_map {
+        exit::interrupt_renders();
+    }
+    let started = Instant::now();
+    let config = config::build(options)?;
+    stats::stage("parse", started);
+    verbose!("Worked out the view in {:.1}ms", milliseconds(started));
+    render_config(options, config).map(|_| ())
+}
+
+//Renders `config` to the output `options` name, handing back the file it
+//wrote (a cancelled render leaves it as it was)
+fn render_config(options : &Options, config : config::Config)
+    -> Result<Option<PathBuf>, Failure>
+{
+    let named = auto_output(options, &config)?
+        .map(|output| Options { output : Some(output), ..options.clone() });
+    let options = named.as_ref().unwrap_or(options);
+    let output = options.output.as_ref().unwrap();
+    if check_clobber(options, output)? {
+        info!("Skipping {}, it already exists (--skip-existing)", output.display());
+        return Ok(None);
+    }
+    let bounds = config.view.bounds;
+
+    if options.format == Format::Csv {
+        //A CSV cell per pixel gets unwieldy very quickly,
+        //so this is only meant for small teaching/debugging grids
+        if bounds.0 * bounds.1 > CSV_MAX_PIXELS {
+            return Err(Failure::new(Kind::TooBig, format!(
+                "--format csv is limited to {} pixels (e.g. 500x500), got {}x{}",
+                CSV_MAX_PIXELS, bounds.0, bounds.1)));
+        }
+        if config.chromatic.is_some() {
+            return Err("--chromatic only applies to PNG output".into());
+        }
+        if config.diff_limits.is_some() {
+            return Err("--diff-limits only applies to PNG output".into());
+        }
+        if config.time_budget.is_some() {
+            return Err("--time-budget only applies to PNG output".into());
+        }
+        if config.preview_stride > 1 {
+            return Err("--preview-stride only applies to PNG output".into());
+        }
+        if options.caption().is_some() {
+            return Err("--title and --title-coordinates only apply to PNG output".into());
+        }
+        if config.heatmap || config.cost_map {
+            return Err("--heatmap and --cost-map only apply to PNG output".into());
+        }
+        if config.blend.is_some() {
+            return Err("--blend only applies to PNG output, the counts it blends aren't \
+                        whole numbers".into());
+        }
+    }
+
+    let renderer = image_renderer(options, &config);
+    let renderer = Some(&renderer);
+
+    let estimate = estimate(options, &config, &[config.view], 1, renderer);
+    if dry_run(options, || plan::for_render(options, &config, estimate))? {
+        return Ok(None);
+    }
+    check_memory(&config)?;
+    if log::enabled(Level::Verbose) {
+        verbose!("Configuration :");
+        for line in plan::lines(&plan::for_render(options, &config, estimate)) {
+            verbose!("  {}", line);
+        }
+    }
+
+    let started = Instant::now();
+    let progress = Progress::start("image", rows_to_render(&config, &config.view), 1);
+    if options.format == Format::Csv {
+        let counts = stats::time("iterate", || render_counts(&config, renderer));
+        drop(progress);
+        verbose!("Rendered in {:.1}ms", milliseconds(started));
+        let writing_started = Instant::now();
+        stats::time("write", || {
+            write_csv(output, &counts, bounds, &options.csv_interior, options.force)
+        }).map_err(writing("CSV file", output))?;
+        verbose!("Wrote the CSV file in {:.1}ms", milliseconds(writing_started));
+    } else {
+        let (image, reached) = match config.heatmap || config.cost_map {
+            true => (stats::time("iterate", || heatmap::render(&config, renderer)), None),
+            false => render_image_or_partial(&config, &config.view, renderer)
+        };
+        if config.cancel.as_ref().is_some_and(|cancel| cancel.load(Ordering::Relaxed)) {
+            return Ok(None);
+        }
+        if let Some(reached) = reached {
+            drop(progress);
+            return Err(write_partial(options, output, &image, reached));
+        }
+        verbose!("Rendered in {:.1}ms", milliseconds(started));
+        warn_if_uniform(options, &image, "the image");
+        let image = with_caption(options, image, &config.view);
+        let writing_started = Instant::now();
+        progress::encoding(output);
+        write_image(output, &image, options.force, options.png_compression)
+            .map_err(writing("PNG file", output))?;
+        progress::written(output);
+        drop(progress);
+        verbose!("Encoded and wrote the PNG file in {:.1}ms",
+                 milliseconds(writing_started));
+        if options.open {
+            open_in_viewer(output);
+        }
+    }
+    let View { upper_left, lower_right, .. } = config.view;
+    let (width, zoom) = requested_scale(options, &config.view);
+    match config.origin {
+        Some(ref origin) =>
+            info!("Wrote {} : {}x{} around {} width {:e} zoom {:e} in {:.2}s",
+                  output.display(), bounds.0, bounds.1, origin.show(), width, zoom,
+                  started.elapsed().as_secs_f64()),
+        None =>
+            info!("Wrote {} : {}x{} from {},{} to {},{} width {:e} zoom {:e} in {:.2}s",
+                  output.display(), bounds.0, bounds.1, upper_left.re, upper_left.im,
+                  lower_right.re, lower_right.im, width, zoom,
+                  started.elapsed().as_secs_f64())
+    }
+    Ok(Some(output.clone()))
+}
+
+//The width and zoom of the view as --width or --zoom (and --zoom-by) asked
+//for them, those of `view` when it was given by its corners. Worked back
+//out of the corners instead, they would be off by the rounding of the
+//corners to f64, --zoom 1e15 around -0.74 coming out as 1.0008e15.
+fn requested_scale(options : &Options, view : &View) -> (f64, f64) {
+    let by = options.zoom_by.unwrap_or(1.0);
+    match (options.center, options.width, options.zoom) {
+        (Some(_), Some(width), _) => (width / by, config::FULL_WIDTH / width * by),
+        (Some(_), None, Some(zoom)) => (config::FULL_WIDTH / (zoom * by), zoom * by),
+        _ => (view.width(), view.zoom())
+    }
+}
+
+//Only the modes that render more than once need a long lived pool
+//(and a single threaded run never leaves the main thread)
+fn image_renderer(options : &Options, config : &config::Config) -> Renderer {
+    if renders_repeatedly(config) {
+        new_renderer(options)
+    } else {
+        Renderer::spawning(options.thread_count())
+            .with_schedule(options.schedule, options.chunk_rows)
+    }
+}
+
+//<********render --bench**************>//
+//Times N renders of the view, after a first one to warm up the caches and
+//the allocator that isn't counted. Each goes through the renderer and the
+//render_image_or_partial() a plain render would, iterating and colouring
+//everything, and only the caption, encoding and writing are left out. With
+//--bench-log the results are also added to a CSV file as a line of
+//BENCH_LOG_HEADER, to compare over time : "view" is the FNV-1a hash of the
+//view's --permalink, so lines of the same view at the same size compare.
+const BENCH_LOG_HEADER : &str = "unix_time,version,threads,scheduler,schedule,view,size,\
+                                 runs,mean_ms,median_ms,stddev_ms,pixels_per_second";
+
+pub fn bench(options : &Options) -> Result<(), Failure> {
+    exit::interrupt_renders();
+    let runs = options.bench.unwrap();
+    let config = stats::time("parse", || config::build(options))?;
+    check_memory(&config)?;
+    let renderer = image_renderer(options, &config);
+    let mut times = Vec::with_capacity(runs);
+    for run in 0..=runs {
+        let seconds = timed_render(&config, &renderer).ok_or_else(|| {
+            Failure::new(Kind::Interrupted,
+                         format!("interrupted after {} of {} runs", times.len(), runs))
+        })?;
+        match run {
+            0 => verbose!("Warm-up run in {:.1}ms", seconds * 1000.0),
+            _ => {
+                verbose!("Run {} in {:.1}ms", run, seconds * 1000.0);
+                times.push(seconds);
+            }
+        }
+    }
+
+    let (width, height) = config.view.bounds;
+    let mean = times.iter().sum::<f64>() / runs as f64;
+    let mut sorted = times.clone();
+    sorted.sort_by(f64::total_cmp);
+    let median = (sorted[(runs - 1) / 2] + sorted[runs / 2]) / 2.0;
+    let deviation = match runs {
+        1 => 0.0,
+        _ => (times.iter().map(|time| (time - mean).powi(2)).sum::<f64>()
+              / (runs - 1) as f64).sqrt()
+    };
+    let pixels_per_second = (width * height) as f64 / mean;
+    println!("{}x{}, {} runs after a warm-up : mean {:.1}ms, median {:.1}ms, std dev \
+              {:.1}ms, {:.2} Mpixels/s", width, height, runs, mean * 1000.0,
+             median * 1000.0, deviation * 1000.0, pixels_per_second / 1e6);
+
+    let path = match options.bench_log {
+        Some(ref path) => path,
+        None => return Ok(())
+    };
+    let view = permalink::encode(&cli::permalink_lines(&resolved_view(options, &config)));
+    let line = format!("{},{},{},{},{},{:016x},{}x{},{},{:.3},{:.3},{:.3},{:.0}",
+                       SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)
+                           .map_or(0, |since| since.as_secs()),
+                       env!("CARGO_PKG_VERSION"), options.thread_count(), SCHEDULER,
+                       options.schedule.name(), fnv1a(view.as_bytes()), width, height,
+                       runs, mean * 1000.0, median * 1000.0, deviation * 1000.0,
+                       pixels_per_second);
+    append_bench_log(path, &line).map_err(writing("bench log", path))?;
+    verbose!("Added the results to {}", path.display());
+    Ok(())
+}
+
+//The seconds a render of the image took, None if it was interrupted
+fn timed_render(config : &config::Config, renderer : &Renderer) -> Option<f64> {
+    let started = Instant::now();
+    let (_, reached) = render_image_or_partial(config, &config.view, Some(renderer));
+    match reached {
+        Some(_) => None,
+        None => Some(started.elapsed().as_secs_f64())
+    }
+}
+
+//Starting the file with BENCH_LOG_HEADER if it is new or empty
+fn append_bench_log(path : &Path, line : &str) -> std::io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    if file.metadata()?.len() == 0 {
+        writeln!(file, "{}", BENCH_LOG_HEADER)?;
+    }
+    writeln!(file, "{}", line)
+}
+
+//<********render --scaling-sweep*******>//
+//Renders the view once for each thread count of the list, after a warm-up
+//at the first, the same way --bench does, and reports each time against
+//the single threaded one (rendered first when 1 isn't in the list) :
+//
+//    speedup     the single threaded time over this one
+//    efficiency  the speedup over the threads, 100% when every thread is
+//                kept busy the whole render
+//
+//Scaling "falls off" at the first count whose efficiency is under
+//FALL_OFF_EFFICIENCY, where adding threads stops paying for itself. How
+//far the counts go before that depends on the schedule as much as on the
+//machine, which is why the report names it.
+const FALL_OFF_EFFICIENCY : f64 = 0.75;
+
+pub fn scaling_sweep(options : &Options) -> Result<(), Failure> {
+    exit::interrupt_renders();
+    let config = stats::time("parse", || config::build(options))?;
+    check_memory(&config)?;
+    let mut counts = options.scaling_sweep.clone().unwrap();
+    if !counts.contains(&1) {
+        counts.insert(0, 1);
+    }
+    let mut times = Vec::with_capacity(counts.len());
+    for (index, &threads) in counts.iter().enumerate() {
+        let threaded = Options { threads, single_threaded : false, ..options.clone() };
+        let renderer = image_renderer(&threaded, &config);
+        let interrupted = || Failure::new(Kind::Interrupted, format!(
+            "interrupted after {} of {} thread counts", times.len(), counts.len()));
+        if index == 0 {
+            let seconds = timed_render(&config, &renderer).ok_or_else(interrupted)?;
+            verbose!("Warm-up run in {:.1}ms", seconds * 1000.0);
+        }
+        let seconds = timed_render(&config, &renderer).ok_or_else(interrupted)?;
+        verbose!("{} threads in {:.1}ms", threads, seconds * 1000.0);
+        times.push(seconds);
+    }
+
+    let single = times[counts.iter().position(|&threads| threads == 1).unwrap()];
+    let rows = counts.iter().zip(&times)
+        .map(|(&threads, &seconds)| {
+            let speedup = single / seconds;
+            (threads, seconds, speedup, speedup / threads as f64)
+        })
+        .collect::<Vec<_>>();
+    let falls_off = rows.iter()
+        .find(|&&(_, _, _, efficiency)| efficiency < FALL_OFF_EFFICIENCY)
+        .map(|&(threads, _, _, _)| threads);
+    let (width, height) = config.view.bounds;
+
+    if options.json {
+        let runs = rows.iter()
+            .map(|&(threads, seconds, speedup, efficiency)| Json::Object(vec![
+                ("threads", Json::Integer(threads as u64)),
+                ("ms", Json::Number(seconds * 1000.0)),
+                ("speedup", Json::Number(speedup)),
+                ("efficiency", Json::Number(efficiency))
+            ]))
+            .collect();
+        println!("{}", Json::Object(vec![
+            ("scheduler", Json::String(SCHEDULER.to_string())),
+            ("schedule", Json::String(options.schedule.name().to_string())),
+            ("size", Json::Object(vec![
+                ("width", Json::Integer(width as u64)),
+                ("height", Json::Integer(height as u64))
+            ])),
+            ("runs", Json::Array(runs)),
+            ("falls_off_at", falls_off.map_or(Json::Null, |threads| {
+                Json::Integer(threads as u64)
+            }))
+        ]).to_json());
+        return Ok(());
+    }
+    println!("{}x{}, scheduler {}, schedule {}", width, height, SCHEDULER,
+             options.schedule.name());
+    println!("{:>8}  {:>10}  {:>8}  {:>10}", "threads", "time", "speedup", "efficiency");
+    for &(threads, seconds, speedup, efficiency) in &rows {
+        println!("{:>8}  {:>8.1}ms  {:>7.2}x  {:>9.0}%{}", threads, seconds * 1000.0, speedup,
+                 efficiency * 100.0, match Some(threads) == falls_off {
+                     true => "  <- falls off",
+                     false => ""
+                 });
+    }
+    match falls_off {
+        Some(threads) => println!("Scaling falls off at {} threads, under {:.0}% efficiency",
+                                  threads, FALL_OFF_EFFICIENCY * 100.0),
+        None => println!("No fall off, every count is at {:.0}% efficiency or better",
+                         FALL_OFF_EFFICIENCY * 100.0)
+    }
+    Ok(())
+}
+
+//What an interrupted render got to, next to the `output` it was for (or on
+//standard output too), and the failure to exit with
+fn write_partial(options : &Options, output : &Path, image : &Image, reached : f64)
+    -> Failure
+{
+    let partial = match is_stdout(output) {
+        true => output.to_path_buf(),
+        false => {
+            let mut name = output.file_stem().unwrap_or_default().to_os_string();
+            name.push(".partial.png");
+            output.with_file_name(name)
+        }
+    };
+    progress::encoding(&partial);
+    let written = write_image(&partial, image, true, options.png_compression);
+    progress::written(&partial);
+    let done = format!("interrupted with {:.1}% of the rows rendered", reached * 100.0);
+    match written {
+        Ok(()) => Failure::new(Kind::Interrupted, format!(
+            "{}, wrote them to {} (the rest is a checkerboard)", done, partial.display())),
+        Err(error) => Failure::new(Kind::Interrupted, format!(
+            "{}, and writing them to {} failed : {}", done, partial.display(), error))
+    }
+}
+
+//A render far beyond what the machine can hold would otherwise abort the
+//program halfway through, so the memory is asked for (and handed back) up
+//front, after --max-pixels and --max-memory have had their say
+fn check_memory(config : &config::Config) -> Result<(), Failure> {
+    let (width, height) = config.view.bounds;
+    if let Some(max_pixels) = config.max_pixels {
+        if width.checked_mul(height).is_none_or(|pixels| pixels > max_pixels) {
+            return Err(Failure::new(Kind::TooBig, format!(
+                "a {}x{} render is over the limit of {} pixels, try a smaller --size \
+                 or raise the limit with --max-pixels (or MANDELBROT_MAX_PIXELS)",
+                width, height, max_pixels)));
+        }
+    }
+    let bytes = width.checked_mul(height)
+        .filter(|&pixels| pixels <= isize::MAX as usize / 64)
+        .map(|_| estimated_memory(config));
+    if let (Some(bytes), Some(max_memory)) = (bytes, config.max_memory) {
+        if bytes > max_memory {
+            return Err(Failure::new(Kind::TooBig, format!(
+                "a {}x{} render needs about {} bytes, over the limit of {}, try a smaller \
+                 --size or raise the limit with --max-memory (or MANDELBROT_MAX_MEMORY)",
+                width, height, bytes, max_memory)));
+        }
+    }
+    match bytes {
+        Some(bytes) if Vec::<u8>::new().try_reserve_exact(bytes).is_ok() => Ok(()),
+        _ => Err(Failure::new(Kind::TooBig, format!(
+            "a {}x{} render needs more memory than can be had, try a smaller --size",
+            width, height)))
+    }
+}
+
+//`image` of `view` with the rows of --title under it, if there are any
+fn with_caption(options : &Options, image : Image, view : &View) -> Image {
+    let caption = match options.caption() {
+        Some(caption) => caption,
+        None => return image
+    };
+    let mut lines = caption.title.iter().cloned().collect::<Vec<_>>();
+    if caption.coordinates {
+        let (center, decimals) = (view.center(), center_decimals(view));
+        lines.push(format!("center {:.*},{:.*} width {}", decimals, center.re, decimals,
+                           center.im, short_number(view.width())));
+    }
+    text::captioned(image, &lines, &caption)
+}
+
+//<********Uniform renders****************>//
+//A view that lies wholly inside the set renders all black, and one far out
+//of it all the same shade, which nearly always means a mistyped corner
+//rather than the picture that was wanted. A render with more than
+//UNIFORM_SHARE of its pixels the same colour gets a warning, unless
+//--allow-uniform.
+const UNIFORM_SHARE : f64 = 0.99;
+
+fn warn_if_uniform(options : &Options, image : &Image, what : &str) {
+    if options.allow_uniform {
+        return;
+    }
+    let (color, share) = commonest_color(image);
+    if share > UNIFORM_SHARE {
+        warning!("{} is {:.1}% #{:02x}{:02x}{:02x}, the view may be all inside the set or \
+                  far outside it (check the corners, or --allow-uniform)",
+                 what, share * 100.0, color[0], color[1], color[2]);
+    }
+}
+
+//The colour most of the pixels of `image` are, and what share of them are
+fn commonest_color(image : &Image) -> ([u8; 3], f64) {
+    let channels = match image.color {
+        ColorType::Gray(_) => 1,
+        _ => 3
+    };
+    let mut counts = std::collections::HashMap::new();
+    for pixel in image.pixels.chunks(channels) {
+        *counts.entry(pixel).or_insert(0) += 1;
+    }
+    match counts.into_iter().max_by_key(|&(_, count)| count) {
+        Some((pixel, count)) => {
+            let color = match *pixel {
+                [gray] => [gray; 3],
+                _ => [pixel[0], pixel[1], pixel[2]]
+            };
+            (color, count as f64 / (image.pixels.len() / channels) as f64)
+        }
+        None => ([0; 3], 0.0)
+    }
+}
+
+fn milliseconds(since : Instant) -> f64 {
+    since.elapsed().as_secs_f64() * 1000.0
+}
+
+//<********--output auto****************>//
+//A file name made up from what the render shows, so that renders made
+//while exploring don't overwrite each other :
+//
+//    mandelbrot_c-0.7436+0.1318i_w2e-5_l5000_1920x1080.png
+//
+//The center gets more decimals the narrower the view, enough to tell
+//neighbouring views apart. A name that would be too long for a file system
+//is cut short and ends in a hash of the whole name instead, and when the
+//file is already there _2, _3 and so on go before the extension.
+const MAX_NAME : usize = 128;
+
+//The file name to use when the output is `auto`, None for any other output
+fn auto_output(options : &Options, config : &config::Config)
+    -> Result<Option<PathBuf>, Failure>
+{
+    if options.output.as_deref() != Some(Path::new(cli::AUTO_OUTPUT)) {
+        return Ok(None);
+    }
+    let view = &config.view;
+    let center = config.center();
+    let decimals = center_decimals(view);
+    let mut name = format!("c{:.*}{:+.*}i_w{}_l{}_{}x{}", decimals, center.re,
+                           decimals, center.im, short_number(view.width()),
+                           config.limit, view.bounds.0, view.bounds.1);
+    if let Some(c) = config.julia {
+        name = format!("julia_{}_k{:.6}{:+.6}i", name, c.re, c.im);
+    } else {
+        name = format!("mandelbrot_{}", name);
+    }
+    let mut name = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || "+-._".contains(c) { c } else { '_' })
+        .collect::<String>();
+    if name.len() > MAX_NAME {
+        let hash = format!("{:016x}", fnv1a(name.as_bytes()));
+        name.truncate(MAX_NAME - hash.len() - 1);
+        name = format!("{}_{}", name, hash);
+    }
+
+    let extension = match options.format {
+        Format::Png => "png",
+        Format::Csv => "csv"
+    };
+    let directory = options.output_dir.as_deref().unwrap_or(Path::new("."));
+    std::fs::create_dir_all(directory)
+        .map_err(|error| Failure::io(format!("error creating {} : {}", directory.display(),
+                                             error)))?;
+    let mut path = directory.join(format!("{}.{}", name, extension));
+    let mut suffix = 2;
+    while path.exists() {
+        path = directory.join(format!("{}_{}.{}", name, suffix, extension));
+        suffix += 1;
+    }
+    Ok(Some(path))
+}
+
+//Enough decimals of the center to tell neighbouring views apart
+fn center_decimals(view : &View) -> usize {
+    ((-view.width().log10()).ceil().max(0.0) as usize + 2).max(4)
+}
+
+//3 significant digits, without the zeros : 2e-5, 1.25e-3, 3.5e0
+fn short_number(value : f64) -> String {
+    let text = format!("{:.2e}", value);
+    let (mantissa, exponent) = text.split_once('e').unwrap();
+    format!("{}e{}", mantissa.trim_end_matches('0').trim_end_matches('.'), exponent)
+}
+
+//With --estimate, roughly how many seconds rendering `images` images like
+//`views` takes. A dry run puts it in the plan, otherwise it is printed
+//before the real run starts.
+fn estimate(options : &Options, config : &config::Config, views : &[config::View],
+            images : usize, renderer : Option<&Renderer>)
+    -> Option<f64>
+{
+    if !options.estimate {
+        return None;
+    }
+    let seconds = estimate_seconds(config, views, renderer) * images as f64;
+    if !options.dry_run {
+        info!("Estimated render time : {:.1}s", seconds);
+    }
+    warning!("the estimate comes from a sparse sample, how long a pixel \
+              takes varies a lot near the set so it can easily be off by 2x");
+    Some(seconds)
+}
+
+//With --dry-run prints the plan, everything having been checked the way a
+//real run would, and says the run should stop there
+fn dry_run<P>(options : &Options, plan : P) -> Result<bool, String>
+    where P : FnOnce() -> Json
+{
+    if options.json && !options.dry_run {
+        return Err("--json only applies to --dry-run and --scaling-sweep".to_string());
+    }
+    if options.dry_run {
+        plan::print(&plan(), options.json);
+    }
+    Ok(options.dry_run)
+}
+
+//Without --force writing over an existing file is an error, or with
+//--skip-existing a reason to leave that file be, in which case this says
+//true. Checked before rendering rather than only when the file is
+//written, so nothing is lost but the time it takes to look; the file is
+//then still created with create_new (see create_output()) in case some
+//other run got there in the meantime.
+fn check_clobber(options : &Options, filename : &Path) -> Result<bool, Failure> {
+    if options.force || is_stdout(filename) || !filename.exists() {
+        return Ok(false);
+    }
+    if options.skip_existing {
+        return Ok(true);
+    }
+    Err(Failure::io(format!("{} already exists, use --force to overwrite it (or \
+                             --skip-existing to leave it be)", filename.display())))
+}
+
+//The message for an error writing the `what` `filename`, spelling out the
+//case of a file that turned up while rendering
+fn writing<'a>(what : &'a str, filename : &'a Path)
+    -> impl Fn(std::io::Error) -> Failure + 'a
+{
+    move |error| Failure::io(match error.kind() {
+        std::io::ErrorKind::AlreadyExists =>
+            format!("{} appeared while rendering and was left alone, use --force to \
+                     overwrite it", filename.display()),
+        _ => format!("error writing {} {} : {}", what, filename.display(), error)
+    })
+}
+
+//Hands the written file to whatever the desktop opens images with.
+//Only a convenience, so failing to launch the viewer is just a warning,
+//and there's nothing to open when the image went to stdout.
+fn open_in_viewer(filename : &Path) {
+    if is_stdout(filename) {
+        return;
+    }
+    let launched = if cfg!(target_os = "macos") {
+        process::Command::new("open").arg(filename).spawn()
+    } else if cfg!(windows) {
+        //The empty argument is the window title `start` would otherwise
+        //take a quoted file name for
+        process::Command::new("cmd").args(["/C", "start", ""]).arg(filename).spawn()
+    } else {
+        process::Command::new("xdg-open").arg(filename).spawn()
+    };
+    if let Err(error) = launched {
+        warning!("couldn't open {} in an image viewer : {}", filename.display(),
+                 error);
+    }
+}
+
+//<********--save-bookmark, --permalink****>//
+//The view the options work out to, as a center and a width so that it fits
+//any image size it is loaded for
+fn resolved_view(options : &Options, config : &config::Config) -> Options {
+    Options {
+        center : Some(config.view.center()),
+        width : Some(config.view.width()),
+        zoom : None,
+        upper_left : None,
+        lower_right : None,
+        ..options.clone()
+    }
+}
+
+pub fn save_bookmark(options : &Options) -> Result<(), Failure> {
+    let name = options.save_bookmark.as_ref().unwrap();
+    let resolved = resolved_view(options, &config::build(options)?);
+    let path = bookmarks::save(name, &cli::bookmark_toml(&resolved), options.force)?;
+    info!("Saved bookmark {} to {}", name, path.display());
+    Ok(())
+}
+
+//Printed to stdout, for a script to put in a URL
+pub fn permalink(options : &Options) -> Result<(), Failure> {
+    let resolved = resolved_view(options, &config::build(options)?);
+    println!("{}", permalink::encode(&cli::permalink_lines(&resolved)));
+    Ok(())
+}
+
+//<********render --watch****************>//
+//Renders, then polls the config file (and the user presets file, since
+//the config can name a preset) for changes. A burst of saves is waited out
+//until the files have been left alone for WATCH_SETTLE, and a change that
+//comes in while the previous render is still going cancels it.
+//
+//A config that no longer parses, or doesn't render, is reported and the
+//last image written is kept. Once an image has been written it is this
+//session's own, so later renders overwrite it without --force, and
+//--open only opens the first one.
+const WATCH_POLL : Duration = Duration::from_millis(200);
+const WATCH_SETTLE : Duration = Duration::from_millis(300);
+
+struct WatchedRender {
+    cancel : Arc<AtomicBool>,
+    thread : thread::JoinHandle<Result<Option<PathBuf>, Failure>>
+}
+
+//`args` are the arguments `options` came from, parsed again on a change
+pub fn watch(args : &[OsString], options : &Options) -> Result<(), Failure> {
+    if is_stdout(options.output.as_ref().unwrap()) {
+        return Err("--watch can't write the image to stdout".into());
+    }
+    let mut files = vec![options.config.clone().unwrap()];
+    files.extend(presets::user_file());
+    info!("Watching {} for changes, Ctrl-C to stop", files.iter()
+          .map(|file| file.display().to_string()).collect::<Vec<_>>().join(" and "));
+
+    let mut stamps = modified(&files);
+    let mut written = false;
+    let mut current = Some(start_render(options.clone()));
+    loop {
+        thread::sleep(WATCH_POLL);
+        if current.as_ref().is_some_and(|render| render.thread.is_finished()) {
+            written |= finish_render(current.take().unwrap());
+        }
+        if modified(&files) == stamps {
+            continue;
+        }
+        loop {
+            stamps = modified(&files);
+            thread::sleep(WATCH_SETTLE);
+            if modified(&files) == stamps {
+                break;
+            }
+        }
+
+        if let Some(render) = current.take() {
+            if !render.thread.is_finished() {
+                info!("Cancelling the render in progress");
+            }
+            render.cancel.store(true, Ordering::Relaxed);
+            written |= finish_render(render);
+        }
+        info!("Change detected, rendering again");
+        match cli::parse(args) {
+            Ok((_, mut options)) => {
+                if written {
+                    options.force = true;
+                    options.open = false;
+                }
+                current = Some(start_render(options));
+            }
+            Err(message) => error!("{} (keeping the last image)", message)
+        }
+    }
+}
+
+//When each of `files` was last changed, None for one that doesn't exist
+fn modified(files : &[PathBuf]) -> Vec<Option<SystemTime>> {
+    files.iter()
+        .map(|file| std::fs::metadata(file).and_then(|metadata| metadata.modified()).ok())
+        .collect()
+}
+
+fn start_render(options : Options) -> WatchedRender {
+    let cancel = Arc::new(AtomicBool::new(false));
+    let flag = cancel.clone();
+    let thread = thread::spawn(move || {
+        let config = config::build(&options)?;
+        render_config(&options, config::Config { cancel : Some(flag), ..config })
+    });
+    WatchedRender { cancel, thread }
+}
+
+//Waits for `render` and reports how it went, saying whether it wrote the
+//output
+fn finish_render(render : WatchedRender) -> bool {
+    match render.thread.join() {
+        //render_config() has said what it wrote
+        Ok(Ok(Some(_))) => true,
+        Ok(Ok(None)) => false,
+        Ok(Err(message)) => {
+            error!("{} (keeping the last image)", message);
+            false
+        }
+        //The panic has already been printed
+        Err(_) => false
+    }
+}
+
+//<********render --preview-palette*******>//
+//Writes a swatch of the palette, coloured the way --palette-wrap and
+//--linear-blend would colour a render, without computing any escape counts
+pub fn preview_palette(options : &Options) -> Result<(), Failure> {
+    let output = options.output.as_ref().unwrap();
+    if check_clobber(options, output)? {
+        info!("Skipping {}, it already exists (--skip-existing)", output.display());
+        return Ok(());
+    }
+    let palette = options.preview_palette.clone().unwrap();
+    let palette = if options.linear_blend {
+        palette.blended_linearly()
+            .ok_or_else(|| "--linear-blend only applies to palettes blended between \
+                            colour stops (the built-in ones)".to_string())?
+    } else {
+        palette
+    };
+    let palette = palette.with_wrap(options.palette_wrap);
+
+    let image = Image {
+        pixels : palette.swatch(options.iterations, options.swatch_size),
+        bounds : options.swatch_size,
+        color : ColorType::RGB(8)
+    };
+    write_image(output, &image, options.force, options.png_compression)
+        .map_err(writing("PNG file", output))?;
+    if options.open {
+        open_in_viewer(output);
+    }
+    Ok(())
+}
+
+//<********render --pyramid**************>//
+//The view as the tiles of a web map : level z splits it into 2^z by 2^z
+//tiles of TILE_SIZE pixels, tile (x, y) being x from the left and y from
+//the top, written to DIR/z/x/y.png. The edges between tiles are all worked
+//out from the corners of the whole view the same way, so the right edge of
+//one tile is bit for bit the left edge of the next and they line up.
+const TILE_SIZE : usize = 256;
+
+pub fn pyramid(options : &Options) -> Result<(), Failure> {
+    let directory = options.pyramid.as_ref().unwrap();
+    let options = Options { size : Some((TILE_SIZE, TILE_SIZE)), ..options.clone() };
+    let config = config::build(&options)?;
+    let View { upper_left, lower_right, .. } = config.view;
+    let (width, height) = (lower_right.re - upper_left.re, upper_left.im - lower_right.im);
+    let renderer = new_renderer(&options);
+
+    let total = (0..options.levels).map(|level| 1usize << (2 * level)).sum::<usize>();
+    info!("Writing {} tiles in {} levels to {}", total, options.levels,
+          directory.display());
+    let _progress = Progress::start("tile", rows_to_render(&config, &config.view), total);
+    exit::catch_interrupt();
+    let mut done = 0;
+    for level in 0..options.levels {
+        let tiles = 1usize << level;
+        //Where edge `k` of `tiles` falls, counting from the upper left
+        let edge_re = |k : usize| upper_left.re + width * (k as f64 / tiles as f64);
+        let edge_im = |k : usize| upper_left.im - height * (k as f64 / tiles as f64);
+        let started = Instant::now();
+        for x in 0..tiles {
+            let column = directory.join(level.to_string()).join(x.to_string());
+            std::fs::create_dir_all(&column)
+                .map_err(|error| Failure::io(format!("error creating {} : {}",
+                                                     column.display(), error)))?;
+            for y in 0..tiles {
+                let view = View {
+                    bounds : (TILE_SIZE, TILE_SIZE),
+                    upper_left : Complex { re : edge_re(x), im : edge_im(y) },
+                    lower_right : Complex { re : edge_re(x + 1), im : edge_im(y + 1) },
+                    rotation : config.view.rotation
+                };
+                if exit::interrupted() {
+                    return Err(Failure::new(Kind::Interrupted, format!(
+                        "interrupted after {} of {} tiles", done, total)));
+                }
+                done += 1;
+                let filename = column.join(format!("{}.png", y));
+                if check_clobber(&options, &filename)? {
+                    continue;
+                }
+                let image = render_image(&config, &view, Some(&renderer));
+                progress::encoding(&filename);
+                write_image_whole(&filename, &image, options.force,
+                                  options.png_compression)
+                    .map_err(writing("PNG file", &filename))?;
+                progress::written(&filename);
+                progress::unit_done("tile", done - 1, total);
+            }
+        }
+        verbose!("Level {} : {} tiles in {:.1}s", level, tiles * tiles,
+                 started.elapsed().as_secs_f64());
+    }
+    Ok(())
+}
+
+//<********render --jobs*******************>//
+//Renders the jobs of a job file (see cli::read_jobs()) one after the other,
+//or --parallel-jobs at a time, carrying on past the ones that fail. A table
+//of how each went is printed at the end, and if any of them failed so
+//does the whole run.
+struct JobResult {
+    name : String,
+    output : String,
+    seconds : f64,
+    outcome : Result<(), String>
+}
+
+pub fn jobs(options : &Options) -> Result<(), Failure> {
+    let path = options.jobs.as_ref().unwrap();
+    let mut jobs = cli::read_jobs(path)?;
+    //The table at the end says how they went
+    progress::forgo();
+    if let Some(ref only) = options.only {
+        jobs.retain(|job| &job.name == only);
+        if jobs.is_empty() {
+            return Err(format!("{} has no job named '{}'", path.display(), only).into());
+        }
+    }
+
+    let results = Mutex::new((0..jobs.len()).map(|_| None).collect::<Vec<_>>());
+    let next = AtomicUsize::new(0);
+    //After a Ctrl-C the jobs under way are finished and no more are started
+    exit::catch_interrupt();
+    let work = || while !exit::interrupted() {
+        let index = next.fetch_add(1, Ordering::SeqCst);
+        let job = match jobs.get(index) {
+            Some(job) => job,
+            None => break
+        };
+        let result = run_job(job, options);
+        results.lock().unwrap()[index] = Some(result);
+    };
+    crossbeam::scope(|spawner| {
+        for _ in 0..options.parallel_jobs.min(jobs.len()) {
+            spawner.spawn(work);
+        }
+    });
+
+    let results = results.into_inner().unwrap().into_iter().flatten().collect::<Vec<_>>();
+    println!("{:<24}{:<8}{:>10}  OUTPUT", "JOB", "STATUS", "TIME");
+    for result in &results {
+        println!("{:<24}{:<8}{:>9.2}s  {}", result.name,
+                 if result.outcome.is_ok() { "ok" } else { "failed" },
+                 result.seconds, result.output);
+    }
+    let failed = results.iter()
+        .filter_map(|result| result.outcome.as_ref().err().map(|error| (&result.name, error)))
+        .collect::<Vec<_>>();
+    for (name, error) in &failed {
+        error!("{} : {}", name, error);
+    }
+    if results.len() < jobs.len() {
+        return Err(Failure::new(Kind::Interrupted, format!(
+            "interrupted after {} of {} jobs", results.len(), jobs.len())));
+    }
+    match failed.len() {
+        0 => Ok(()),
+        count => Err(format!("{} of {} jobs failed", count, results.len()).into())
+    }
+}
+
+//Renders each line of JSON on stdin as a job (see cli::read_json_job()) as
+//soon as it has been read, answering each with a line of JSON on stdout :
+//
+//    {"line":1,"name":"line1","status":"ok","output":"a.png","seconds":0.25,"error":null}
+//
+//A line that can't be read or rendered gets a status of "error" and the
+//reason, and the next line is read all the same. The run ends with stdin.
+pub fn jobs_stdin(options : &Options) -> Result<(), Failure> {
+    let stdin = std::io::stdin();
+    for (index, line) in stdin.lock().lines().enumerate() {
+        let line = line.map_err(|error| Failure::io(format!("error reading stdin : {}",
+                                                            error)))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut job = cli::read_json_job(&line, index + 1);
+        //Stdout carries the results
+        let output = job.options.as_ref().ok().and_then(|options| options.output.as_deref());
+        if output.is_some_and(is_stdout) {
+            job.options = Err("--jobs-stdin can't write images to stdout".to_string());
+        }
+        let result = run_job(&job, options);
+        println!("{}", Json::Object(vec![
+            ("line", Json::Integer(index as u64 + 1)),
+            ("name", Json::String(result.name)),
+            ("status", Json::String(if result.outcome.is_ok() { "ok" } else { "error" }
+                                    .to_string())),
+            ("output", Json::String(result.output)),
+            ("seconds", Json::Number(result.seconds)),
+            ("error", result.outcome.err().map_or(Json::Null, Json::String))
+        ]).to_json());
+    }
+    Ok(())
+}
+
+//`every` is the command line, whose --force and --skip-existing go for
+//every job (cli::EVERY_JOB)
+fn run_job(job : &cli::Job, every : &Options) -> JobResult {
+    let start = Instant::now();
+    //The pattern until the job gets far enough to fill it in
+    let mut output = job.options.as_ref().ok()
+        .and_then(|options| options.output.as_ref())
+        .map(|output| output.display().to_string())
+        .unwrap_or_default();
+    let outcome = job.options.clone().and_then(|mut options| {
+        options.force |= every.force;
+        options.skip_existing |= every.skip_existing;
+        //{center} is wherever the view ends up, however it was given. Job
+        //files are text, so the output is too.
+        let config = config::build(&options)?;
+        let center = config.view.center();
+        output = output
+            .replace("{name}", &job.name)
+            .replace("{center}", &format!("{},{}", center.re, center.im));
+        options.output = Some(PathBuf::from(&output));
+        info!("Job {} : rendering {}", job.name, output);
+        render_config(&options, config).map(|_| ()).map_err(|failure| failure.message)
+    });
+    JobResult {
+        name : job.name.clone(),
+        output,
+        seconds : start.elapsed().as_secs_f64(),
+        outcome
+    }
+}
+
+//<********animate*******************>//
+//Zooms into the center of the view, each frame `zoom_per_frame` times
+//narrower than the one before, writing the frames to the --output pattern
+//with {} replaced by the zero padded frame number. Alongside the frames a
+//manifest lists each frame file with a hash of its contents, which
+//verify-manifest can check later on.
+//
+//With --julia-path every frame is instead the Julia set of the next
+//constant along the path, in the same view unless --zoom-per-frame says
+//otherwise.
+//
+//With --pan-per-frame every frame also moves on by that many pixels, and
+//keeps to the same width unless --zoom-per-frame says otherwise.
+//
+//Under --perturbation or --mode perturbation the frames take their
+//reference orbits on from the frame before, and with --orbit-cache from the
+//run before (see orbits.rs).
+pub fn animate(options : &Options) -> Result<(), Failure> {
+    let config = stats::time("parse", || config::build(options))?;
+    let pattern = options.output.as_ref().unwrap();
+    if frame_filename(pattern, 0) == *pattern {
+        return Err(format!("the --output pattern '{}' needs a {{}} where the \
+                            frame number goes", pattern.display()).into());
+    }
+    let manifest_path = match options.manifest {
+        Some(ref manifest) => manifest.clone(),
+        None => pattern.with_file_name("manifest.txt")
+    };
+    //The manifest is rewritten when frames are being skipped, it has to list
+    //them all
+    if !options.skip_existing {
+        check_clobber(options, &manifest_path)?;
+    }
+    let mut existing = Vec::new();
+    for frame in 0..options.frames {
+        existing.push(check_clobber(options, &frame_filename(pattern, frame))?);
+    }
+
+    //The same threads render every frame
+    let renderer = new_renderer(options);
+
+    //Deeper frames take longer, so the estimate samples the first, middle
+    //and last frames
+    let mut sampled = vec![0, options.frames / 2, options.frames.saturating_sub(1)];
+    sampled.dedup();
+    let views = sampled.iter()
+        .map(|&frame| config::frame_view(&config.view, options, frame))
+        .collect::<Vec<_>>();
+    let estimate = estimate(options, &config, &views, options.frames, Some(&renderer));
+    if dry_run(options, || plan::for_animate(options, &config, &manifest_path,
+                                             frame_filename, estimate))? {
+        return Ok(());
+    }
+    check_memory(&config)?;
+    //The reference orbits of --perturbation, kept from frame to frame
+    let orbits = Arc::new(match options.orbit_cache {
+        Some(ref path) => Cache::read(path, orbits::MAX_POINTS).unwrap_or_else(|error| {
+            warning!("--orbit-cache {} : {}, starting without the orbits in it",
+                     path.display(), error);
+            Cache::new(orbits::MAX_POINTS)
+        }),
+        None => Cache::new(orbits::MAX_POINTS)
+    });
+    let config = config::Config { orbits : Some(orbits.clone()), ..config };
+    let mut manifest = String::from(MANIFEST_HEADER);
+    let rendering = existing.iter().filter(|&&skipped| !skipped).count();
+    //--parallel-frames renders that many frames at a time, each on its share
+    //of the threads, and holds as many
+    let parallel = options.parallel_frames.min(rendering).max(1);
+    if let Some(max_memory) = config.max_memory.filter(|_| parallel > 1) {
+        let bytes = estimated_memory(&config).saturating_mul(parallel);
+        if bytes > max_memory {
+            return Err(Failure::new(Kind::TooBig, format!(
+                "{} frames of {}x{} at a time need about {} bytes, over the limit of {}, \
+                 try fewer --parallel-frames or raise the limit with --max-memory",
+                parallel, config.view.bounds.0, config.view.bounds.1, bytes, max_memory)));
+        }
+    }
+    let renderers = match parallel {
+        1 => vec![renderer],
+        _ => (0..parallel)
+            .map(|_| renderer_of(options, (options.thread_count() / parallel).max(1)))
+            .collect()
+    };
+    let progress = Progress::start("frame", rows_to_render(&config, &config.view), rendering);
+    //The frames done before a Ctrl-C are listed, the rest left for later
+    exit::catch_interrupt();
+    //Frames zoomed in too far for --precision f32 go in f64, said once
+    let coarse = |frame| {
+        config.precision == Precision::F32
+            && !config::f32_resolves(&config::frame_view(&config.view, options, frame))
+    };
+    if let Some(frame) = (0..options.frames).find(|&frame| !existing[frame] && coarse(frame)) {
+        warning!("--precision f32 : from frame {} on the pixels are too close for f32 to \
+                  tell apart, rendering in f64 instead", frame + 1);
+    }
+    //Frames that only move by whole pixels are windows of one canvas, each
+    //moving the counts of the frame before along (see pan_canvas()), which
+    //any zooming, a Julia path or a render that isn't plain counts rules
+    //out
+    let pan = match options.pan_per_frame {
+        Some((columns, rows)) if columns.fract() == 0.0 && rows.fract() == 0.0
+            && columns.abs() < config.view.bounds.0 as f64
+            && rows.abs() < config.view.bounds.1 as f64
+            && options.frame_zoom() == 1.0 && options.julia_path.is_none()
+            && counts_move_along(&config) =>
+        {
+            let step = (columns as isize, rows as isize);
+            let (canvas, first) = pan_canvas(&config.view, step, options.frames);
+            Some((canvas, first, step))
+        }
+        _ => None
+    };
+    //Frame `frame`, captioned, `previous` being the counts of the last
+    //frame rendered on the same thread and which frame it was
+    let render_frame = |frame : usize, renderer : &Renderer,
+                        previous : &mut Option<(usize, Vec<Option<u32>>)>| {
+        let view = config::frame_view(&config.view, options, frame);
+        let julia = options.julia_path.map(|path| path.at(frame, options.frames));
+        let frame_config = config::Config {
+            julia : julia.or(config.julia),
+            precision : if coarse(frame) { Precision::F64 } else { config.precision },
+            ..config.zoomed_limits(options.zoom_at(frame), options.iteration_scale)
+        };
+        let image = match pan {
+            Some((canvas, first, step)) => {
+                let canvas = config::Config { view : canvas, ..frame_config };
+                let corner = ((first.0 as isize + step.0 * frame as isize) as usize,
+                              (first.1 as isize + step.1 * frame as isize) as usize);
+                let window = (corner, view.bounds);
+                let counts = match previous.take() {
+                    Some((last, counts)) if last + 1 == frame =>
+                        moved_counts(&canvas, Some(renderer), &counts, window, step),
+                    _ => window_counts(&canvas, Some(renderer), window)
+                };
+                let image = colour_counts(&canvas, &counts, view.bounds, canvas.limit);
+                *previous = Some((frame, counts));
+                image
+            }
+            None => render_image(&frame_config, &view, Some(renderer))
+        };
+        warn_if_uniform(options, &image, &format!("frame {}", frame + 1));
+        with_caption(options, image, &view)
+    };
+    //Writes frame `frame` (None if it was already there) and lists it
+    let mut write_frame = |frame : usize, image : Option<Image>| -> Result<(), Failure> {
+        let filename = frame_filename(pattern, frame);
+        match image {
+            Some(image) => {
+                progress::encoding(&filename);
+                write_image_whole(&filename, &image, options.force, options.png_compression)
+                    .map_err(writing("frame", &filename))?;
+                progress::written(&filename);
+                progress::unit_done("frame", frame, options.frames);
+            }
+            None => info!("Frame {} of {} : {} (already there, skipped)", frame + 1,
+                          options.frames, filename.display())
+        }
+        let hash = hash_file(&filename)
+            .map_err(|error| Failure::io(format!("error reading back {} : {}",
+                                                 filename.display(), error)))?;
+        manifest.push_str(&format!("{:016x} {}\n", hash, file_name(&filename)));
+        if existing[frame] {
+            return Ok(());
+        }
+        let view = config::frame_view(&config.view, options, frame);
+        match options.julia_path.map(|path| path.at(frame, options.frames)) {
+            Some(c) => info!("Frame {} of {} : {} (julia {},{})",
+                             frame + 1, options.frames, filename.display(), c.re, c.im),
+            None => info!("Frame {} of {} : {} (width {:e}, zoom {:e}, {} iterations)",
+                          frame + 1, options.frames, filename.display(), view.width(),
+                          view.zoom(),
+                          suggest_iterations(config.limit, options.zoom_at(frame),
+                                             options.iteration_scale))
+        }
+        Ok(())
+    };
+    //The frames are handed out to the renderers and come back here in order,
+    //each written as soon as the ones before it are
+    let reorder = Reorder::new(options.frames, parallel);
+    let mut listed = 0;
+    let mut failed = None;
+    crossbeam::scope(|spawner| {
+        for renderer in &renderers {
+            let (reorder, render_frame, existing) = (&reorder, &render_frame, &existing);
+            spawner.spawn(move || {
+                let mut previous = None;
+                loop {
+                    //The frames under way are finished and no more are started
+                    if exit::interrupted() {
+                        reorder.close();
+                    }
+                    let frame = match reorder.next() {
+                        Some(frame) => frame,
+                        None => break
+                    };
+                    let image = match existing[frame] {
+                        true => None,
+                        false => Some(render_frame(frame, renderer, &mut previous))
+                    };
+                    reorder.put(frame, image);
+                }
+            });
+        }
+        while let Some((frame, image)) = reorder.take() {
+            if let Err(failure) = write_frame(frame, image) {
+                failed = Some(failure);
+                reorder.close();
+                break;
+            }
+            listed += 1;
+        }
+    });
+    drop(progress);
+    if let Some(failure) = failed {
+        return Err(failure);
+    }
+    let interrupted = Some(listed).filter(|&listed| listed < options.frames);
+
+    create_output(&manifest_path, options.force || options.skip_existing)
+        .and_then(|mut file| file.write_all(manifest.as_bytes()))
+        .map_err(writing("manifest", &manifest_path))?;
+    if let Some(ref path) = options.orbit_cache {
+        orbits.write(path).map_err(writing("orbit cache", path))?;
+    }
+    match interrupted {
+        Some(frames) => Err(Failure::new(Kind::Interrupted, format!(
+            "interrupted after {} of {} frames, {} lists those (--skip-existing picks \
+             up from there)", frames, options.frames, manifest_path.display()))),
+        None => Ok(())
+    }
+}
+
+//The first {} of `pattern` replaced by the frame number, the rest of the
+//pattern kept byte for byte whether or not it is UTF-8
+#[cfg(unix)]
+fn frame_filename(pattern : &Path, frame : usize) -> PathBuf {
+    use std::os::unix::ffi::{OsStrExt, OsStringExt};
+    let bytes = pattern.as_os_str().as_bytes();
+    match bytes.windows(2).position(|pair| pair == b"{}") {
+        Some(at) => {
+            let mut filename = bytes[..at].to_vec();
+            filename.extend_from_slice(format!("{:04}", frame).as_bytes());
+            filename.extend_from_slice(&bytes[at + 2..]);
+            PathBuf::from(OsString::from_vec(filename))
+        }
+        None => pattern.to_path_buf()
+    }
+}
+
+//Elsewhere an OS string can't be taken apart, so the part of the path the
+//{} is in has to be text
+#[cfg(not(unix))]
+fn frame_filename(pattern : &Path, frame : usize) -> PathBuf {
+    let mut replaced = false;
+    pattern.iter()
+        .map(|part| match part.to_str() {
+            Some(text) if !replaced && text.contains("{}") => {
+                replaced = true;
+                OsString::from(text.replacen("{}", &format!("{:04}", frame), 1))
+            }
+            _ => part.to_os_string()
+        })
+        .collect()
+}
+
+//The manifest is text, so a name that isn't gets U+FFFD for its odd bytes
+fn file_name(path : &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+//<********Frame manifests*************>//
+//The manifest is a text file starting with MANIFEST_HEADER followed by a
+//line per frame : the 64-bit FNV-1a hash of the file in hex, a space, and
+//the file name relative to the manifest's directory.
+const MANIFEST_HEADER : &str = "# mandelbrot animation manifest\n";
+
+fn hash_file(path : &Path) -> Result<u64, std::io::Error> {
+    let mut contents = Vec::new();
+    File::open(path)?.read_to_end(&mut contents)?;
+    Ok(fnv1a(&contents))
+}
+
+fn fnv1a(bytes : &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+//<********verify-manifest***********>//
+pub fn verify_manifest(options : &Options) -> Result<(), Failure> {
+    let manifest_path = options.manifest.as_ref().unwrap();
+    let mut manifest = String::new();
+    File::open(manifest_path)
+        .and_then(|mut file| file.read_to_string(&mut manifest))
+        .map_err(|error| Failure::io(format!("error reading manifest {} : {}",
+                                             manifest_path.display(), error)))?;
+    if !manifest.starts_with(MANIFEST_HEADER) {
+        return Err(format!("{} is not an animation manifest", manifest_path.display()).into());
+    }
+    let directory = manifest_path.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut failures = 0;
+    let mut frames = 0;
+    for line in manifest.lines().skip(1).filter(|line| !line.is_empty()) {
+        let mut fields = line.splitn(2, ' ');
+        let (hash, name) = match (fields.next(), fields.next()) {
+            (Some(hash), Some(name)) => (hash, name),
+            _ => return Err(format!("malformed manifest line '{}'", line).into())
+        };
+        let expected = u64::from_str_radix(hash, 16)
+            .map_err(|_| format!("malformed hash in manifest line '{}'", line))?;
+
+        frames += 1;
+        match hash_file(&directory.join(name)) {
+            Ok(actual) if actual == expected => println!("ok       {}", name),
+            Ok(_) => {
+                println!("MISMATCH {}", name);
+                failures += 1;
+            }
+            Err(error) => {
+                println!("MISSING  {} ({})", name, error);
+                failures += 1;
+            }
+        }
+    }
+
+    if failures > 0 {
+        return Err(format!("{} of {} frames failed verification", failures, frames).into());
+    }
+    println!("All {} frames verified", frames);
+    Ok(())
+}
+
+//<********verify********************>//
+//Known answers, to check that a build renders what it should on the
+//platform it was built for. Each check prints ok or FAILED, and the run
+//fails if any of them did. The golden image is the full set at
+//VERIFY_SIZE in gray : any change to the iteration or the gray mapping
+//changes its hash, and so does a compiler or platform that rounds
+//differently. Everything else is checked by the tests.
+const VERIFY_SIZE : (usize, usize) = (64, 48);
+const VERIFY_GOLDEN_HASH : u64 = 0x1132_ea3f_5b59_0984;
+
+//The area is about 1.5066, a limited number of iterations puts it a little
+//higher and the sample a little either way
+const VERIFY_AREA : (f64, f64) = (1.45, 1.60);
+
+pub fn verify() -> Result<(), Failure> {
+    let golden = Options {
+        size : Some(VERIFY_SIZE),
+        upper_left : Some(Complex { re : -2.0, im : 1.2 }),
+        lower_right : Some(Complex { re : 1.0, im : -1.2 }),
+        fit : Some(Fit::Stretch),
+        ..Options::default()
+    };
+    let config = config::build(&golden)?;
+    let image = render_image(&config, &config.view, Some(&Renderer::single_threaded()));
+    let (_, area, _) = estimate_area(1000, 100_000, 1);
+    let hash = fnv1a(&image.pixels);
+    let checks = [
+        ("the origin never escapes",
+         escape_time(Complex { re : 0.0, im : 0.0 }, 255).is_none(), String::new()),
+        ("2+0i escapes straight away",
+         matches!(escape_time(Complex { re : 2.0, im : 0.0 }, 255), Some(0) | Some(1)),
+         format!("got {:?}", escape_time(Complex { re : 2.0, im : 0.0 }, 255))),
+        ("the area comes out plausible", area > VERIFY_AREA.0 && area < VERIFY_AREA.1,
+         format!("got {:.4}, expected {} to {}", area, VERIFY_AREA.0, VERIFY_AREA.1)),
+        ("the golden image matches", hash == VERIFY_GOLDEN_HASH,
+         format!("hash {:016x}, expected {:016x}", hash, VERIFY_GOLDEN_HASH))
+    ];
+
+    let mut failures = 0;
+    for (name, passed, detail) in &checks {
+        if *passed {
+            println!("ok       {}", name);
+        } else {
+            println!("FAILED   {} ({})", name, detail);
+            failures += 1;
+        }
+    }
+    if failures > 0 {
+        return Err(format!("{} of {} checks failed", failures, checks.len()).into());
+    }
+    println!("All {} checks passed", checks.len());
+    Ok(())
+}
+
+//<********census********************>//
+//Renders the view's escape counts and lists the mini-Mandelbrots found in
+//them, see census.rs, one line each with a CENTER and SIZE to zoom in with
+
+//Green, which the gray render the finds are marked on doesn't have
+const CENSUS_MARKER : [u8; 3] = [0, 255, 0];
+
+pub fn census(options : &Options) -> Result<(), Failure> {
+    let config = config::build(options)?;
+    if config.julia.is_some() {
+        return Err("census looks in the Mandelbrot set, not in Julia sets".into());
+    }
+    if config.preview_stride > 1 {
+        return Err("census needs the count of every pixel, it can't be combined with \
+                    --preview-stride".into());
+    }
+    if config.adaptive.is_some() || config.limit_map.is_some()
+        || config.diff_limits.is_some() || config.time_budget.is_some()
+    {
+        return Err("census counts at one --iterations limit, it can't be combined with \
+                    --adaptive-iterations, --limit-center/--limit-edge, --diff-limits or \
+                    --time-budget".into());
+    }
+    //The finds are listed on stdout, so the marked image can't go there
+    if let Some(ref output) = options.output {
+        if is_stdout(output) || output.as_os_str() == cli::AUTO_OUTPUT
+            || options.format == Format::Csv
+        {
+            return Err("census --output takes the name of a PNG file to mark the finds \
+                        on".into());
+        }
+    }
+    let marking = match options.output {
+        Some(ref output) if check_clobber(options, output)? => {
+            info!("Skipping {}, it already exists (--skip-existing)", output.display());
+            false
+        }
+        Some(_) => true,
+        None => false
+    };
+
+    check_memory(&config)?;
+    let started = Instant::now();
+    let renderer = new_renderer(options);
+    let progress = Progress::start("image", rows_to_render(&config, &config.view), 1);
+    let counts = render_counts(&config, Some(&renderer));
+    drop(progress);
+    verbose!("Rendered in {:.1}ms", milliseconds(started));
+    let candidates = census::find(&counts, &config.view, options.census_threshold);
+
+    println!("{:<8}{:<48}{:<14}PIXELS", "KIND", "CENTER", "SIZE");
+    for candidate in &candidates {
+        println!("{:<8}{:<48}{:<14.3e}{}", match candidate.kind {
+                     census::Kind::Island => "island",
+                     census::Kind::Peak => "peak"
+                 },
+                 format!("{},{}", candidate.center.re, candidate.center.im),
+                 candidate.size, candidate.pixels);
+    }
+    let islands = candidates.iter()
+        .filter(|candidate| matches!(candidate.kind, census::Kind::Island))
+        .count();
+    info!("Found {} islands and {} peaks in {:.2}s", islands, candidates.len() - islands,
+          started.elapsed().as_secs_f64());
+
+    if marking {
+        let output = options.output.as_ref().unwrap();
+        let image = colour_counts(&config, &counts, config.view.bounds, config.limit);
+        let image = marked(image, &census::outlines(&candidates, config.view.bounds),
+                           CENSUS_MARKER);
+        write_image(output, &image, options.force, options.png_compression)
+            .map_err(writing("PNG file", output))?;
+        info!("Wrote {}", output.display());
+    }
+    Ok(())
+}
+
+//<********probe*********************>//
+//One point, or with --to a cross-section : a line of CSV per point along
+//the way, RE,IM,COUNT with the count left blank for points in the set.
+//With --orbit the line of CSV is per iteration instead, N,RE,IM for the z
+//that iteration N came to.
+pub fn probe(options : &Options) -> Result<(), Failure> {
+    let point = options.point.unwrap();
+    let limit = options.iterations;
+    if options.orbit {
+        for (i, z) in orbit(point, limit).iter().enumerate() {
+            println!("{},{},{}", i + 1, z.re, z.im);
+        }
+        return Ok(());
+    }
+    if let Some(end) = options.line_to {
+        let counts = sample_line(point, end, options.steps, limit);
+        for (i, count) in counts.iter().enumerate() {
+            let t = if counts.len() > 1 { i as f64 / (counts.len() - 1) as f64 } else { 0.0 };
+            let sample = point + (end - point) * t;
+            println!("{},{},{}", sample.re, sample.im,
+                     count.map(|count| count.to_string()).unwrap_or_default());
+        }
+        return Ok(());
+    }
+    match escape_z(point, limit) {
+        Some((count, z)) => println!("{},{} escapes after {} iterations \
+                                      (|z| = {} at the time, arg = {})",
+                                     point.re, point.im, count + 1,
+                                     z.norm(), z.arg()),
+        None => println!("{},{} did not escape within {} iterations, \
+                          it is taken to be in the set",
+                         point.re, point.im, limit)
+    }
+    Ok(())
+}
+
+//<********measure*******************>//
+//Where two pixels of a view are on the complex plane and how far apart,
+//for planning a zoom. A pixel is at its upper left corner, as
+//pixel_to_point() puts it, so WIDTH,HEIGHT is the lower right corner of
+//the view. Under --precision fixed128 or big the points are the center plus
+//the pixels' offsets, with every digit the bits are worth (see big.rs),
+//and the spans are the difference of the offsets.
+
+pub fn measure(options : &Options) -> Result<(), Failure> {
+    let config = config::build(options)?;
+    let bounds = config.view.bounds;
+    let pixels = [options.from_pixel.unwrap(), options.to_pixel.unwrap()];
+    if let Some(&(x, y)) = pixels.iter().find(|&&(x, y)| x > bounds.0 || y > bounds.1) {
+        return Err(format!("the pixel {},{} is outside the {}x{} view, which runs from 0,0 \
+                            to {},{}", x, y, bounds.0, bounds.1, bounds.0, bounds.1).into());
+    }
+    let measured = measured(&config, &pixels);
+    for (pixel, (point, _)) in pixels.iter().zip(&measured) {
+        println!("{},{} is at {}", pixel.0, pixel.1, point);
+    }
+    let span = measured[1].1 - measured[0].1;
+    println!("Spans {:e} along the real axis and {:e} along the imaginary axis",
+             span.re.abs(), span.im.abs());
+    println!("Distance {:e}", span.norm());
+    Ok(())
+}
+
+//The points `pixels` of config's view are at, written out, and as the f64
+//offsets they are from config.origin (or from 0,0 without a deep precision)
+fn measured(config : &config::Config, pixels : &[(usize, usize)])
+    -> Vec<(String, Complex<f64>)>
+{
+    pixels.iter()
+        .map(|&pixel| {
+            let offset = pixel_to_point(&config.view, pixel);
+            match config.origin {
+                Some(ref origin) => (origin.offset_by(offset).show(), offset),
+                None => (format!("{},{}", offset.re, offset.im), offset)
+            }
+        })
+        .collect()
+}
+
+//<********area**********************>//
+//A Monte Carlo estimate : the fraction of uniformly random points in a box
+//around the set that don't escape, times the area of the box. Each sample
+//is a yes/no trial, so the standard error of the fraction p is
+//sqrt(p(1 - p)/N), and the 95% interval is 1.96 of those either way.
+//
+//Points that escape only after more than --iterations are counted as
+//inside, so the estimate comes out high unless the limit is generous.
+
+//The box sampled from, all of the set lies inside it
+const AREA_UPPER_LEFT : (f64, f64) = (-2.0, 1.25);
+const AREA_LOWER_RIGHT : (f64, f64) = (0.5, -1.25);
+
+pub fn area(options : &Options) -> Result<(), Failure> {
+    let limit = options.iterations;
+    let (inside, estimate, error) = estimate_area(limit, options.samples, options.seed);
+    println!("Area of the set is about {:.6} +/- {:.6} (95% confidence, {} of {} \
+              points inside at an iteration limit of {}, seed {})",
+             estimate, 1.96 * error, inside, options.samples, limit, options.seed);
+    Ok(())
+}
+
+//(points inside, the area, its standard error) from `samples` points
+fn estimate_area(limit : u32, samples : usize, seed : u64) -> (usize, f64, f64) {
+    let mut rng = Rng::new(seed);
+    let mut inside = 0;
+    for _ in 0..samples {
+        let point = Complex {
+            re : rng.range(AREA_UPPER_LEFT.0, AREA_LOWER_RIGHT.0),
+            im : rng.range(AREA_LOWER_RIGHT.1, AREA_UPPER_LEFT.1)
+        };
+        if escape_time(point, limit).is_none() {
+            inside += 1;
+        }
+    }
+
+    let box_area = (AREA_LOWER_RIGHT.0 - AREA_UPPER_LEFT.0)
+        * (AREA_UPPER_LEFT.1 - AREA_LOWER_RIGHT.1);
+    let fraction = inside as f64 / samples as f64;
+    let error = (fraction * (1.0 - fraction) / samples as f64).sqrt() * box_area;
+    (inside, fraction * box_area, error)
+}
+
+//<********stitch********************>//
+//Lays out the input images left to right, top to bottom, `columns` per
+//row. All inputs have to be the same size.
+pub fn stitch(options : &Options) -> Result<(), Failure> {
+    let output = options.output.as_ref().unwrap();
+    if check_clobber(options, output)? {
+        info!("Skipping {}, it already exists (--skip-existing)", output.display());
+        return Ok(());
+    }
+
+    let mut tiles = Vec::new();
+    for input in &options.inputs {
+        let tile = image::open(input)
+            .map_err(|error| Failure::io(format!("error reading {} : {}", input.display(),
+                                                 error)))?
+            .to_rgb();
+        tiles.push(tile);
+    }
+    let (tile_width, tile_height) = tiles[0].dimensions();
+    let mismatched = tiles.iter()
+        .position(|tile| tile.dimensions() != (tile_width, tile_height));
+    if let Some(index) = mismatched {
+        return Err(format!("{} is {}x{} but {} is {}x{}, all images have to be \
+                            the same size", options.inputs[index].display(),
+                           tiles[index].width(), tiles[index].height(),
+                           options.inputs[0].display(), tile_width, tile_height).into());
+    }
+
+    let bounds = (tile_width as usize, tile_height as usize);
+    let tiles = tiles.into_iter()
+        .map(|tile| Image { pixels : tile.into_raw(), bounds, color : ColorType::RGB(8) })
+        .collect();
+
+    write_image(output, &grid(tiles, options.columns), options.force,
+                options.png_compression)
+        .map_err(writing("PNG file", output))?;
+    if options.open {
+        open_in_viewer(output);
+    }
+    Ok(())
+}
+
+//The RGB `tiles`, all the same size, left to right and top to bottom
+//`columns` to a row, the last row black where it runs out
+fn grid(tiles : Vec<Image>, columns : usize) -> Image {
+    let (tile_width, tile_height) = tiles[0].bounds;
+    let columns = columns.min(tiles.len());
+    let rows = tiles.len().div_ceil(columns);
+    let bounds = (tile_width * columns, tile_height * rows);
+    let mut pixels = vec![0; bounds.0 * bounds.1 * 3];
+    for (index, tile) in tiles.into_iter().enumerate() {
+        let (left, top) = (index % columns * tile_width, index / columns * tile_height);
+        for (row, tile_row) in tile.pixels.chunks(tile_width * 3).enumerate() {
+            let start = ((top + row) * bounds.0 + left) * 3;
+            pixels[start..start + tile_width * 3].copy_from_slice(tile_row);
+        }
+    }
+    Image { pixels, bounds, color : ColorType::RGB(8) }
+}
+
+//<********contact-sheet********************>//
+//Every preset (see presets.rs, the user's own too) rendered at --cell-size
+//with its own view and limit, its name under it, and laid out --columns
+//to a row as stitch does : what they all look like at a glance, in the
+//colours given.
+const LABEL : text::Caption = text::Caption {
+    title : None,
+    coordinates : false,
+    border : [0, 0, 0],
+    font_size : Some(10)
+};
+
+pub fn contact_sheet(options : &Options) -> Result<(), Failure> {
+    let output = options.output.as_ref().unwrap();
+    if check_clobber(options, output)? {
+        info!("Skipping {}, it already exists (--skip-existing)", output.display());
+        return Ok(());
+    }
+    if options.format != Format::Png {
+        return Err("contact-sheet only writes PNG".into());
+    }
+
+    let presets = presets::all()?;
+    let renderer = new_renderer(options);
+    let started = Instant::now();
+    let mut cells = Vec::with_capacity(presets.len());
+    for (index, preset) in presets.iter().enumerate() {
+        let cell = Options {
+            center : Some(preset.center),
+            width : Some(preset.width),
+            iterations : preset.iterations,
+            size : Some(options.cell_size),
+            ..options.clone()
+        };
+        let config = config::build(&cell)
+            .map_err(|message| format!("preset {} : {}", preset.name, message))?;
+        check_memory(&config)?;
+        let image = render_image(&config, &config.view, Some(&renderer));
+        cells.push(text::captioned(image, std::slice::from_ref(&preset.name), &LABEL));
+        verbose!("Rendered {} ({} of {})", preset.name, index + 1, presets.len());
+    }
+
+    let sheet = grid(cells, options.columns);
+    write_image(output, &sheet, options.force, options.png_compression)
+        .map_err(writing("PNG file", output))?;
+    info!("Wrote {} : {} presets in {}x{} in {:.2}s", output.display(), presets.len(),
+          sheet.bounds.0, sheet.bounds.1, started.elapsed().as_secs_f64());
+    Ok(())
+}
+
+//<********serve*********************>//
+//A deliberately small HTTP server: requests are handled one at a time on
+//a shared Renderer. `GET /render?size=400x300&center=-0.75,0&width=3`
+//takes the render options (without their leading --) as query parameters
+//and answers with the PNG. Every request is held to the server's pixel
+//limit, --max-pixels if it was given and DEFAULT_SERVE_MAX_PIXELS if not,
+//to its --max-memory and to its --max-request-iterations, so that no one
+//request keeps the others waiting for long. A client gets SERVE_TIMEOUT to
+//send its request and again to take the answer, and a request line or
+//header longer than MAX_REQUEST_LINE is answered with an error unread.
+#[cfg(feature = "serve")]
+const SERVE_TIMEOUT : Duration = Duration::from_secs(10);
+#[cfg(feature = "serve")]
+const MAX_REQUEST_LINE : usize = 8192;
+#[cfg(feature = "serve")]
+const MAX_HEADERS : usize = 100;
+
+//What serve holds every request to : pixels, bytes and iterations a pixel
+#[cfg(feature = "serve")]
+type Limits = (Option<usize>, Option<usize>, u32);
+
+#[cfg(feature = "serve")]
+pub fn serve(options : &Options) -> Result<(), Failure> {
+    let listener = TcpListener::bind(&options.listen)
+        .map_err(|error| Failure::io(format!("can't listen on {} : {}", options.listen,
+                                             error)))?;
+    let max_pixels = match options.sources.iter().any(|&(name, _)| name == "--max-pixels") {
+        true => options.max_pixels,
+        false => Some(cli::DEFAULT_SERVE_MAX_PIXELS)
+    };
+    info!("Serving on http://{}/render, up to {} pixels and {} iterations a request",
+          options.listen,
+          max_pixels.map_or("any number of".to_string(), |pixels| pixels.to_string()),
+          options.max_request_iterations);
+
+    let renderer = new_renderer(options);
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let limits = (max_pixels, options.max_memory, options.max_request_iterations);
+                if let Err(error) = handle_request(stream, &renderer, limits) {
+                    warning!("error answering a request : {}", error);
+                }
+            }
+            Err(error) => warning!("failed connection : {}", error)
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "serve")]
+fn handle_request(stream : TcpStream, renderer : &Renderer, limits : Limits)
+    -> Result<(), std::io::Error>
+{
+    stream.set_read_timeout(Some(SERVE_TIMEOUT))?;
+    stream.set_write_timeout(Some(SERVE_TIMEOUT))?;
+    let mut reader = BufReader::new(stream);
+    let request = read_request(&mut reader)?;
+    let mut stream = reader.into_inner();
+
+    match request.and_then(|request_line| render_request(&request_line, renderer, limits)) {
+        Ok(png) => {
+            write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: image/png\r\n\
+                            Content-Length: {}\r\nConnection: close\r\n\r\n",
+                   png.len())?;
+            stream.write_all(&png)
+        }
+        Err((status, message)) => {
+            write!(stream, "HTTP/1.1 {}\r\nContent-Type: text/plain\r\n\
+                            Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                   status, message.len() + 1, message)?;
+            writeln!(stream)
+        }
+    }
+}
+
+//The request line of the request `reader` has, once its headers have been
+//read past (nothing in them matters here), or the HTTP status and message
+//to answer a request too long to take with
+#[cfg(feature = "serve")]
+fn read_request(reader : &mut impl BufRead)
+    -> Result<Result<String, (&'static str, String)>, std::io::Error>
+{
+    //A line, None if it goes on past MAX_REQUEST_LINE
+    let mut line = || -> Result<Option<String>, std::io::Error> {
+        let mut line = String::new();
+        reader.by_ref().take(MAX_REQUEST_LINE as u64).read_line(&mut line)?;
+        Ok(Some(line).filter(|line| line.len() < MAX_REQUEST_LINE || line.ends_with('\n')))
+    };
+    let too_long = |what| format!("the {} is over {} bytes", what, MAX_REQUEST_LINE);
+    let request_line = match line()? {
+        Some(request_line) => request_line,
+        None => return Ok(Err(("414 URI Too Long", too_long("request line"))))
+    };
+    for _ in 0..=MAX_HEADERS {
+        match line()? {
+            Some(header) if header.trim().is_empty() => return Ok(Ok(request_line)),
+            Some(_) => {}
+            None => return Ok(Err(("431 Request Header Fields Too Large", too_long("header"))))
+        }
+    }
+    Ok(Err(("431 Request Header Fields Too Large",
+            format!("more than {} headers", MAX_HEADERS))))
+}
+
+//The most iterations any pixel of `config` may take
+#[cfg(feature = "serve")]
+fn most_iterations(config : &config::Config) -> u32 {
+    [Some(config.limit), config.limit_map.map(|(center, edge)| center.max(edge)),
+     config.diff_limits.map(|(low, high)| low.max(high)), config.adaptive.map(|(_, max)| max)]
+        .iter()
+        .flatten()
+        .max()
+        .map_or(0, |&most| most)
+}
+
+//Renders the view a request line asks for into PNG bytes, or gives back
+//the HTTP status and message to answer with. The pixels, the memory and
+//the iterations are held to the server's own `limits`, whatever the
+//request says.
+#[cfg(feature = "serve")]
+fn render_request(request_line : &str, renderer : &Renderer, limits : Limits)
+    -> Result<Vec<u8>, (&'static str, String)>
+{
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method, target),
+        _ => return Err(("400 Bad Request", "malformed request".to_string()))
+    };
+    if method != "GET" {
+        return Err(("405 Method Not Allowed", "only GET is supported".to_string()));
+    }
+    let (path, query) = match target.find('?') {
+        Some(index) => (&target[..index], &target[index + 1..]),
+        None => (target, "")
+    };
+    if path != "/render" {
+        return Err(("404 Not Found", "try /render?size=...".to_string()));
+    }
+
+    let pairs = query.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.find('=') {
+            Some(index) => (percent_decode(&pair[..index]),
+                            percent_decode(&pair[index + 1..])),
+            None => (percent_decode(pair), String::new())
+        })
+        .collect::<Vec<_>>();
+    let options = cli::parse_named(Command::Render, &pairs)
+        .map_err(|message| ("400 Bad Request", message))?;
+    let (max_pixels, max_memory, max_iterations) = limits;
+    let config = config::Config { max_pixels, max_memory, ..config::build(&options)
+        .map_err(|message| ("400 Bad Request", message))? };
+    check_memory(&config).map_err(|failure| ("400 Bad Request", failure.message))?;
+    if most_iterations(&config) > max_iterations {
+        return Err(("400 Bad Request", format!(
+            "{} iterations is over the server's limit of {} a pixel",
+            most_iterations(&config), max_iterations)));
+    }
+
+    let image = match config.heatmap || config.cost_map {
+        true => heatmap::render(&config, Some(renderer)),
+        false => render_image(&config, &config.view, Some(renderer))
+    };
+    let mut png = Vec::new();
+    encode_png(&mut png, &image, options.png_compression)
+        .map_err(|error| ("500 Internal Server Error", error.to_string()))?;
+    Ok(png)
+}
+
+//Undoes the %XX escapes (and + for space) of a query string component
+#[cfg(feature = "serve")]
+fn percent_decode(s : &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+                match u8::from_str_radix(hex, 16) {
+                    Ok(byte) => {
+                        decoded.push(byte);
+                        i += 2;
+                    }
+                    Err(_) => decoded.push(b'%')
+                }
+            }
+            byte => decoded.push(byte)
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::big;
+
+    #[test]
+    fn the_summary_gives_the_zoom_asked_for() {
+        let options = Options {
+            size : Some((64, 48)),
+            center : Some(Complex { re : -0.743643887, im : 0.131825904 }),
+            zoom : Some(1e15),
+            ..Options::default()
+        };
+        let view = config::build(&options).unwrap().view;
+        assert_ne!(view.zoom(), 1e15);
+        assert_eq!(requested_scale(&options, &view), (4e-15, 1e15));
+        let zoomed_by = Options { zoom_by : Some(10.0), ..options.clone() };
+        assert_eq!(requested_scale(&zoomed_by, &view), (4e-16, 1e16));
+        let width = Options { zoom : None, width : Some(0.5), ..options };
+        assert_eq!(requested_scale(&width, &view), (0.5, 8.0));
+        //Corners have nothing else to go by
+        let corners = Options {
+            size : Some((40, 30)),
+            upper_left : Some(Complex { re : -2.0, im : 1.5 }),
+            lower_right : Some(Complex { re : 2.0, im : -1.5 }),
+            ..Options::default()
+        };
+        let view = config::build(&corners).unwrap().view;
+        assert_eq!(requested_scale(&corners, &view), (4.0, 1.0));
+    }
+
+    #[test]
+    fn measure_spans_the_view_from_corner_to_corner() {
+        let config = config::build(&Options {
+            size : Some((64, 48)),
+            upper_left : Some(Complex { re : -2.0, im : 1.2 }),
+            lower_right : Some(Complex { re : 1.0, im : -1.2 }),
+            fit : Some(Fit::Stretch),
+            ..Options::default()
+        }).unwrap();
+        let corners = measured(&config, &[(0, 0), config.view.bounds]);
+        assert_eq!((corners[0].0.as_str(), corners[1].0.as_str()), ("-2,1.2", "1,-1.2"));
+        assert_eq!(corners[1].1 - corners[0].1, config.view.lower_right - config.view.upper_left);
+    }
+
+    #[test]
+    fn a_deep_measure_is_written_with_the_digits_of_the_center() {
+        let config = big::tests::centered(Precision::Big(256), 1e-30, false);
+        let middle = (config.view.bounds.0 / 2, config.view.bounds.1 / 2);
+        let measured = measured(&config, &[middle]);
+        assert_eq!(measured[0].0, big::tests::MISIUREWICZ);
+    }
+
+    #[cfg(feature = "serve")]
+    #[test]
+    fn serve_refuses_requests_over_its_iteration_limit() {
+        let renderer = new_renderer(&Options { threads : 1, ..Options::default() });
+        let limits = (Some(10_000), None, 1000);
+        let request = |query : &str| render_request(
+            &format!("GET /render?size=8x6&center=-0.5,0&width=3&{} HTTP/1.1", query),
+            &renderer, limits);
+        assert!(request("iterations=1000").is_ok());
+        for query in ["iterations=4000000000", "limit-center=100&limit-edge=5000",
+                      "adaptive-iterations&max-iterations=2000"] {
+            match request(query) {
+                Err((status, message)) => {
+                    assert_eq!(status, "400 Bad Request");
+                    assert!(message.contains("limit of 1000"), "{}", message);
+                }
+                Ok(_) => panic!("{} rendered", query)
+            }
+        }
+    }
+
+    #[cfg(feature = "serve")]
+    #[test]
+    fn serve_reads_no_further_than_its_line_limits() {
+        let read = |request : String| read_request(&mut request.as_bytes()).unwrap();
+        let line = "GET /render?size=8x6 HTTP/1.1\r\n";
+        assert_eq!(read(format!("{}Host: here\r\n\r\n", line)), Ok(line.to_string()));
+        let long = format!("GET /render?{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_REQUEST_LINE));
+        assert_eq!(read(long).unwrap_err().0, "414 URI Too Long");
+        let header = format!("{}Cookie: {}\r\n\r\n", line, "a".repeat(MAX_REQUEST_LINE));
+        assert_eq!(read(header).unwrap_err().0, "431 Request Header Fields Too Large");
+        let headers = format!("{}{}\r\n", line, "A: b\r\n".repeat(MAX_HEADERS + 1));
+        assert_eq!(read(headers).unwrap_err().0, "431 Request Header Fields Too Large");
+        assert!(read(format!("{}{}\r\n", line, "A: b\r\n".repeat(MAX_HEADERS))).is_ok());
+    }
+}
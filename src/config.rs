@@ -0,0 +1,1059 @@
+//<***************Building a render configuration*****************>//
+//Turns the Options that came off the command line (or out of an HTTP
+//query) into a Config: the view in terms of its corners, plus how to
+//iterate and colour it. Every subcommand that renders goes through here
+//so they all agree on what the shared options mean.
+
+use num::Complex;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+use crate::big::Point;
+use crate::blend::Blend;
+use crate::cli::{Exterior, Mode, Options, Precision, BIG_BITS, DEFAULT_BAND_WIDTH};
+use crate::fixed;
+use crate::orbits::Cache;
+use crate::palette::Palette;
+use crate::simd;
+use crate::{aspect_mismatch, corners_from_center, fit_view, parse_complex, Fit, Real,
+            ASPECT_TOLERANCE};
+
+//The width of the view at --zoom 1, which takes in the whole set
+pub const FULL_WIDTH : f64 = 4.0;
+
+//The closest neighbouring pixels may be under --precision f32, relative to
+//the size of the coordinates around the view : about 8 of f32's steps,
+//past which the picture turns visibly blocky
+pub const F32_MIN_STEP : f64 = 1e-6;
+
+//A window onto the complex plane and the pixels it is sampled at
+#[derive(Clone, Copy)]
+pub struct View {
+    pub bounds : (usize, usize),
+    pub upper_left : Complex<f64>,
+    pub lower_right : Complex<f64>,
+    //The corners are those of the window before it is turned, None for an
+    //upright one
+    pub rotation : Option<Rotation>
+}
+
+//--rotate : the sampling grid turned counterclockwise around `pivot`, the
+//center of the whole view (a tile or a band of it keeps the same pivot).
+//`turn` is the unit complex number every offset from the pivot is
+//multiplied by, i.e. the rotation matrix [cos -sin; sin cos].
+#[derive(Clone, Copy)]
+pub struct Rotation {
+    pub degrees : f64,
+    pub turn : Complex<f64>,
+    pub pivot : Complex<f64>
+}
+
+impl Rotation {
+    pub fn new(degrees : f64, pivot : Complex<f64>) -> Rotation {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        Rotation { degrees, turn : Complex { re : cos, im : sin }, pivot }
+    }
+}
+
+impl View {
+    pub fn center(&self) -> Complex<f64> {
+        Complex {
+            re : (self.upper_left.re + self.lower_right.re) / 2.0,
+            im : (self.upper_left.im + self.lower_right.im) / 2.0
+        }
+    }
+
+    pub fn width(&self) -> f64 {
+        self.lower_right.re - self.upper_left.re
+    }
+
+    //Magnification relative to the FULL_WIDTH view
+    pub fn zoom(&self) -> f64 {
+        FULL_WIDTH / self.width()
+    }
+
+    //Where a point of the upright window ends up once it is turned
+    pub fn rotated(&self, point : Complex<f64>) -> Complex<f64> {
+        self.rotated_in(point)
+    }
+
+    //rotated() worked out in F
+    pub fn rotated_in<F : Real>(&self, point : Complex<F>) -> Complex<F> {
+        match self.rotation {
+            Some(Rotation { turn, pivot, .. }) => {
+                let (turn, pivot) = (F::complex(turn), F::complex(pivot));
+                pivot + (point - pivot) * turn
+            }
+            None => point
+        }
+    }
+
+    //The same view moved by `by` times its width (re) and height (im)
+    pub fn panned(&self, by : Complex<f64>) -> View {
+        let shift = Complex {
+            re : self.width() * by.re,
+            im : (self.upper_left.im - self.lower_right.im) * by.im
+        };
+        View {
+            bounds : self.bounds,
+            upper_left : self.upper_left + shift,
+            lower_right : self.lower_right + shift,
+            rotation : self.rotation.map(|rotation| Rotation {
+                pivot : rotation.pivot + shift,
+                ..rotation
+            })
+        }
+    }
+
+    //The same view scaled down around its center by `factor`
+    pub fn zoomed(&self, factor : f64) -> View {
+        let center = self.center();
+        let half = Complex {
+            re : (self.lower_right.re - self.upper_left.re) / 2.0 / factor,
+            im : (self.upper_left.im - self.lower_right.im) / 2.0 / factor
+        };
+        View {
+            bounds : self.bounds,
+            upper_left : Complex { re : center.re - half.re, im : center.im + half.im },
+            lower_right : Complex { re : center.re + half.re, im : center.im - half.im },
+            ..*self
+        }
+    }
+}
+
+//Everything needed to render a view, whichever subcommand asked for it
+#[derive(Clone)]
+pub struct Config {
+    pub view : View,
+    pub mode : Mode,
+    pub limit : u32,
+    //(center limit, edge limit) of a radial iteration-limit falloff
+    pub limit_map : Option<(u32, u32)>,
+    //(low, high) limits to compare instead of rendering at `limit`
+    pub diff_limits : Option<(u32, u32)>,
+    //(threshold, maximum limit) when the limit is to be found adaptively
+    pub adaptive : Option<(f64, u32)>,
+    pub chromatic : Option<u32>,
+    pub palette : Option<Palette>,
+    //Escape counts to a ring, with --mode bands
+    pub band_width : u32,
+    pub gray : Gray,
+    //How long rendering one image may take before it is cut short
+    pub time_budget : Option<Duration>,
+    pub fast_math : bool,
+    //Iterate rows of points in SIMD lanes, --simd
+    pub simd : bool,
+    pub precision : Precision,
+    //Under --precision big, the center the view's corners are offsets from
+    //(see big.rs)
+    pub origin : Option<Point>,
+    //Iterate only `origin` in that precision and the pixels as differences
+    //from its orbit, see perturbation.rs
+    pub perturbation : bool,
+    //The most pixels `view` may have, --max-pixels
+    pub max_pixels : Option<usize>,
+    //The most bytes its buffers may take up, --max-memory
+    pub max_memory : Option<usize>,
+    //Count the iterations for --report-iterations
+    pub count_iterations : bool,
+    //The constant of the Julia set to render instead of the Mandelbrot set
+    pub julia : Option<Complex<f64>>,
+    //Mark the pixels perturbation had to iterate again
+    pub show_glitches : bool,
+    //Colour the counts over their range with a colorbar, see heatmap.rs
+    pub heatmap : bool,
+    //The same for the iterations each pixel took
+    pub cost_map : bool,
+    //Mix smooth counts and an orbit trap, see blend.rs
+    pub blend : Option<Blend>,
+    //Render only every this many rows, for a preview, 1 for all of them
+    pub preview_stride : usize,
+    //Set from another thread to stop the render early, --watch does so
+    //when the config file changes again before the render is done
+    pub cancel : Option<Arc<AtomicBool>>,
+    //Reference orbits kept from one frame of an animation for the next,
+    //see orbits.rs
+    pub orbits : Option<Arc<Cache>>
+}
+
+impl Config {
+    //The center of the view, wherever `origin` puts it
+    pub fn center(&self) -> Complex<f64> {
+        match self.origin {
+            Some(ref origin) => origin.rounded() + self.view.center(),
+            None => self.view.center()
+        }
+    }
+
+    //The same config with every iteration limit it has grown the way
+    //suggest_iterations() has it for a view `zoom` times narrower
+    pub fn zoomed_limits(&self, zoom : f64, scale : f64) -> Config {
+        let grown = |limit| suggest_iterations(limit, zoom, scale);
+        Config {
+            limit : grown(self.limit),
+            limit_map : self.limit_map.map(|(center, edge)| (grown(center), grown(edge))),
+            diff_limits : self.diff_limits.map(|(low, high)| (grown(low), grown(high))),
+            adaptive : self.adaptive.map(|(threshold, max)| (threshold, grown(max))),
+            ..self.clone()
+        }
+    }
+}
+
+//<********--iteration-scale****************>//
+//The deeper a view the more iterations it takes to show the same detail :
+//points close enough to the boundary to tell apart at that width take
+//longer to escape. With --iteration-scale S an animation's limit grows by
+//S times --iterations for every tenfold zoom, so frame k of a zoom by F a
+//frame gets
+//
+//    iterations * (1 + S * log10(F^k))
+//
+//With F = 1.1 that is a tenfold zoom every 24 frames, and at S = 1 the
+//limit doubles by then and triples by frame 48. S = 0 keeps it fixed, and
+//zooming out never takes the limit below where it started.
+pub fn suggest_iterations(base : u32, zoom : f64, scale : f64) -> u32 {
+    let grown = base as f64 * (1.0 + scale * zoom.log10().max(0.0));
+    grown.round().min(u32::MAX as f64) as u32
+}
+
+//How escape counts map to grays when there is no palette
+#[derive(Clone, Copy)]
+pub struct Gray {
+    pub gamma : f64,
+    //The gray of points that never escape
+    pub interior : u8,
+    pub exterior : Exterior,
+    //The percentiles of the escape counts the gray is stretched between,
+    //with --auto-exposure
+    pub exposure : Option<(f64, f64)>
+}
+
+pub const DEFAULT_EXPOSURE_CLIP : (f64, f64) = (1.0, 99.0);
+
+//The gray with none of the options that change it
+pub const PLAIN_GRAY : Gray = Gray {
+    gamma : 1.0,
+    interior : 0,
+    exterior : Exterior::LightToDark,
+    exposure : None
+};
+
+//The way the Julia constant moves over the frames of an animation
+#[derive(Clone, Copy)]
+pub enum JuliaPath {
+    //Once around the circle, starting on the right of its center
+    Circle { center : Complex<f64>, radius : f64 }
+}
+
+impl JuliaPath {
+    //The constant `frame` frames into `frames`, the last frame stopping
+    //short of where the first one was so that the animation loops
+    pub fn at(&self, frame : usize, frames : usize) -> Complex<f64> {
+        match *self {
+            JuliaPath::Circle { center, radius } => {
+                let angle = 2.0 * std::f64::consts::PI * frame as f64 / frames as f64;
+                center + Complex::from_polar(&radius, &angle)
+            }
+        }
+    }
+}
+
+//The view of frame `frame` of an animation starting at `view` : moved on by
+//--pan-per-frame pixels of `view` a frame, then zoomed into around where
+//that puts its center
+pub fn frame_view(view : &View, options : &Options, frame : usize) -> View {
+    let (columns, rows) = options.pan_per_frame.unwrap_or((0.0, 0.0));
+    let frame_f = frame as f64;
+    view.panned(Complex { re : columns * frame_f / view.bounds.0 as f64,
+                          im : -rows * frame_f / view.bounds.1 as f64 })
+        .zoomed(options.zoom_at(frame))
+}
+
+//The width and height closest to `megapixels` million pixels in the shape
+//of `aspect`. The height is rounded first and the width follows from it, so
+//the shape is as close as whole pixels allow and the count within a row or
+//a column of the target.
+pub fn bounds_from_megapixels(megapixels : f64, aspect : (f64, f64)) -> (usize, usize) {
+    let ratio = aspect.0 / aspect.1;
+    let height = ((megapixels * 1e6 / ratio).sqrt().round() as usize).max(1);
+    let width = ((height as f64 * ratio).round() as usize).max(1);
+    (width, height)
+}
+
+pub fn build(options : &Options) -> Result<Config, String> {
+    if options.aspect.is_some() && options.megapixels.is_none() {
+        return Err("--aspect only applies with --megapixels".to_string());
+    }
+    let bounds = match (options.size, options.megapixels) {
+        (Some(_), Some(_)) =>
+            return Err("give either --size or --megapixels, not both".to_string()),
+        (Some(size), None) => size,
+        (None, Some(megapixels)) => {
+            let bounds = bounds_from_megapixels(megapixels,
+                                                options.aspect.unwrap_or((4.0, 3.0)));
+            info!("Using a size of {}x{} ({:.2} megapixels)", bounds.0, bounds.1,
+                  (bounds.0 * bounds.1) as f64 / 1e6);
+            bounds
+        }
+        (None, None) => return Err("no image size given (--size WIDTHxHEIGHT or \
+                                    --megapixels MP)".to_string())
+    };
+    if bounds.0 == 0 || bounds.1 == 0 {
+        return Err(format!("the image size {}x{} has no pixels, both dimensions \
+                            have to be at least 1", bounds.0, bounds.1));
+    }
+    //Where auto goes as deep as big:BITS, whose every pixel would take
+    //BigInts, it renders by --perturbation
+    let settled = match options.precision {
+        Precision::Auto { fixed } => {
+            let precision = auto_precision(options, bounds, fixed);
+            let perturbation = options.perturbation || matches!(precision, Precision::Big(_));
+            match perturbation {
+                true => info!("--precision auto : {} with --perturbation", precision.name()),
+                false => info!("--precision auto : {}", precision.name())
+            }
+            Some(Options { precision, perturbation, ..options.clone() })
+        }
+        _ => None
+    };
+    let options = settled.as_ref().unwrap_or(options);
+    let (view, origin) = match options.precision.fixed_bits() {
+        Some(bits) => {
+            let (view, origin) = deep_view(options, bounds, bits)?;
+            (view, Some(origin))
+        }
+        None => (resolve_view(options, bounds)?, None)
+    };
+
+    //auto may have settled on a precision that needs no reference orbit
+    let perturbation = options.perturbation && origin.is_some();
+    if options.perturbation && !perturbation && settled.is_none() {
+        return Err(format!("--perturbation measures from an orbit in fixed point, it \
+                            needs --precision fixed128, big:BITS or auto, not {}",
+                           options.precision.name()));
+    }
+
+    let limit_map = match (options.limit_center, options.limit_edge) {
+        (Some(center), Some(edge)) => Some((center, edge)),
+        (None, None) => None,
+        _ => return Err("--limit-center and --limit-edge have to be given together"
+                        .to_string())
+    };
+
+    if options.julia.is_some() && options.julia_path.is_some() {
+        return Err("give either --julia or --julia-path, not both".to_string());
+    }
+    let julia = options.julia.or(options.julia_path.map(|path| path.at(0, options.frames)));
+    if julia.is_some() {
+        if options.mode != Mode::Gray && options.mode != Mode::Bands {
+            return Err("Julia sets are only rendered with --mode gray or bands"
+                       .to_string());
+        }
+        if options.chromatic.is_some() || options.adaptive || limit_map.is_some() {
+            return Err("Julia sets can't be rendered with --chromatic, \
+                        --adaptive-iterations or --limit-center/--limit-edge".to_string());
+        }
+    }
+
+    if options.mode == Mode::Perturbation
+        && (options.adaptive || limit_map.is_some() || options.fast_math || options.simd)
+    {
+        return Err("--mode perturbation can't be combined with --adaptive-iterations, \
+                    --limit-center/--limit-edge, --fast-math or --simd".to_string());
+    }
+    if options.simd && options.fast_math {
+        return Err("give either --simd or --fast-math, not both (--simd already \
+                    iterates on plain numbers)".to_string());
+    }
+    if options.precision != Precision::F64
+        && (options.mode == Mode::Angle || options.mode == Mode::Perturbation
+            || julia.is_some())
+    {
+        return Err(format!("--precision {} only applies to the Mandelbrot set in --mode \
+                            gray or bands", options.precision.name()));
+    }
+    if origin.is_some()
+        && (options.simd || options.fast_math || options.adaptive || limit_map.is_some()
+            || options.diff_limits.is_some() || options.chromatic.is_some()
+            || options.report_iterations || options.title_coordinates)
+    {
+        return Err(format!("--precision {} can't be combined with --simd, --fast-math, \
+                            --adaptive-iterations, --limit-center/--limit-edge, \
+                            --diff-limits, --chromatic, --report-iterations or \
+                            --title-coordinates", options.precision.name()));
+    }
+    if options.precision == Precision::DoubleDouble && options.simd {
+        return Err("--precision dd can't be combined with --simd (no SIMD instructions \
+                    take double-doubles)".to_string());
+    }
+    let precision = match options.precision {
+        Precision::F32 if !f32_resolves(&view) => {
+            warning!("--precision f32 : at zoom {:e} the pixels are too close for f32 to \
+                      tell apart, rendering in f64 instead", view.zoom());
+            Precision::F64
+        }
+        precision => precision
+    };
+    if options.simd {
+        match simd::lanes(precision) {
+            1 => warning!("--simd : this CPU has neither AVX2 nor NEON, the points are \
+                           iterated one at a time"),
+            lanes => verbose!("Iterating {} points at a time with {}", lanes, simd::name())
+        }
+    }
+
+    if options.diff_limits.is_some()
+        && (options.mode != Mode::Gray || julia.is_some() || options.palette.is_some()
+            || options.chromatic.is_some() || options.adaptive || limit_map.is_some())
+    {
+        return Err("--diff-limits has its own colours, it can't be combined with \
+                    --mode, --julia, --palette, --chromatic, --adaptive-iterations or \
+                    --limit-center/--limit-edge".to_string());
+    }
+
+    if options.rotate + options.rotate_by != 0.0 && options.mode == Mode::Perturbation {
+        return Err("--rotate and --rotate-by can't be combined with --mode perturbation, whose \
+                    pixels are measured along the upright grid".to_string());
+    }
+
+    let plain = options.mode != Mode::Angle && options.mode != Mode::Perturbation;
+    if options.report_iterations && (!plain || julia.is_some()) {
+        return Err("--report-iterations counts the plain Mandelbrot iteration, not that \
+                    of --mode angle, --mode perturbation or Julia sets".to_string());
+    }
+
+    if options.show_glitches && options.mode != Mode::Perturbation {
+        return Err("--show-glitches only applies to --mode perturbation".to_string());
+    }
+
+    if options.heatmap && options.cost_map {
+        return Err("give either --heatmap or --cost-map, not both".to_string());
+    }
+    let heatmap = match (options.heatmap, options.cost_map) {
+        (true, _) => Some("--heatmap"),
+        (_, true) => Some("--cost-map"),
+        _ => None
+    };
+    if let Some(name) = heatmap {
+        if options.mode == Mode::Angle || options.mode == Mode::Bands
+            || options.chromatic.is_some() || options.diff_limits.is_some()
+            || options.show_glitches || options.time_budget.is_some()
+            || options.preview_stride > 1 || options.palette_wrap
+        {
+            return Err(format!("{} can't be combined with --mode angle or bands, \
+                                --chromatic, --diff-limits, --show-glitches, --time-budget, \
+                                --preview-stride or --palette-wrap", name));
+        }
+    }
+    //A colouring of its own, in f64 like --mode angle
+    if options.blend.is_some()
+        && (options.mode != Mode::Gray || julia.is_some() || heatmap.is_some()
+            || options.chromatic.is_some() || options.diff_limits.is_some()
+            || limit_map.is_some() || options.precision != Precision::F64
+            || options.perturbation || options.simd || options.fast_math
+            || options.report_iterations)
+    {
+        return Err("--blend colours the Mandelbrot set in --mode gray on its own, it can't \
+                    be combined with Julia sets, --heatmap, --cost-map, --chromatic, \
+                    --diff-limits, --limit-center/--limit-edge, --precision, \
+                    --perturbation, --simd, --fast-math or --report-iterations"
+                   .to_string());
+    }
+    //Inside the set a pixel costs the limit, which has to be the same everywhere
+    if options.cost_map && (options.adaptive || limit_map.is_some()) {
+        return Err("--cost-map can't be combined with --adaptive-iterations or \
+                    --limit-center/--limit-edge".to_string());
+    }
+    if options.palette.is_some() && options.mode == Mode::Angle {
+        return Err("--palette doesn't apply to --mode angle".to_string());
+    }
+    let palette = match options.palette {
+        Some(ref palette) if options.linear_blend => Some(palette.blended_linearly()
+            .ok_or_else(|| "--linear-blend only applies to palettes blended between \
+                            colour stops (the built-in ones), a list of colours is \
+                            used as it is".to_string())?),
+        None if options.linear_blend =>
+            return Err("--linear-blend only applies with a --palette".to_string()),
+        ref palette => palette.clone()
+    };
+    if options.band_width != DEFAULT_BAND_WIDTH && options.mode != Mode::Bands {
+        return Err("--band-width only applies to --mode bands".to_string());
+    }
+    if options.palette.is_some() || heatmap.is_some() || options.blend.is_some()
+        || options.mode == Mode::Angle || options.mode == Mode::Bands
+    {
+        let gray_options = [("--gamma", options.gamma != 1.0),
+                            ("--interior-value", options.interior_value != 0),
+                            ("--exterior", options.exterior != Exterior::LightToDark),
+                            ("--auto-exposure", options.auto_exposure)];
+        if let Some(&(name, _)) = gray_options.iter().find(|&&(_, given)| given) {
+            return Err(format!("{} only applies without a --palette, --heatmap, \
+                                --cost-map or --blend, and not to --mode angle or bands",
+                               name));
+        }
+    }
+    if options.exposure_clip.is_some() && !options.auto_exposure {
+        return Err("--exposure-clip only applies with --auto-exposure".to_string());
+    }
+    if options.auto_exposure
+        && (options.chromatic.is_some() || options.diff_limits.is_some()
+            || limit_map.is_some())
+    {
+        return Err("--auto-exposure can't be combined with --chromatic, --diff-limits \
+                    or --limit-center/--limit-edge".to_string());
+    }
+    if let Some(offset) = options.chromatic {
+        if options.palette.is_some() {
+            return Err("--chromatic can't be combined with --palette".to_string());
+        }
+        if options.mode != Mode::Gray {
+            return Err("--chromatic only applies to --mode gray".to_string());
+        }
+        if options.time_budget.is_some() {
+            return Err("--time-budget can't be combined with --chromatic, a channel \
+                        cut short would tint the picture instead of leaving it black"
+                       .to_string());
+        }
+        if offset.checked_mul(2).is_none_or(|twice| twice >= options.iterations) {
+            return Err(format!("--chromatic {} is too large for an iteration \
+                                limit of {}", offset, options.iterations));
+        }
+    }
+
+    Ok(Config {
+        view,
+        mode : options.mode,
+        limit : options.iterations,
+        limit_map,
+        adaptive : if options.adaptive {
+            Some((options.adaptive_threshold, options.max_iterations))
+        } else {
+            None
+        },
+        diff_limits : options.diff_limits,
+        chromatic : options.chromatic,
+        palette : palette.map(|palette| palette.with_wrap(options.palette_wrap)),
+        band_width : options.band_width,
+        gray : Gray {
+            gamma : options.gamma,
+            interior : options.interior_value,
+            exterior : options.exterior,
+            exposure : if options.auto_exposure {
+                Some(options.exposure_clip.unwrap_or(DEFAULT_EXPOSURE_CLIP))
+            } else {
+                None
+            }
+        },
+        time_budget : options.time_budget,
+        fast_math : options.fast_math,
+        simd : options.simd,
+        precision,
+        origin,
+        perturbation,
+        max_pixels : options.max_pixels,
+        max_memory : options.max_memory,
+        count_iterations : options.report_iterations,
+        julia,
+        show_glitches : options.show_glitches,
+        heatmap : options.heatmap,
+        cost_map : options.cost_map,
+        blend : options.blend,
+        preview_stride : options.preview_stride,
+        cancel : None,
+        orbits : None
+    })
+}
+
+//Works out the corners of the view, either given directly or through
+//--center/--width, and checks the aspect ratio against the pixels
+fn resolve_view(options : &Options, bounds : (usize, usize)) -> Result<View, String> {
+    let corners = options.upper_left.is_some() || options.lower_right.is_some();
+    let centered = options.center.is_some() || options.width.is_some()
+        || options.zoom.is_some();
+    if corners && centered {
+        return Err("give either the corners or --center/--width, not both"
+                   .to_string());
+    }
+    if options.width.is_some() && options.zoom.is_some() {
+        return Err("give either --width or --zoom, not both".to_string());
+    }
+
+    let width = options.width.or(options.zoom.map(|zoom| FULL_WIDTH / zoom));
+    let (upper_left, lower_right) = match (options.center, width,
+                                           options.upper_left, options.lower_right) {
+        (Some(center), Some(width), _, _) =>
+            corners_from_center(bounds, center, width),
+        (None, None, Some(upper_left), Some(lower_right)) =>
+            (upper_left, lower_right),
+        _ if centered =>
+            return Err("--center and --width (or --zoom) have to be given together"
+                       .to_string()),
+        _ => return Err("no view given (--upper-left and --lower-right, \
+                         or --center and --width or --zoom)".to_string())
+    };
+
+    let (upper_left, lower_right) =
+        check_corners(upper_left, lower_right, options.swap_corners)?;
+
+    let mismatch = aspect_mismatch(bounds, upper_left, lower_right);
+    let (upper_left, lower_right) = match options.fit {
+        Some(fit) => {
+            let (upper_left, lower_right) =
+                fit_view(bounds, upper_left, lower_right, fit);
+            if fit != Fit::Stretch && mismatch > 0.0 {
+                info!("Adjusted view to upper left {},{} lower right {},{}",
+                      upper_left.re, upper_left.im,
+                      lower_right.re, lower_right.im);
+            }
+            (upper_left, lower_right)
+        }
+        None => {
+            if mismatch > ASPECT_TOLERANCE {
+                warning!("the view's aspect ratio is {:.1}% off \
+                          the image's {}x{}, the picture will be stretched \
+                          (use --fit expand|crop|stretch)",
+                         mismatch * 100.0, bounds.0, bounds.1);
+            }
+            (upper_left, lower_right)
+        }
+    };
+
+    let mut view = View { bounds, upper_left, lower_right, rotation : None };
+    //The nudges on top of a loaded view, always the pan first
+    if let Some(by) = options.pan {
+        view = view.panned(by);
+    }
+    if let Some(factor) = options.zoom_by {
+        view = view.zoomed(factor);
+    }
+    if options.pan.is_some() || options.zoom_by.is_some() {
+        let center = view.center();
+        info!("View after --pan/--zoom-by : center {},{} width {}", center.re,
+              center.im, view.width());
+    }
+    //Turned last, around wherever the view ended up
+    let degrees = options.rotate + options.rotate_by;
+    if degrees != 0.0 {
+        view.rotation = Some(Rotation::new(degrees, view.center()));
+    }
+    warn_precision(&view, options.mode, options.precision);
+    Ok(view)
+}
+
+//--precision fixed128 or big : the view around 0,0 and the center it is
+//around, read from all the digits --center was given with when it is a
+//pair of decimals
+fn deep_view(options : &Options, bounds : (usize, usize), bits : usize)
+    -> Result<(View, Point), String>
+{
+    let center = match (options.center, options.width.or(options.zoom)) {
+        (Some(center), Some(_)) => center,
+        _ => return Err(format!("--precision {} needs the view as --center and --width \
+                                 or --zoom", options.precision.name()))
+    };
+    if options.precision == Precision::Fixed128 && center.re.abs().max(center.im.abs()) > 4.0 {
+        return Err(format!("--precision fixed128 only holds centers with parts from -4 to 4, \
+                            not {},{}", center.re, center.im));
+    }
+    let digits = options.center_text.as_ref()
+        .filter(|text| parse_complex(text).ok() == Some(center))
+        .and_then(|text| Point::parse(text, bits));
+    let origin = digits.unwrap_or_else(|| {
+        verbose!("--precision {} : --center isn't a pair of decimals, its f64 value \
+                  is the center", options.precision.name());
+        Point::of(center, bits)
+    });
+    let around = Options { center : Some(Complex { re : 0.0, im : 0.0 }), ..options.clone() };
+    let view = resolve_view(&around, bounds)?;
+    match options.precision {
+        Precision::Big(_) => verbose!("Center {} to {} bits, iterated in {}", origin.show(),
+                                      bits, crate::big::ARITHMETIC),
+        _ => verbose!("Center {} to {} bits", origin.show(), bits)
+    }
+    let pixel = pixel_spacing(&view);
+    if pixel < 2f64.powi(-(bits as i32)) * 16.0 {
+        let more = match options.precision {
+            Precision::Fixed128 => "--precision big:BITS takes more",
+            _ => "give more of them"
+        };
+        warning!("--precision {} : the pixels are {:e} apart, too close for {} bits \
+                  after the binary point to resolve, {}", options.precision.name(), pixel,
+                 bits, more);
+    }
+    Ok((view, origin))
+}
+
+//--precision auto leaves this many bits to spare : a precision is only
+//picked if it tells apart points 2^4 = 16 times closer together than the
+//pixels, the margin the precision warnings go by
+pub const AUTO_HEADROOM_BITS : i32 = 4;
+
+//--precision auto : the quickest precision that keeps the pixels of the
+//view apart with AUTO_HEADROOM_BITS to spare. From the quickest that is
+//f64, fixed128 (quicker than double-doubles as well as deeper, but only for
+//a view given by --center with parts from -4 to 4), double-doubles (the
+//deepest a view given by its corners goes) and big with as many bits as
+//the pixels take, in multiples of 32. Neither fixed point is picked with
+//`fixed` false or with options they can't be combined with, nor anything
+//but f64 for the renders only f64 does. f32 never is, it moves the edges
+//of the set.
+pub fn auto_precision(options : &Options, bounds : (usize, usize), fixed : bool) -> Precision {
+    let width = options.width.or(options.zoom.map(|zoom| FULL_WIDTH / zoom));
+    //The view around 0,0 under a center, whose pixels are as far apart
+    let (center, view) = match (options.center, width, options.upper_left,
+                                options.lower_right) {
+        (Some(center), Some(width), _, _) => {
+            let (upper_left, lower_right) =
+                corners_from_center(bounds, Complex { re : 0.0, im : 0.0 }, width);
+            (center, View { bounds, upper_left, lower_right, rotation : None })
+        }
+        (None, None, Some(upper_left), Some(lower_right)) => {
+            let view = View { bounds, upper_left, lower_right, rotation : None };
+            (view.center(), view)
+        }
+        //No view, which resolve_view() has to say
+        _ => return Precision::F64
+    };
+    let view = options.zoom_by.map_or(view, |factor| view.zoomed(factor));
+    let pixel = pixel_spacing(&view).abs();
+    let magnitude = center.re.abs().max(center.im.abs()).max(1.0);
+    let enough = |bits : usize, scale : f64| {
+        pixel >= scale * 2f64.powi(AUTO_HEADROOM_BITS - bits as i32)
+    };
+
+    let plain = options.mode == Mode::Gray || options.mode == Mode::Bands;
+    if !plain || options.julia.is_some() || options.julia_path.is_some() || options.simd
+        || options.blend.is_some()
+        || enough(f64::MANTISSA_DIGITS as usize - 1, magnitude)
+    {
+        return Precision::F64;
+    }
+    let fixed = fixed && options.center.is_some()
+        && !(options.fast_math || options.adaptive || options.limit_center.is_some()
+             || options.diff_limits.is_some() || options.chromatic.is_some()
+             || options.report_iterations || options.title_coordinates);
+    if fixed && center.re.abs().max(center.im.abs()) <= 4.0 && enough(fixed::BITS, 1.0) {
+        return Precision::Fixed128;
+    }
+    if !fixed || enough(2 * (f64::MANTISSA_DIGITS as usize - 1), magnitude) {
+        return Precision::DoubleDouble;
+    }
+    let bits = (-pixel.log2()).ceil() as usize + AUTO_HEADROOM_BITS as usize;
+    Precision::Big(bits.div_ceil(32).clamp(BIG_BITS.0 / 32, BIG_BITS.1 / 32) * 32)
+}
+
+//Once neighbouring pixels are closer together than f64 can tell apart
+//around the view's center they collapse onto the same points, and the
+//picture turns into blocks and noise. Perturbation only needs the corners
+//themselves to be told apart.
+fn warn_precision(view : &View, mode : Mode, precision : Precision) {
+    if precision.fixed_bits().is_some() {
+        return;
+    }
+    let center = view.center();
+    let pixel = pixel_spacing(view);
+    let resolution = magnitude(view) * f64::EPSILON;
+    let deeper = match (mode, precision) {
+        (Mode::Perturbation, _) => Some("--mode perturbation"),
+        (_, Precision::DoubleDouble) => Some("--precision dd"),
+        _ => None
+    };
+    if let Some(deeper) = deeper {
+        let size = view.width().min(view.upper_left.im - view.lower_right.im);
+        if size < resolution * 16.0 {
+            warning!("at zoom {:e} (width {:e}) the corners of the view are \
+                      too close for f64 around {},{} to place exactly, the view \
+                      comes out a little moved or scaled and even {} can't go \
+                      much deeper (--precision fixed128 or big:BITS do)", view.zoom(),
+                     view.width(), center.re, center.im, deeper);
+        }
+        return;
+    }
+    if pixel < resolution * 16.0 {
+        warning!("at zoom {:e} (width {:e}) the pixels are {:e} apart, \
+                  too close for f64 around {},{} to resolve, expect a blocky \
+                  or noisy picture (--precision dd or --mode perturbation go \
+                  deeper)", view.zoom(), view.width(), pixel, center.re, center.im);
+    }
+}
+
+//How far apart neighbouring pixels are, the nearer way
+fn pixel_spacing(view : &View) -> f64 {
+    (view.width() / view.bounds.0 as f64)
+        .min((view.upper_left.im - view.lower_right.im) / view.bounds.1 as f64)
+}
+
+//The size of the coordinates around the view's center, at least 1 as the
+//orbits go out that far wherever they start
+fn magnitude(view : &View) -> f64 {
+    let center = view.center();
+    center.re.abs().max(center.im.abs()).max(1.0)
+}
+
+//Whether --precision f32 keeps the pixels of `view` apart, see F32_MIN_STEP
+pub fn f32_resolves(view : &View) -> bool {
+    pixel_spacing(view) >= magnitude(view) * F32_MIN_STEP
+}
+
+//The upper left corner has to be left of and above the lower right one,
+//with some room between them, or the picture comes out mirrored, empty or
+//full of NaNs. With `swap` corners given the wrong way round are put
+//right (with a warning) instead of being an error.
+fn check_corners(upper_left : Complex<f64>, lower_right : Complex<f64>, swap : bool)
+    -> Result<(Complex<f64>, Complex<f64>), String>
+{
+    let shown = format!("upper left {},{} lower right {},{}",
+                        upper_left.re, upper_left.im, lower_right.re, lower_right.im);
+    let finite = [upper_left.re, upper_left.im, lower_right.re, lower_right.im]
+        .iter().all(|value| value.is_finite());
+    if !finite {
+        return Err(format!("the view's corners have to be finite numbers, got {}", shown));
+    }
+    if upper_left.re == lower_right.re || upper_left.im == lower_right.im {
+        return Err(format!("the view has no area, its corners need different real \
+                            and imaginary parts, got {}", shown));
+    }
+
+    let mut corners = (upper_left, lower_right);
+    let mut swapped = Vec::new();
+    if corners.0.re > corners.1.re {
+        if !swap {
+            return Err(format!("the upper left corner has to be left of the lower \
+                                right one, but its real part {} isn't less than {} \
+                                ({}, --swap-corners puts them right)",
+                               upper_left.re, lower_right.re, shown));
+        }
+        std::mem::swap(&mut corners.0.re, &mut corners.1.re);
+        swapped.push("real");
+    }
+    if corners.0.im < corners.1.im {
+        if !swap {
+            return Err(format!("the upper left corner has to be above the lower \
+                                right one, but its imaginary part {} isn't greater \
+                                than {} ({}, --swap-corners puts them right)",
+                               upper_left.im, lower_right.im, shown));
+        }
+        std::mem::swap(&mut corners.0.im, &mut corners.1.im);
+        swapped.push("imaginary");
+    }
+    if !swapped.is_empty() {
+        warning!("swapped the {} parts of the corners, rendering upper \
+                  left {},{} lower right {},{}", swapped.join(" and "),
+                 corners.0.re, corners.0.im, corners.1.re, corners.1.im);
+    }
+    Ok(corners)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chromatic(offset : u32) -> Result<Config, String> {
+        build(&Options {
+            size : Some((40, 30)),
+            center : Some(Complex { re : -0.5, im : 0.0 }),
+            width : Some(3.0),
+            iterations : 255,
+            chromatic : Some(offset),
+            ..Options::default()
+        })
+    }
+
+    #[test]
+    fn chromatic_offsets_within_half_the_limit() {
+        assert!(chromatic(127).is_ok());
+        for offset in [128, 200] {
+            assert!(chromatic(offset).err().is_some_and(|error| error.contains("too large")));
+        }
+    }
+
+    #[test]
+    fn chromatic_offsets_that_overflow_when_doubled() {
+        for offset in [2_147_483_648, u32::MAX] {
+            assert!(chromatic(offset).err().is_some_and(|error| error.contains("too large")));
+        }
+    }
+
+    //<********check_corners()*********>//
+    fn point(re : f64, im : f64) -> Complex<f64> {
+        Complex { re, im }
+    }
+
+    fn refused(upper_left : Complex<f64>, lower_right : Complex<f64>, swap : bool) -> String {
+        check_corners(upper_left, lower_right, swap).err().unwrap_or_default()
+    }
+
+    #[test]
+    fn corners_in_order_pass_as_they_are() {
+        let corners = (point(-2.0, 1.2), point(1.0, -1.2));
+        assert_eq!(check_corners(corners.0, corners.1, false), Ok(corners));
+        assert_eq!(check_corners(corners.0, corners.1, true), Ok(corners));
+    }
+
+    #[test]
+    fn misordered_corners_are_refused_naming_the_part() {
+        let error = refused(point(1.0, 1.2), point(-2.0, -1.2), false);
+        assert!(error.contains("left of the lower right") && error.contains("real part 1"));
+        assert!(error.contains("upper left 1,1.2 lower right -2,-1.2"));
+        let error = refused(point(-2.0, -1.2), point(1.0, 1.2), false);
+        assert!(error.contains("above the lower right")
+                && error.contains("imaginary part -1.2"));
+    }
+
+    #[test]
+    fn swap_corners_puts_them_right() {
+        let put_right = Ok((point(-2.0, 1.2), point(1.0, -1.2)));
+        assert_eq!(check_corners(point(1.0, 1.2), point(-2.0, -1.2), true), put_right);
+        assert_eq!(check_corners(point(-2.0, -1.2), point(1.0, 1.2), true), put_right);
+        assert_eq!(check_corners(point(1.0, -1.2), point(-2.0, 1.2), true), put_right);
+    }
+
+    #[test]
+    fn degenerate_corners_are_refused_even_with_swap() {
+        for swap in [false, true] {
+            for (upper_left, lower_right) in [(point(0.5, 0.5), point(0.5, 0.5)),
+                                              (point(-1.0, 1.0), point(-1.0, -1.0)),
+                                              (point(-1.0, 1.0), point(1.0, 1.0))] {
+                assert!(refused(upper_left, lower_right, swap).contains("has no area"));
+            }
+        }
+    }
+
+    #[test]
+    fn corners_that_arent_finite_are_refused() {
+        for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            for (upper_left, lower_right) in [(point(value, 1.0), point(1.0, -1.0)),
+                                              (point(-1.0, value), point(1.0, -1.0)),
+                                              (point(-1.0, 1.0), point(value, -1.0)),
+                                              (point(-1.0, 1.0), point(1.0, value))] {
+                assert!(refused(upper_left, lower_right, true).contains("finite numbers"));
+            }
+        }
+    }
+
+    #[test]
+    fn sizes_without_pixels_are_refused() {
+        for size in [(0, 30), (40, 0), (0, 0)] {
+            let built = build(&Options {
+                size : Some(size),
+                center : Some(point(-0.5, 0.0)),
+                width : Some(3.0),
+                ..Options::default()
+            });
+            assert!(built.err().is_some_and(|error| error.contains("has no pixels")));
+        }
+    }
+
+    #[test]
+    fn zoom_one_is_the_full_four_wide_view() {
+        let view = |width, zoom| resolve_view(&Options {
+            center : Some(point(0.0, 0.0)),
+            width,
+            zoom,
+            ..Options::default()
+        }, (40, 30)).unwrap();
+        let full = view(None, Some(1.0));
+        assert_eq!(corners(&full), (point(-2.0, 1.5), point(2.0, -1.5)));
+        assert_eq!((full.width(), full.zoom()), (FULL_WIDTH, 1.0));
+        assert_eq!(corners(&full), corners(&view(Some(4.0), None)));
+        assert_eq!(corners(&view(None, Some(4.0))), corners(&view(Some(1.0), None)));
+    }
+
+    //<********--pan, --zoom-by and --rotate*********>//
+    //A 4 by 3 window on a 40x30 image, every number in it exact in binary
+    fn nudged(pan : Option<(f64, f64)>, zoom_by : Option<f64>, rotate : f64) -> View {
+        resolve_view(&Options {
+            center : Some(point(-0.5, 0.25)),
+            width : Some(4.0),
+            pan : pan.map(|(re, im)| point(re, im)),
+            zoom_by,
+            rotate,
+            ..Options::default()
+        }, (40, 30)).unwrap()
+    }
+
+    fn corners(view : &View) -> (Complex<f64>, Complex<f64>) {
+        (view.upper_left, view.lower_right)
+    }
+
+    #[test]
+    fn zooming_by_two_twice_is_zooming_by_four() {
+        let view = nudged(None, None, 0.0);
+        assert_eq!(corners(&view.zoomed(2.0).zoomed(2.0)), corners(&view.zoomed(4.0)));
+        assert_eq!(corners(&nudged(None, Some(4.0), 0.0)), corners(&view.zoomed(4.0)));
+        assert_eq!(corners(&view.zoomed(4.0)), (point(-1.0, 0.625), point(0.0, -0.125)));
+    }
+
+    #[test]
+    fn panning_moves_by_fractions_of_the_width_and_height() {
+        //Right and up are positive
+        let panned = nudged(Some((0.25, -0.5)), None, 0.0);
+        assert_eq!(panned.center(), point(-0.5 + 1.0, 0.25 - 1.5));
+        assert_eq!(panned.width(), 4.0);
+        assert_eq!(corners(&nudged(None, None, 0.0).panned(point(0.25, -0.5))),
+                   corners(&panned));
+    }
+
+    #[test]
+    fn the_pan_comes_before_the_zoom() {
+        //So the pan is in fractions of the view before it was magnified
+        let view = nudged(Some((0.25, -0.5)), Some(2.0), 0.0);
+        assert_eq!(view.center(), point(0.5, -1.25));
+        assert_eq!(view.width(), 2.0);
+        assert_eq!(corners(&view), corners(&nudged(None, None, 0.0)
+                                           .panned(point(0.25, -0.5)).zoomed(2.0)));
+        //Rather than the other way round
+        assert_ne!(corners(&view), corners(&nudged(None, None, 0.0)
+                                           .zoomed(2.0).panned(point(0.25, -0.5))));
+    }
+
+    #[test]
+    fn the_rotation_turns_the_nudged_view_around_its_own_center() {
+        //The pan is along the axes of the plane, not of the turned picture
+        let view = nudged(Some((0.25, -0.5)), Some(2.0), 90.0);
+        let upright = nudged(Some((0.25, -0.5)), Some(2.0), 0.0);
+        assert_eq!(corners(&view), corners(&upright));
+        let rotation = view.rotation.unwrap();
+        assert_eq!(rotation.degrees, 90.0);
+        assert_eq!(rotation.pivot, upright.center());
+        //A quarter turn counterclockwise takes the upper left corner, 1 left of
+        //and 0.75 above the pivot, to 1 below and 0.75 left of it
+        let turned = view.rotated(view.upper_left) - rotation.pivot;
+        assert!((turned - point(-0.75, -1.0)).norm() < 1e-12, "{}", turned);
+        assert_eq!(view.rotated(rotation.pivot), rotation.pivot);
+    }
+
+    #[test]
+    fn panning_and_zooming_a_turned_view_keep_the_pivot_at_its_center() {
+        let view = nudged(None, None, 30.0);
+        let panned = view.panned(point(0.25, -0.5));
+        assert_eq!(panned.rotation.unwrap().pivot, panned.center());
+        let zoomed = panned.zoomed(2.0);
+        assert_eq!(zoomed.rotation.unwrap().pivot, zoomed.center());
+        assert_eq!(zoomed.rotation.unwrap().degrees, 30.0);
+    }
+
+    //<********--precision auto*********>//
+    #[test]
+    fn auto_picks_the_quickest_precision_that_keeps_the_pixels_apart() {
+        //Views around -0.75,0.1 given by --center (true) or by their corners
+        //(false), and the precision each has to get
+        let center = point(-0.75, 0.1);
+        for &(width, centered, expected) in &[(3.0, true, "f64"), (1e-15, false, "dd"),
+                                              (1e-15, true, "fixed128"),
+                                              (1e-30, true, "fixed128"),
+                                              (1e-40, true, "big:160")] {
+            let half = Complex { re : width / 2.0, im : width * 3.0 / 8.0 };
+            let options = match centered {
+                true => Options { center : Some(center), width : Some(width),
+                                  ..Options::default() },
+                false => Options { upper_left : Some(center - half.conj()),
+                                   lower_right : Some(center + half.conj()),
+                                   ..Options::default() }
+            };
+            assert_eq!(auto_precision(&options, (64, 48), true).name(), expected,
+                       "{} across", width);
+        }
+    }
+}
@@ -0,0 +1,267 @@
+//<***************Double-double arithmetic*****************>//
+//A value hi + lo with |lo| at most half an ulp of hi : two f64s making one
+//number of about 106 bits of mantissa, 32 significant digits, against
+//f64's 53 bits and 16. Sums and products go through the error-free
+//transformations (Knuth's two-sum, and the fused multiply-add for the
+//rounding error of a product), a quotient through two refinements of the
+//f64 one.
+//
+//It has the operators and the num traits the generic iteration takes, so
+//that --precision dd is the same code as f64 and f32 (see Real), and it
+//works out the reference orbits of --mode perturbation.
+//
+//A double-double is about ten to twenty times slower than an f64, and it
+//only goes as deep as the view's corners, which are still f64 : the points
+//between them are placed apart however close they are, but the corners
+//themselves can't come closer than a few of f64's steps (a width of about
+//1e-16 near the origin).
+
+use std::cmp::Ordering;
+use std::ops::{Add, Div, Mul, Neg, Rem, Sub};
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Double {
+    pub hi : f64,
+    pub lo : f64
+}
+
+impl Double {
+    pub fn from(value : f64) -> Double {
+        Double { hi : value, lo : 0.0 }
+    }
+
+    //a * b exactly
+    pub fn product(a : f64, b : f64) -> Double {
+        let hi = a * b;
+        Double { hi, lo : a.mul_add(b, -hi) }
+    }
+
+    pub fn negated(self) -> Double {
+        Double { hi : -self.hi, lo : -self.lo }
+    }
+
+    pub fn doubled(self) -> Double {
+        Double { hi : self.hi * 2.0, lo : self.lo * 2.0 }
+    }
+
+    //Rounded toward zero. A hi with a fraction has a lo too small to take it
+    //past a whole number, a whole hi leaves it to lo which way to go.
+    pub fn trunc(self) -> Double {
+        let hi = self.hi.trunc();
+        if hi != self.hi {
+            return Double::from(hi);
+        }
+        normalized(hi, if hi > 0.0 { self.lo.floor() } else { self.lo.ceil() })
+    }
+
+    //A decimal such as -0.7436438870371587048, with an optional e exponent,
+    //to all the digits a double-double holds
+    pub fn parse(text : &str) -> Result<Double, String> {
+        let invalid = || format!("expected a decimal number, got '{}'", text);
+        let (mantissa, exponent) = match text.find(['e', 'E']) {
+            Some(index) => (&text[..index],
+                            text[index + 1..].parse::<i32>().map_err(|_| invalid())?),
+            None => (text, 0)
+        };
+        let (negative, mantissa) = match mantissa.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, mantissa.strip_prefix('+').unwrap_or(mantissa))
+        };
+        let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        if whole.is_empty() && fraction.is_empty() {
+            return Err(invalid());
+        }
+        let ten = Double::from(10.0);
+        let mut value = Double::from(0.0);
+        for digit in whole.chars().chain(fraction.chars()) {
+            let digit = digit.to_digit(10).ok_or_else(invalid)?;
+            value = value * ten + Double::from(digit as f64);
+        }
+        let exponent = exponent - fraction.len() as i32;
+        for _ in 0..exponent.unsigned_abs() {
+            value = if exponent > 0 { value * ten } else { value / ten };
+        }
+        Ok(if negative { value.negated() } else { value })
+    }
+}
+
+//hi + lo with the two overlapping no more than they should
+pub fn normalized(hi : f64, lo : f64) -> Double {
+    let sum = hi + lo;
+    Double { hi : sum, lo : lo - (sum - hi) }
+}
+
+impl Add for Double {
+    type Output = Double;
+
+    fn add(self, other : Double) -> Double {
+        let sum = self.hi + other.hi;
+        let virtual_other = sum - self.hi;
+        let error = (self.hi - (sum - virtual_other)) + (other.hi - virtual_other);
+        normalized(sum, error + self.lo + other.lo)
+    }
+}
+
+impl Sub for Double {
+    type Output = Double;
+
+    fn sub(self, other : Double) -> Double {
+        self.add(other.negated())
+    }
+}
+
+impl Mul for Double {
+    type Output = Double;
+
+    fn mul(self, other : Double) -> Double {
+        let product = Double::product(self.hi, other.hi);
+        normalized(product.hi,
+                   product.lo + self.hi * other.lo + self.lo * other.hi)
+    }
+}
+
+//The f64 quotient, and twice the quotient of what it leaves over
+impl Div for Double {
+    type Output = Double;
+
+    fn div(self, other : Double) -> Double {
+        let first = self.hi / other.hi;
+        let rest = self - other * Double::from(first);
+        let second = rest.hi / other.hi;
+        let rest = rest - other * Double::from(second);
+        let third = rest.hi / other.hi;
+        normalized(first, second) + Double::from(third)
+    }
+}
+
+impl Rem for Double {
+    type Output = Double;
+
+    fn rem(self, other : Double) -> Double {
+        self - other * (self / other).trunc()
+    }
+}
+
+impl Neg for Double {
+    type Output = Double;
+
+    fn neg(self) -> Double {
+        self.negated()
+    }
+}
+
+//A normalized double-double orders by hi, and by lo when the his are equal
+impl PartialOrd for Double {
+    fn partial_cmp(&self, other : &Double) -> Option<Ordering> {
+        match self.hi.partial_cmp(&other.hi) {
+            Some(Ordering::Equal) => self.lo.partial_cmp(&other.lo),
+            ordering => ordering
+        }
+    }
+}
+
+impl num::Zero for Double {
+    fn zero() -> Double {
+        Double::from(0.0)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.hi == 0.0 && self.lo == 0.0
+    }
+}
+
+impl num::One for Double {
+    fn one() -> Double {
+        Double::from(1.0)
+    }
+}
+
+impl num::Num for Double {
+    type FromStrRadixErr = String;
+
+    fn from_str_radix(text : &str, radix : u32) -> Result<Double, String> {
+        match radix {
+            10 => Double::parse(text),
+            _ => Err(format!("double-doubles are only read in base 10, not {}", radix))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num::{BigRational, Signed};
+    use num::Complex;
+
+    use crate::config::View;
+    use crate::pixel_to_point;
+
+    //Checked against the exact rationals of the same numbers, to within this
+    //many bits of them (a double-double holds about 106, a few steps can lose
+    //a bit each)
+    const BITS : i32 = 100;
+
+    fn exact(value : f64) -> BigRational {
+        BigRational::from_float(value).expect("not a finite number")
+    }
+
+    fn exactly(value : Double) -> BigRational {
+        exact(value.hi) + exact(value.lo)
+    }
+
+    fn decimal(digits : i64, places : usize) -> BigRational {
+        BigRational::new(digits.into(), num::pow(num::BigInt::from(10), places))
+    }
+
+    fn parsed(text : &str) -> Double {
+        Double::parse(text).expect("not a decimal")
+    }
+
+    #[test]
+    fn double_doubles_agree_with_exact_rationals() {
+        //A cancellation f64 loses everything to, products and quotients that
+        //don't come out even, decimals f64 can't hold, and a step of the
+        //iteration
+        let (one, tiny) = (Double::from(1.0), Double::from(2f64.powi(-60)));
+        let (re, im) = (parsed("-0.7436438870371587048"), parsed("0.1318259042053119704"));
+        let (c_re, c_im) = (parsed("-0.74364388703715870475"),
+                            parsed("0.13182590420531197049"));
+        let step = (re * re - im * im + c_re, (re * im).doubled() + c_im);
+        let cases = [
+            ("(1 + 2^-60) - 1", (one + tiny) - one, exact(2f64.powi(-60))),
+            ("0.1 * 0.1", Double::from(0.1) * Double::from(0.1), exact(0.1) * exact(0.1)),
+            ("1 / 3", one / Double::from(3.0), exact(1.0) / exact(3.0)),
+            ("0.1", parsed("0.1"), decimal(1, 1)),
+            ("-0.7436438870371587048", re, decimal(-7_436_438_870_371_587_048, 19)),
+            ("re * im", re * im, exactly(re) * exactly(im)),
+            ("re / im", re / im, exactly(re) / exactly(im)),
+            ("re^2 - im^2 + c", step.0,
+             exactly(re) * exactly(re) - exactly(im) * exactly(im) + exactly(c_re)),
+            ("2 re im + c", step.1, exact(2.0) * exactly(re) * exactly(im) + exactly(c_im))
+        ];
+        let bound = exact(2f64.powi(BITS));
+        for (name, got, expected) in cases {
+            assert!((exactly(got) - &expected).abs() * &bound <= expected.abs(),
+                    "{} is off by more than 2^-{}", name, BITS);
+        }
+    }
+
+    #[test]
+    fn double_doubles_keep_apart_the_pixels_f64_cant() {
+        //A view 3e-16 wide, where f64 puts the pixels of a row on a few of its
+        //steps
+        const WIDTH : f64 = 3e-16;
+        let center = Complex { re : -0.743_643_887_037_158_7, im : 0.131_825_904_205_311_97 };
+        let half = Complex { re : WIDTH / 2.0, im : WIDTH / 2.0 };
+        let view = View { bounds : (64, 48), upper_left : center - half.conj(),
+                          lower_right : center + half.conj(), rotation : None };
+        let mut plain = (0..64).map(|x| pixel_to_point::<f64>(&view, (x, 0)).re)
+            .collect::<Vec<_>>();
+        plain.dedup();
+        let mut double = (0..64).map(|x| pixel_to_point::<Double>(&view, (x, 0)).re)
+            .collect::<Vec<_>>();
+        double.dedup();
+        assert_eq!(double.len(), 64);
+        assert!(plain.len() < 64, "{} apart in f64", plain.len());
+    }
+}
@@ -0,0 +1,178 @@
+//<***************Exit codes*****************>//
+//Every way a run can fail comes down to one of these, and main() prints
+//the message on one line and exits with its code :
+//
+//    0    it worked
+//    2    the command line doesn't parse (an unknown option, a bad value,
+//         a config file that can't be read)
+//    3    the options parse but the view or the parameters can't be
+//         rendered as asked
+//    4    reading or writing a file, stdin/stdout or a socket failed
+//    5    over a limit, the output is too big to be made as asked
+//    130  interrupted with Ctrl-C, what was finished before is kept (and
+//         what a render had got to, in OUTPUT.partial.png)
+//
+//Most errors are a plain String where they come from, which counts as 3;
+//the places that know better say which it is.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Kind {
+    Usage,
+    Invalid,
+    Io,
+    TooBig,
+    Interrupted
+}
+
+pub struct Failure {
+    pub kind : Kind,
+    pub message : String
+}
+
+impl Failure {
+    pub fn new(kind : Kind, message : String) -> Failure {
+        Failure { kind, message }
+    }
+
+    pub fn io(message : String) -> Failure {
+        Failure::new(Kind::Io, message)
+    }
+
+    pub fn code(&self) -> i32 {
+        match self.kind {
+            Kind::Usage => 2,
+            Kind::Invalid => 3,
+            Kind::Io => 4,
+            Kind::TooBig => 5,
+            Kind::Interrupted => 130
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl From<String> for Failure {
+    fn from(message : String) -> Failure {
+        Failure::new(Kind::Invalid, message)
+    }
+}
+
+impl From<&str> for Failure {
+    fn from(message : &str) -> Failure {
+        Failure::new(Kind::Invalid, message.to_string())
+    }
+}
+
+//<********Ctrl-C***************************>//
+//The commands that write many files (animate, --pyramid, --jobs) catch the
+//first Ctrl-C (or SIGTERM) and stop once the file being written is done,
+//so that no half written file is left behind and the exit code says that
+//the rest is missing. A plain render stops its rows where they are instead
+//and writes what it has (see interrupt_renders()). A second Ctrl-C ends
+//the program on the spot as usual.
+static INTERRUPTED : AtomicBool = AtomicBool::new(false);
+static STOP_RENDERS : AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+pub fn catch_interrupt() {
+    //signal() of the C library std links against anyway, rather than a
+    //crate just for this
+    extern "C" {
+        fn signal(signum : i32, handler : usize) -> usize;
+    }
+    const SIGINT : i32 = 2;
+    const SIGTERM : i32 = 15;
+    const SIG_DFL : usize = 0;
+
+    extern "C" fn interrupted(_ : i32) {
+        INTERRUPTED.store(true, Ordering::Relaxed);
+        //Both are async-signal-safe
+        unsafe {
+            signal(SIGINT, SIG_DFL);
+            signal(SIGTERM, SIG_DFL);
+        }
+    }
+    unsafe {
+        signal(SIGINT, interrupted as extern "C" fn(i32) as usize);
+        signal(SIGTERM, interrupted as extern "C" fn(i32) as usize);
+    }
+}
+
+//The console calls the handler on a thread of its own, so the second
+//Ctrl-C can exit from there
+#[cfg(windows)]
+pub fn catch_interrupt() {
+    extern "system" {
+        fn SetConsoleCtrlHandler(handler : usize, add : i32) -> i32;
+    }
+
+    extern "system" fn interrupted(_ : u32) -> i32 {
+        if INTERRUPTED.swap(true, Ordering::Relaxed) {
+            std::process::exit(130);
+        }
+        1
+    }
+    let handler = interrupted as extern "system" fn(u32) -> i32;
+    unsafe { SetConsoleCtrlHandler(handler as usize, 1); }
+}
+
+//Elsewhere Ctrl-C ends the program as it always did
+#[cfg(not(any(unix, windows)))]
+pub fn catch_interrupt() {}
+
+//Whether Ctrl-C has been pressed since catch_interrupt()
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+//catch_interrupt(), and the renders check for a Ctrl-C between rows and
+//leave the rows they haven't got to
+pub fn interrupt_renders() {
+    STOP_RENDERS.store(true, Ordering::Relaxed);
+    catch_interrupt();
+}
+
+pub fn stopping_renders() -> bool {
+    STOP_RENDERS.load(Ordering::Relaxed)
+}
+
+//Whether the renders should stop where they are
+pub fn renders_interrupted() -> bool {
+    stopping_renders() && interrupted()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(kind : Kind) -> i32 {
+        Failure::new(kind, String::new()).code()
+    }
+
+    #[test]
+    fn each_kind_exits_with_its_documented_code() {
+        assert_eq!(code(Kind::Usage), 2);
+        assert_eq!(code(Kind::Invalid), 3);
+        assert_eq!(code(Kind::Io), 4);
+        assert_eq!(code(Kind::TooBig), 5);
+        assert_eq!(code(Kind::Interrupted), 130);
+    }
+
+    #[test]
+    fn plain_messages_count_as_invalid() {
+        let from_string = Failure::from("no view given".to_string());
+        assert!(from_string.kind == Kind::Invalid && from_string.code() == 3);
+        let from_str = Failure::from("no view given");
+        assert!(from_str.kind == Kind::Invalid && from_str.code() == 3);
+        let io = Failure::io("error writing out.png".to_string());
+        assert!(io.kind == Kind::Io && io.code() == 4);
+        assert_eq!(io.to_string(), "error writing out.png");
+    }
+}
@@ -0,0 +1,99 @@
+//<***************Evaluating coordinates like -pi/4*****************>//
+//A small recursive descent evaluator for the arithmetic allowed in the
+//parts of a complex value : numbers, the constants pi and e, + - * /,
+//unary minus and parentheses, with the usual precedence.
+//
+//    expression = term (('+' | '-') term)*
+//    term       = factor (('*' | '/') factor)*
+//    factor     = ('+' | '-') factor | '(' expression ')' | number | constant
+
+pub fn evaluate(s : &str) -> Result<f64, String> {
+    let mut parser = Parser { chars : s.chars().filter(|c| !c.is_whitespace()).collect(),
+                              position : 0 };
+    let value = parser.expression()?;
+    match parser.peek() {
+        None => Ok(value),
+        Some(c) => Err(format!("unexpected '{}' in '{}'", c, s))
+    }
+}
+
+struct Parser {
+    chars : Vec<char>,
+    position : usize
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).cloned()
+    }
+
+    fn expression(&mut self) -> Result<f64, String> {
+        let mut value = self.term()?;
+        while let Some(operator) = self.peek().filter(|&c| c == '+' || c == '-') {
+            self.position += 1;
+            let right = self.term()?;
+            value = if operator == '+' { value + right } else { value - right };
+        }
+        Ok(value)
+    }
+
+    fn term(&mut self) -> Result<f64, String> {
+        let mut value = self.factor()?;
+        while let Some(operator) = self.peek().filter(|&c| c == '*' || c == '/') {
+            self.position += 1;
+            let right = self.factor()?;
+            value = if operator == '*' { value * right } else { value / right };
+        }
+        Ok(value)
+    }
+
+    fn factor(&mut self) -> Result<f64, String> {
+        match self.peek() {
+            None => Err("expression ends too early".to_string()),
+            Some('+') => { self.position += 1; self.factor() }
+            Some('-') => { self.position += 1; self.factor().map(|value| -value) }
+            Some('(') => {
+                self.position += 1;
+                let value = self.expression()?;
+                if self.peek() != Some(')') {
+                    return Err("missing ')'".to_string());
+                }
+                self.position += 1;
+                Ok(value)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => self.number(),
+            Some(c) if c.is_ascii_alphabetic() => self.constant(),
+            Some(c) => Err(format!("unexpected '{}'", c))
+        }
+    }
+
+    //Digits with an optional fraction and exponent, 1.5e-3 included
+    fn number(&mut self) -> Result<f64, String> {
+        let start = self.position;
+        let mut previous = None;
+        while let Some(c) = self.peek() {
+            let exponent_sign = (c == '+' || c == '-')
+                && (previous == Some('e') || previous == Some('E'));
+            if !(c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E' || exponent_sign) {
+                break;
+            }
+            previous = Some(c);
+            self.position += 1;
+        }
+        let text : String = self.chars[start..self.position].iter().collect();
+        text.parse().map_err(|_| format!("'{}' isn't a number", text))
+    }
+
+    fn constant(&mut self) -> Result<f64, String> {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+            self.position += 1;
+        }
+        let name : String = self.chars[start..self.position].iter().collect();
+        match name.as_str() {
+            "pi" => Ok(std::f64::consts::PI),
+            "e" => Ok(std::f64::consts::E),
+            _ => Err(format!("unknown constant '{}', expected pi or e", name))
+        }
+    }
+}
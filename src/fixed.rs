@@ -0,0 +1,138 @@
+//<***************128-bit fixed point*****************>//
+//For --precision fixed128 : the fixed point of --precision big (see
+//big.rs) with its bits set at 124 after the binary point and every number
+//in an i128, Q4.124, values from -8 to 8 in steps of 2^-124. That is
+//deeper than double-doubles (zooms to about 1e35) and far quicker than
+//BigInts, a product being four 64x64 bit multiplications into 256 bits and
+//a shift back down.
+//
+//The iteration is the one of big.rs step for step, with the products cut
+//down toward zero as a BigInt's shift does, so the counts are the same as
+//--precision big:124 to the pixel (but by the edge of the set when that
+//iterates in astro-float's BigFloats, see big.rs). Only the radius check differs, to keep
+//within the 8 an i128 holds : a part over 2 has escaped before it gets
+//squared, and the squares, each at most 4 by then, are checked against 4
+//one at a time instead of being added up. A z that hasn't escaped has
+//|z| <= 2, so 2 re im is at most 4, and the next z has parts of at most 4
+//plus those of c, which is why points with a part past 3.5 are counted as
+//escaping at once (as they do, being more than 2 from 0) without their
+//parts ever being i128s.
+
+use num::{Complex, Float};
+use std::ops::ControlFlow::{Break, Continue};
+
+use crate::big::Point;
+use crate::config::View;
+use crate::{in_stretches, pixel_to_point, render_bands_within, rows_of, Budget, Renderer};
+
+//The bits after the binary point
+pub const BITS : usize = 124;
+
+//How far from 0 the parts of c may be and still go through the iteration
+const REACH : f64 = 3.5;
+
+//A point as --precision fixed128 holds it, None if a part isn't between
+//-8 and 8
+pub fn of(point : &Point) -> Option<(i128, i128)> {
+    let part = |value : &num::BigInt| i128::from_str_radix(&value.to_str_radix(16), 16).ok();
+    Some((part(&point.re)?, part(&point.im)?))
+}
+
+//`value` * 2^BITS, cut down toward zero
+fn fixed(value : f64) -> i128 {
+    let (mantissa, exponent, sign) = value.integer_decode();
+    let shift = exponent as i32 + BITS as i32;
+    let magnitude = match shift >= 0 {
+        true => (mantissa as i128) << shift,
+        false => (mantissa as i128).checked_shr(shift.unsigned_abs()).unwrap_or(0)
+    };
+    if sign < 0 { -magnitude } else { magnitude }
+}
+
+//a * b in 256 bits, as its high and low 128 : a and b are below 2^127, so
+//the halves multiplied by each other and their sums all fit in a u128
+fn wide(a : u128, b : u128) -> (u128, u128) {
+    const LOW : u128 = u64::MAX as u128;
+    let (a_high, a_low, b_high, b_low) = (a >> 64, a & LOW, b >> 64, b & LOW);
+    let middle = a_low * b_high + a_high * b_low;
+    let (low, carry) = (a_low * b_low).overflowing_add(middle << 64);
+    (a_high * b_high + (middle >> 64) + carry as u128, low)
+}
+
+//a * b / 2^shift, cut down toward zero
+fn product(a : i128, b : i128, shift : usize) -> i128 {
+    let (high, low) = wide(a.unsigned_abs(), b.unsigned_abs());
+    let magnitude = (high << (128 - shift) | low >> shift) as i128;
+    if (a < 0) != (b < 0) { -magnitude } else { magnitude }
+}
+
+fn escape_time(c : (i128, i128), limit : u32) -> Option<u32> {
+    let (two, four) = (2 << BITS, 4 << BITS);
+    let z = (0i128, 0i128, 0i128, 0i128);
+    in_stretches(0..limit, z, move |(mut re, mut im, mut square_re, mut square_im), stretch| {
+        for i in stretch {
+            let twice_re_im = product(re, im, BITS - 1);
+            re = square_re - square_im + c.0;
+            im = twice_re_im + c.1;
+            if re.abs() > two || im.abs() > two {
+                return Break(i);
+            }
+            square_re = product(re, re, BITS);
+            square_im = product(im, im, BITS);
+
+            if square_re > four - square_im {
+                return Break(i);
+            }
+        }
+        Continue((re, im, square_re, square_im))
+    }).break_value()
+}
+
+//The escape counts of the pixels of `view`, whose corners are offsets from
+//`center`, which has to be one of()
+pub fn counts(renderer : Option<&Renderer>, budget : Option<&Budget>, view : &View,
+              center : &Point, limit : u32)
+    -> Vec<Option<u32>>
+{
+    let (center_re, center_im) = of(center).expect("center out of fixed point range");
+    let rounded = center.rounded();
+    let bounds = view.bounds;
+    let mut counts = vec![None;bounds.0 * bounds.1];
+    render_bands_within(renderer, budget, &mut counts, bounds, |band, top| {
+        for row in 0..rows_of(band.len(), top, bounds) {
+            for column in 0..bounds.0 {
+                let offset : Complex<f64> = pixel_to_point(view, (column, top + row));
+                let near = rounded + offset;
+                let far = near.re.abs().max(near.im.abs()) > REACH;
+                band[column + bounds.0 * row] = match far {
+                    true => Some(0),
+                    false => escape_time((center_re + fixed(offset.re),
+                                          center_im + fixed(offset.im)), limit)
+                };
+            }
+        }
+    });
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::big::tests::{centered, differing, distinct, OFF, STRUCTURED};
+    use crate::cli::Precision;
+    use crate::render_counts;
+
+    #[test]
+    fn fixed128_counts_as_big_does_by_the_edge_of_the_set() {
+        //Deeper than double-doubles go and within what fixed128 does
+        let fixed = render_counts(&centered(Precision::Fixed128, 1e-25, false), None);
+        let big = render_counts(&centered(Precision::Big(BITS), 1e-25, false), None);
+        assert!(distinct(&fixed) >= STRUCTURED, "{} different counts", distinct(&fixed));
+        match cfg!(feature = "astro-float") {
+            true => assert!(differing(&fixed, &big) as f64 <= OFF * fixed.len() as f64,
+                            "{} of {} differ", differing(&fixed, &big), fixed.len()),
+            false => assert!(fixed == big, "{} of {} differ", differing(&fixed, &big),
+                             fixed.len())
+        }
+    }
+}
@@ -0,0 +1,190 @@
+//<***************Heatmaps*****************>//
+//For --heatmap : the escape counts shown as data rather than as a picture.
+//The counts are spread evenly over the palette (viridis unless --palette
+//names another) from the lowest in the view to the highest, instead of
+//indexing its table, and a colorbar to the right of the image shows which
+//colour stands for which count, its ticks labelled in the font of text.rs
+//the way a palette swatch is. Points inside the set are black.
+//
+//--cost-map is the same picture of what the pixels cost : the iterations
+//escape_time() ran for each, from none up to the most any pixel of the
+//view took (magma unless --palette names another). A point that escapes
+//at count n took n + 1, one inside the set the whole limit, so the set
+//and its boundary, where the time goes, are the bright parts.
+
+use image::ColorType;
+
+use crate::config::Config;
+use crate::palette::Palette;
+use crate::text;
+use crate::{render_counts, Image, Renderer};
+
+//The palettes without a --palette, perceptually uniform and readable in
+//gray
+pub const DEFAULT_PALETTE : &str = "viridis";
+pub const COST_PALETTE : &str = "magma";
+
+//How many counts the colorbar is labelled with, the lowest and the highest
+//among them, where there is room for them all
+const TICKS : usize = 5;
+
+const BACKGROUND : [u8; 3] = [0, 0, 0];
+const INK : [u8; 3] = [255, 255, 255];
+
+//The counts of `config`'s view coloured as a heatmap (or its --cost-map),
+//with its colorbar
+pub fn render(config : &Config, renderer : Option<&Renderer>) -> Image {
+    let counts = render_counts(config, renderer);
+    let name = if config.cost_map { COST_PALETTE } else { DEFAULT_PALETTE };
+    let default = || Palette::builtin(name).expect("no default heatmap palette");
+    let palette = config.palette.clone().unwrap_or_else(default);
+    if !config.cost_map {
+        return heatmap(&counts, range(&counts), config.view.bounds, &palette);
+    }
+    let costs = costs(&counts, config.limit);
+    let most = costs.iter().flatten().max().copied().unwrap_or(0);
+    verbose!("Cost map : up to {} iterations a pixel, {} in all", most,
+             costs.iter().flatten().map(|&cost| cost as u64).sum::<u64>());
+    heatmap(&costs, (0, most), config.view.bounds, &palette)
+}
+
+//The iterations escape_time() ran for each of `counts`, every one Some
+pub fn costs(counts : &[Option<u32>], limit : u32) -> Vec<Option<u32>> {
+    counts.iter().map(|&count| Some(count.map_or(limit, |count| count + 1))).collect()
+}
+
+//The lowest and highest counts of the points that escaped, (0, 0) if none did
+fn range(counts : &[Option<u32>]) -> (u32, u32) {
+    let escaped = counts.iter().flatten();
+    match (escaped.clone().min(), escaped.max()) {
+        (Some(&low), Some(&high)) => (low, high),
+        _ => (0, 0)
+    }
+}
+
+//`counts` spread over `palette` from the first of `range` to the second
+fn heatmap(counts : &[Option<u32>], range : (u32, u32), bounds : (usize, usize),
+           palette : &Palette)
+    -> Image
+{
+    let (low, high) = range;
+    let span = (high - low).max(1) as f64;
+    verbose!("Heatmap from {} to {}", low, high);
+
+    //The colorbar and its ticks and labels, left to right after a margin
+    let (width, height) = bounds;
+    let scale = (height / 100).max(1);
+    let margin = 4 * scale;
+    let ticks = labels((low, high), height, scale);
+    let label_width = ticks.iter()
+        .map(|count| text::width(&count.to_string(), scale))
+        .max()
+        .unwrap_or(0);
+    let bar = (width + margin, width + margin + 6 * scale);
+    let label_left = bar.1 + 3 * scale;
+    let total = label_left + label_width + margin;
+    //Its rows, the highest count at the top
+    let (top, bottom) = (margin.min(height), height.saturating_sub(margin));
+    let rows = bottom.saturating_sub(top + 1).max(1);
+
+    let mut pixels = vec![BACKGROUND;total * height];
+    for (row, counts) in counts.chunks(width.max(1)).enumerate() {
+        for (column, &count) in counts.iter().enumerate() {
+            if let Some(count) = count {
+                pixels[row * total + column] = palette.at((count - low) as f64 / span);
+            }
+        }
+    }
+    for row in top..bottom {
+        let color = palette.at((bottom - 1 - row) as f64 / rows as f64);
+        pixels[row * total + bar.0..row * total + bar.1].fill(color);
+    }
+    let mut pixels = pixels.concat();
+    //Labels only where a line of text fits
+    if height >= 7 * scale {
+        for &count in &ticks {
+            let above = ((count - low) as f64 / span * rows as f64).round() as usize;
+            let row = (bottom.max(top + 1) - 1).saturating_sub(above);
+            for column in bar.1..bar.1 + 2 * scale {
+                let index = (row * total + column) * 3;
+                pixels[index..index + 3].copy_from_slice(&INK);
+            }
+            let text_top = row.saturating_sub(5 * scale / 2).min(height - 5 * scale);
+            text::draw(&mut pixels, total, &count.to_string(), (label_left, text_top), scale,
+                       INK);
+        }
+    }
+    Image { pixels, bounds : (total, height), color : ColorType::RGB(8) }
+}
+
+//The counts along the colorbar from the bottom up, as few as two (or one,
+//for a view of a single count) when the image isn't tall enough for
+//TICKS lines of text
+fn labels(range : (u32, u32), height : usize, scale : usize) -> Vec<u32> {
+    let (low, high) = range;
+    let ticks = match height >= TICKS * 8 * scale {
+        true => TICKS,
+        false => 2
+    };
+    let mut labels = (0..ticks)
+        .map(|i| low + ((high - low) as f64 * i as f64 / (ticks - 1) as f64).round() as u32)
+        .collect::<Vec<_>>();
+    labels.dedup();
+    labels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Options;
+    use crate::config;
+    use num::Complex;
+
+    fn full_set() -> Config {
+        config::build(&Options {
+            size : Some((64, 48)),
+            center : Some(Complex { re : -0.5, im : 0.0 }),
+            width : Some(3.0),
+            ..Options::default()
+        }).unwrap()
+    }
+
+    //The colours `image` has at the lowest and the highest of `values` (the
+    //counts or the costs of its pixels), the image being wider than `bounds`
+    //for its colorbar
+    fn ends(image : &Image, values : &[Option<u32>], bounds : (usize, usize)) -> [[u8; 3]; 2] {
+        let at = |value : Option<&u32>| {
+            let index = values.iter().position(|other| other.as_ref() == value).unwrap();
+            let at = (index / bounds.0 * image.bounds.0 + index % bounds.0) * 3;
+            [image.pixels[at], image.pixels[at + 1], image.pixels[at + 2]]
+        };
+        [at(values.iter().flatten().min()), at(values.iter().flatten().max())]
+    }
+
+    fn palette_ends(name : &str) -> [[u8; 3]; 2] {
+        let palette = Palette::builtin(name).unwrap();
+        [palette.at(0.0), palette.at(1.0)]
+    }
+
+    #[test]
+    fn a_heatmap_spans_its_palette_from_the_lowest_count_to_the_highest() {
+        let config = Config { heatmap : true, ..full_set() };
+        let counts = render_counts(&config, None);
+        let image = render(&config, None);
+        assert!(image.bounds.0 > config.view.bounds.0 && image.bounds.1 == config.view.bounds.1,
+                "{:?} for a {:?} view", image.bounds, config.view.bounds);
+        assert_eq!(ends(&image, &counts, config.view.bounds), palette_ends(DEFAULT_PALETTE));
+    }
+
+    #[test]
+    fn a_cost_map_spans_its_palette_up_to_the_set() {
+        let config = Config { cost_map : true, ..full_set() };
+        let counts = render_counts(&config, None);
+        let costs = costs(&counts, config.limit);
+        //The most iterations are those of the set, every one of them
+        let interior = counts.iter().position(|count| count.is_none()).unwrap();
+        assert_eq!(costs[interior], Some(config.limit));
+        let image = render(&config, None);
+        assert_eq!(ends(&image, &costs, config.view.bounds)[1], palette_ends(COST_PALETTE)[1]);
+    }
+}
@@ -0,0 +1,207 @@
+//<***************JSON in and out*****************>//
+//Writing is for machine readable output (--dry-run --json, the result
+//lines of --jobs-stdin). Reading only goes as far as an object of options,
+//which is read into the same entries a TOML config file gives :
+//
+//    {"output" : "a.png", "size" : "400x300", "iterations" : 500}
+//
+//A value is a string, a number or a boolean, or an object of those
+//standing for a [section]. A null value is the same as leaving the key out.
+
+use crate::toml::{Entry, Value};
+
+pub enum Json {
+    Null,
+    Integer(u64),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>)
+}
+
+impl Json {
+    pub fn to_json(&self) -> String {
+        match *self {
+            Json::Null => "null".to_string(),
+            Json::Integer(value) => value.to_string(),
+            //JSON has no infinities or NaN
+            Json::Number(value) if !value.is_finite() => "null".to_string(),
+            Json::Number(value) => value.to_string(),
+            Json::String(ref text) => quote(text),
+            Json::Array(ref items) => format!("[{}]", items.iter()
+                .map(Json::to_json).collect::<Vec<_>>().join(",")),
+            Json::Object(ref fields) => format!("{{{}}}", fields.iter()
+                .map(|(key, value)| format!("{}:{}", quote(key), value.to_json()))
+                .collect::<Vec<_>>().join(","))
+        }
+    }
+}
+
+fn quote(s : &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c)
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+//The options object in `text`, its entries all said to be on `line`
+pub fn parse_options(text : &str, line : usize) -> Result<Vec<Entry>, String> {
+    let mut reader = Reader { chars : text.chars().collect(), position : 0 };
+    let mut entries = Vec::new();
+    reader.object(line, None, &mut entries)?;
+    reader.skip_whitespace();
+    match reader.peek() {
+        None => Ok(entries),
+        Some(c) => Err(format!("unexpected '{}' after the object", c))
+    }
+}
+
+struct Reader {
+    chars : Vec<char>,
+    position : usize
+}
+
+impl Reader {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).cloned()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, expected : char) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(c) if c == expected => { self.position += 1; Ok(()) }
+            Some(c) => Err(format!("expected '{}', got '{}'", expected, c)),
+            None => Err(format!("expected '{}', got the end of the line", expected))
+        }
+    }
+
+    //An object of options, or of the options of `section`
+    fn object(&mut self, line : usize, section : Option<&str>, entries : &mut Vec<Entry>)
+        -> Result<(), String>
+    {
+        self.expect('{')?;
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.position += 1;
+            return Ok(());
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return Err("expected a \"key\"".to_string());
+            }
+            let key = self.string()?;
+            self.expect(':')?;
+            self.skip_whitespace();
+            let value = match self.peek() {
+                Some('"') => Some(Value::String(self.string()?)),
+                Some('{') if section.is_none() => {
+                    self.object(line, Some(&key), entries)?;
+                    None
+                }
+                Some('{') => return Err(format!("'{}' : sections don't nest", key)),
+                Some('[') => return Err(format!("'{}' : arrays aren't options", key)),
+                Some(c) if c == '-' || c.is_ascii_digit() =>
+                    Some(Value::Number(self.number()?)),
+                Some(c) if c.is_ascii_alphabetic() => match self.word().as_str() {
+                    "true" => Some(Value::Boolean(true)),
+                    "false" => Some(Value::Boolean(false)),
+                    "null" => None,
+                    word => return Err(format!("'{}' : unexpected '{}'", key, word))
+                },
+                _ => return Err(format!("'{}' : missing value", key))
+            };
+            if let Some(value) = value {
+                entries.push(Entry {
+                    line,
+                    section : section.map(String::from),
+                    item : None,
+                    key,
+                    value
+                });
+            }
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some('}') => { self.position += 1; return Ok(()); }
+                _ => return Err("expected ',' or '}'".to_string())
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        //The opening quote
+        self.position += 1;
+        let mut value = String::new();
+        loop {
+            let c = self.peek().ok_or_else(|| "unterminated string".to_string())?;
+            self.position += 1;
+            match c {
+                '"' => return Ok(value),
+                '\\' => {
+                    let escape = self.peek().ok_or_else(|| "unterminated string".to_string())?;
+                    self.position += 1;
+                    value.push(match escape {
+                        '"' => '"',
+                        '\\' => '\\',
+                        '/' => '/',
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        'u' => self.unicode_escape()?,
+                        other => return Err(format!("unsupported escape \\{}", other))
+                    });
+                }
+                c => value.push(c)
+            }
+        }
+    }
+
+    //The XXXX of \uXXXX, which has to be a character of its own (the
+    //surrogate pairs of characters outside the BMP aren't put back together)
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let hex : String = self.chars.iter().skip(self.position).take(4).collect();
+        self.position += 4;
+        u32::from_str_radix(&hex, 16).ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| format!("unsupported escape \\u{}", hex))
+    }
+
+    //Kept as written, the option it is for parses it
+    fn number(&mut self) -> Result<String, String> {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_digit() || "+-.eE".contains(c)) {
+            self.position += 1;
+        }
+        let text : String = self.chars[start..self.position].iter().collect();
+        match text.parse::<f64>() {
+            Ok(_) => Ok(text),
+            Err(_) => Err(format!("'{}' isn't a number", text))
+        }
+    }
+
+    fn word(&mut self) -> String {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+            self.position += 1;
+        }
+        self.chars[start..self.position].iter().collect()
+    }
+}
@@ -0,0 +1,75 @@
+//<***************Logging*****************>//
+//Everything the program says about what it is doing goes to stderr through
+//the macros here, so that an image written to stdout ("-") isn't corrupted
+//and every command is as chatty as every other one. How much gets said is
+//set once, from the command line :
+//
+//    --quiet     nothing but errors
+//    (default)   warnings and a line per image written
+//    -v          also the resolved configuration and how long each stage took
+//    -vv         also how long every band of every render took
+//
+//Stdout is left to what a command is asked to produce (probe's answers,
+//--dry-run's plan, an image written to -).
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum Level {
+    Quiet,
+    Normal,
+    Verbose,
+    Debug
+}
+
+static LEVEL : AtomicUsize = AtomicUsize::new(Level::Normal as usize);
+
+pub fn set_level(level : Level) {
+    LEVEL.store(level as usize, Ordering::Relaxed);
+}
+
+//Whether messages of `level` are shown
+pub fn enabled(level : Level) -> bool {
+    LEVEL.load(Ordering::Relaxed) >= level as usize
+}
+
+//Always shown, --quiet included
+macro_rules! error {
+    ($($arg : tt)*) => { eprintln!("Error : {}", format_args!($($arg)*)) }
+}
+
+//Something the user probably didn't mean, hidden by --quiet
+macro_rules! warning {
+    ($($arg : tt)*) => {
+        if $crate::log::enabled($crate::log::Level::Normal) {
+            eprintln!("Warning : {}", format_args!($($arg)*));
+        }
+    }
+}
+
+//What happened, hidden by --quiet
+macro_rules! info {
+    ($($arg : tt)*) => {
+        if $crate::log::enabled($crate::log::Level::Normal) {
+            eprintln!($($arg)*);
+        }
+    }
+}
+
+//Stage timings and the resolved configuration, with -v
+macro_rules! verbose {
+    ($($arg : tt)*) => {
+        if $crate::log::enabled($crate::log::Level::Verbose) {
+            eprintln!($($arg)*);
+        }
+    }
+}
+
+//Per band details, with -vv
+macro_rules! debug {
+    ($($arg : tt)*) => {
+        if $crate::log::enabled($crate::log::Level::Debug) {
+            eprintln!($($arg)*);
+        }
+    }
+}
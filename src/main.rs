@@ -1,14 +1,67 @@
 extern crate crossbeam;
+extern crate deflate;
 extern crate image;
+extern crate png;
+extern crate scoped_threadpool;
+
+#[macro_use]
+mod log;
+
+mod big;
+mod blend;
+mod bookmarks;
+mod census;
+mod cli;
+mod commands;
+mod config;
+mod double;
+mod exit;
+mod expr;
+mod fixed;
+mod heatmap;
+mod json;
+mod orbits;
+mod palette;
+mod permalink;
+mod perturbation;
+mod plan;
+mod presets;
+mod progress;
+mod reorder;
+mod simd;
+mod stats;
+mod rng;
+mod steal;
+mod text;
+mod toml;
+
+use cli::{Command, Exterior, Mode, PngCompression, Precision, Schedule};
+use config::{Config, Gray, View};
+use double::Double;
+use exit::Failure;
+use palette::Palette;
+use steal::Stealing;
 
 use image::ColorType;
 use image::png::PNGEncoder;
-use std::fs::File;
+use scoped_threadpool::Pool;
+use std::cell::{Cell, RefCell};
+use std::fs::{File, OpenOptions};
+use std::io::BufWriter;
+use std::marker::PhantomData;
+use std::ops::ControlFlow::{self, Break, Continue};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Instant;
 
 use num::Complex;
 
 //Traits
 use std::io::Write;
+use std::ffi::OsString;
 use std::str::FromStr;
 
 //<***Through the function escape-time()************>//
@@ -20,75 +73,547 @@ use std::str::FromStr;
 //and also if the limit is passed without it flying away, it is within the set
 //
 fn escape_time(c : Complex<f64>,limit : u32) -> Option<u32> {
-    let mut z = Complex { re : 0.0 , im : 0.0 };
-    for i in 0..limit {
-        z = z * z + c;
+    escape_z(c,limit).map(|(i,_)| i)
+}
+
+//escape_time() of every one of `points` in one call, for callers with
+//points of their own rather than a window of pixels (a plotting library,
+//say). Renderer::escape_times() shares them out between threads.
+pub fn escape_times(points : &[Complex<f64>], limit : u32) -> Vec<Option<u32>> {
+    points.iter().map(|&point| escape_time(point, limit)).collect()
+}
+
+//Same iteration as escape_time(), but also hands back the value of z at
+//the moment it flew past the radius (for colourings that look at z itself)
+fn escape_z<F : Real>(c : Complex<F>,limit : u32) -> Option<(u32, Complex<F>)> {
+    let z = Complex { re : F::of(0.0) , im : F::of(0.0) };
+    in_stretches(0..limit, z, move |mut z, stretch| {
+        for i in stretch {
+            z = z * z + c;
+
+            if z.norm_sqr() > F::of(4.0) {
+                return Break((i, z));
+            }
+        }
+        Continue(z)
+    }).break_value()
+}
+
+//Every z escape_z() goes through, from the first (c itself) to the one
+//that flew past the radius, or `limit` of them for a point that doesn't
+//escape. For plotting the path an orbit takes.
+fn orbit(c : Complex<f64>, limit : u32) -> Vec<Complex<f64>> {
+    let z = Complex { re : 0.0, im : 0.0 };
+    let orbit = Vec::with_capacity(limit.min(4096) as usize);
+    let iterated = in_stretches(0..limit, (z, orbit), move |(mut z, mut orbit), stretch| {
+        for _ in stretch {
+            z = z * z + c;
+            orbit.push(z);
 
-        if z.norm_sqr() > 4.0 {
-            return Some(i);
+            if z.norm_sqr() > 4.0 {
+                return Break(orbit);
+            }
         }
+        Continue((z, orbit))
+    });
+    match iterated {
+        Break(orbit) | Continue((_, orbit)) => orbit
     }
-    None
 }
-//Parsing string values that are separated by a given character ('x' or comma)
-//to yield two string values that are parsed to another type
-fn parse_pair<T : FromStr>(s : &str, separator :char) -> Option<(T , T)>{
-    match s.find(separator) {
-        None => None,
-        Some(index) => {
-            match (T::from_str(&s[..index]),T::from_str(&s[index+1..])) {
-                (Ok(l) , Ok(r)) => Some(( l , r )),
-                _ => None
+
+//<********Julia sets***************>//
+//The same iteration with the roles swapped : c is one constant for the
+//whole picture and each point is where z starts. Every c gives its own
+//Julia set, connected when c is in the Mandelbrot set and dust outside.
+fn escape_time_julia(z : Complex<f64>, c : Complex<f64>, limit : u32) -> Option<u32> {
+    in_stretches(0..limit, z, move |mut z, stretch| {
+        for i in stretch {
+            z = z * z + c;
+
+            if z.norm_sqr() > 4.0 {
+                return Break(i);
+            }
+        }
+        Continue(z)
+    }).break_value()
+}
+
+//<********f64 or f32*****************>//
+//The floating point type the points are worked out and iterated in,
+//--precision. pixel_to_point() and the arithmetics below are written once
+//over it : f64 by default, f32 for quick previews of shallow views, twice
+//as many points to a SIMD register but only about 7 digits to a number (see
+//config::F32_MIN_STEP for how deep that goes), and double-double (see
+//double.rs) for twice f64's digits, down to where the corners run out.
+trait Real : num::Num + Copy + PartialOrd + Send + Sync + 'static {
+    fn of(value : f64) -> Self;
+
+    fn complex(value : Complex<f64>) -> Complex<Self> {
+        Complex { re : Self::of(value.re), im : Self::of(value.im) }
+    }
+
+    //How many of the points at the start of a row simd.rs has iterated in
+    //whole groups of lanes, having put their counts in `counts`
+    fn in_groups(points : &[Complex<Self>], limit : u32, counts : &mut [Option<u32>])
+        -> usize;
+}
+
+impl Real for f64 {
+    fn of(value : f64) -> f64 {
+        value
+    }
+
+    fn in_groups(points : &[Complex<f64>], limit : u32, counts : &mut [Option<u32>])
+        -> usize
+    {
+        simd::in_groups_f64(points, limit, counts)
+    }
+}
+
+impl Real for f32 {
+    fn of(value : f64) -> f32 {
+        value as f32
+    }
+
+    fn in_groups(points : &[Complex<f32>], limit : u32, counts : &mut [Option<u32>])
+        -> usize
+    {
+        simd::in_groups_f32(points, limit, counts)
+    }
+}
+
+//No SIMD instructions take double-doubles
+impl Real for Double {
+    fn of(value : f64) -> Double {
+        Double::from(value)
+    }
+
+    fn in_groups(_ : &[Complex<Double>], _ : u32, _ : &mut [Option<u32>]) -> usize {
+        0
+    }
+}
+
+//<********The same iteration on plain f64s*********>//
+//escape_time() reads like the maths, but every step goes through the
+//Complex operators. The renders can instead be built around the loop
+//spelled out on re and im, which is what --fast-math picks. It does
+//exactly the operations Complex's Mul and Add do, in the same order, so
+//the counts (and so the pictures) are identical bit for bit; only the
+//speed differs.
+//
+//The render functions take the arithmetic as a type parameter rather than
+//an argument, so each gets compiled with its loop inlined. An arithmetic
+//that works on whole rows (--simd, see simd.rs) gets handed a row of points
+//at a time instead, the others keep going pixel by pixel, which is quicker
+//for them than filling a row first.
+//
+//Each arithmetic comes in either Real, f64 unless it says otherwise.
+trait Arithmetic {
+    type Real : Real;
+
+    const BY_ROW : bool = false;
+
+    fn escape_time(c : Complex<Self::Real>, limit : u32) -> Option<u32>;
+
+    //escape_time() of every one of `points` into `counts`
+    fn escape_row(points : &[Complex<Self::Real>], limit : u32,
+                  counts : &mut [Option<u32>]) {
+        for (count, &point) in counts.iter_mut().zip(points) {
+            *count = Self::escape_time(point, limit);
+        }
+    }
+}
+
+//escape_time() as it is, the default
+struct ComplexArithmetic<F = f64>(PhantomData<F>);
+
+struct ScalarArithmetic<F = f64>(PhantomData<F>);
+
+impl<F : Real> Arithmetic for ComplexArithmetic<F> {
+    type Real = F;
+
+    fn escape_time(c : Complex<F>, limit : u32) -> Option<u32> {
+        escape_z(c, limit).map(|(i, _)| i)
+    }
+}
+
+impl<F : Real> Arithmetic for ScalarArithmetic<F> {
+    type Real = F;
+
+    fn escape_time(c : Complex<F>, limit : u32) -> Option<u32> {
+        let z = (F::of(0.0), F::of(0.0));
+        in_stretches(0..limit, z, move |(mut re, mut im), stretch| {
+            for i in stretch {
+                //z * z + c, as (re*re - im*im, re*im + im*re) + (c.re, c.im)
+                let (square_re, square_im) = (re * re - im * im, re * im + im * re);
+                re = square_re + c.re;
+                im = square_im + c.im;
+
+                if re * re + im * im > F::of(4.0) {
+                    return Break(i);
+                }
             }
+            Continue((re, im))
+        }).break_value()
+    }
+}
+
+//ScalarArithmetic's loop on a whole row, a group of points per SIMD
+//register, and on single points the same as ScalarArithmetic
+struct SimdArithmetic<F = f64>(PhantomData<F>);
+
+impl<F : Real> Arithmetic for SimdArithmetic<F> {
+    type Real = F;
+
+    const BY_ROW : bool = true;
+
+    fn escape_time(c : Complex<F>, limit : u32) -> Option<u32> {
+        ScalarArithmetic::<F>::escape_time(c, limit)
+    }
+
+    fn escape_row(points : &[Complex<F>], limit : u32, counts : &mut [Option<u32>]) {
+        simd::escape_row(points, limit, counts);
+    }
+}
+
+//Any of the arithmetics, counting the iterations it goes through for
+//--report-iterations (see stats.rs). Only renders asked to count are built
+//around it, the others don't pay for so much as a check.
+struct Counted<E>(PhantomData<E>);
+
+impl<E : Arithmetic> Arithmetic for Counted<E> {
+    type Real = E::Real;
+
+    const BY_ROW : bool = E::BY_ROW;
+
+    fn escape_time(c : Complex<E::Real>, limit : u32) -> Option<u32> {
+        let count = E::escape_time(c, limit);
+        stats::iterated(count.map_or(limit, |i| i + 1) as u64);
+        count
+    }
+
+    fn escape_row(points : &[Complex<E::Real>], limit : u32,
+                  counts : &mut [Option<u32>]) {
+        E::escape_row(points, limit, counts);
+        stats::iterated(counts.iter()
+                        .map(|count| count.map_or(limit, |i| i + 1) as u64)
+                        .sum());
+    }
+}
+
+//The escape counts of `n` evenly spaced points from `start` to `end`
+//(both included), for plotting how the counts change along a path through
+//the set
+fn sample_line(start : Complex<f64>, end : Complex<f64>, n : usize, limit : u32)
+    -> Vec<Option<u32>>
+{
+    (0..n).map(|i| {
+        let t = if n > 1 { i as f64 / (n - 1) as f64 } else { 0.0 };
+        escape_time(start + (end - start) * t, limit)
+    }).collect()
+}
+
+//Parsing string values that are separated by a given character ('x' or comma)
+//to yield two string values that are parsed to another type.
+//
+//Spaces around either value and parentheses around the whole pair are
+//allowed, "( -1.2, 0.35 )" reads the same as "-1.2,0.35", and the Unicode
+//minus sign web pages tend to use is read as a plain '-'.
+fn parse_pair<T : FromStr>(s : &str, separator :char) -> Result<(T , T), PairError>
+    where T::Err : std::fmt::Display
+{
+    let (l, r) = split_pair(s, separator)?;
+    let l = T::from_str(&l).map_err(|error| PairError::Part(0, l.clone(), error.to_string()))?;
+    let r = T::from_str(&r).map_err(|error| PairError::Part(1, r.clone(), error.to_string()))?;
+    Ok(( l , r ))
+}
+
+//What went wrong with a pair, for the caller to word in its own terms
+#[derive(Debug)]
+enum PairError {
+    //There was no separator (the char) to split at
+    MissingSeparator(char),
+    //The first (0) or second (1) value, its text and why it didn't parse
+    Part(usize, String, String)
+}
+
+impl PairError {
+    //`what` is the kind of pair, `parts` the names of its two values and
+    //`example` what a good one looks like
+    fn describe(&self, what : &str, parts : [&str; 2], example : &str) -> String {
+        match *self {
+            PairError::MissingSeparator(separator) =>
+                format!("expected {} such as {}, there's no '{}' between the {} and \
+                         the {}", what, example, separator, parts[0], parts[1]),
+            PairError::Part(index, ref text, ref reason) =>
+                format!("the {} '{}' isn't valid : {}", parts[index], text, reason)
         }
     }
 }
 
+fn split_pair(s : &str, separator : char) -> Result<(String, String), PairError> {
+    let s = normalize(s);
+    match s.find(separator) {
+        None => Err(PairError::MissingSeparator(separator)),
+        Some(index) => Ok((s[..index].trim().to_string(),
+                           s[index + separator.len_utf8()..].trim().to_string()))
+    }
+}
+
+//Trims, takes off parentheses around the whole thing and turns Unicode
+//minus signs into plain ones
+fn normalize(s : &str) -> String {
+    let s = s.trim().replace('\u{2212}', "-");
+    match s.strip_prefix('(').and_then(|inner| inner.strip_suffix(')')) {
+        Some(inner) => inner.trim().to_string(),
+        None => s
+    }
+}
+
 
 //Using parse_pair() function above to parse a string to a Complex number type.
-fn parse_complex(s : &str) -> Option<Complex<f64>> {
-    match parse_pair(s,',') {
-        Some((re,im)) => Some(Complex { re , im }),
-        None => None
+//Plain numbers are parsed directly; only the parts that aren't are tried
+//as expressions such as -pi/4 (see expr.rs). Without a comma the value is
+//read in a+bi form instead (see parse_algebraic()), and with a polar:
+//prefix as a modulus and angle (see parse_polar()).
+fn parse_complex(s : &str) -> Result<Complex<f64>, PairError> {
+    if let Some(polar) = normalize(s).strip_prefix("polar:") {
+        return parse_polar(polar);
     }
+    let (re, im) = match split_pair(s, ',') {
+        Err(PairError::MissingSeparator(_)) => return parse_algebraic(&normalize(s)),
+        parts => parts?
+    };
+    Ok(Complex { re : parse_part(0, re)?, im : parse_part(1, im)? })
 }
 
+//f64::from_str() takes inf and nan, and an expression can divide by zero,
+//but a point that isn't finite iterates to nothing but NaNs
+fn parse_part(index : usize, text : String) -> Result<f64, PairError> {
+    match f64::from_str(&text).or_else(|_| expr::evaluate(&text)) {
+        Ok(value) if value.is_finite() => Ok(value),
+        Ok(_) => Err(PairError::Part(index, text, "not a finite number".to_string())),
+        Err(reason) => Err(PairError::Part(index, text, reason))
+    }
+}
+
+//polar:MODULUS,ANGLE where the angle needs a unit, deg or rad, as in
+//polar:0.7,45deg or polar:1,pi/4rad
+fn parse_polar(s : &str) -> Result<Complex<f64>, PairError> {
+    let (modulus, angle) = split_pair(s, ',')?;
+    let modulus_text = modulus.clone();
+    let modulus = parse_part(0, modulus)?;
+    if modulus < 0.0 {
+        return Err(PairError::Part(0, modulus_text,
+                                   "the modulus can't be negative".to_string()));
+    }
+    let radians = if let Some(degrees) = angle.strip_suffix("deg") {
+        let degrees = parse_part(1, degrees.trim().to_string())?;
+        //Whole quarter turns exactly, cos(90deg) in floating point is 6e-17
+        //rather than 0, which is enough to move a point like i off the set
+        if degrees % 90.0 == 0.0 {
+            let (re, im) = match (degrees / 90.0).rem_euclid(4.0) as u32 {
+                0 => (1.0, 0.0),
+                1 => (0.0, 1.0),
+                2 => (-1.0, 0.0),
+                _ => (0.0, -1.0)
+            };
+            return Ok(Complex { re : modulus * re, im : modulus * im });
+        }
+        degrees.to_radians()
+    } else if let Some(radians) = angle.strip_suffix("rad") {
+        parse_part(1, radians.trim().to_string())?
+    } else {
+        return Err(PairError::Part(1, angle,
+                                   "the angle needs a unit, deg or rad".to_string()));
+    };
+    Ok(Complex::from_polar(&modulus, &radians))
+}
+
+//The way papers write complex numbers : -0.74+0.13i, 0.5-i, 0.7i (no real
+//part), i, -i or just 0.5 (no imaginary part). The imaginary part starts at
+//the last sign that isn't the sign of an exponent, so 1.5e-3+2e-4i splits
+//into 1.5e-3 and +2e-4.
+fn parse_algebraic(s : &str) -> Result<Complex<f64>, PairError> {
+    //A real number first, so that pi isn't taken for p times i
+    let imaginary = match (parse_part(0, s.to_string()), s.strip_suffix('i')) {
+        (Ok(re), _) => return Ok(Complex { re, im : 0.0 }),
+        (Err(error), None) => return Err(error),
+        (Err(_), Some(imaginary)) => imaginary
+    };
+
+    let bytes = imaginary.as_bytes();
+    let exponent = |index : usize| matches!(bytes[index - 1], b'e' | b'E')
+        && index >= 2 && (bytes[index - 2].is_ascii_digit() || bytes[index - 2] == b'.');
+    let split = (1..bytes.len()).rev()
+        .find(|&index| (bytes[index] == b'+' || bytes[index] == b'-')
+              && !exponent(index) && !matches!(bytes[index - 1], b'*' | b'/'));
+    let (re, im) = match split {
+        Some(index) => (parse_part(0, imaginary[..index].trim().to_string())?,
+                        &imaginary[index..]),
+        None => (0.0, imaginary)
+    };
+    let im = match im.trim() {
+        "" | "+" => 1.0,
+        "-" => -1.0,
+        //A multiplication sign is fine too, as in 0.5*i
+        im => parse_part(1, im.strip_suffix('*').unwrap_or(im).trim().to_string())?
+    };
+    Ok(Complex { re, im })
+}
 
 //<***********Converting Pixels to points on the Complex plane*******>//
-fn pixel_to_point(
-   bounds : (usize, usize ),
-   pixel : ( usize , usize ),
-   upper_left : Complex<f64>,
-   lower_right : Complex<f64>
-    ) -> Complex<f64>
+//The pixel's place in the upright window, then for a --rotate view the
+//offset from the pivot goes through the rotation matrix, which turns the
+//grid and so leaves the picture turned the other way. All of it is worked
+//out in F, the corners too.
+fn pixel_to_point<F : Real>(
+   view : &View,
+   pixel : ( usize , usize )
+    ) -> Complex<F>
 {
+    let View { bounds, upper_left, lower_right, .. } = *view;
+    let (upper_left, lower_right) = (F::complex(upper_left), F::complex(lower_right));
     let width = lower_right.re - upper_left.re;
     let height = upper_left.im - lower_right.im;
     
-    Complex {
-        re : upper_left.re + pixel.0  as f64 / bounds.0 as f64 * width,
-        im : upper_left.im - pixel.1 as f64 / bounds.1 as f64 * height
-    }
+    view.rotated_in(Complex {
+        re : upper_left.re + F::of(pixel.0 as f64) / F::of(bounds.0 as f64) * width,
+        im : upper_left.im - F::of(pixel.1 as f64) / F::of(bounds.1 as f64) * height
+    })
+}
+
+//<***********Viewing a window around a center point*******>//
+//Works out the upper left and lower right corners of a window of the
+//given complex-plane width centered on `center`. The height follows from
+//the pixel dimensions so that pixels stay square and nothing is stretched.
+fn corners_from_center(
+    bounds : (usize, usize),
+    center : Complex<f64>,
+    width : f64
+    ) -> (Complex<f64>, Complex<f64>)
+{
+    let height = width * bounds.1 as f64 / bounds.0 as f64;
+
+    (Complex { re : center.re - width / 2.0, im : center.im + height / 2.0 },
+     Complex { re : center.re + width / 2.0, im : center.im - height / 2.0 })
+}
+
+//<***********Keeping the aspect ratio honest*******>//
+//What to do when the corners don't have the same aspect ratio as the
+//pixel dimensions (which would stretch circles into ellipses)
+#[derive(Clone, Copy, PartialEq)]
+enum Fit {
+    //Grow the short side of the view around its center
+    Expand,
+    //Shrink the long side of the view around its center
+    Crop,
+    //Keep the corners as given and accept the distortion
+    Stretch
+}
+
+//Beyond this relative difference the user gets warned about the stretch
+const ASPECT_TOLERANCE : f64 = 0.005;
+
+//How far the complex-plane aspect ratio (width / height) of the view is
+//from the aspect ratio of the image in pixels, as a fraction of the latter
+fn aspect_mismatch(
+    bounds : (usize, usize),
+    upper_left : Complex<f64>,
+    lower_right : Complex<f64>
+    ) -> f64
+{
+    let pixel_aspect = bounds.0 as f64 / bounds.1 as f64;
+    let view_aspect = (lower_right.re - upper_left.re)
+        / (upper_left.im - lower_right.im);
+    (view_aspect - pixel_aspect).abs() / pixel_aspect
+}
+
+//Adjusts the corners symmetrically around the center of the view so the
+//complex-plane aspect ratio matches bounds.0 / bounds.1
+fn fit_view(
+    bounds : (usize, usize),
+    upper_left : Complex<f64>,
+    lower_right : Complex<f64>,
+    fit : Fit
+    ) -> (Complex<f64>, Complex<f64>)
+{
+    let width = lower_right.re - upper_left.re;
+    let height = upper_left.im - lower_right.im;
+    let pixel_aspect = bounds.0 as f64 / bounds.1 as f64;
+    //A view that is too wide for the image has to gain height when
+    //expanding or lose width when cropping, and the other way around
+    let too_wide = width / height > pixel_aspect;
+
+    let (width, height) = match (fit, too_wide) {
+        (Fit::Stretch, _) => return (upper_left, lower_right),
+        (Fit::Expand, true) | (Fit::Crop, false) => (width, width / pixel_aspect),
+        (Fit::Expand, false) | (Fit::Crop, true) => (height * pixel_aspect, height)
+    };
+    let center = Complex {
+        re : (upper_left.re + lower_right.re) / 2.0,
+        im : (upper_left.im + lower_right.im) / 2.0
+    };
+
+    (Complex { re : center.re - width / 2.0, im : center.im + height / 2.0 },
+     Complex { re : center.re + width / 2.0, im : center.im - height / 2.0 })
 }
 
 //<********render function********************>//
 //<*****Assigns grayscale pixel values to our window*********>//
-fn render(
+//`pixels` holds whole rows of the window starting at row `top`, either all
+//of them or the band of them a thread was given. Every point is worked out
+//from the window's own corners, so the picture comes out the same however
+//the window is split up.
+fn render<E : Arithmetic>(
     pixels : &mut[u8],
-    bounds : (usize,usize),
-    upper_left : Complex<f64>,
-    lower_right : Complex<f64>)
+    top : usize,
+    view : &View,
+    limit : u32,
+    gray : Gray)
+{
+    if !E::BY_ROW {
+        return render_with_limits::<E, _>(pixels, top, view, |_| limit, gray);
+    }
+    let bounds = view.bounds;
+    let rows = rows_of(pixels.len(), top, bounds);
+    let mut points = vec![Complex { re : E::Real::of(0.0), im : E::Real::of(0.0) }; bounds.0];
+    let mut counts = vec![None; bounds.0];
+
+    for row in 0..rows {
+        for (column, point) in points.iter_mut().enumerate() {
+            *point = pixel_to_point(view, (column, top + row));
+        }
+        E::escape_row(&points, limit, &mut counts);
+        for (pixel, &count) in pixels[bounds.0 * row..bounds.0 * (row + 1)].iter_mut()
+            .zip(&counts)
+        {
+            *pixel = gray_value(count, limit, gray);
+        }
+    }
+}
+
+//Same as render(), but the iteration limit can change across the window:
+//limit_for() is asked for the limit of every (column,row) pixel, which lets
+//the caller spend more iterations where the detail matters.
+//Each pixel's brightness is scaled against its own limit.
+fn render_with_limits<E : Arithmetic, L>(
+    pixels : &mut[u8],
+    top : usize,
+    view : &View,
+    limit_for : L,
+    gray : Gray)
+    where L : Fn((usize,usize)) -> u32
 {
-    assert!(pixels.len() == bounds.0 * bounds.1);
+    let bounds = view.bounds;
+    let rows = rows_of(pixels.len(), top, bounds);
 
-    for row in 0..bounds.1 {
+    for row in 0..rows {
         for column in 0..bounds.0 {
             //We will move across the width of the image window
             //calling the pixel_to_point() function on all the individual points
             //moving across each row before moving to the next row
-            let point = pixel_to_point(
-                bounds,(column,row),upper_left,lower_right
-                );
+            let point = pixel_to_point(view, (column, top + row));
             //Since we have a mutable reference to the pixels slice variable
             //Lets change the pixel values for each point accordingly
             //We're working with single-number grayscale pixel values that 
@@ -98,116 +623,2716 @@ fn render(
             //giving a range of possible values from 0 to 255. 
             //Typically zero is black & 255 is white. 
             //Values in between make up the different shades of gray.
-            //We use 255 as the limit of possible iterations it took
+            //We use 255 as the default limit of possible iterations it took
             //for us to find out whether we're dealing with a mandelbrot set
-            pixels[column + bounds.0 * row] = match escape_time(point,255) {
-                None => 0,
-                Some(count) => 255 - count as u8
+            let limit = limit_for((column,top + row));
+            pixels[column + bounds.0 * row] =
+                gray_value(E::escape_time(point,limit), limit, gray);
+        }
+    }
+}
+
+//How many rows a buffer of `len` pixels starting at row `top` covers,
+//checking that they are whole rows inside the window
+fn rows_of(len : usize, top : usize, bounds : (usize,usize)) -> usize {
+    assert!(len.is_multiple_of(bounds.0),
+            "pixel buffer of {} isn't a whole number of {} pixel rows", len, bounds.0);
+    assert!(top + len / bounds.0 <= bounds.1,
+            "pixel buffer of {} rows from row {} goes past the {} rows of a {}x{} window",
+            len / bounds.0, top, bounds.1, bounds.0, bounds.1);
+    len / bounds.0
+}
+
+//A radial falloff of the iteration limit: `center_limit` in the middle of
+//the view going linearly down to `edge_limit` at its corners.
+fn radial_limit(
+    point : Complex<f64>,
+    view : &View,
+    center_limit : u32,
+    edge_limit : u32
+    ) -> u32
+{
+    //The middle of a turned view is where it is turned around, and the
+    //turn keeps the distances to it
+    let center = view.rotated(view.center());
+    //Distance to the center as a fraction of the distance to a corner
+    let distance = ((point - center).norm_sqr()
+                    / (view.rotated(view.upper_left) - center).norm_sqr()).sqrt().min(1.0);
+    let limit = center_limit as f64
+        + (edge_limit as f64 - center_limit as f64) * distance;
+    (limit.round() as u32).max(1)
+}
+
+//<********render_raw function****************>//
+//Same walk over the window as render(), but instead of turning the escape
+//counts into brightness straight away we keep the raw counts around.
+//This lets the caller post-process the field (e.g. combine several renders)
+//before anything is quantized into a pixel.
+fn render_raw<E : Arithmetic>(
+    counts : &mut[Option<u32>],
+    top : usize,
+    view : &View,
+    limit : u32)
+{
+    render_raw_window::<E>(counts, top, view, ((0, 0), view.bounds), limit)
+}
+
+//render_raw() for a `window` of the view's grid, the (column, row) of its
+//upper left pixel and its (columns, rows), `top` being a row of the window
+fn render_raw_window<E : Arithmetic>(
+    counts : &mut[Option<u32>],
+    top : usize,
+    view : &View,
+    window : ((usize, usize), (usize, usize)),
+    limit : u32)
+{
+    let ((left, first), bounds) = window;
+    let rows = rows_of(counts.len(), top, bounds);
+    if !E::BY_ROW {
+        for row in 0..rows {
+            for column in 0..bounds.0 {
+                let point = pixel_to_point(view, (left + column, first + top + row));
+                counts[column + bounds.0 * row] = E::escape_time(point,limit);
             }
         }
+        return;
+    }
+    let mut points = vec![Complex { re : E::Real::of(0.0), im : E::Real::of(0.0) }; bounds.0];
+
+    for row in 0..rows {
+        for (column, point) in points.iter_mut().enumerate() {
+            *point = pixel_to_point(view, (left + column, first + top + row));
+        }
+        E::escape_row(&points, limit, &mut counts[bounds.0 * row..bounds.0 * (row + 1)]);
     }
 }
 
-fn write_image(filename : &str,pixels : &[u8], bounds : (usize , usize))
-    -> Result<(), std::io::Error>
+//Same as render_raw(), but for the Julia set of `c`
+fn render_julia(
+    counts : &mut[Option<u32>],
+    top : usize,
+    view : &View,
+    c : Complex<f64>,
+    limit : u32)
 {
-    let output = File::create(filename)?;
+    let bounds = view.bounds;
+    let rows = rows_of(counts.len(), top, bounds);
 
-    let encoder = PNGEncoder::new(output);
-    encoder.encode(&pixels,
-                   bounds.0 as u32,
-                   bounds.1 as u32,
-                   ColorType::Gray(8))?;
-    Ok(())
+    for row in 0..rows {
+        for column in 0..bounds.0 {
+            let point = pixel_to_point(view, (column, top + row));
+            counts[column + bounds.0 * row] = escape_time_julia(point, c, limit);
+        }
+    }
+}
+
+//The grayscale mapping used by render(): interior points are black and
+//the quicker a point escapes, the brighter it gets.
+//Counts are scaled into 0..255 so limits above 255 don't wrap around,
+//for the default limit of 255 this is exactly 255 - count.
+//
+//--interior-value fills the interior with another gray, and --exterior
+//dark-to-light turns the ramp around so that quickly escaping points are
+//the dark ones (count * 255 / limit), for masks that want it that way.
+//
+//With a gamma other than 1 the brightness (0 to 1) is raised to the power
+//gamma before it is quantized, the curve of a typical display at 2.2 : it
+//darkens the midtones and deepens the contrast around the set, while a
+//gamma below 1 brings out the dim, slowly escaping regions instead. A
+//gamma of exactly 1 keeps to the integer mapping above.
+fn gray_value(count : Option<u32>, limit : u32, gray : Gray) -> u8 {
+    let count = match count {
+        None => return gray.interior,
+        Some(count) => count
+    };
+    if gray.gamma == 1.0 {
+        let scaled = (count as u64 * 255 / limit as u64) as u8;
+        return match gray.exterior {
+            Exterior::LightToDark => 255 - scaled,
+            Exterior::DarkToLight => scaled
+        };
+    }
+    let fraction = count as f64 / limit as f64;
+    let brightness = match gray.exterior {
+        Exterior::LightToDark => 1.0 - fraction,
+        Exterior::DarkToLight => fraction
+    };
+    (brightness.powf(gray.gamma) * 255.0).round() as u8
 }
 
+//<********--auto-exposure*********************>//
+//Most views use only part of the range of counts up to the limit, which
+//leaves them dim or washed out. Auto-exposure takes the counts at two
+//percentiles of the escaping points (interior points left out, there are
+//often more of them than of anything else) and maps those to the ends of
+//the gray ramp instead of 0 and the limit, clipping what lies beyond.
+//In between the ramp stays linear, unlike histogram equalization which
+//flattens the gradients that show the shape of the set.
+fn exposure_range(counts : &[Option<u32>], clip : (f64, f64)) -> (u32, u32) {
+    let mut escaped = counts.iter().filter_map(|&count| count).collect::<Vec<_>>();
+    if escaped.is_empty() {
+        return (0, 1);
+    }
+    escaped.sort_unstable();
+    let at = |percent : f64| {
+        escaped[((escaped.len() - 1) as f64 * percent / 100.0).round() as usize]
+    };
+    let low = at(clip.0);
+    (low, at(clip.1).max(low + 1))
+}
 
+//gray_value() with the ramp running from `range.0` to `range.1` rather
+//than from 0 to the limit
+fn exposed_value(count : Option<u32>, range : (u32, u32), gray : Gray) -> u8 {
+    let count = match count {
+        None => return gray.interior,
+        Some(count) => count
+    };
+    let (low, high) = range;
+    let fraction = (count.clamp(low, high) - low) as f64 / (high - low) as f64;
+    let brightness = match gray.exterior {
+        Exterior::LightToDark => 1.0 - fraction,
+        Exterior::DarkToLight => fraction
+    };
+    (brightness.powf(gray.gamma) * 255.0).round() as u8
+}
 
-//<<******************MAIN FUNCTION*****************>>//
-//<<******************MAIN FUNCTION*****************>>//
-fn main() {
-    let available_cpus = num_cpus::get();
-    //Returns the number of available CPUs of the current system
-    
-    let num_of_cores = num_cpus::get_physical();
-    //Returns the number of physical cores of the current system.
-    //
-    println!( "Number of cpus = {} and number of physical cores = {}",
-              available_cpus , num_of_cores);
+//<********Colouring through a palette*********>//
+//Looks every escape count up in the palette, interior points are black
+fn colorize(pixels : &mut [[u8; 3]], counts : &[Option<u32>], palette : &Palette) {
+    assert_eq!(pixels.len(), counts.len(), "pixel buffer and escape counts differ in size");
 
-    //println!("Hello, world!");
-    let args : Vec<String> = std::env::args().collect();
-    if args.len() != 5 {
-        writeln!(std::io::stderr(),
-        "Usage : mandelbrot File Pixels Upperleft Lowerright")
-            .unwrap();
-        writeln!(std::io::stderr(),
-        "Example : {} mandelbrot.png 1000x750 -1.20,0.34 -1.0,2.0", args[0])
-            .unwrap();
-        std::process::exit(1);
+    for (pixel, count) in pixels.iter_mut().zip(counts) {
+        *pixel = match *count {
+            None => [0, 0, 0],
+            Some(count) => palette.color(count)
+        };
     }
+}
 
-    let bounds = parse_pair(&args[2],'x')
-        .expect("Error parsing image dimensions");
-    let upper_left = parse_complex(&args[3])
-        .expect("ERROR parsing upper left complex corner point");
-    let lower_right = parse_complex(&args[4])
-        .expect("ERROR parsing lower right complex corner point.");
-    
-    //The statement below equates all the pixel values
-    //in the image widow to zero
-    let mut pixels = vec![0;bounds.0 * bounds.1];
-    
-    let threads = 8;
-    let rows_per_band = bounds.1 / threads + 1;
-    {
-        //Height of a single band is rows_per_band
-        //height of overall window/image is bounds.1 
-        
-        //Not sure why we add 1
-        //we then need to obtain mutable non-overlapping iterable chunks 
-        //of ChunkMut type
-        //which we will then iterate over by transferring owneship of the 
-        //elements to a closure using the into_iter() method.
-        //
-        //Using the enumerate method we can get the current iteration count (i)
-        //as well as the value (band) returned by the next iteration.
-        let bands : Vec<&mut[u8]> =
-            pixels.chunks_mut(rows_per_band * bounds.0).collect();
-        crossbeam::scope(|spawner| {
-            for (i,band) in bands.into_iter().enumerate() {
-                //top is essentially the pixel value at the top upper_left
-                //corner of the band
-                //for example, for the top-most band, if bounds.1 = 1000
-                //and threads = 8, then 1000/8 = 250, so top = 250*0=0
-                //and for the second band from the top=>250*1=250, and so on...
-                let top = rows_per_band * i;
-                
-                //Since the bands value is one long vector slice value,
-                //consisting of all the values of the band 
-                //while being dimension-agnostic
-                //dividing by the width (bounds.0) recovers our dimensions
-                let height = band.len() / bounds.0;
-                let band_bounds = (bounds.0 , height);
-                
-                let band_upper_left = 
-                    pixel_to_point(bounds, (0, top),
-                    upper_left,lower_right);
-
-                let band_lower_right = 
-                    pixel_to_point(bounds, (bounds.0,top + height),
-                    upper_left,lower_right);
+//<********Colouring in bands******************>//
+//For --mode bands : a point that escaped after `count` iterations gets the
+//colour count % width steps along a ramp of `width`, light to dark or
+//through the palette, so the same colours come round again every `width`
+//counts and the escape counts show as sharp rings instead of a gradient.
+//Interior points stay black.
+fn banded(counts : &[Option<u32>], bounds : (usize, usize), width : u32,
+          palette : Option<&Palette>)
+    -> Image
+{
+    let step = |count : u32| (count % width) as f64 / (width - 1) as f64;
+    match palette {
+        Some(palette) => Image {
+            pixels : counts.iter()
+                .flat_map(|&count| count.map_or([0, 0, 0], |count| palette.at(step(count))))
+                .collect(),
+            bounds,
+            color : ColorType::RGB(8)
+        },
+        None => Image {
+            pixels : counts.iter()
+                .map(|&count| count.map_or(0, |count| {
+                    255 - (step(count) * 255.0).round() as u8
+                }))
+                .collect(),
+            bounds,
+            color : ColorType::Gray(8)
+        }
+    }
+}
 
-                spawner.spawn(move || {
-                    render(band, band_bounds, band_upper_left, band_lower_right);
-                });
+//<********Colouring by escape angle************>//
+//Colours the outside of the set by the argument (angle) of z at the moment
+//it escapes, going once around the hue wheel as the angle goes from -pi
+//to pi. z.arg() jumps from pi to -pi across the negative real axis, but
+//both ends land on the same hue so the branch cut leaves no visible seam.
+//Interior points stay black.
+fn render_angle(
+    pixels : &mut[[u8; 3]],
+    top : usize,
+    view : &View,
+    limit : u32)
+{
+    let bounds = view.bounds;
+    let rows = rows_of(pixels.len(), top, bounds);
+
+    for row in 0..rows {
+        for column in 0..bounds.0 {
+            let point = pixel_to_point::<f64>(view, (column, top + row));
+            pixels[column + bounds.0 * row] = match escape_z(point,limit) {
+                None => [0, 0, 0],
+                Some((_, z)) => {
+                    let turn = (z.arg() + std::f64::consts::PI)
+                        / (2.0 * std::f64::consts::PI);
+                    hue_to_rgb(turn)
+                }
             }
-        });
+        }
+    }
+}
+
+//Fully saturated, full brightness colour at `hue` turns around the colour
+//wheel (0 and 1 are both red). Values outside 0..1 wrap around.
+fn hue_to_rgb(hue : f64) -> [u8; 3] {
+    let h = (hue - hue.floor()) * 6.0;
+    let x = 1.0 - (h % 2.0 - 1.0).abs();
+    let (r, g, b) = match h as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x)
+    };
+    [(r * 255.0).round() as u8, (g * 255.0).round() as u8,
+     (b * 255.0).round() as u8]
+}
+
+//<********Splitting the window into bands*******>//
+//A band of the buffer together with the row of the window it starts at
+type Band<'a, T> = (&'a mut [T], usize);
+
+//How many bands a window is cut into for `threads` threads. With a band
+//per thread the render takes as long as its slowest band, and the bands
+//through the set take far longer than those outside it. With
+//BANDS_PER_THREAD bands a thread, handed out to the threads as they become
+//free, a thread that got quick bands goes on to take more of them and the
+//slow ones get shared out; more than a few a thread only adds overhead.
+//There are never more bands than rows.
+const BANDS_PER_THREAD : usize = 4;
+
+fn optimal_bands(bounds : (usize,usize), threads : usize) -> usize {
+    (threads.max(1) * BANDS_PER_THREAD).clamp(1, bounds.1.max(1))
+}
+
+//The (top row, rows) of the `count` bands a window is cut into : as even
+//as they go, the first bounds.1 % count bands a row taller than the rest.
+//There are never more bands than rows, so every band gets at least one row.
+fn band_layout(bounds : (usize,usize), count : usize) -> Vec<(usize, usize)> {
+    let bands = count.clamp(1, bounds.1.max(1));
+    let (rows, taller) = (bounds.1 / bands, bounds.1 % bands);
+    let mut top = 0;
+    let layout = (0..bands)
+        .map(|band| {
+            let height = if band < taller { rows + 1 } else { rows };
+            top += height;
+            (top - height, height)
+        })
+        .collect::<Vec<_>>();
+    if bounds.1 > 0 {
+        assert!(layout.len() == count.clamp(1, bounds.1)
+                && layout.iter().all(|&(_, rows)| rows > 0) && top == bounds.1,
+                "bands {:?} don't split {} rows into {} bands", layout, bounds.1, count);
+    }
+    layout
+}
+
+//A buffer handed out in bands has to hold the whole window
+fn check_window(len : usize, bounds : (usize,usize)) {
+    assert!(len == bounds.0 * bounds.1,
+            "pixel buffer {} != {}x{}={}", len, bounds.0, bounds.1, bounds.0 * bounds.1);
+}
+
+//<********--schedule cost-balanced***************>//
+//Equal bands aren't equal work : rows through the set take far longer than
+//rows outside it. A quick pre-pass iterates every COST_SAMPLE_STEP.0th
+//pixel of every COST_SAMPLE_STEP.1th row, and what it took stands for the
+//cost of the rows around it; the window is then cut into a band per thread
+//of about the same total cost, fewer rows where the set is.
+const COST_SAMPLE_STEP : (usize, usize) = (32, 8);
+
+//The estimated cost of every row of `view`, worked out on `threads` threads
+fn row_costs(config : &Config, view : &View, threads : usize) -> Vec<f64> {
+    let bounds = view.bounds;
+    let (step, limit) = (COST_SAMPLE_STEP, config.limit);
+    let started = Instant::now();
+    let sampled = bounds.1.div_ceil(step.1);
+    let mut sampled_costs = vec![0.0; sampled];
+    render_in_scope(&mut sampled_costs, (1, sampled), threads, (Schedule::Bands, 1), &[],
+                    |band : &mut [f64], top| {
+        for (i, cost) in band.iter_mut().enumerate() {
+            let row = (top + i) * step.1;
+            *cost = (0..bounds.0).step_by(step.0)
+                .map(|column| {
+                    let point = pixel_to_point(view, (column, row));
+                    let count = match config.julia {
+                        Some(c) => escape_time_julia(point, c, limit),
+                        None => escape_time(point, limit)
+                    };
+                    //Every pixel costs something, even one that escapes at once
+                    count.unwrap_or(limit) as f64 + 1.0
+                })
+                .sum();
+        }
+    });
+    debug!("Pre-pass of {} rows for --schedule cost-balanced in {:.1}ms", sampled,
+           started.elapsed().as_secs_f64() * 1000.0);
+    (0..bounds.1).map(|row| sampled_costs[row / step.1]).collect()
+}
+
+//The (top row, rows) of `count` bands of about the same total of
+//`costs`, a cost per row : each band ends at the row that takes the
+//running total closest to its share. Like band_layout() there are never
+//more bands than rows and every band gets at least one.
+fn balanced_layout(costs : &[f64], count : usize) -> Vec<(usize, usize)> {
+    let rows = costs.len();
+    let bands = count.clamp(1, rows.max(1));
+    if rows == 0 {
+        return vec![(0, 0)];
+    }
+    let total = costs.iter().sum::<f64>();
+    let (mut top, mut done) = (0, 0.0);
+    (0..bands)
+        .map(|band| {
+            //Rows have to be left for the bands after this one
+            let last = rows - (bands - band - 1);
+            let share = total * (band + 1) as f64 / bands as f64;
+            let start = top;
+            done += costs[start];
+            top += 1;
+            while top < last && (band + 1 == bands || done + costs[top] / 2.0 < share) {
+                done += costs[top];
+                top += 1;
+            }
+            (start, top - start)
+        })
+        .collect()
+}
+
+//Cuts the buffer into the horizontal bands of band_layout(), ready to be
+//handed out to threads.
+fn split_into_bands<T>(
+    buffer : &mut [T],
+    bounds : (usize,usize),
+    count : usize)
+    -> Vec<Band<'_, T>>
+{
+    cut_bands(buffer, bounds, band_layout(bounds, count))
+}
+
+//Cuts the buffer into the bands of a layout that covers the window
+fn cut_bands<T>(
+    buffer : &mut [T],
+    bounds : (usize,usize),
+    layout : Vec<(usize, usize)>)
+    -> Vec<Band<'_, T>>
+{
+    check_window(buffer.len(), bounds);
+    //Mutable, non-overlapping pieces of the buffer, each cut off the front
+    //of what is left.
+    //
+    //top is the row of the window the band starts at, which is all a band
+    //needs to know : its points are worked out from the corners of the
+    //whole window (see render()).
+    let mut rest = buffer;
+    layout.into_iter()
+        .map(|(top, rows)| {
+            let (band, after) = std::mem::take(&mut rest).split_at_mut(rows * bounds.0);
+            rest = after;
+            (band, top)
+        })
+        .collect()
+}
+
+//The work of rendering a window, the way the threads pick it up
+enum Work<'a, T> {
+    //Made up beforehand, a share of the window per thread
+    Shares(Vec<Vec<Band<'a, T>>>),
+    //Bands the threads take from their own deques and steal from each
+    //other's, with the rows of a chunk if they are split up into chunks
+    //as they go
+    Stolen(Stealing<Band<'a, T>>, Option<usize>)
+}
+
+//The buffer cut up into the pieces `schedule` hands out : optimal_bands()
+//bands, stolen by threads that run out of their own; every `threads`th
+//row one row at a time for each thread with interleaving; or a band per
+//thread that is rendered `chunk_rows` rows at a time, the rest of it left
+//for its thread to carry on with or for other threads to steal; or a band
+//per thread of about the same share of `row_costs`, equal bands if there
+//aren't costs for the window's rows. There are never more threads given
+//work than there are bands.
+fn split_work<'a, T : Send>(
+    buffer : &'a mut [T],
+    bounds : (usize,usize),
+    threads : usize,
+    schedule : Schedule,
+    chunk_rows : usize,
+    row_costs : &[f64])
+    -> Work<'a, T>
+{
+    match schedule {
+        Schedule::Bands => {
+            let count = optimal_bands(bounds, threads);
+            let bands = split_into_bands(buffer, bounds, count);
+            Work::Stolen(Stealing::new(bands, threads.clamp(1, count)), None)
+        }
+        Schedule::Interleaved => {
+            check_window(buffer.len(), bounds);
+            let count = threads.clamp(1, bounds.1.max(1));
+            let mut shares = (0..count).map(|_| Vec::new()).collect::<Vec<_>>();
+            for (row, band) in buffer.chunks_mut(bounds.0.max(1)).enumerate() {
+                shares[row % count].push((band, row));
+            }
+            Work::Shares(shares)
+        }
+        Schedule::Dynamic => {
+            let bands = split_into_bands(buffer, bounds, threads);
+            let threads = bands.len();
+            Work::Stolen(Stealing::new(bands, threads), Some(chunk_rows.max(1)))
+        }
+        Schedule::CostBalanced => {
+            let layout = match row_costs.len() == bounds.1 {
+                true => balanced_layout(row_costs, threads),
+                false => band_layout(bounds, threads)
+            };
+            Work::Shares(cut_bands(buffer, bounds, layout).into_iter()
+                         .map(|band| vec![band]).collect())
+        }
+    }
+}
 
+//What a thread does with its share, with -vv saying how long it took
+fn render_share<T, F>(share : Vec<Band<'_, T>>, bounds : (usize,usize), render_band : &F)
+    where F : Fn(&mut [T], usize)
+{
+    let started = Instant::now();
+    let rows = share.iter().map(|(band, _)| band.len() / bounds.0.max(1)).sum::<usize>();
+    let first = share.first().map_or(0, |&(_, top)| top);
+    let rows_of = match share.get(1) {
+        Some(&(_, second)) => format!("{} rows from row {} in steps of {}", rows, first,
+                                      second - first),
+        None => format!("Band of rows {} to {}", first, first + rows)
+    };
+    for (band, top) in share {
+        render_band(band, top);
     }
+    debug!("{} ({}x{}) in {:.1}ms", rows_of, bounds.0, rows,
+           started.elapsed().as_secs_f64() * 1000.0);
+}
+
+//What a thread does with stolen work, the same. A band longer than
+//`chunk_rows` has its first chunk rendered and the rest pushed back.
+fn render_stolen<T, F>(stealing : &Stealing<Band<'_, T>>, chunk_rows : Option<usize>,
+                       bounds : (usize,usize), render_band : &F)
+    where T : Send,
+          F : Fn(&mut [T], usize)
+{
+    let started = Instant::now();
+    let (mut pieces, mut rows) = (0, 0);
+    stealing.work(|(band, top), local| {
+        let (band, top) = match chunk_rows {
+            Some(chunk_rows) if band.len() > chunk_rows * bounds.0 => {
+                let (chunk, rest) = band.split_at_mut(chunk_rows * bounds.0);
+                local.push((rest, top + chunk_rows));
+                (chunk, top)
+            }
+            _ => (band, top)
+        };
+        pieces += 1;
+        rows += band.len() / bounds.0.max(1);
+        render_band(band, top);
+    });
+    debug!("{} rows in {} pieces ({}x{}) in {:.1}ms", rows, pieces, bounds.0, rows,
+           started.elapsed().as_secs_f64() * 1000.0);
+}
+
+//Which threads render_in_bands() renders on, for --version and bug reports
+#[cfg(not(feature = "rayon"))]
+pub const SCHEDULER : &str = "crossbeam";
+#[cfg(feature = "rayon")]
+pub const SCHEDULER : &str = "rayon";
+
+//Hands the work of the window out to threads of its own, which call
+//render_band() on every piece of it with the piece's top row. Works for
+//any per-pixel type so that both render() and render_raw() can share the
+//same threading code. The threads only live for the duration of this one
+//call.
+#[cfg(not(feature = "rayon"))]
+fn render_in_bands<T, F>(
+    buffer : &mut [T],
+    bounds : (usize,usize),
+    threads : usize,
+    schedule : (Schedule, usize),
+    row_costs : &[f64],
+    render_band : F)
+    where T : Send,
+          F : Fn(&mut [T], usize) + Sync
+{
+    render_in_scope(buffer, bounds, threads, schedule, row_costs, render_band)
+}
 
-    write_image(&args[1], &pixels, bounds)
-        .expect("error writing PNG file!!");
+//Built with the rayon feature, rayon shares the rows out and --schedule
+//doesn't come into it
+#[cfg(feature = "rayon")]
+fn render_in_bands<T, F>(
+    buffer : &mut [T],
+    bounds : (usize,usize),
+    threads : usize,
+    _schedule : (Schedule, usize),
+    _row_costs : &[f64],
+    render_band : F)
+    where T : Send,
+          F : Fn(&mut [T], usize) + Sync
+{
+    render_on_rayon(buffer, bounds, threads, render_band)
+}
+
+//A row at a time on a rayon pool of `threads` threads, made for this one
+//call the way render_in_scope() spawns its threads
+#[cfg(feature = "rayon")]
+fn render_on_rayon<T, F>(
+    buffer : &mut [T],
+    bounds : (usize,usize),
+    threads : usize,
+    render_band : F)
+    where T : Send,
+          F : Fn(&mut [T], usize) + Sync
+{
+    use rayon::prelude::*;
 
+    check_window(buffer.len(), bounds);
+    let started = Instant::now();
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads.max(1))
+        .build()
+        .expect("couldn't start rayon's threads");
+    pool.install(|| {
+        buffer.par_chunks_mut(bounds.0.max(1))
+            .enumerate()
+            .for_each(|(row, band)| render_band(band, row));
+    });
+    debug!("{} rows ({}x{}) on {} rayon threads in {:.1}ms", bounds.1, bounds.0, bounds.1,
+           threads.max(1), started.elapsed().as_secs_f64() * 1000.0);
+}
 
+//The threads crossbeam spawns, with `schedule` sharing the work out
+fn render_in_scope<T, F>(
+    buffer : &mut [T],
+    bounds : (usize,usize),
+    threads : usize,
+    schedule : (Schedule, usize),
+    row_costs : &[f64],
+    render_band : F)
+    where T : Send,
+          F : Fn(&mut [T], usize) + Sync
+{
+    let render_band = &render_band;
+    match split_work(buffer, bounds, threads, schedule.0, schedule.1, row_costs) {
+        Work::Shares(shares) => crossbeam::scope(|spawner| {
+            for share in shares {
+                spawner.spawn(move || {
+                    render_share(share, bounds, render_band);
+                });
+            }
+        }),
+        Work::Stolen(stealing, chunk_rows) => {
+            let stealing = &stealing;
+            crossbeam::scope(|spawner| {
+                for _ in 0..stealing.threads() {
+                    spawner.spawn(move || {
+                        render_stolen(stealing, chunk_rows, bounds, render_band);
+                    });
+                }
+            })
+        }
+    }
+}
+
+//<********Renderer: a reusable pool of threads*******>//
+//render_in_bands() spins up and tears down its threads on every call,
+//which is fine for the single render the plain CLI does. Modes that render
+//the same window many times over (chromatic channels, adaptive limits)
+//instead create one Renderer and reuse its threads for every render.
+//
+//A single threaded Renderer has no pool at all: it hands the whole window
+//to render_band() on the calling thread, which takes threading out of the
+//picture when debugging. Since a point only depends on its place in the
+//window the result is the same as with any number of threads, and with
+//any schedule. A spawning Renderer has no pool either, it stands for
+//render_in_bands() with its number of threads.
+pub struct Renderer {
+    //Pool::scoped() wants &mut, the Mutex lets render_view() take &self
+    pool : Option<Mutex<Pool>>,
+    threads : usize,
+    //The schedule and, for Dynamic, the rows in a chunk
+    schedule : (Schedule, usize),
+    //For CostBalanced, the row_costs() of the window being rendered
+    row_costs : Mutex<Vec<f64>>,
+    //escape_times() of fewer points than this stay on the calling thread
+    parallel_threshold : usize
+}
+
+//Below this many points starting the threads takes longer than iterating
+//the points
+const PARALLEL_THRESHOLD : usize = 4096;
+
+impl Renderer {
+    pub fn new(threads : usize) -> Renderer {
+        Renderer {
+            pool : Some(Mutex::new(Pool::new(threads as u32))),
+            threads,
+            schedule : (Schedule::Bands, 1),
+            row_costs : Mutex::new(Vec::new()),
+            parallel_threshold : PARALLEL_THRESHOLD
+        }
+    }
+
+    pub fn single_threaded() -> Renderer {
+        Renderer { pool : None, ..Renderer::spawning(1) }
+    }
+
+    pub fn spawning(threads : usize) -> Renderer {
+        Renderer {
+            pool : None,
+            threads,
+            schedule : (Schedule::Bands, 1),
+            row_costs : Mutex::new(Vec::new()),
+            parallel_threshold : PARALLEL_THRESHOLD
+        }
+    }
+
+    //With --schedule cost-balanced, runs the pre-pass that the renders of
+    //`view` after it are balanced by. A single thread has nothing to balance.
+    fn balance_for(&self, config : &Config, view : &View) {
+        if self.schedule.0 == Schedule::CostBalanced && self.threads > 1 {
+            *self.row_costs.lock().unwrap() = row_costs(config, view, self.threads);
+        }
+    }
+
+    pub fn with_schedule(self, schedule : Schedule, chunk_rows : usize) -> Renderer {
+        Renderer { schedule : (schedule, chunk_rows), ..self }
+    }
+
+    pub fn with_parallel_threshold(self, points : usize) -> Renderer {
+        Renderer { parallel_threshold : points, ..self }
+    }
+
+    //escape_times() on the threads, as a window a point wide and a point a
+    //row, once there are parallel_threshold points or more
+    pub fn escape_times(&self, points : &[Complex<f64>], limit : u32) -> Vec<Option<u32>> {
+        if self.threads <= 1 || points.len() < self.parallel_threshold {
+            return escape_times(points, limit);
+        }
+        let mut counts = vec![None; points.len()];
+        render_in_bands(&mut counts, (1, points.len()), self.threads, (Schedule::Bands, 1),
+                        &[], |band, top| {
+            for (i, count) in band.iter_mut().enumerate() {
+                *count = escape_time(points[top + i], limit);
+            }
+        });
+        counts
+    }
+
+    //The plain grayscale picture of the `viewport` (upper left and lower
+    //right corners) at `limit`, a byte per pixel, into a `buffer` that has
+    //to hold exactly bounds.0 * bounds.1 of them. Rendering one frame after
+    //another into the same buffer saves allocating a new one every time.
+    pub fn render_into(&self, buffer : &mut [u8], bounds : (usize,usize),
+                       viewport : (Complex<f64>, Complex<f64>), limit : u32)
+        -> Result<(), String>
+    {
+        if buffer.len() != bounds.0 * bounds.1 {
+            return Err(format!("a {}x{} render needs a buffer of {} bytes, got {}",
+                               bounds.0, bounds.1, bounds.0 * bounds.1, buffer.len()));
+        }
+        let (upper_left, lower_right) = viewport;
+        let view = View { bounds, upper_left, lower_right, rotation : None };
+        self.render_view(buffer, bounds, |band, top| {
+            render::<ComplexArithmetic>(band, top, &view, limit, config::PLAIN_GRAY);
+        });
+        Ok(())
+    }
+
+    //render_into() a buffer of its own
+    pub fn render_to_vec(&self, bounds : (usize,usize),
+                         viewport : (Complex<f64>, Complex<f64>), limit : u32)
+        -> Vec<u8>
+    {
+        let mut pixels = vec![0; bounds.0 * bounds.1];
+        self.render_into(&mut pixels, bounds, viewport, limit)
+            .expect("a buffer made to the size fits");
+        pixels
+    }
+
+    //Same contract as render_in_bands(), but the work is done on the
+    //pool's long-lived threads.
+    pub fn render_view<T, F>(
+        &self,
+        buffer : &mut [T],
+        bounds : (usize,usize),
+        render_band : F)
+        where T : Send,
+              F : Fn(&mut [T], usize) + Sync
+    {
+        let render_band = counted(bounds, render_band);
+        let pool = match self.pool {
+            Some(ref pool) => pool,
+            None if self.threads > 1 =>
+                return render_in_bands(buffer, bounds, self.threads, self.schedule,
+                                       &self.row_costs.lock().unwrap(), render_band),
+            None => {
+                check_window(buffer.len(), bounds);
+                return render_share(vec![(buffer, 0)], bounds, &render_band);
+            }
+        };
+        let row_costs = self.row_costs.lock().unwrap();
+        let work = split_work(buffer, bounds, self.threads, self.schedule.0,
+                              self.schedule.1, &row_costs);
+        let render_band = &render_band;
+        let mut pool = pool.lock().unwrap();
+        match work {
+            Work::Shares(shares) => pool.scoped(|scope| {
+                for share in shares {
+                    scope.execute(move || {
+                        render_share(share, bounds, render_band);
+                    });
+                }
+            }),
+            Work::Stolen(stealing, chunk_rows) => {
+                let stealing = &stealing;
+                pool.scoped(|scope| {
+                    for _ in 0..stealing.threads() {
+                        scope.execute(move || {
+                            render_stolen(stealing, chunk_rows, bounds, render_band);
+                        });
+                    }
+                })
+            }
+        }
+    }
+}
+
+//Renders the way the renderer does if there is one, otherwise in bands on
+//a thread per CPU spawned just for this render
+fn render_bands<T, F>(
+    renderer : Option<&Renderer>,
+    buffer : &mut [T],
+    bounds : (usize,usize),
+    render_band : F)
+    where T : Send,
+          F : Fn(&mut [T], usize) + Sync
+{
+    match renderer {
+        Some(renderer) => renderer.render_view(buffer, bounds, render_band),
+        None => render_in_bands(buffer, bounds, num_cpus::get(), (Schedule::Bands, 1), &[],
+                                counted(bounds, render_band))
+    }
+}
+
+//render_band() a row at a time, each row counted for the progress reports
+//as it is finished, and the band timed for the statistics
+fn counted<T, F>(bounds : (usize,usize), render_band : F) -> impl Fn(&mut [T], usize) + Sync
+    where F : Fn(&mut [T], usize) + Sync
+{
+    move |band, top| {
+        let started = stats::band_started();
+        let rows = band.len() / bounds.0.max(1);
+        for (i, row) in band.chunks_mut(bounds.0.max(1)).enumerate() {
+            render_band(row, top + i);
+            progress::rows_done(1);
+        }
+        stats::band_done(started, top, rows);
+        stats::band_iterated();
+    }
+}
+
+//How many rows rendering `view` the way `config` has it goes through,
+//every pass over the window counted, for the progress reports. None when
+//that depends on what the renders find, as with an adaptive limit.
+fn rows_to_render(config : &Config, view : &View) -> Option<usize> {
+    let rows = strided_view(view, config.preview_stride).bounds.1;
+    let passes = if config.adaptive.is_some() {
+        return None;
+    } else if config.diff_limits.is_some() {
+        2
+    } else if config.chromatic.is_some() {
+        3
+    } else {
+        1
+    };
+    Some(rows * passes)
+}
+
+//<********Rendering against the clock*********>//
+//With --time-budget a band is rendered a row at a time, and once the
+//deadline has passed the remaining rows are skipped. Every buffer starts
+//out as black (or as interior counts, which colour black), so whatever
+//wasn't reached in time stays black. Each band fills in from its top, so a
+//picture that is cut short has a black strip at the bottom of every band
+//(or of the whole picture, with interleaved rows).
+//
+//A render that can be cancelled, or interrupted with Ctrl-C, is checked the
+//same way, row by row.
+//
+//A row can take any time at all with a limit in the billions, so the
+//iteration loops look too, every BUDGET_CHECK_EVERY iterations of a point
+//(see in_stretches()). A point given up on stays interior, and its row
+//counts as not reached.
+struct Budget {
+    stop : Stop,
+    rows_done : AtomicUsize,
+    //The rows of the window that were skipped, in any of the passes
+    unreached : Vec<AtomicBool>
+}
+
+//What a budget runs out on, which the threads rendering under it keep a
+//copy of for their iteration loops to look at
+#[derive(Clone)]
+struct Stop {
+    deadline : Option<Instant>,
+    cancel : Option<Arc<AtomicBool>>
+}
+
+impl Stop {
+    fn cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+    }
+
+    fn now(&self) -> bool {
+        self.deadline.is_some_and(|deadline| Instant::now() >= deadline) || self.cancelled()
+            || exit::renders_interrupted()
+    }
+}
+
+//A point still iterating looks at the budget this often, tens of thousands
+//of times a second in f64 and several times a second in BigInts
+const BUDGET_CHECK_EVERY : u32 = 1 << 14;
+
+thread_local! {
+    //The stop of the budget this thread is rendering under, if any
+    static WATCHED : RefCell<Option<Stop>> = const { RefCell::new(None) };
+    //Whether a point has been given up on since gave_up() last said
+    static GAVE_UP : Cell<bool> = const { Cell::new(false) };
+}
+
+//Runs an iteration loop over `iterations` a stretch of BUDGET_CHECK_EVERY
+//of them at a time, and gives up once the budget this thread renders under
+//has run out. `stretch` takes what the loop keeps from one iteration to the
+//next (z, for one) and hands it back to go on, or breaks off with what the
+//loop came to. Continue, with that state, if it went to the end of
+//`iterations` or gave up.
+#[inline(always)]
+fn in_stretches<S, T>(iterations : Range<u32>, state : S,
+                      stretch : impl Fn(S, Range<u32>) -> ControlFlow<T, S> + Copy)
+    -> ControlFlow<T, S>
+{
+    let (mut from, limit, mut state) = (iterations.start, iterations.end, state);
+    while from < limit {
+        let to = limit.min(from.saturating_add(BUDGET_CHECK_EVERY));
+        state = one_stretch(stretch, state, from..to)?;
+        if watched_run_out() {
+            break;
+        }
+        from = to;
+    }
+    Continue(state)
+}
+
+//Apart, and with `stretch` and `state` handed over by value, so that the
+//loop in `stretch` is compiled as the same tight loop it would be without a
+//budget. Inlined into the loop over the stretches, or reading c through a
+//reference, the f64 loops come out a fifth slower, LLVM packing z and c
+//into SIMD registers.
+#[inline(never)]
+fn one_stretch<S, T>(stretch : impl Fn(S, Range<u32>) -> ControlFlow<T, S>, state : S,
+                     iterations : Range<u32>)
+    -> ControlFlow<T, S>
+{
+    stretch(state, iterations)
+}
+
+//Whether the budget this thread renders under has run out, noting that a
+//point was given up on if it has
+#[cold]
+#[inline(never)]
+fn watched_run_out() -> bool {
+    let run_out = WATCHED.with(|watched| watched.borrow().as_ref().is_some_and(Stop::now));
+    if run_out {
+        GAVE_UP.with(|gave_up| gave_up.set(true));
+    }
+    run_out
+}
+
+//Runs `f` with the iteration loops on this thread giving up once `stop`
+//does
+fn watching<R>(stop : &Stop, f : impl FnOnce() -> R) -> R {
+    let outer = WATCHED.with(|watched| watched.replace(Some(stop.clone())));
+    let result = f();
+    WATCHED.with(|watched| *watched.borrow_mut() = outer);
+    result
+}
+
+//Whether a point on this thread has been given up on since the last time
+//this was asked
+fn gave_up() -> bool {
+    GAVE_UP.with(|gave_up| gave_up.replace(false))
+}
+
+impl Budget {
+    //None when a render of `config`, of `rows` rows, neither has a time
+    //budget nor can be cancelled or interrupted
+    fn new(config : &Config, rows : usize) -> Option<Budget> {
+        if config.time_budget.is_none() && config.cancel.is_none()
+            && !exit::stopping_renders()
+        {
+            return None;
+        }
+        Some(Budget {
+            stop : Stop {
+                deadline : config.time_budget.map(|duration| Instant::now() + duration),
+                cancel : config.cancel.clone()
+            },
+            rows_done : AtomicUsize::new(0),
+            unreached : (0..rows).map(|_| AtomicBool::new(false)).collect()
+        })
+    }
+
+    fn rows_done(&self) -> usize {
+        self.rows_done.load(Ordering::Relaxed)
+    }
+
+    fn cancelled(&self) -> bool {
+        self.stop.cancelled()
+    }
+
+    fn run_out(&self) -> bool {
+        self.stop.now()
+    }
+
+    //The fraction of the rows every pass got to
+    fn reached(&self) -> f64 {
+        let unreached = self.unreached.iter()
+            .filter(|row| row.load(Ordering::Relaxed))
+            .count();
+        1.0 - unreached as f64 / self.unreached.len().max(1) as f64
+    }
+}
+
+//render_band() as is without a budget, row by row against the deadline
+//(or the cancel flag) with one
+fn within_budget<'a, T, F>(
+    budget : Option<&'a Budget>,
+    bounds : (usize,usize),
+    render_band : F)
+    -> impl Fn(&mut [T], usize) + Sync + 'a
+    where F : Fn(&mut [T], usize) + Sync + 'a
+{
+    move |band, top| match budget {
+        None => render_band(band, top),
+        Some(budget) => watching(&budget.stop, || {
+            let rows = band.len() / bounds.0.max(1);
+            gave_up();
+            for (i, row) in band.chunks_mut(bounds.0).enumerate() {
+                if budget.run_out() {
+                    for skipped in &budget.unreached[top + i..top + rows] {
+                        skipped.store(true, Ordering::Relaxed);
+                    }
+                    break;
+                }
+                render_band(row, top + i);
+                //A row with a point given up on part way is as good as
+                //not reached, and the budget has run out for the rest
+                if gave_up() {
+                    for skipped in &budget.unreached[top + i..top + rows] {
+                        skipped.store(true, Ordering::Relaxed);
+                    }
+                    break;
+                }
+                budget.rows_done.fetch_add(1, Ordering::Relaxed);
+            }
+        })
+    }
+}
+
+//render_bands() under an optional budget
+fn render_bands_within<T, F>(
+    renderer : Option<&Renderer>,
+    budget : Option<&Budget>,
+    buffer : &mut [T],
+    bounds : (usize,usize),
+    render_band : F)
+    where T : Send,
+          F : Fn(&mut [T], usize) + Sync
+{
+    render_bands(renderer, buffer, bounds, within_budget(budget, bounds, render_band));
+}
+
+//<********Chromatic aberration****************>//
+//Renders the escape field three times, once per colour channel, with the
+//iteration limit of each channel pulled down by a further `offset`
+//(red at limit, green at limit - offset, blue at limit - 2*offset).
+//Points near the boundary escape "late" in one channel and are counted as
+//interior in another, so the edges of the set pick up coloured fringes.
+//
+//Since it is three full renders this costs roughly 3x the time of the
+//plain grayscale plot, which is why it is off unless --chromatic is given.
+fn render_chromatic<E : Arithmetic>(
+    renderer : Option<&Renderer>,
+    budget : Option<&Budget>,
+    pixels : &mut [u8],
+    view : &View,
+    limit : u32,
+    offset : u32,
+    gray : Gray)
+{
+    let bounds = view.bounds;
+    assert!(pixels.len() == bounds.0 * bounds.1 * 3,
+            "RGB pixel buffer {} != {}x{}x3={}", pixels.len(), bounds.0, bounds.1,
+            bounds.0 * bounds.1 * 3);
+    assert!(offset.checked_mul(2).is_some_and(|twice| twice < limit),
+            "chromatic offset {} is too large for an iteration limit of {}",
+            offset, limit);
+
+    let mut counts = vec![None; bounds.0 * bounds.1];
+    for channel in 0..3 {
+        let channel_limit = limit - offset * channel as u32;
+        //Rows a stopped render doesn't get to stay black in every channel
+        //after the first, not the counts of the channel before
+        counts.fill(None);
+        render_bands_within(renderer, budget, &mut counts, bounds, |band, top| {
+            render_raw::<E>(band, top, view, channel_limit);
+        });
+        //Pack this channel into every third byte of the RGB buffer
+        for (pixel, count) in pixels.chunks_mut(3).zip(&counts) {
+            pixel[channel] = gray_value(*count, channel_limit, gray);
+        }
+    }
+}
+
+//<********Rendering a whole image*************>//
+//A finished picture, ready to be encoded
+pub struct Image {
+    pub pixels : Vec<u8>,
+    pub bounds : (usize, usize),
+    pub color : ColorType
+}
+
+//Renders `view` the way `config` asks for, picking the right render
+//function for the colouring mode
+fn render_image(config : &Config, view : &View, renderer : Option<&Renderer>)
+    -> Image
+{
+    render_image_or_partial(config, view, renderer).0
+}
+
+//render_image(), and once exit::interrupt_renders() has been called the
+//fraction of the rows it got to before a Ctrl-C stopped it, None if it
+//wasn't stopped. The rows it didn't get to are filled with fill_unreached().
+fn render_image_or_partial(config : &Config, view : &View, renderer : Option<&Renderer>)
+    -> (Image, Option<f64>)
+{
+    let rendered = strided_view(view, config.preview_stride);
+    let bounds = rendered.bounds;
+    if let Some(renderer) = renderer {
+        renderer.balance_for(config, &rendered);
+    }
+    //The budget starts now, so it takes in finding an adaptive limit
+    let budget = Budget::new(config, bounds.1);
+    let (started, colorized) = (Instant::now(), stats::spent("colorize"));
+    let budget_ref = budget.as_ref();
+    //(config::build() has settled --precision auto)
+    let render = || match config.precision {
+        Precision::F64 | Precision::Auto { .. } =>
+            render_image_in::<f64>(config, &rendered, renderer, budget_ref),
+        Precision::F32 => render_image_in::<f32>(config, &rendered, renderer, budget_ref),
+        Precision::DoubleDouble =>
+            render_image_in::<Double>(config, &rendered, renderer, budget_ref),
+        //The view is offsets in f64 from config.origin, see big.rs
+        Precision::Fixed128 | Precision::Big(_) =>
+            render_image_in::<f64>(config, &rendered, renderer, budget_ref)
+    };
+    //This thread iterates too, the reference orbits among other things
+    let mut image = match budget_ref {
+        Some(budget) => watching(&budget.stop, render),
+        None => render()
+    };
+    //All of it but the colouring
+    stats::add("iterate",
+               started.elapsed().saturating_sub(stats::spent("colorize") - colorized));
+    let mut reached = None;
+    if let Some(ref budget) = budget {
+        if exit::renders_interrupted() {
+            fill_unreached(&mut image, &budget.unreached);
+            reached = Some(budget.reached());
+        } else if budget.rows_done() < bounds.1 && !budget.cancelled() {
+            warning!("the time budget ran out after {} of {} rows, \
+                      the rest of the image is left black", budget.rows_done(), bounds.1);
+        }
+    }
+    if config.preview_stride > 1 {
+        return (rows_repeated(image, config.preview_stride, view.bounds.1), reached);
+    }
+    (image, reached)
+}
+
+//The rows an interrupted render didn't get to are a checkerboard of
+//PARTIAL_SQUARE pixel squares in the two grays of PARTIAL_FILL, which
+//nothing rendered looks like
+const PARTIAL_FILL : (u8, u8) = (0x40, 0xc0);
+const PARTIAL_SQUARE : usize = 8;
+
+fn fill_unreached(image : &mut Image, unreached : &[AtomicBool]) {
+    let channels = match image.color {
+        ColorType::Gray(8) => 1,
+        _ => 3
+    };
+    let width = image.bounds.0;
+    for (y, row) in image.pixels.chunks_mut((width * channels).max(1)).enumerate() {
+        if !unreached[y].load(Ordering::Relaxed) {
+            continue;
+        }
+        for (x, pixel) in row.chunks_mut(channels).enumerate() {
+            let light = (x / PARTIAL_SQUARE + y / PARTIAL_SQUARE) % 2 == 1;
+            pixel.fill(if light { PARTIAL_FILL.1 } else { PARTIAL_FILL.0 });
+        }
+    }
+}
+
+//<********--preview-stride*******************>//
+//A quick look at a view before rendering it properly : only every
+//`stride`th row is rendered, and each is repeated down over the rows it
+//stands for, which cuts the work by the stride and keeps the size of the
+//image. Row r of the strided view sits where row r * stride of the full
+//one does, so the preview lines up with the real thing, though the points
+//aren't worked out bit for bit the same way.
+fn strided_view(view : &View, stride : usize) -> View {
+    if stride <= 1 {
+        return *view;
+    }
+    let rows = view.bounds.1.div_ceil(stride);
+    let height = view.upper_left.im - view.lower_right.im;
+    View {
+        bounds : (view.bounds.0, rows),
+        upper_left : view.upper_left,
+        lower_right : Complex {
+            re : view.lower_right.re,
+            im : view.upper_left.im - height * (rows * stride) as f64 / view.bounds.1 as f64
+        },
+        ..*view
+    }
+}
+
+//Every row of `image` `stride` times over, cut off at `height` rows
+fn rows_repeated(image : Image, stride : usize, height : usize) -> Image {
+    let row = image.bounds.0 * match image.color {
+        ColorType::Gray(8) => 1,
+        _ => 3
+    };
+    let pixels = image.pixels.chunks(row.max(1))
+        .flat_map(|pixels| std::iter::repeat_n(pixels, stride))
+        .take(height)
+        .flatten()
+        .copied()
+        .collect();
+    Image { pixels, bounds : (image.bounds.0, height), color : image.color }
+}
+
+//render_image_within() in F, with the arithmetic `config` asks for
+fn render_image_in<F : Real>(config : &Config, view : &View, renderer : Option<&Renderer>,
+                             budget : Option<&Budget>)
+    -> Image
+{
+    match (config.simd, config.fast_math, config.count_iterations) {
+        (true, _, false) =>
+            render_image_within::<SimdArithmetic<F>>(config, view, renderer, budget),
+        (false, true, false) =>
+            render_image_within::<ScalarArithmetic<F>>(config, view, renderer, budget),
+        (false, false, false) =>
+            render_image_within::<ComplexArithmetic<F>>(config, view, renderer, budget),
+        (true, _, true) =>
+            render_image_within::<Counted<SimdArithmetic<F>>>(config, view, renderer, budget),
+        (false, true, true) => render_image_within::<Counted<ScalarArithmetic<F>>>(
+            config, view, renderer, budget),
+        (false, false, true) => render_image_within::<Counted<ComplexArithmetic<F>>>(
+            config, view, renderer, budget)
+    }
+}
+
+fn render_image_within<E : Arithmetic>(config : &Config, view : &View,
+                                       renderer : Option<&Renderer>,
+                                       budget : Option<&Budget>)
+    -> Image
+{
+    let bounds = view.bounds;
+    let limit = match config.adaptive {
+        None => config.limit,
+        Some((threshold, max_limit)) => {
+            let limit = adaptive_limit::<E>(renderer, view, threshold, max_limit);
+            info!("Using an iteration limit of {}", limit);
+            limit
+        }
+    };
+
+    if let Some(c) = config.julia {
+        let mut counts = vec![None;bounds.0 * bounds.1];
+        render_bands_within(renderer, budget, &mut counts, bounds, |band, top| {
+            render_julia(band, top, view, c, limit);
+        });
+        return colour_counts(config, &counts, bounds, limit);
+    }
+
+    if let Some(ref origin) = config.origin {
+        let counts = deep_counts(config, renderer, budget, view, origin, limit);
+        return colour_counts(config, &counts, bounds, limit);
+    }
+
+    if config.mode == Mode::Perturbation {
+        let (counts, glitches) = perturbation::counts(renderer, budget, view, limit,
+                                                         config.orbits.as_deref());
+        let image = colour_counts(config, &counts, bounds, limit);
+        if !config.show_glitches {
+            return image;
+        }
+        info!("{} of {} pixels glitched against the first reference orbit, {} passes of \
+               new references fixed {} of them", glitches.first.len(), counts.len(),
+              glitches.passes, glitches.fixed);
+        return marked(image, &glitches.first, GLITCH_MARKER);
+    }
+
+    if let Some((low, high)) = config.diff_limits {
+        let mut counts = [vec![None;bounds.0 * bounds.1], vec![None;bounds.0 * bounds.1]];
+        for (counts, limit) in counts.iter_mut().zip([low, high]) {
+            render_bands_within(renderer, budget, counts, bounds, |band, top| {
+                render_raw::<E>(band, top, view, limit);
+            });
+        }
+        let pixels = stats::time("colorize", || {
+            limit_difference(&counts[0], &counts[1], (low, high), config.gray)
+        });
+        return Image { pixels, bounds, color : ColorType::RGB(8) };
+    }
+
+    if config.mode == Mode::Angle {
+        let mut pixels = vec![[0, 0, 0];bounds.0 * bounds.1];
+        render_bands_within(renderer, budget, &mut pixels, bounds, |band, top| {
+            render_angle(band, top, view, limit);
+        });
+        return Image { pixels : pixels.concat(), bounds, color : ColorType::RGB(8) };
+    }
+
+    if let Some(blend) = config.blend {
+        let mut pixels = vec![[0, 0, 0];bounds.0 * bounds.1];
+        render_bands_within(renderer, budget, &mut pixels, bounds, |band, top| {
+            blend::render(band, top, view, limit, blend, config.palette.as_ref());
+        });
+        return Image { pixels : pixels.concat(), bounds, color : ColorType::RGB(8) };
+    }
+
+    if config.mode == Mode::Bands {
+        let mut counts = vec![None;bounds.0 * bounds.1];
+        render_bands_within(renderer, budget, &mut counts, bounds, |band, top| {
+            render_raw::<E>(band, top, view, limit);
+        });
+        return colour_counts(config, &counts, bounds, limit);
+    }
+
+    if let Some(ref palette) = config.palette {
+        let mut counts = vec![None;bounds.0 * bounds.1];
+        render_bands_within(renderer, budget, &mut counts, bounds, |band, top| {
+            render_raw::<E>(band, top, view, limit);
+        });
+        let mut pixels = vec![[0, 0, 0];bounds.0 * bounds.1];
+        stats::time("colorize", || colorize(&mut pixels, &counts, palette));
+        return Image { pixels : pixels.concat(), bounds, color : ColorType::RGB(8) };
+    }
+
+    if let Some(offset) = config.chromatic {
+        //Three bytes (R, G, B) per pixel this time
+        let mut pixels = vec![0;bounds.0 * bounds.1 * 3];
+        render_chromatic::<E>(renderer, budget, &mut pixels, view, limit, offset,
+                              config.gray);
+        return Image { pixels, bounds, color : ColorType::RGB(8) };
+    }
+
+    //The exposure depends on every count, so they are all needed first
+    if config.gray.exposure.is_some() {
+        let mut counts = vec![None;bounds.0 * bounds.1];
+        render_bands_within(renderer, budget, &mut counts, bounds, |band, top| {
+            render_raw::<E>(band, top, view, limit);
+        });
+        return colour_counts(config, &counts, bounds, limit);
+    }
+
+    //The statement below equates all the pixel values
+    //in the image widow to zero
+    let mut pixels = vec![0;bounds.0 * bounds.1];
+    match config.limit_map {
+        Some((center_limit, edge_limit)) => {
+            render_bands_within(renderer, budget, &mut pixels, bounds, |band, top| {
+                render_with_limits::<E, _>(band, top, view, |pixel| {
+                    radial_limit(pixel_to_point(view, pixel), view, center_limit, edge_limit)
+                }, config.gray);
+            });
+        }
+        None => {
+            render_bands_within(renderer, budget, &mut pixels, bounds, |band, top| {
+                render::<E>(band, top, view, limit, config.gray);
+            });
+        }
+    }
+    Image { pixels, bounds, color : ColorType::Gray(8) }
+}
+
+//Escape counts rendered some other way than the plain Mandelbrot
+//iteration, coloured with the palette or in gray
+fn colour_counts(config : &Config, counts : &[Option<u32>], bounds : (usize, usize),
+                 limit : u32)
+    -> Image
+{
+    stats::time("colorize", || colour_counts_now(config, counts, bounds, limit))
+}
+
+fn colour_counts_now(config : &Config, counts : &[Option<u32>], bounds : (usize, usize),
+                     limit : u32)
+    -> Image
+{
+    if config.mode == Mode::Bands {
+        return banded(counts, bounds, config.band_width, config.palette.as_ref());
+    }
+    if let Some(ref palette) = config.palette {
+        let mut pixels = vec![[0, 0, 0];bounds.0 * bounds.1];
+        colorize(&mut pixels, counts, palette);
+        return Image { pixels : pixels.concat(), bounds, color : ColorType::RGB(8) };
+    }
+    let pixels = match config.gray.exposure {
+        Some(clip) => {
+            let range = exposure_range(counts, clip);
+            verbose!("Exposed between counts {} and {}", range.0, range.1);
+            counts.iter().map(|&count| exposed_value(count, range, config.gray)).collect()
+        }
+        None => counts.iter().map(|&count| gray_value(count, limit, config.gray)).collect()
+    };
+    Image { pixels, bounds, color : ColorType::Gray(8) }
+}
+
+//<********Comparing two iteration limits******>//
+//For --diff-limits : what iterating up to `high` instead of `low` changes.
+//A point that escapes within `low` iterations escapes at the same count
+//either way, so the only pixels that change are those that looked interior
+//at `low` but escape before `high`. They are painted from yellow (escaping
+//just past `low`) to red (escaping just before `high`), over the rest of
+//the picture in a dimmed gray, points interior at both limits staying black.
+fn limit_difference(low_counts : &[Option<u32>], high_counts : &[Option<u32>],
+                    limits : (u32, u32), gray : Gray)
+    -> Vec<u8>
+{
+    let (low, high) = limits;
+    let mut changed = 0;
+    let pixels = low_counts.iter().zip(high_counts)
+        .flat_map(|(&before, &after)| match (before, after) {
+            (None, Some(count)) => {
+                changed += 1;
+                let t = (count - low) as f64 / (high - low) as f64;
+                [255 - (55.0 * t) as u8, (220.0 * (1.0 - t)) as u8, 0]
+            }
+            _ => [gray_value(before, low, gray) / 2; 3]
+        })
+        .collect();
+    info!("{} of {} pixels escape only with the limit of {}", changed,
+          low_counts.len(), high);
+    pixels
+}
+
+//Bright magenta, which no palette or gray level comes out as
+const GLITCH_MARKER : [u8; 3] = [255, 0, 255];
+
+//`image` with the pixels at `indices` painted `color`, in RGB whatever it
+//was before
+fn marked(image : Image, indices : &[usize], color : [u8; 3]) -> Image {
+    let mut pixels = match image.color {
+        ColorType::Gray(8) => image.pixels.iter().flat_map(|&gray| [gray; 3]).collect(),
+        _ => image.pixels
+    };
+    for &index in indices {
+        pixels[index * 3..index * 3 + 3].copy_from_slice(&color);
+    }
+    Image { pixels, bounds : image.bounds, color : ColorType::RGB(8) }
+}
+
+//Roughly how many bytes the buffers render_image() allocates for `config`
+//take up at their peak, the PNG encoder's own buffers not included
+fn estimated_memory(config : &Config) -> usize {
+    let pixels = config.view.bounds.0 * config.view.bounds.1;
+    let count = std::mem::size_of::<Option<u32>>();
+    let image = if config.diff_limits.is_some() {
+        //Both renders' counts, then the pixels
+        pixels * (count * 2 + 3)
+    } else if config.mode == Mode::Angle || config.blend.is_some() {
+        //The [u8; 3] pixels and their concatenation
+        pixels * 6
+    } else if config.mode == Mode::Perturbation {
+        //Each pixel's outcome, then its count, then its colour
+        pixels * (count * 2 + if config.palette.is_some() { 6 } else { 1 })
+    } else if config.mode == Mode::Bands {
+        //The counts, then the colours
+        pixels * (count + if config.palette.is_some() { 3 } else { 1 })
+    } else if config.palette.is_some() {
+        pixels * (count + 6)
+    } else if config.chromatic.is_some() {
+        pixels * (count + 3)
+    } else if config.gray.exposure.is_some() {
+        //The counts, then the grays
+        pixels * (count + 1)
+    } else {
+        pixels
+    };
+    //--perturbation keeps the reference orbit around as well
+    let orbit = match config.perturbation {
+        true => (config.limit as usize + 1) * std::mem::size_of::<Complex<f64>>(),
+        false => 0
+    };
+    //Finding an adaptive limit keeps two renders' counts around
+    match config.adaptive {
+        Some(_) => image.max(pixels * count * 2),
+        None => image + orbit
+    }
+}
+
+//The raw escape counts of the view `config` is for, rendered with the
+//arithmetic it asks for
+fn render_counts(config : &Config, renderer : Option<&Renderer>) -> Vec<Option<u32>> {
+    if config.mode == Mode::Perturbation {
+        return perturbation::counts(renderer, None, &config.view, config.limit,
+                                   config.orbits.as_deref()).0;
+    }
+    if let Some(ref origin) = config.origin {
+        return deep_counts(config, renderer, None, &config.view, origin, config.limit);
+    }
+    if let Some(renderer) = renderer {
+        renderer.balance_for(config, &config.view);
+    }
+    window_counts(config, renderer, ((0, 0), config.view.bounds))
+}
+
+//The plain escape counts of a `window` of config.view's grid, as in
+//render_raw_window(), which may reach past the edges of the view
+fn window_counts(config : &Config, renderer : Option<&Renderer>,
+                 window : ((usize, usize), (usize, usize)))
+    -> Vec<Option<u32>>
+{
+    type Window = ((usize, usize), (usize, usize));
+    fn counts<E : Arithmetic>(config : &Config, renderer : Option<&Renderer>, window : Window)
+        -> Vec<Option<u32>>
+    {
+        let (view, bounds, limit) = (&config.view, window.1, config.limit);
+        let mut counts = vec![None;bounds.0 * bounds.1];
+        render_bands(renderer, &mut counts, bounds, |band, top| {
+            render_raw_window::<E>(band, top, view, window, limit);
+        });
+        counts
+    }
+    fn counts_in<F : Real>(config : &Config, renderer : Option<&Renderer>, window : Window)
+        -> Vec<Option<u32>>
+    {
+        match (config.simd, config.fast_math, config.count_iterations) {
+            (true, _, false) => counts::<SimdArithmetic<F>>(config, renderer, window),
+            (false, true, false) => counts::<ScalarArithmetic<F>>(config, renderer, window),
+            (false, false, false) => counts::<ComplexArithmetic<F>>(config, renderer, window),
+            (true, _, true) => counts::<Counted<SimdArithmetic<F>>>(config, renderer, window),
+            (false, true, true) =>
+                counts::<Counted<ScalarArithmetic<F>>>(config, renderer, window),
+            (false, false, true) =>
+                counts::<Counted<ComplexArithmetic<F>>>(config, renderer, window)
+        }
+    }
+    match config.precision {
+        Precision::F64 | Precision::Auto { .. } => counts_in::<f64>(config, renderer, window),
+        Precision::F32 => counts_in::<f32>(config, renderer, window),
+        Precision::DoubleDouble => counts_in::<Double>(config, renderer, window),
+        Precision::Fixed128 | Precision::Big(_) => counts_in::<f64>(config, renderer, window)
+    }
+}
+
+//<********Panning by whole pixels*******************>//
+//The frames of an animation that pans by whole pixels without zooming are
+//windows of one canvas, a grid big enough for all of them : the pixels a
+//frame has in common with the one before are the very same points of the
+//grid, so their counts are moved along instead of rendered again, and only
+//the strips the frame moves into are rendered. What comes out is bit for
+//bit what rendering the window afresh gives.
+
+//The canvas of `frames` windows the size of `view`, each `step` (columns,
+//rows) right and down from the one before, the grid being that of `view`
+//and the first window where it is. Also the (column, row) of the first
+//window in the canvas.
+fn pan_canvas(view : &View, step : (isize, isize), frames : usize)
+    -> (View, (usize, usize))
+{
+    let travel = |step : isize| step.unsigned_abs() * frames.saturating_sub(1);
+    let first = (if step.0 < 0 { travel(step.0) } else { 0 },
+                 if step.1 < 0 { travel(step.1) } else { 0 });
+    let bounds = (view.bounds.0 + travel(step.0), view.bounds.1 + travel(step.1));
+    let pixel = Complex {
+        re : view.width() / view.bounds.0 as f64,
+        im : (view.upper_left.im - view.lower_right.im) / view.bounds.1 as f64
+    };
+    let upper_left = Complex { re : view.upper_left.re - first.0 as f64 * pixel.re,
+                               im : view.upper_left.im + first.1 as f64 * pixel.im };
+    let lower_right = Complex { re : upper_left.re + bounds.0 as f64 * pixel.re,
+                                im : upper_left.im - bounds.1 as f64 * pixel.im };
+    (View { bounds, upper_left, lower_right, rotation : None }, first)
+}
+
+//Whether the image `config` renders is its plain escape counts coloured
+//one by one, each depending on its own point and nothing else, which a
+//count moved along with its point then still is
+fn counts_move_along(config : &Config) -> bool {
+    matches!(config.mode, Mode::Gray | Mode::Bands) && config.julia.is_none()
+        && config.origin.is_none() && config.view.rotation.is_none()
+        && config.limit_map.is_none() && config.diff_limits.is_none()
+        && config.adaptive.is_none() && config.chromatic.is_none() && config.blend.is_none()
+        && config.time_budget.is_none() && config.preview_stride == 1
+        && !config.count_iterations && !config.heatmap && !config.cost_map
+}
+
+//The counts of `window` of config.view's grid, `previous` being those of
+//the window `step` pixels back from it : the pixels the two have in
+//common are moved along, the rows and columns `window` moves into are
+//rendered. With nothing in common it is rendered whole.
+fn moved_counts(config : &Config, renderer : Option<&Renderer>, previous : &[Option<u32>],
+                window : ((usize, usize), (usize, usize)), step : (isize, isize))
+    -> Vec<Option<u32>>
+{
+    let ((left, top), (columns, rows)) = window;
+    let moved = (step.0.unsigned_abs(), step.1.unsigned_abs());
+    if moved.0 >= columns || moved.1 >= rows {
+        return window_counts(config, renderer, window);
+    }
+    let kept = (columns - moved.0, rows - moved.1);
+    //Pixel (x, y) of the window is pixel (x + step.0, y + step.1) of the
+    //one before, the pixels kept run from `to` in the window and from
+    //`from` in the one before
+    let from = (if step.0 > 0 { moved.0 } else { 0 }, if step.1 > 0 { moved.1 } else { 0 });
+    let to = (if step.0 < 0 { moved.0 } else { 0 }, if step.1 < 0 { moved.1 } else { 0 });
+    let mut counts = vec![None; columns * rows];
+    for row in 0..kept.1 {
+        let (at, was) = ((to.1 + row) * columns + to.0, (from.1 + row) * columns + from.0);
+        counts[at..at + kept.0].copy_from_slice(&previous[was..was + kept.0]);
+    }
+    //The rows moved into right across, then the columns moved into beside
+    //the rows kept
+    if moved.1 > 0 {
+        let row = if step.1 > 0 { kept.1 } else { 0 };
+        let strip = window_counts(config, renderer, ((left, top + row), (columns, moved.1)));
+        counts[row * columns..(row + moved.1) * columns].copy_from_slice(&strip);
+    }
+    if moved.0 > 0 {
+        let column = if step.0 > 0 { kept.0 } else { 0 };
+        let strip = window_counts(config, renderer,
+                                  ((left + column, top + to.1), (moved.0, kept.1)));
+        for (row, counted) in strip.chunks(moved.0).enumerate() {
+            let at = (to.1 + row) * columns + column;
+            counts[at..at + moved.0].copy_from_slice(counted);
+        }
+    } else {
+        //The rows moved along whole are done as far as the progress
+        //reports go
+        progress::rows_done(kept.1);
+    }
+    counts
+}
+
+//The counts of a view laid out around `origin`, in the fixed point of
+//config.precision, or against its orbit under --perturbation
+fn deep_counts(config : &Config, renderer : Option<&Renderer>, budget : Option<&Budget>,
+               view : &View, origin : &big::Point, limit : u32)
+    -> Vec<Option<u32>>
+{
+    if config.perturbation {
+        return perturbation::deep_counts(renderer, budget, view, origin, limit,
+                                         config.orbits.as_deref());
+    }
+    match config.precision {
+        Precision::Fixed128 => fixed::counts(renderer, budget, view, origin, limit),
+        _ => big::counts(renderer, budget, view, origin, limit)
+    }
+}
+
+//<********Estimating the render time*********>//
+//Every ESTIMATE_STRIDE-th pixel across and down is rendered, on the same
+//threads and with the same settings as the real thing, and the time it
+//took is scaled up by how many more pixels the full view has
+const ESTIMATE_STRIDE : usize = 16;
+
+//Roughly how many seconds rendering each of `views` would take, on average
+fn estimate_seconds(config : &Config, views : &[View], renderer : Option<&Renderer>)
+    -> f64
+{
+    let sample_config = Config { time_budget : None, count_iterations : false,
+                                 ..config.clone() };
+    let total : f64 = views.iter()
+        .map(|view| {
+            let bounds = view.bounds;
+            let sample = View {
+                bounds : (bounds.0.div_ceil(ESTIMATE_STRIDE),
+                          bounds.1.div_ceil(ESTIMATE_STRIDE)),
+                ..*view
+            };
+            let start = Instant::now();
+            render_image(&sample_config, &sample, renderer);
+            let scale = (bounds.0 * bounds.1) as f64
+                / (sample.bounds.0 * sample.bounds.1) as f64;
+            start.elapsed().as_secs_f64() * scale
+        })
+        .sum();
+    total / views.len() as f64
+}
+
+//Whether rendering `config` takes more than one pass over the window,
+//in which case it pays to keep a Renderer's threads around
+fn renders_repeatedly(config : &Config) -> bool {
+    config.adaptive.is_some() || config.chromatic.is_some()
+}
+
+//<********--png-compression****************>//
+//The image crate's encoder filters every row with PNG's Sub filter and
+//deflates it at its fast setting, which is what --png-compression default
+//still does. The other settings write the same filtered rows through
+//deflate at another setting instead. On a 2000x1500 render :
+//
+//    fast     run-length only    about half the time, within 10% of the size
+//    default  the image crate's  (30ms gray, 90ms with a palette)
+//    best     a longer search    10 to 20 times the time, 10 to 20% smaller
+//
+//Filtered rows of the set repeat a lot, which is why run-lengths alone come
+//out so close; how close depends on the picture.
+fn encode_png<W : Write>(output : W, image : &Image, compression : PngCompression)
+    -> Result<(), std::io::Error>
+{
+    use png::HasParameters;
+
+    let (width, height) = (image.bounds.0 as u32, image.bounds.1 as u32);
+    let deflating = match compression {
+        PngCompression::Default => {
+            return PNGEncoder::new(output).encode(&image.pixels, width, height, image.color)
+        }
+        PngCompression::Fast => deflate::CompressionOptions::rle(),
+        PngCompression::Best => deflate::CompressionOptions::high()
+    };
+    let (color, bits) = image.color.into();
+    let mut encoder = png::Encoder::new(output, width, height);
+    encoder.set(color).set(bits);
+    let mut writer = encoder.write_header()?;
+    let channels = match image.color {
+        ColorType::Gray(_) => 1,
+        _ => 3
+    };
+    let mut zlib = deflate::write::ZlibEncoder::new(Vec::new(), deflating);
+    let mut filtered = Vec::with_capacity(image.bounds.0 * channels + 1);
+    for row in image.pixels.chunks(image.bounds.0 * channels).filter(|row| !row.is_empty()) {
+        //Sub : each byte less the one a pixel to its left
+        filtered.clear();
+        filtered.push(1);
+        filtered.extend(row.iter().enumerate().map(|(i, &byte)| match i.checked_sub(channels) {
+            Some(left) => byte.wrapping_sub(row[left]),
+            None => byte
+        }));
+        zlib.write_all(&filtered)?;
+    }
+    writer.write_chunk(png::chunk::IDAT, &zlib.finish()?)?;
+    //Dropping the writer writes the closing chunk
+    Ok(())
+}
+
+//The file name that stands for standard output
+const STDOUT : &str = "-";
+
+fn is_stdout(filename : &Path) -> bool {
+    filename == Path::new(STDOUT)
+}
+
+//Opens `filename` for writing, or standard output for "-". Unless it may
+//`overwrite` an existing file the file is created with create_new, so it
+//fails with AlreadyExists even when another process made the file since
+//the check before rendering.
+fn create_output(filename : &Path, overwrite : bool)
+    -> Result<Box<dyn Write>, std::io::Error>
+{
+    if is_stdout(filename) {
+        Ok(Box::new(std::io::stdout()))
+    } else if overwrite {
+        Ok(Box::new(File::create(filename)?))
+    } else {
+        Ok(Box::new(OpenOptions::new().write(true).create_new(true).open(filename)?))
+    }
+}
+
+fn write_image(filename : &Path, image : &Image, overwrite : bool,
+               compression : PngCompression)
+    -> Result<(), std::io::Error>
+{
+    //Opened first, so that a file in the way fails before the work of
+    //encoding, and encoded apart from the writing to time the two
+    let mut output = create_output(filename, overwrite)?;
+    let mut png = Vec::new();
+    stats::time("encode", || encode_png(&mut png, image, compression))?;
+    stats::time("write", || output.write_all(&png).and_then(|_| output.flush()))
+}
+
+//write_image() to FILENAME.part first and only then put in place, for the
+//commands that pick up where they stopped with --skip-existing : a run
+//killed halfway through a file (a second Ctrl-C) leaves no truncated file
+//that would be taken for a finished one. Without `overwrite` the file goes
+//in with a hard link, which fails like create_new does if it is there.
+fn write_image_whole(filename : &Path, image : &Image, overwrite : bool,
+                     compression : PngCompression)
+    -> Result<(), std::io::Error>
+{
+    if is_stdout(filename) {
+        return write_image(filename, image, overwrite, compression);
+    }
+    let mut part = filename.as_os_str().to_os_string();
+    part.push(".part");
+    let part = Path::new(&part);
+    let placed = write_image(part, image, true, compression).and_then(|_| match overwrite {
+        true => std::fs::rename(part, filename),
+        false => std::fs::hard_link(part, filename).or_else(|error| match error.kind() {
+            //FAT and some network shares have no hard links
+            std::io::ErrorKind::Unsupported | std::io::ErrorKind::PermissionDenied =>
+                copy_new(part, filename),
+            _ => Err(error)
+        })
+    });
+    let _ = std::fs::remove_file(part);
+    placed
+}
+
+//Copies `from` to a new file `to`, failing like create_new does if there is
+//one there already. A copy that fails halfway is taken out again.
+fn copy_new(from : &Path, to : &Path) -> Result<(), std::io::Error> {
+    let mut output = OpenOptions::new().write(true).create_new(true).open(to)?;
+    let copied = File::open(from)
+        .and_then(|mut input| std::io::copy(&mut input, &mut output))
+        .and_then(|_| output.flush());
+    if copied.is_err() {
+        let _ = std::fs::remove_file(to);
+    }
+    copied
+}
+
+
+//<********Adaptive iteration limit************>//
+//Instead of guessing the iteration limit we keep doubling it
+//(255, 510, 1020, ...) and compare the escape counts of successive renders.
+//A pixel is "newly resolved" when it looked like part of the set at the
+//lower limit but escapes at the higher one. Once the fraction of newly
+//resolved pixels drops below `threshold` more iterations won't add much
+//detail, so the higher of the two limits is returned.
+//The escalation never goes past `max_limit`.
+//
+//This costs a render per doubling on top of the final render, the price
+//paid for not having to tune --iterations by hand.
+fn adaptive_limit<E : Arithmetic>(
+    renderer : Option<&Renderer>,
+    view : &View,
+    threshold : f64,
+    max_limit : u32) -> u32
+{
+    let bounds = view.bounds;
+    let mut limit = 255.min(max_limit);
+    let mut previous = vec![None; bounds.0 * bounds.1];
+    render_bands(renderer, &mut previous, bounds, |band, top| {
+        render_raw::<E>(band, top, view, limit);
+    });
+
+    let mut current = vec![None; bounds.0 * bounds.1];
+    while limit < max_limit {
+        let next_limit = limit.saturating_mul(2).min(max_limit);
+        render_bands(renderer, &mut current, bounds, |band, top| {
+            render_raw::<E>(band, top, view, next_limit);
+        });
+
+        let newly_resolved = previous.iter().zip(&current)
+            .filter(|&(before, after)| before.is_none() && after.is_some())
+            .count();
+        let fraction = newly_resolved as f64 / previous.len() as f64;
+        verbose!("Iteration limit {} resolved {} more pixels ({:.4}%)",
+                 next_limit, newly_resolved, fraction * 100.0);
+
+        limit = next_limit;
+        if fraction < threshold {
+            break;
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    limit
+}
+
+//Largest image (in pixels) that --format csv will agree to write
+const CSV_MAX_PIXELS : usize = 500 * 500;
+
+//Writes the raw escape counts as a grid of comma separated values,
+//one line per row of the image. Interior points (no escape within the
+//limit) are written as `interior` which is blank by default.
+fn write_csv(filename : &Path, counts : &[Option<u32>], bounds : (usize, usize),
+             interior : &str, overwrite : bool)
+    -> Result<(), std::io::Error>
+{
+    let mut output = BufWriter::new(create_output(filename, overwrite)?);
+
+    for row in counts.chunks(bounds.0) {
+        let cells : Vec<String> = row.iter()
+            .map(|count| match count {
+                None => interior.to_string(),
+                Some(count) => count.to_string()
+            })
+            .collect();
+        writeln!(output, "{}", cells.join(","))?;
+    }
+    output.flush()
+}
+
+//<<******************MAIN FUNCTION*****************>>//
+//<<******************MAIN FUNCTION*****************>>//
+fn main() {
+    //println!("Hello, world!");
+    let started = Instant::now();
+    //Not args(), which panics on an argument that isn't UTF-8 : file names
+    //don't have to be
+    let args : Vec<OsString> = std::env::args_os().collect();
+    let program = args[0].to_string_lossy().into_owned();
+    let (command, options) = match cli::parse(&args[1..]) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            error!("{}\nTry {} --help", message, program);
+            std::process::exit(Failure::new(exit::Kind::Usage, message).code());
+        }
+    };
+    log::set_level(options.verbosity);
+    if let Err(message) = progress::configure(options.progress_json,
+                                              options.progress_file.as_deref()) {
+        fail(message.into());
+    }
+    if options.stats.is_some() || log::enabled(log::Level::Verbose) {
+        stats::enable();
+    }
+    stats::stage("parse", started);
+
+    let available_cpus = num_cpus::get();
+    //Returns the number of available CPUs of the current system
+    
+    let num_of_cores = num_cpus::get_physical();
+    //Returns the number of physical cores of the current system.
+    verbose!( "Number of cpus = {} and number of physical cores = {}",
+             available_cpus , num_of_cores);
+    verbose!("Spawned threads are {}'s", SCHEDULER);
+    if options.help {
+        print!("{}", cli::usage(&program, command));
+        return;
+    }
+    if options.version {
+        println!("{} {} (scheduler {})", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"),
+                 SCHEDULER);
+        return;
+    }
+    if options.list_presets {
+        return match presets::all() {
+            Ok(presets) => {
+                for preset in presets {
+                    println!("{:<20}center {},{} width {} iterations {}", preset.name,
+                             preset.center.re, preset.center.im, preset.width,
+                             preset.iterations);
+                }
+            }
+            Err(message) => fail(message.into())
+        };
+    }
+    if options.list_bookmarks {
+        return match bookmarks::all() {
+            Ok((_, bookmarks)) => {
+                for bookmark in bookmarks {
+                    let settings = bookmark.entries.iter()
+                        .filter_map(|entry| match entry.value {
+                            toml::Value::String(ref value) | toml::Value::Number(ref value) =>
+                                Some(format!("{} {}", entry.key, value)),
+                            toml::Value::Boolean(true) => Some(entry.key.clone()),
+                            toml::Value::Boolean(false) => None
+                        })
+                        .collect::<Vec<_>>();
+                    println!("{:<20}{}", bookmark.name, settings.join(" "));
+                }
+            }
+            Err(message) => fail(message.into())
+        };
+    }
+    if options.print_config {
+        print!("{}", cli::to_toml(command, &options));
+        return;
+    }
+
+    //--precision auto sticks to the floating point precisions where the
+    //fixed point ones don't apply
+    let options = match options.precision {
+        Precision::Auto { .. } if !renders_once(command, &options) =>
+            cli::Options { precision : Precision::Auto { fixed : false }, ..options },
+        _ => options
+    };
+    let result = match command {
+        _ if options.precision.fixed_bits().is_some() && !renders_deep(command, &options) =>
+            Err(format!("--precision {} only applies to rendering a single image (render, \
+                         or render --bench), to animate and to measure",
+                        options.precision.name()).into()),
+        _ if options.save_bookmark.is_some() => commands::save_bookmark(&options),
+        _ if options.permalink => commands::permalink(&options),
+        Command::Render if options.preview_palette.is_some() =>
+            commands::preview_palette(&options),
+        Command::Render if options.pyramid.is_some() => commands::pyramid(&options),
+        Command::Render if options.jobs.is_some() => commands::jobs(&options),
+        Command::Render if options.jobs_stdin => commands::jobs_stdin(&options),
+        Command::Render if options.watch => commands::watch(&args[1..], &options),
+        Command::Render if options.bench.is_some() => commands::bench(&options),
+        Command::Render if options.scaling_sweep.is_some() =>
+            commands::scaling_sweep(&options),
+        Command::Render => commands::render(&options),
+        Command::Animate => commands::animate(&options),
+        #[cfg(feature = "serve")]
+        Command::Serve => commands::serve(&options),
+        #[cfg(not(feature = "serve"))]
+        Command::Serve => Err(Failure::new(exit::Kind::Usage,
+                                           "built without the serve feature".to_string())),
+        Command::Probe => commands::probe(&options),
+        Command::Area => commands::area(&options),
+        Command::Stitch => commands::stitch(&options),
+        Command::VerifyManifest => commands::verify_manifest(&options),
+        Command::Verify => commands::verify(),
+        Command::Census => commands::census(&options),
+        Command::ContactSheet => commands::contact_sheet(&options),
+        Command::Measure => commands::measure(&options)
+    };
+    //Whatever the command got through, even if it failed
+    let reported = report_stats(&options);
+    if options.report_iterations && result.is_ok() {
+        info!("Went through {} iterations in all", stats::iterations());
+    }
+    if let Err(failure) = result {
+        fail(failure);
+    }
+    if let Err(message) = reported {
+        fail(message.into());
+    }
+}
+
+//Whether the command renders one image of the view (or measures it),
+//--precision big having no view in the plain sense for the others to work
+//from
+fn renders_once(command : Command, options : &cli::Options) -> bool {
+    if command == Command::Measure {
+        return true;
+    }
+    command == Command::Render && options.save_bookmark.is_none() && !options.permalink
+        && options.preview_palette.is_none() && options.pyramid.is_none()
+        && options.jobs.is_none() && !options.jobs_stdin && !options.watch
+        && options.scaling_sweep.is_none()
+}
+
+//Whether the command renders the view in a precision it names, once or as
+//animate's frames zooming into its center. --precision auto still sticks to
+//the floating point ones for animate : it goes by the first frame, which the
+//deeper ones soon outgrow.
+fn renders_deep(command : Command, options : &cli::Options) -> bool {
+    renders_once(command, options)
+        || command == Command::Animate && options.save_bookmark.is_none() && !options.permalink
+}
+
+//The -v summary of the timing statistics and the --stats file
+fn report_stats(options : &cli::Options) -> Result<(), String> {
+    if log::enabled(log::Level::Verbose) {
+        for line in stats::lines() {
+            verbose!("{}", line);
+        }
+    }
+    let path = match options.stats {
+        Some(ref path) => path,
+        None => return Ok(())
+    };
+    stats::write(path, vec![
+        ("threads", json::Json::Integer(options.thread_count() as u64)),
+        ("scheduler", json::Json::String(SCHEDULER.to_string())),
+        ("schedule", json::Json::String(options.schedule.name().to_string()))
+    ])
+}
+
+//Prints what went wrong and exits with the code for it, see exit.rs
+fn fail(failure : Failure) -> ! {
+    error!("{}", failure);
+    std::process::exit(failure.code());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    //<********orbit()*********>//
+    #[test]
+    fn an_orbit_starts_at_c_and_ends_where_it_escaped() {
+        let point = |re, im| Complex { re, im };
+        //Escaping after 3, 15 and 97 iterations
+        for c in [point(1.0, 0.0), point(0.3, 0.6), point(0.251, 0.0)] {
+            let orbit = orbit(c, 1000);
+            let (count, z) = escape_z(c, 1000).unwrap();
+            assert_eq!(orbit[0], c);
+            assert_eq!(orbit.len(), count as usize + 1);
+            assert_eq!(*orbit.last().unwrap(), z);
+            assert!(z.norm_sqr() > 4.0);
+            assert!(orbit[..orbit.len() - 1].iter().all(|z| z.norm_sqr() <= 4.0));
+        }
+        assert_eq!(orbit(point(1.0, 0.0), 1000), [point(1.0, 0.0), point(2.0, 0.0),
+                                                  point(5.0, 0.0)]);
+        //Cut off at the limit, escaping or not
+        for c in [point(0.251, 0.0), point(-1.0, 0.0), point(0.0, 0.0)] {
+            for limit in [0, 1, 7, 50] {
+                let orbit = orbit(c, limit);
+                assert_eq!(orbit.len(), limit as usize);
+                assert!(orbit.first().is_none_or(|&first| first == c));
+            }
+        }
+    }
+
+    //<********sample_line()*********>//
+    #[test]
+    fn sampling_along_the_real_axis() {
+        let (start, end) = (Complex { re : -2.0, im : 0.0 }, Complex { re : 0.5, im : 0.0 });
+        for n in [0, 1, 2, 11, 101] {
+            assert_eq!(sample_line(start, end, n, 200).len(), n);
+        }
+        //Every 0.25 from -2 to 0.5 : all of [-2, 0.25] is in the set, 0.5 isn't
+        let samples = sample_line(start, end, 11, 200);
+        assert!(samples[..10].iter().all(Option::is_none), "{:?}", samples);
+        assert_eq!(samples[10], escape_time(end, 200));
+        assert!(samples[10].is_some());
+        //Both ends are sampled, a single sample is the start
+        assert_eq!(sample_line(end, start, 2, 200), [samples[10], None]);
+        assert_eq!(sample_line(end, start, 1, 200), [samples[10]]);
+    }
+
+    //<********parse_pair()*********>//
+    fn image_size(s : &str) -> Result<(usize, usize), String> {
+        parse_pair::<usize>(s, 'x')
+            .map_err(|error| error.describe("an image size", ["width", "height"], "1000x750"))
+    }
+
+    fn pair(s : &str) -> Result<(f64, f64), String> {
+        parse_pair::<f64>(s, ',')
+            .map_err(|error| error.describe("a pair", ["first", "second"], "1,2"))
+    }
+
+    #[test]
+    fn pairs_with_spaces_and_parentheses() {
+        for s in ["-1.2,0.35", "-1.2, 0.35", " -1.2 ,0.35 ", "(-1.2,0.35)", "( -1.2 , 0.35 )",
+                  "  (-1.2, 0.35)  "] {
+            assert_eq!(pair(s), Ok((-1.2, 0.35)), "{:?}", s);
+        }
+        assert_eq!(image_size("1000x750"), Ok((1000, 750)));
+        assert_eq!(image_size(" 1000 x 750 "), Ok((1000, 750)));
+    }
+
+    #[test]
+    fn pairs_with_unicode_minus_signs() {
+        assert_eq!(pair("\u{2212}1.2,\u{2212}0.35"), Ok((-1.2, -0.35)));
+        assert_eq!(pair("(\u{2212}1.2, 0.35)"), Ok((-1.2, 0.35)));
+    }
+
+    #[test]
+    fn only_parentheses_around_the_whole_pair_come_off() {
+        assert!(pair("(-1.2,0.35").is_err());
+        assert!(pair("-1.2,0.35)").is_err());
+        assert!(pair("((-1.2,0.35))").is_err());
+    }
+
+    #[test]
+    fn pair_without_a_separator() {
+        assert_eq!(image_size("1000"), Err("expected an image size such as 1000x750, there's no \
+                                      'x' between the width and the height".to_string()));
+        assert_eq!(pair("(1 2)").err().map(|error| error.contains("no ','")), Some(true));
+    }
+
+    #[test]
+    fn pair_errors_name_the_part_and_its_text() {
+        assert_eq!(image_size("10x7a"), Err("the height '7a' isn't valid : invalid digit found \
+                                       in string".to_string()));
+        assert_eq!(image_size("x750"), Err("the width '' isn't valid : cannot parse integer from \
+                                      empty string".to_string()));
+        let error = pair("1.0junk, 2").unwrap_err();
+        assert!(error.starts_with("the first '1.0junk' isn't valid : "), "{}", error);
+        let error = pair("1.0,2.0junk").unwrap_err();
+        assert!(error.starts_with("the second '2.0junk' isn't valid : "), "{}", error);
+    }
+
+    #[test]
+    fn complex_points_with_spaces_parentheses_and_unicode_minus() {
+        for s in ["-0.5,0.25", " -0.5 , 0.25 ", "(-0.5, 0.25)", "(\u{2212}0.5,0.25)"] {
+            assert_eq!(parse_complex(s).ok(), Some(Complex { re : -0.5, im : 0.25 }), "{:?}", s);
+        }
+        let error = parse_complex("-0.5,0.25x").unwrap_err()
+            .describe("a complex point", ["real part", "imaginary part"], "-0.5,0.25");
+        assert!(error.starts_with("the imaginary part '0.25x' isn't valid"), "{}", error);
+    }
+
+    //<********parse_part()*********>//
+    fn part(text : &str) -> Result<f64, String> {
+        parse_part(1, text.to_string())
+            .map_err(|error| error.describe("a pair", ["first", "second"], "1,2"))
+    }
+
+    #[test]
+    fn parts_that_arent_finite_are_refused() {
+        for text in ["inf", "-inf", "+infinity", "Infinity", "NaN", "nan", "1e309", "-1e400"] {
+            assert_eq!(part(text),
+                       Err(format!("the second '{}' isn't valid : not a finite number", text)));
+        }
+        //Expressions that come out infinite or NaN as well
+        for text in ["1/0", "-1/0", "0/0", "1e300*1e300"] {
+            assert!(part(text).is_err(), "{:?}", text);
+        }
+        assert_eq!(part("1e308"), Ok(1e308));
+        assert_eq!(part("-0"), Ok(0.0));
+    }
+
+    #[test]
+    fn points_with_a_part_that_isnt_finite_are_refused() {
+        for s in ["nan,0", "0,inf", "-inf,-inf", "(1e400, 0)", "inf+0i", "0+nani",
+                  "polar:inf,0deg", "polar:1,nanrad"] {
+            assert!(parse_complex(s).is_err(), "{:?}", s);
+        }
+    }
+
+    //<********parse_polar()*********>//
+    #[test]
+    fn polar_quarter_turns_in_degrees_and_radians() {
+        let close = |point : Complex<f64>, (re, im) : (f64, f64)| {
+            (point.re - re).abs() < 1e-12 && (point.im - im).abs() < 1e-12
+        };
+        for (degrees, radians, expected) in [(0, "0", (2.0, 0.0)), (90, "pi/2", (0.0, 2.0)),
+                                             (180, "pi", (-2.0, 0.0)),
+                                             (270, "3*pi/2", (0.0, -2.0)),
+                                             (-90, "-pi/2", (0.0, -2.0)),
+                                             (450, "5*pi/2", (0.0, 2.0))] {
+            let from_degrees = parse_polar(&format!("2,{}deg", degrees)).unwrap();
+            let from_radians = parse_polar(&format!("2,{}rad", radians)).unwrap();
+            assert!(close(from_degrees, expected), "{}deg gave {}", degrees, from_degrees);
+            assert!(close(from_radians, expected), "{}rad gave {}", radians, from_radians);
+            assert_eq!(parse_complex(&format!("polar:2, {}deg", degrees)).unwrap(),
+                       from_degrees);
+        }
+        //Quarter turns in degrees are exact, not off by a rounding error
+        assert_eq!(parse_polar("1,90deg").unwrap(), Complex { re : 0.0, im : 1.0 });
+        assert_eq!(parse_polar("0.5,180deg").unwrap(), Complex { re : -0.5, im : 0.0 });
+        let between = parse_polar("0.7,45deg").unwrap();
+        assert!(close(between, (0.7 / 2f64.sqrt(), 0.7 / 2f64.sqrt())), "{}", between);
+        for s in ["1,90", "-1,0deg", "1,90grad"] {
+            assert!(parse_polar(s).is_err(), "{:?}", s);
+        }
+    }
+
+    //<********parse_algebraic()*********>//
+    #[test]
+    fn algebraic_forms() {
+        let cases = [
+            ("-0.74+0.13i", -0.74, 0.13),
+            ("-0.743643887+0.131825904i", -0.743643887, 0.131825904),
+            ("0.5-0.25i", 0.5, -0.25),
+            ("i", 0.0, 1.0),
+            ("-i", 0.0, -1.0),
+            ("+i", 0.0, 1.0),
+            ("2i", 0.0, 2.0),
+            ("0.7i", 0.0, 0.7),
+            ("-0.7i", 0.0, -0.7),
+            ("0.5", 0.5, 0.0),
+            ("-2", -2.0, 0.0),
+            ("0.5-i", 0.5, -1.0),
+            ("0.5+i", 0.5, 1.0),
+            ("0.5*i", 0.0, 0.5),
+            ("1e-3-2e-3i", 1e-3, -2e-3),
+            ("1.5e-3+2e-4i", 1.5e-3, 2e-4),
+            ("1.5E+3-2E-4i", 1.5e3, -2e-4),
+            ("-1e-3i", 0.0, -1e-3),
+            ("( -0.74 + 0.13i )", -0.74, 0.13),
+            ("\u{2212}0.74\u{2212}0.13i", -0.74, -0.13)
+        ];
+        for &(s, re, im) in &cases {
+            assert_eq!(parse_complex(s).ok(), Some(Complex { re, im }), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn algebraic_forms_with_expressions() {
+        let point = parse_complex("pi/4-pi/4i").unwrap();
+        assert_eq!(point, Complex { re : std::f64::consts::FRAC_PI_4,
+                                    im : -std::f64::consts::FRAC_PI_4 });
+        //pi is a real number, not p times i
+        assert_eq!(parse_complex("pi").ok(),
+                   Some(Complex { re : std::f64::consts::PI, im : 0.0 }));
+    }
+
+    #[test]
+    fn algebraic_forms_refused() {
+        for s in ["", "j", "1+2j", "1++2i", "1+2ii", "abc", "0.5-xi", "1e-i",
+                  "--i", "1+2i3"] {
+            assert!(parse_complex(s).is_err(), "{:?} parsed as {:?}", s,
+                    parse_complex(s).ok());
+        }
+    }
+
+    //<********fit_view()*********>//
+    //A 4 by 2 view, twice as wide as it is tall, around 1+1i
+    const UPPER_LEFT : Complex<f64> = Complex { re : -1.0, im : 2.0 };
+    const LOWER_RIGHT : Complex<f64> = Complex { re : 3.0, im : 0.0 };
+
+    fn size(corners : (Complex<f64>, Complex<f64>)) -> (f64, f64) {
+        (corners.1.re - corners.0.re, corners.0.im - corners.1.im)
+    }
+
+    fn center(corners : (Complex<f64>, Complex<f64>)) -> Complex<f64> {
+        (corners.0 + corners.1) / 2.0
+    }
+
+    #[test]
+    fn a_view_of_the_image_aspect_is_no_mismatch() {
+        assert_eq!(aspect_mismatch((400, 200), UPPER_LEFT, LOWER_RIGHT), 0.0);
+        assert!(aspect_mismatch((400, 201), UPPER_LEFT, LOWER_RIGHT) < ASPECT_TOLERANCE);
+    }
+
+    #[test]
+    fn landscape_view_on_a_square_image() {
+        assert_eq!(aspect_mismatch((100, 100), UPPER_LEFT, LOWER_RIGHT), 1.0);
+        let expanded = fit_view((100, 100), UPPER_LEFT, LOWER_RIGHT, Fit::Expand);
+        assert_eq!(size(expanded), (4.0, 4.0));
+        assert_eq!(center(expanded), Complex { re : 1.0, im : 1.0 });
+        let cropped = fit_view((100, 100), UPPER_LEFT, LOWER_RIGHT, Fit::Crop);
+        assert_eq!(size(cropped), (2.0, 2.0));
+        assert_eq!(center(cropped), Complex { re : 1.0, im : 1.0 });
+    }
+
+    #[test]
+    fn landscape_view_on_a_portrait_image() {
+        let expanded = fit_view((100, 200), UPPER_LEFT, LOWER_RIGHT, Fit::Expand);
+        assert_eq!(size(expanded), (4.0, 8.0));
+        let cropped = fit_view((100, 200), UPPER_LEFT, LOWER_RIGHT, Fit::Crop);
+        assert_eq!(size(cropped), (1.0, 2.0));
+        for corners in [expanded, cropped] {
+            assert_eq!(center(corners), Complex { re : 1.0, im : 1.0 });
+            assert_eq!(aspect_mismatch((100, 200), corners.0, corners.1), 0.0);
+        }
+    }
+
+    #[test]
+    fn portrait_view_on_a_landscape_image() {
+        let (upper_left, lower_right) = (Complex { re : 0.0, im : 3.0 },
+                                         Complex { re : 1.0, im : -1.0 });
+        let expanded = fit_view((300, 100), upper_left, lower_right, Fit::Expand);
+        assert_eq!(size(expanded), (12.0, 4.0));
+        let cropped = fit_view((300, 100), upper_left, lower_right, Fit::Crop);
+        assert_eq!(size(cropped).0, 1.0);
+        assert!((size(cropped).1 - 1.0 / 3.0).abs() < 1e-12);
+        for corners in [expanded, cropped] {
+            assert_eq!(center(corners), Complex { re : 0.5, im : 1.0 });
+            assert!(aspect_mismatch((300, 100), corners.0, corners.1) < 1e-12);
+        }
+    }
+
+    #[test]
+    fn stretch_keeps_the_corners() {
+        assert_eq!(fit_view((100, 300), UPPER_LEFT, LOWER_RIGHT, Fit::Stretch),
+                   (UPPER_LEFT, LOWER_RIGHT));
+    }
+
+    //<********Renderer*********>//
+    //The full set at a size whose 47 rows no thread count here divides
+    fn full_set_options() -> cli::Options {
+        cli::Options {
+            size : Some((61, 47)),
+            upper_left : Some(Complex { re : -2.0, im : 1.2 }),
+            lower_right : Some(Complex { re : 1.0, im : -1.2 }),
+            fit : Some(Fit::Stretch),
+            ..cli::Options::default()
+        }
+    }
+
+    fn full_set() -> Config {
+        config::build(&full_set_options()).unwrap()
+    }
+
+    #[test]
+    fn threads_render_what_a_single_thread_does() {
+        let config = full_set();
+        let alone = render_image(&config, &config.view, Some(&Renderer::single_threaded()));
+        for threads in [2, 3, 4, 7, 16, 64] {
+            let pooled = render_image(&config, &config.view, Some(&Renderer::new(threads)));
+            assert!(pooled.pixels == alone.pixels, "{} pooled threads", threads);
+            for schedule in [Schedule::Bands, Schedule::Interleaved, Schedule::Dynamic,
+                             Schedule::CostBalanced] {
+                for chunk_rows in [1, 5] {
+                    for renderer in [Renderer::new(threads), Renderer::spawning(threads)] {
+                        let renderer = renderer.with_schedule(schedule, chunk_rows);
+                        let image = render_image(&config, &config.view, Some(&renderer));
+                        assert!(image.pixels == alone.pixels, "{} threads, {} schedule, \
+                                 {} rows a chunk", threads, schedule.name(), chunk_rows);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn render_into_renders_what_a_single_thread_does() {
+        let config = full_set();
+        let viewport = (config.view.upper_left, config.view.lower_right);
+        let alone = Renderer::single_threaded()
+            .render_to_vec(config.view.bounds, viewport, config.limit);
+        assert!(alone == render_image(&config, &config.view, None).pixels);
+        for threads in [2, 7] {
+            let mut buffer = vec![0; alone.len()];
+            Renderer::new(threads)
+                .render_into(&mut buffer, config.view.bounds, viewport, config.limit)
+                .unwrap();
+            assert!(buffer == alone, "{} threads", threads);
+        }
+        let mut too_small = vec![0; alone.len() - 1];
+        assert!(Renderer::new(2)
+                .render_into(&mut too_small, config.view.bounds, viewport, config.limit)
+                .is_err());
+    }
+
+    fn differing<T : PartialEq>(a : &[T], b : &[T]) -> usize {
+        a.iter().zip(b).filter(|(a, b)| a != b).count()
+    }
+
+    #[test]
+    fn panned_frames_are_their_windows_rendered_afresh() {
+        //The windows are the frames' views to within the rounding of their
+        //corners, which sends a few pixels on the edge of the set the other
+        //way
+        const OFF : f64 = 0.01;
+        let config = full_set();
+        let bounds = config.view.bounds;
+        //Along a row, a column, both ways, most of the way across and all
+        //of it, where nothing is left to move along
+        for step in [(3, 0), (0, -2), (-5, 4), (7, 7), (-63, 1), (1, -47), (64, 0)] {
+            let options = cli::Options { pan_per_frame : Some((step.0 as f64, step.1 as f64)),
+                                         ..full_set_options() };
+            let (canvas, first) = pan_canvas(&config.view, step, 3);
+            let canvas = Config { view : canvas, ..config.clone() };
+            let window = |frame : isize| {
+                (((first.0 as isize + step.0 * frame) as usize,
+                  (first.1 as isize + step.1 * frame) as usize), bounds)
+            };
+            let mut counts = window_counts(&canvas, None, window(0));
+            for frame in 0..3 {
+                if frame > 0 {
+                    counts = moved_counts(&canvas, None, &counts, window(frame), step);
+                    let afresh = window_counts(&canvas, Some(&Renderer::new(3)), window(frame));
+                    assert!(counts == afresh, "{:?} : {} of {} differ in frame {}", step,
+                            differing(&counts, &afresh), counts.len(), frame);
+                }
+                let view = config::frame_view(&config.view, &options, frame as usize);
+                let rendered = render_counts(&Config { view, ..config.clone() }, None);
+                let off = differing(&counts, &rendered);
+                assert!(off as f64 <= OFF * counts.len() as f64, "{:?} : {} of {} differ from \
+                        the view of frame {}", step, off, counts.len(), frame);
+            }
+        }
+    }
+
+    #[test]
+    fn only_plain_counts_move_along() {
+        let config = full_set();
+        assert!(counts_move_along(&config));
+        let fire = Config { palette : Palette::builtin("fire"), ..config.clone() };
+        assert!(counts_move_along(&fire));
+        let turned = config::build(&cli::Options { rotate : 30.0, ..full_set_options() })
+            .unwrap();
+        for config in [turned,
+                       Config { julia : Some(Complex { re : -0.8, im : 0.156 }),
+                                ..config.clone() },
+                       Config { limit_map : Some((255, 64)), ..config.clone() },
+                       Config { chromatic : Some(3), ..config.clone() },
+                       Config { mode : Mode::Angle, ..config.clone() },
+                       Config { preview_stride : 2, ..config.clone() }] {
+            assert!(!counts_move_along(&config));
+        }
+    }
+
+    #[test]
+    fn every_arithmetic_renders_the_same_image() {
+        let config = full_set();
+        let image = render_image(&config, &config.view, None);
+        let counts = render_counts(&config, None);
+        let unturned = config::build(&cli::Options { rotate : 0.0, ..full_set_options() })
+            .unwrap();
+        assert!(unturned.view.rotation.is_none());
+        for (name, config) in [("--rotate 0", unturned),
+                               ("--fast-math", Config { fast_math : true, ..config.clone() }),
+                               ("--simd", Config { simd : true, ..config.clone() })] {
+            let rendered = render_image(&config, &config.view, None);
+            assert!(rendered.pixels == image.pixels, "{} : {} pixels differ", name,
+                    differing(&rendered.pixels, &image.pixels));
+            assert!(render_counts(&config, None) == counts, "{}", name);
+        }
+    }
+
+    #[test]
+    fn other_precisions_agree_with_f64_but_for_the_edge_of_the_set() {
+        //Only as far as the rounding goes, which sends a few pixels on the
+        //edge of the set the other way
+        const OFF : f64 = 0.01;
+        let config = full_set();
+        let counts = render_counts(&config, None);
+        let single = Config { precision : Precision::F32, ..config.clone() };
+        let single_counts = render_counts(&single, None);
+        assert!(render_counts(&Config { simd : true, ..single }, None) == single_counts);
+        for (name, precision) in [("f32", Precision::F32), ("dd", Precision::DoubleDouble)] {
+            let other = render_counts(&Config { precision, ..config.clone() }, None);
+            let off = differing(&other, &counts);
+            assert!(off as f64 <= OFF * counts.len() as f64, "{} : {} of {} differ", name,
+                    off, counts.len());
+        }
+    }
+
+    #[test]
+    fn escape_times_agrees_with_escape_time() {
+        let config = full_set();
+        let (upper_left, lower_right) = (config.view.upper_left, config.view.lower_right);
+        //Along the diagonal, from outside the set through the middle of it
+        //and out again
+        let points = (0..3000)
+            .map(|i| upper_left + (lower_right - upper_left) * (i as f64 / 3000.0))
+            .collect::<Vec<_>>();
+        let one_by_one = points.iter()
+            .map(|&point| escape_time(point, config.limit))
+            .collect::<Vec<_>>();
+        assert!(escape_times(&points, config.limit) == one_by_one);
+        assert!(Renderer::new(7).escape_times(&points, config.limit) == one_by_one);
+        assert!(Renderer::spawning(7).with_parallel_threshold(1)
+                .escape_times(&points, config.limit) == one_by_one);
+    }
+
+    #[test]
+    fn every_schedule_renders_every_pixel_once() {
+        //Rows of a pixel each, handed out a row at a time over and over, for
+        //the schedulers to lose or repeat one if they are ever going to
+        const WINDOW : (usize, usize) = (1, 5000);
+        const RUNS : u32 = 20;
+        let schedules = [Schedule::Bands, Schedule::Interleaved, Schedule::Dynamic,
+                         Schedule::CostBalanced];
+        let mut times = vec![0; WINDOW.0 * WINDOW.1];
+        for _ in 0..RUNS {
+            for &schedule in &schedules {
+                render_in_scope(&mut times, WINDOW, 7, (schedule, 1), &[],
+                                |band : &mut [u32], _| {
+                                    band.iter_mut().for_each(|time| *time += 1)
+                                });
+            }
+        }
+        let expected = RUNS * schedules.len() as u32;
+        let missed = times.iter().filter(|&&time| time != expected).count();
+        assert_eq!(missed, 0, "{} of {} pixels weren't rendered {} times", missed, times.len(),
+                   expected);
+    }
+
+    #[test]
+    fn every_png_compression_reads_back_the_same() {
+        let config = full_set();
+        let gray = render_image(&config, &config.view, None);
+        let palette = Config { palette : Palette::builtin("fire"), ..config.clone() };
+        let colour = render_image(&palette, &palette.view, None);
+        for image in [gray, colour] {
+            for compression in [PngCompression::Fast, PngCompression::Default,
+                                PngCompression::Best] {
+                let mut png = Vec::new();
+                encode_png(&mut png, &image, compression).unwrap();
+                let decoded = image::load_from_memory(&png).unwrap();
+                assert!(decoded.raw_pixels() == image.pixels);
+            }
+        }
+    }
+
+    //A row of points that never escape would take hours at this limit
+    #[test]
+    fn time_budget_runs_out_inside_a_row() {
+        for threads in [1, 3] {
+            let config = config::build(&cli::Options {
+                size : Some((10, 10)),
+                upper_left : Some(Complex { re : -0.1, im : 0.1 }),
+                lower_right : Some(Complex { re : 0.0, im : 0.0 }),
+                fit : Some(Fit::Stretch),
+                iterations : u32::MAX,
+                time_budget : Some(Duration::from_millis(20)),
+                ..cli::Options::default()
+            }).unwrap();
+            let started = Instant::now();
+            let renderer = match threads {
+                1 => Renderer::single_threaded(),
+                _ => Renderer::new(threads)
+            };
+            let (image, _) = render_image_or_partial(&config, &config.view, Some(&renderer));
+            assert!(started.elapsed() < Duration::from_secs(10), "{} threads", threads);
+            assert!(image.pixels.iter().all(|&pixel| pixel == 0), "{} threads", threads);
+        }
+    }
+
+    #[test]
+    fn cancel_stops_every_chromatic_channel() {
+        for threads in [1, 3] {
+            let mut config = config::build(&cli::Options {
+                size : Some((10, 10)),
+                upper_left : Some(Complex { re : -0.1, im : 0.1 }),
+                lower_right : Some(Complex { re : 0.0, im : 0.0 }),
+                fit : Some(Fit::Stretch),
+                iterations : u32::MAX,
+                chromatic : Some(1),
+                ..cli::Options::default()
+            }).unwrap();
+            config.cancel = Some(Arc::new(AtomicBool::new(true)));
+            let started = Instant::now();
+            let renderer = match threads {
+                1 => Renderer::single_threaded(),
+                _ => Renderer::new(threads)
+            };
+            let (image, _) = render_image_or_partial(&config, &config.view, Some(&renderer));
+            assert!(started.elapsed() < Duration::from_secs(10), "{} threads", threads);
+            assert_eq!(image.color, ColorType::RGB(8));
+            assert!(image.pixels.iter().all(|&pixel| pixel == 0), "{} threads", threads);
+        }
+    }
+
+    //<********gray_value()*********>//
+    #[test]
+    fn gamma_one_is_the_linear_ramp_and_more_darkens_the_midtones() {
+        let gray = |gamma, exterior| Gray { gamma, exterior, ..config::PLAIN_GRAY };
+        for limit in [255, 256, 1000, 100_000] {
+            for count in (0..=limit).step_by((limit / 300).max(1) as usize) {
+                let scaled = (count as u64 * 255 / limit as u64) as u8;
+                let light_to_dark = gray(1.0, Exterior::LightToDark);
+                let dark_to_light = gray(1.0, Exterior::DarkToLight);
+                assert_eq!(gray_value(Some(count), limit, light_to_dark), 255 - scaled);
+                assert_eq!(gray_value(Some(count), limit, dark_to_light), scaled);
+            }
+        }
+        //Along a gradient of counts the ends stay put, the grays stay in
+        //order and the ones in between come out darker
+        let (linear, curved) = (gray(1.0, Exterior::LightToDark),
+                                gray(2.2, Exterior::LightToDark));
+        let ramp = |gray| (0..=1000).map(|count| gray_value(Some(count), 1000, gray))
+            .collect::<Vec<_>>();
+        let (linear_ramp, curved_ramp) = (ramp(linear), ramp(curved));
+        assert_eq!((curved_ramp[0], curved_ramp[1000]), (255, 0));
+        assert!(curved_ramp.windows(2).all(|pair| pair[0] >= pair[1]));
+        assert!(curved_ramp.iter().zip(&linear_ramp).all(|(curved, linear)| curved <= linear));
+        //Half the brightness is 128 on the linear ramp, 0.5^2.2 of it (55) with gamma
+        assert_eq!(linear_ramp[500], 128);
+        assert_eq!(curved_ramp[500], (0.5f64.powf(2.2) * 255.0).round() as u8);
+        //The interior is left as it is
+        assert_eq!(gray_value(None, 1000, Gray { interior : 77, ..curved }), 77);
+    }
+
+    //<********band_layout()*********>//
+    #[test]
+    fn bands_cover_every_row_once_without_an_empty_one() {
+        for height in 1..=70 {
+            for threads in 1..=80 {
+                let layout = band_layout((3, height), threads);
+                let context = format!("{} rows in {} bands : {:?}", height, threads, layout);
+                assert_eq!(layout.len(), threads.min(height), "{}", context);
+                //In order, each starting where the last one stopped, so no row
+                //is left out or in two bands
+                let mut next = 0;
+                for &(top, rows) in &layout {
+                    assert_eq!(top, next, "{}", context);
+                    assert!(rows > 0, "{}", context);
+                    next = top + rows;
+                }
+                assert_eq!(next, height, "{}", context);
+                //And as even as they go
+                let tallest = layout.iter().map(|&(_, rows)| rows).max().unwrap();
+                let shortest = layout.iter().map(|&(_, rows)| rows).min().unwrap();
+                assert!(tallest - shortest <= 1, "{}", context);
+            }
+        }
+    }
+
+    #[test]
+    fn a_window_without_rows_is_one_empty_band() {
+        for threads in [0, 1, 8] {
+            assert_eq!(band_layout((3, 0), threads), [(0, 0)]);
+        }
+        assert_eq!(band_layout((3, 5), 0), [(0, 5)]);
+    }
+
+    //<********Writing without overwriting*********>//
+    //A directory of its own for each test, under the temp directory
+    fn scratch(test : &str) -> std::path::PathBuf {
+        let directory = std::env::temp_dir()
+            .join(format!("mandelbrot-write-{}-{}", std::process::id(), test));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    fn gray(value : u8) -> Image {
+        Image { pixels : vec![value; 4], bounds : (2, 2), color : ColorType::Gray(8) }
+    }
+
+    fn png(value : u8) -> Vec<u8> {
+        let mut png = Vec::new();
+        encode_png(&mut png, &gray(value), PngCompression::Fast).unwrap();
+        png
+    }
+
+    #[test]
+    fn an_existing_file_is_only_overwritten_when_asked() {
+        let directory = scratch("plain");
+        let file = directory.join("out.png");
+        std::fs::write(&file, b"not mine").unwrap();
+        let error = write_image(&file, &gray(0), false, PngCompression::Fast).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::read(&file).unwrap(), b"not mine");
+
+        write_image(&file, &gray(0), true, PngCompression::Fast).unwrap();
+        assert_eq!(std::fs::read(&file).unwrap(), png(0));
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn a_whole_image_is_only_put_over_a_file_when_asked() {
+        let directory = scratch("whole");
+        let file = directory.join("out.png");
+        write_image_whole(&file, &gray(10), false, PngCompression::Fast).unwrap();
+        assert_eq!(std::fs::read(&file).unwrap(), png(10));
+
+        let error = write_image_whole(&file, &gray(20), false, PngCompression::Fast)
+            .unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::read(&file).unwrap(), png(10));
+
+        write_image_whole(&file, &gray(30), true, PngCompression::Fast).unwrap();
+        assert_eq!(std::fs::read(&file).unwrap(), png(30));
+        //Nothing but the image is left behind either way
+        let names = std::fs::read_dir(&directory).unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["out.png"]);
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn copy_new_fails_like_create_new() {
+        let directory = scratch("copy");
+        let (from, to) = (directory.join("out.png.part"), directory.join("out.png"));
+        std::fs::write(&from, png(40)).unwrap();
+        copy_new(&from, &to).unwrap();
+        assert_eq!(std::fs::read(&to).unwrap(), png(40));
+
+        std::fs::write(&from, png(50)).unwrap();
+        let error = copy_new(&from, &to).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::read(&to).unwrap(), png(40));
+
+        //Nothing is left behind when there is nothing to copy
+        let missing = directory.join("missing.png");
+        assert!(copy_new(&directory.join("gone.part"), &missing).is_err());
+        assert!(!missing.exists());
+        let _ = std::fs::remove_dir_all(&directory);
+    }
 }
@@ -0,0 +1,275 @@
+//<***************Reference orbits kept from frame to frame*****************>//
+//The frames of a deep zoom all zoom into the same center, and under
+//--perturbation at --precision fixed128 or big:BITS the orbit of that
+//center in BigInts is the one part of a frame the series approximation
+//doesn't cut down : every frame would work it out again, only for the
+//limit to have grown a little under --iteration-scale. The cache keeps the
+//orbits animate works out, found by their point to the bit and kept with
+//their last Z in full, so that a frame at the same limit or below takes the
+//orbit as it is and one above takes it on from where it got to. Either way
+//it is bit for bit what big::orbit() gives.
+//
+//The series approximation is fitted again for each frame : where it holds
+//depends on how far the frame's corners are from the center, and working
+//out its coefficients along the orbit is the least of the fitting.
+//
+//Under plain --perturbation the first reference of a frame is kept too
+//(see perturbation.rs), and the next frame measures from it as long as its
+//point is inside that frame, extending its orbit if the limit has grown.
+//A frame in which more than KEPT_GLITCHES of the pixels glitched against it
+//doesn't keep it, and the frame after picks one at its own center again.
+//
+//At most `most` points of orbits are kept, MAX_POINTS for animate, the
+//orbit used longest ago dropped first. With --orbit-cache FILE the orbits
+//are read from FILE before the first frame and written back after the
+//last, for the next run to go on from, as text :
+//
+//    # mandelbrot reference orbits
+//    orbit BITS C_RE C_IM Z_RE Z_IM ESCAPED POINTS
+//    RE IM
+//    ...
+//
+//the BigInts of C in decimal, the parts of Z as big::Z::parts() gives them
+//(BigInts in decimal too, or BigFloats in hex with --features astro-float,
+//whose files have a header of their own), ESCAPED true or false, then a line
+//for each of the POINTS points of the orbit with the f64 bits of its parts
+//in hex.
+
+use num::{BigInt, Complex};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use crate::big::{Orbit, Point, Z};
+use crate::perturbation::Reference;
+
+//16 bytes each, 128MiB in all
+pub const MAX_POINTS : usize = 1 << 23;
+
+//The fraction of a frame's pixels that may glitch against its first
+//reference for the next frame to measure from it as well
+pub const KEPT_GLITCHES : f64 = 0.01;
+
+#[cfg(not(feature = "astro-float"))]
+const HEADER : &str = "# mandelbrot reference orbits\n";
+#[cfg(feature = "astro-float")]
+const HEADER : &str = "# mandelbrot reference orbits in astro-float\n";
+
+pub struct Cache {
+    most : usize,
+    kept : Mutex<Kept>
+}
+
+struct Kept {
+    //The one used longest ago first
+    orbits : Vec<Orbit>,
+    reference : Option<Reference>
+}
+
+impl Cache {
+    //An empty cache of `most` points at most
+    pub fn new(most : usize) -> Cache {
+        Cache { most, kept : Mutex::new(Kept { orbits : Vec::new(), reference : None }) }
+    }
+
+    //The orbits write() left in `path`, none if there is no such file
+    pub fn read(path : &Path, most : usize) -> Result<Cache, String> {
+        let cache = Cache::new(most);
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(cache),
+            Err(error) => return Err(error.to_string())
+        };
+        let orbits = parse(&text)?;
+        let mut kept = cache.kept.lock().unwrap();
+        kept.orbits = orbits;
+        kept.fit(most);
+        drop(kept);
+        Ok(cache)
+    }
+
+    pub fn write(&self, path : &Path) -> std::io::Result<()> {
+        let kept = self.kept.lock().unwrap();
+        let mut file = BufWriter::new(std::fs::File::create(path)?);
+        file.write_all(HEADER.as_bytes())?;
+        for orbit in &kept.orbits {
+            let (z_re, z_im) = orbit.z.parts();
+            writeln!(file, "orbit {} {} {} {} {} {} {}", orbit.c.bits, orbit.c.re, orbit.c.im,
+                     z_re, z_im, orbit.escaped, orbit.points.len())?;
+            for point in &orbit.points {
+                writeln!(file, "{:016x} {:016x}", point.re.to_bits(), point.im.to_bits())?;
+            }
+        }
+        file.flush()
+    }
+
+    //The orbit of `c` up to `limit`, as big::orbit() works it out
+    pub fn orbit(&self, c : &Point, limit : u32) -> Vec<Complex<f64>> {
+        let mut kept = self.kept.lock().unwrap();
+        let mut orbit = match kept.orbits.iter().position(|orbit| orbit.c == *c) {
+            Some(at) => kept.orbits.remove(at),
+            None => Orbit::new(c)
+        };
+        let had = orbit.points.len() - 1;
+        orbit.extend(limit);
+        verbose!("Reference orbit : {} iterations kept from before, {} more worked out",
+                 had.min(limit as usize), orbit.points.len() - 1 - had);
+        let points = orbit.to(limit).to_vec();
+        kept.orbits.push(orbit);
+        kept.fit(self.most);
+        points
+    }
+
+    //The reference the last frame kept, if any, for this one to measure
+    //from
+    pub fn take_reference(&self) -> Option<Reference> {
+        self.kept.lock().unwrap().reference.take()
+    }
+
+    pub fn keep_reference(&self, reference : Reference) {
+        self.kept.lock().unwrap().reference = Some(reference);
+    }
+
+    //The points of orbits held, for the tests
+    #[cfg(test)]
+    fn points(&self) -> usize {
+        self.kept.lock().unwrap().orbits.iter().map(|orbit| orbit.points.len()).sum()
+    }
+}
+
+impl Kept {
+    //Drops the orbits used longest ago until there are no more than `most`
+    //points left
+    fn fit(&mut self, most : usize) {
+        let mut points = self.orbits.iter().map(|orbit| orbit.points.len()).sum::<usize>();
+        while points > most {
+            points -= self.orbits.remove(0).points.len();
+        }
+    }
+}
+
+//The orbits of a cache file, or what is wrong with it
+fn parse(text : &str) -> Result<Vec<Orbit>, String> {
+    let text = text.strip_prefix(HEADER).ok_or("not an orbit cache")?;
+    let mut lines = text.lines().enumerate().map(|(index, line)| (index + 2, line));
+    let mut orbits = Vec::new();
+    while let Some((number, line)) = lines.next() {
+        let malformed = || format!("line {} : malformed orbit '{}'", number, line);
+        let fields = line.split(' ').collect::<Vec<_>>();
+        let (c, z, escaped, points) = match fields[..] {
+            ["orbit", bits, c_re, c_im, z_re, z_im, escaped, points] => {
+                let big = |text| BigInt::from_str(text).map_err(|_| malformed());
+                let bits = usize::from_str(bits).ok().filter(|&bits| bits > 0)
+                    .ok_or_else(malformed)?;
+                let c = Point { re : big(c_re)?, im : big(c_im)?, bits };
+                let z = Z::from_parts(&c, z_re, z_im).ok_or_else(malformed)?;
+                (c, z, bool::from_str(escaped).map_err(|_| malformed())?,
+                 usize::from_str(points).ok().filter(|&points| points > 0)
+                     .ok_or_else(malformed)?)
+            }
+            _ => return Err(malformed())
+        };
+        let mut orbit = Orbit { c, points : Vec::new(), z, escaped };
+        for _ in 0..points {
+            let (number, line) = lines.next()
+                .ok_or_else(|| format!("the orbit of line {} stops short", number))?;
+            let part = |text| u64::from_str_radix(text, 16).map(f64::from_bits);
+            let point = line.split_once(' ')
+                .and_then(|(re, im)| Some(Complex { re : part(re).ok()?, im : part(im).ok()? }))
+                .ok_or_else(|| format!("line {} : malformed point '{}'", number, line))?;
+            orbit.points.push(point);
+        }
+        orbits.push(orbit);
+    }
+    Ok(orbits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::big;
+
+    //Near the center of the period 3 bulb, whose orbit never escapes
+    const CENTER : &str =
+        "-0.122561166876653612372948457770316283612547918352917306549113,\
+         0.744861766619744236593170127024215496722536574210286018931651";
+
+    fn center(bits : usize) -> Point {
+        Point::parse(CENTER, bits).unwrap()
+    }
+
+    #[test]
+    fn kept_orbits_are_what_big_orbit_gives() {
+        let cache = Cache::new(MAX_POINTS);
+        //Worked out, taken as it is, cut short and taken on
+        for limit in [300, 300, 100, 2000] {
+            assert_eq!(cache.orbit(&center(160), limit), big::orbit(&center(160), limit));
+        }
+        assert_eq!(cache.points(), 2001);
+        //One that escapes, which there is no taking on
+        let escaping = Point::of(Complex { re : 0.5, im : 0.5 }, 160);
+        for limit in [10, 5000] {
+            assert_eq!(cache.orbit(&escaping, limit), big::orbit(&escaping, limit));
+        }
+        //Another point to the bit is another orbit
+        assert_eq!(cache.orbit(&center(256), 300), big::orbit(&center(256), 300));
+    }
+
+    #[test]
+    fn the_orbits_used_longest_ago_go_first() {
+        let cache = Cache::new(2500);
+        let (first, second) = (center(160), center(256));
+        cache.orbit(&first, 1000);
+        cache.orbit(&second, 1000);
+        cache.orbit(&first, 1000);
+        let third = Point::of(Complex { re : 0.0, im : 0.0 }, 160);
+        cache.orbit(&third, 1000);
+        let kept = cache.kept.lock().unwrap().orbits.iter()
+            .map(|orbit| orbit.c.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(kept, [first, third]);
+        //One too long to keep at all isn't
+        cache.orbit(&center(160), 3000);
+        assert!(cache.points() <= 2500);
+    }
+
+    #[test]
+    fn orbits_read_back_as_they_were_written() {
+        let path = std::env::temp_dir()
+            .join(format!("mandelbrot-orbits-{}.txt", std::process::id()));
+        let cache = Cache::new(MAX_POINTS);
+        cache.orbit(&center(160), 500);
+        cache.orbit(&Point::of(Complex { re : 0.5, im : 0.5 }, 128), 500);
+        cache.write(&path).unwrap();
+        let read = Cache::read(&path, MAX_POINTS).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(read.kept.lock().unwrap().orbits, cache.kept.lock().unwrap().orbits);
+        //And go on from where they got to
+        assert_eq!(read.orbit(&center(160), 1500), big::orbit(&center(160), 1500));
+        //No file is no orbits
+        assert_eq!(Cache::read(&path, MAX_POINTS).unwrap().points(), 0);
+    }
+
+    #[test]
+    fn a_file_that_isnt_an_orbit_cache_is_refused() {
+        //The line of an orbit of 1,1 from Z = 0
+        let (re, im) = Orbit::new(&Point::of(Complex { re : 1.0, im : 1.0 }, 64)).z.parts();
+        let line = |bits, escaped, points| {
+            format!("orbit {} 1 1 {} {} {} {}", bits, re, im, escaped, points)
+        };
+        assert!(parse(&format!("{}\n0 0\n", line(64, "false", "1"))).is_err());
+        let orbit = format!("{}{}\n{:016x} 0\n", HEADER, line(64, "false", "2"),
+                            1.5f64.to_bits());
+        assert_eq!(parse(&orbit).unwrap_err(), "the orbit of line 2 stops short");
+        for line in [line(64, "false", "0"), line(64, "maybe", "1"), line(0, "false", "1"),
+                     "orbit 64 x 1 1 1".to_string(), format!("orbit 64 1 1 {} zz false 1", re),
+                     line(64, "false", "1").replace("orbit", "path")] {
+            assert!(parse(&format!("{}{}\n0 0\n", HEADER, line)).is_err(), "{}", line);
+        }
+        assert!(parse(&format!("{}{}\n0 zz\n", HEADER, line(64, "false", "1"))).is_err());
+        assert_eq!(parse(&format!("{}{}\n0 0\n", HEADER, line(64, "false", "1"))).unwrap()
+                       .len(), 1);
+        assert_eq!(parse(HEADER).unwrap().len(), 0);
+    }
+}
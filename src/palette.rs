@@ -0,0 +1,315 @@
+//<***************Colour palettes*****************>//
+//A palette is a lookup table of colours indexed directly by escape count:
+//a point that escapes after `count` iterations gets colours[count].
+//
+//The table is usually much shorter than the iteration limit, so counts past
+//its end have to go somewhere. By default they are clamped to the last
+//colour (every slow-escaping point looks the same); with wrapping they cycle
+//back through the table from the start (count % len), which keeps bands of
+//colour going all the way up to the limit.
+//
+//Colours are blended between stops as the sRGB values they are written in
+//unless the palette is built in linear light, where the stops are decoded
+//to the light they stand for, blended and encoded back. Blending the
+//encoded values comes out too dark halfway between two bright colours,
+//which shows as muddy midtones.
+
+use crate::text;
+
+#[derive(Clone)]
+pub struct Palette {
+    colors : Vec<[u8; 3]>,
+    //The stops the table was interpolated between, empty for
+    //from_colors() tables that are used as they are
+    stops : Vec<[u8; 3]>,
+    wrap : bool,
+    //What parse() would take to give back this palette
+    spec : String
+}
+
+//Entries in the built-in palettes
+const BUILTIN_SIZE : usize = 256;
+
+//Built-in palettes and the colour stops they are interpolated between
+const BUILTINS : &[(&str, &[[u8; 3]])] = &[
+    ("gray", &[[255, 255, 255], [0, 0, 0]]),
+    ("fire", &[[0, 0, 0], [128, 0, 0], [255, 64, 0], [255, 200, 0], [255, 255, 255]]),
+    ("ocean", &[[0, 0, 48], [0, 64, 160], [0, 200, 220], [255, 255, 255]]),
+    //matplotlib's perceptually uniform colormaps at every tenth of the way,
+    //for --heatmap
+    ("viridis", &[[68, 1, 84], [72, 36, 117], [65, 68, 135], [53, 95, 141], [42, 120, 142],
+                  [33, 145, 140], [34, 168, 132], [68, 191, 112], [122, 209, 81],
+                  [189, 223, 38], [253, 231, 37]]),
+    ("magma", &[[0, 0, 4], [20, 14, 54], [59, 15, 112], [100, 26, 128], [140, 41, 129],
+                [183, 55, 121], [222, 73, 104], [247, 112, 92], [254, 159, 109],
+                [254, 207, 146], [252, 253, 191]])
+];
+
+//How the colours between two stops are worked out
+#[derive(Clone, Copy, PartialEq)]
+pub enum Blend {
+    Srgb,
+    Linear
+}
+
+impl Palette {
+    //Builds a table of `size` colours going linearly through the stops
+    pub fn from_stops(stops : &[[u8; 3]], size : usize, blend : Blend) -> Palette {
+        assert!(stops.len() >= 2, "a palette needs at least two colour stops");
+        assert!(size >= 2, "a palette needs at least two entries");
+
+        let segments = (stops.len() - 1) as f64;
+        let colors = (0..size).map(|i| {
+            let position = i as f64 / (size - 1) as f64 * segments;
+            let segment = (position as usize).min(stops.len() - 2);
+            let t = position - segment as f64;
+            let (from, to) = (stops[segment], stops[segment + 1]);
+            let mut color = [0; 3];
+            for channel in 0..3 {
+                color[channel] = match blend {
+                    Blend::Srgb => {
+                        let value = from[channel] as f64
+                            + (to[channel] as f64 - from[channel] as f64) * t;
+                        value.round() as u8
+                    }
+                    Blend::Linear => {
+                        let (from, to) = (to_linear(from[channel]), to_linear(to[channel]));
+                        from_linear(from + (to - from) * t)
+                    }
+                };
+            }
+            color
+        }).collect();
+
+        Palette { colors, stops : stops.to_vec(), wrap : false, spec : hex_list(stops) }
+    }
+
+    //The same palette with its table built again blending in linear light,
+    //None for a palette that has no stops to blend between
+    pub fn blended_linearly(&self) -> Option<Palette> {
+        if self.stops.is_empty() {
+            return None;
+        }
+        Some(Palette {
+            spec : self.spec.clone(),
+            wrap : self.wrap,
+            ..Palette::from_stops(&self.stops, self.colors.len(), Blend::Linear)
+        })
+    }
+
+    //Uses the colours as they are, one table entry each
+    pub fn from_colors(colors : Vec<[u8; 3]>) -> Palette {
+        assert!(!colors.is_empty(), "a palette needs at least one colour");
+        let spec = hex_list(&colors);
+        Palette { colors, stops : Vec::new(), wrap : false, spec }
+    }
+
+    pub fn builtin(name : &str) -> Option<Palette> {
+        BUILTINS.iter()
+            .find(|&&(builtin, _)| builtin == name)
+            .map(|&(builtin, stops)| Palette {
+                spec : builtin.to_string(),
+                ..Palette::from_stops(stops, BUILTIN_SIZE, Blend::Srgb)
+            })
+    }
+
+    pub fn builtin_names() -> Vec<&'static str> {
+        BUILTINS.iter().map(|&(name, _)| name).collect()
+    }
+
+    //Either the name of a built-in palette or a comma separated list of
+    //#rrggbb colours making up the table
+    pub fn parse(spec : &str) -> Result<Palette, String> {
+        if let Some(palette) = Palette::builtin(spec) {
+            return Ok(palette);
+        }
+        if !spec.starts_with('#') {
+            return Err(format!("unknown palette '{}', expected one of {} \
+                                or a list of #rrggbb colours",
+                               spec, Palette::builtin_names().join(", ")));
+        }
+        let colors = spec.split(',')
+            .map(parse_hex_color)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Palette::from_colors(colors))
+    }
+
+    pub fn spec(&self) -> &str {
+        &self.spec
+    }
+
+    pub fn with_wrap(mut self, wrap : bool) -> Palette {
+        self.wrap = wrap;
+        self
+    }
+
+    //The colour for a point that escaped after `count` iterations.
+    //Never indexes outside the table, see the top of the file for how
+    //counts past the end are treated.
+    pub fn color(&self, count : u32) -> [u8; 3] {
+        let count = count as usize;
+        let index = if count < self.colors.len() {
+            count
+        } else if self.wrap {
+            count % self.colors.len()
+        } else {
+            self.colors.len() - 1
+        };
+        self.colors[index]
+    }
+
+    //The colour for a smooth count (see blend.rs), between the colours of
+    //the whole counts either side of it in linear light
+    pub fn between(&self, count : f64) -> [u8; 3] {
+        let whole = count.floor();
+        let (low, high) = (self.color(whole as u32), self.color(whole as u32 + 1));
+        let fraction = count - whole;
+        let mut color = [0; 3];
+        for (channel, value) in color.iter_mut().enumerate() {
+            *value = from_linear(to_linear(low[channel]) * (1.0 - fraction)
+                                 + to_linear(high[channel]) * fraction);
+        }
+        color
+    }
+
+    //The colour `fraction` (0 to 1) of the way along the table
+    pub fn at(&self, fraction : f64) -> [u8; 3] {
+        let last = self.colors.len() - 1;
+        self.colors[((fraction * last as f64).round() as usize).min(last)]
+    }
+}
+
+//The sRGB transfer function, between an 8-bit encoded value and the
+//linear light (0 to 1) it stands for
+pub fn to_linear(value : u8) -> f64 {
+    let value = value as f64 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn from_linear(light : f64) -> u8 {
+    let value = if light <= 0.0031308 {
+        light * 12.92
+    } else {
+        1.055 * light.powf(1.0 / 2.4) - 0.055
+    };
+    (value * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+//<***************Palette swatches*****************>//
+//A strip of the colours counts 0 to `limit` get, left to right, for
+//picking a palette without rendering anything. Under the strip the two
+//ends are labelled with their counts when there's room, in the font of
+//text.rs scaled up with the height.
+impl Palette {
+    pub fn swatch(&self, limit : u32, bounds : (usize, usize)) -> Vec<u8> {
+        let (width, height) = bounds;
+        let scale = (height / 24).max(1);
+        let labels = [0.to_string(), limit.to_string()];
+        let label_width = |label : &String| text::width(label, scale);
+        let label_height = 7 * scale;
+        let labelled = height > label_height * 2
+            && label_width(&labels[0]) + label_width(&labels[1]) + 3 * scale <= width;
+        let strip = if labelled { height - label_height } else { height };
+
+        let mut pixels = vec![0; width * height * 3];
+        for column in 0..width {
+            let count = if width > 1 {
+                (column as f64 * limit as f64 / (width - 1) as f64).round() as u32
+            } else {
+                0
+            };
+            let color = self.color(count);
+            for row in 0..strip {
+                let index = (row * width + column) * 3;
+                pixels[index..index + 3].copy_from_slice(&color);
+            }
+        }
+
+        if labelled {
+            let top = strip + scale;
+            text::draw(&mut pixels, width, &labels[0], (scale, top), scale, [255, 255, 255]);
+            let right = width - scale - label_width(&labels[1]);
+            text::draw(&mut pixels, width, &labels[1], (right, top), scale, [255, 255, 255]);
+        }
+        pixels
+    }
+}
+
+fn hex_list(colors : &[[u8; 3]]) -> String {
+    colors.iter()
+        .map(|color| format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2]))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+pub fn parse_hex_color(s : &str) -> Result<[u8; 3], String> {
+    let hex = s.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        return Err(format!("expected a colour like #ff8800, got '{}'", s));
+    }
+    let mut color = [0; 3];
+    for (channel, value) in color.iter_mut().enumerate() {
+        *value = u8::from_str_radix(&hex[channel * 2..channel * 2 + 2], 16)
+            .map_err(|_| format!("expected a colour like #ff8800, got '{}'", s))?;
+    }
+    Ok(color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE_TO_BLACK : &[[u8; 3]] = &[[255, 255, 255], [0, 0, 0]];
+
+    #[test]
+    fn linear_light_blends_midtones_brighter() {
+        //Half the light of white is 188 encoded, half the encoded value 128
+        let linear = Palette::from_stops(WHITE_TO_BLACK, 3, Blend::Linear);
+        let srgb = Palette::from_stops(WHITE_TO_BLACK, 3, Blend::Srgb);
+        assert_eq!(linear.color(1), [188; 3]);
+        assert_eq!(srgb.color(1), [128; 3]);
+        //The stops themselves come out as they are either way
+        for palette in [&linear, &srgb] {
+            assert_eq!((palette.color(0), palette.color(2)), ([255; 3], [0; 3]));
+        }
+        assert_eq!(Palette::builtin("gray").unwrap().blended_linearly().unwrap()
+                   .colors, Palette::from_stops(WHITE_TO_BLACK, BUILTIN_SIZE,
+                                                Blend::Linear).colors);
+    }
+
+    #[test]
+    fn counts_past_the_table_clamp_or_wrap() {
+        let colors = vec![[0, 0, 0], [10, 10, 10], [20, 20, 20]];
+        let clamped = Palette::from_colors(colors.clone());
+        let wrapped = Palette::from_colors(colors.clone()).with_wrap(true);
+        for count in 0..3 {
+            assert_eq!(clamped.color(count), colors[count as usize]);
+            assert_eq!(wrapped.color(count), colors[count as usize]);
+        }
+        //At the length and past it, the last colour or round again from the
+        //first
+        for (count, index) in [(3, 0), (4, 1), (5, 2), (6, 0), (301, 1)] {
+            assert_eq!(clamped.color(count), colors[2], "count {}", count);
+            assert_eq!(wrapped.color(count), colors[index], "count {}", count);
+        }
+        assert_eq!(clamped.color(u32::MAX), colors[2]);
+        assert_eq!(wrapped.color(u32::MAX), colors[u32::MAX as usize % 3]);
+        //A table of one colour gives it for everything
+        let single = Palette::from_colors(vec![[1, 2, 3]]).with_wrap(true);
+        assert_eq!((single.color(1), single.color(7)), ([1, 2, 3], [1, 2, 3]));
+    }
+
+    #[test]
+    fn only_palettes_with_stops_blend_linearly() {
+        //A #rrggbb list is used as it is, there is nothing to blend
+        let list = Palette::parse("#ffffff,#808080,#000000").unwrap();
+        assert!(list.blended_linearly().is_none());
+        let fire = Palette::parse("fire").unwrap().with_wrap(true);
+        let blended = fire.blended_linearly().unwrap();
+        assert_eq!((blended.spec(), blended.wrap), ("fire", true));
+    }
+}
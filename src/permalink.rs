@@ -0,0 +1,81 @@
+//<***************Permalinks*****************>//
+//A view as one line of text, short enough to paste into a chat or append
+//to a viewer's URL as its #fragment. --permalink prints it and
+//--from-permalink PERMALINK renders it again :
+//
+//    v1.Y2VudGVyPSItMC41LDAiCndpZHRoPTMKaXRlcmF0aW9ucz0xMDAw
+//
+//is the version, a dot and the URL safe base64 (no padding) of the keys a
+//bookmark keeps, one `key=value` line each as in a config file, leaving out
+//those at their defaults :
+//
+//    center="-0.5,0"
+//    width=3
+//    iterations=1000
+//
+//New options only add keys, which a v1 permalink can hold as it is. The
+//version goes up if the part after the dot ever changes its form, and a
+//permalink of a version this program doesn't know is an error rather than
+//being read as something else.
+
+use crate::toml::{self, Entry};
+
+const VERSION : &str = "v1";
+
+const ALPHABET : &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+//The permalink of the `key=value` lines `lines`
+pub fn encode(lines : &[String]) -> String {
+    format!("{}.{}", VERSION, base64(lines.join("\n").as_bytes()))
+}
+
+//The entries of `permalink`, without a [section]
+pub fn decode(permalink : &str) -> Result<Vec<Entry>, String> {
+    let (version, payload) = permalink.trim().split_once('.')
+        .ok_or_else(|| "expected VERSION.DATA as --permalink prints it".to_string())?;
+    if version != VERSION {
+        return Err(format!("version '{}' isn't known to this program, which reads {}",
+                           version, VERSION));
+    }
+    let bytes = unbase64(payload)?;
+    let text = String::from_utf8(bytes)
+        .map_err(|_| "the data isn't a permalink's keys".to_string())?;
+    let entries = toml::parse(&text)?;
+    match entries.iter().find(|entry| entry.section.is_some()) {
+        Some(entry) => Err(format!("line {} : a permalink has no [sections]", entry.line)),
+        None => Ok(entries)
+    }
+}
+
+//Three bytes to four characters, the last group cut short instead of padded
+fn base64(bytes : &[u8]) -> String {
+    let mut text = String::with_capacity((bytes.len() * 4).div_ceil(3));
+    for group in bytes.chunks(3) {
+        let bits = group.iter().enumerate()
+            .fold(0u32, |bits, (index, &byte)| bits | (byte as u32) << (16 - 8 * index));
+        for index in 0..group.len() + 1 {
+            text.push(ALPHABET[(bits >> (18 - 6 * index) & 63) as usize] as char);
+        }
+    }
+    text
+}
+
+fn unbase64(text : &str) -> Result<Vec<u8>, String> {
+    let values = text.bytes()
+        .map(|c| ALPHABET.iter().position(|&letter| letter == c)
+             .ok_or_else(|| format!("'{}' isn't a base64 character", c as char)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut bytes = Vec::with_capacity(values.len() * 3 / 4);
+    for group in values.chunks(4) {
+        if group.len() == 1 {
+            return Err("the data is cut short".to_string());
+        }
+        let bits = group.iter().enumerate()
+            .fold(0u32, |bits, (index, &value)| bits | (value as u32) << (18 - 6 * index));
+        for index in 0..group.len() - 1 {
+            bytes.push((bits >> (16 - 8 * index)) as u8);
+        }
+    }
+    Ok(bytes)
+}
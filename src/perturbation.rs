@@ -0,0 +1,647 @@
+//<***************Perturbation from a reference orbit*****************>//
+//Once a view is narrower than about 1e-13, neighbouring pixels are closer
+//together than f64 can tell apart near the set and the plain render turns
+//blocky. Perturbation iterates one reference point C in higher precision
+//and every pixel c = C + dc only as its difference from that orbit :
+//
+//    z(n+1) = Z(n+1) + dz(n+1),    dz(n+1) = 2 Z(n) dz(n) + dz(n)^2 + dc
+//
+//The differences are small enough to be f64 no matter how deep the zoom.
+//The reference orbit is worked out in double-double arithmetic (a pair of
+//f64s, about 32 significant digits, see double.rs), which is what keeps it
+//accurate.
+//
+//A pixel whose z comes much closer to 0 than the reference's Z does has
+//lost the precision of its difference (a "glitch"). The test is
+//Pauldelbrot's criterion, checked after every iteration :
+//
+//    |Z(n) + dz(n)| < |Z(n)| / 1000,   i.e. |z|^2 < GLITCH_TOLERANCE |Z|^2
+//
+//z then is mostly the rounding error of dz, and where it goes from there
+//has little to do with where the pixel's own orbit goes. A pixel that is
+//still going when the reference orbit has escaped is a glitch too, there
+//being nothing left to measure it from. Unchecked, both come out as flat
+//blobs of the wrong count, the reference's own where it escaped first.
+//
+//The glitched pixels are gathered into the regions they touch each other
+//in, and every region is iterated again against a new reference at the
+//pixel nearest its middle, which has orbits much like theirs. That goes on
+//in passes, over whatever is still glitched, until no more than
+//GLITCH_FRACTION of the image is or MAX_GLITCH_PASSES passes have been
+//made, and the rest falls back to the plain f64 iteration. -v reports the
+//passes, --show-glitches marks the pixels the first reference glitched.
+//
+//The view's corners are still f64, so a zoom can't go deeper than f64 can
+//place the corners apart (a width of roughly 1e-15 near the origin).
+//--perturbation goes past that, see deep_counts().
+
+use num::Complex;
+use std::ops::ControlFlow::{Break, Continue};
+
+use crate::big::{self, Point};
+use crate::config::View;
+use crate::double::Double;
+use crate::orbits::{Cache, KEPT_GLITCHES};
+use crate::{escape_time, in_stretches, pixel_to_point, render_bands_within, rows_of, Budget,
+            Renderer};
+
+//|z|^2 below this fraction of |Z|^2 counts as a glitch, i.e. |z| < |Z|/1000
+const GLITCH_TOLERANCE : f64 = 1e-6;
+
+//Glitch passes stop once no more than this fraction of the pixels is
+//glitched, the rest being few enough for f64
+const GLITCH_FRACTION : f64 = 1e-4;
+const MAX_GLITCH_PASSES : usize = 8;
+//New references per pass at most, for the largest regions, the rest
+//waiting for the next pass
+const MAX_PASS_REFERENCES : usize = 32;
+
+//How the glitches of a render went
+pub struct Glitches {
+    //The indices of the pixels that glitched against the first reference
+    pub first : Vec<usize>,
+    //The passes of new references made over them
+    pub passes : usize,
+    //How many of them the new references fixed, the rest having been left
+    //to f64
+    pub fixed : usize
+}
+
+//The escape counts of the pixels of `view`, the same as escape_time()
+//gives for them within what f64 can resolve, and how their glitches went.
+//The first reference is the one `cache` kept from the frame before if
+//there is one for this view, see orbits.rs.
+pub fn counts(renderer : Option<&Renderer>, budget : Option<&Budget>, view : &View,
+              limit : u32, cache : Option<&Cache>)
+    -> (Vec<Option<u32>>, Glitches)
+{
+    let View { bounds, upper_left, lower_right, .. } = *view;
+    let step = ((lower_right.re - upper_left.re) / bounds.0 as f64,
+                (upper_left.im - lower_right.im) / bounds.1 as f64);
+    let first = cache.and_then(Cache::take_reference)
+        .and_then(|kept| kept.moved(upper_left, step, bounds, limit))
+        .unwrap_or_else(|| Reference::new(upper_left, step,
+                                          (bounds.0 as f64 / 2.0, bounds.1 as f64 / 2.0),
+                                          limit));
+
+    //Anything a budget keeps from being reached stays interior (black)
+    let mut outcomes = vec![Outcome::Stayed;bounds.0 * bounds.1];
+    render_bands_within(renderer, budget, &mut outcomes, bounds, |band, top| {
+        for (i, outcome) in band.iter_mut().enumerate() {
+            let pixel = (i % bounds.0, top + i / bounds.0);
+            *outcome = first.iterate(first.delta(pixel, step), limit, true);
+        }
+    });
+
+    let first_glitched = (0..outcomes.len())
+        .filter(|&index| outcomes[index] == Outcome::Glitched)
+        .collect::<Vec<_>>();
+    if let Some(cache) = cache {
+        if first_glitched.len() as f64 <= KEPT_GLITCHES * outcomes.len() as f64 {
+            cache.keep_reference(first);
+        }
+    }
+    let mut glitched = first_glitched.clone();
+    let mut passes = 0;
+    while passes < MAX_GLITCH_PASSES
+        && glitched.len() as f64 > GLITCH_FRACTION * outcomes.len() as f64
+        && !budget.is_some_and(Budget::run_out)
+    {
+        passes += 1;
+        for region in regions(&glitched, bounds).iter().take(MAX_PASS_REFERENCES) {
+            let at = middle(region, bounds);
+            let reference = Reference::new(upper_left, step,
+                                           ((at % bounds.0) as f64, (at / bounds.0) as f64),
+                                           limit);
+            for &index in region {
+                let pixel = (index % bounds.0, index / bounds.0);
+                outcomes[index] = reference.iterate(reference.delta(pixel, step), limit, true);
+            }
+        }
+        glitched.retain(|&index| outcomes[index] == Outcome::Glitched);
+    }
+    let fixed = first_glitched.len() - glitched.len();
+    verbose!("Glitch correction : {} of {} pixels glitched against the first reference, \
+              {} passes of new references fixed {}, {} left to f64", first_glitched.len(),
+             outcomes.len(), passes, fixed, glitched.len());
+    if !budget.is_some_and(Budget::run_out) {
+        for &index in &glitched {
+            let pixel = (index % bounds.0, index / bounds.0);
+            outcomes[index] = match escape_time(pixel_to_point(view, pixel), limit) {
+                Some(count) => Outcome::Escaped(count),
+                None => Outcome::Stayed
+            };
+        }
+    }
+
+    let counts = outcomes.iter()
+        .map(|outcome| match *outcome {
+            Outcome::Escaped(count) => Some(count),
+            Outcome::Stayed | Outcome::Glitched => None
+        })
+        .collect();
+    (counts, Glitches { first : first_glitched, passes, fixed })
+}
+
+//The escape counts of the pixels of `view` against the first reference
+//alone with no check for glitches, what perturbation comes to without
+//them (for the tests to tell the corrected counts from)
+#[cfg(test)]
+pub fn unchecked_counts(view : &View, limit : u32) -> Vec<Option<u32>> {
+    let View { bounds, upper_left, lower_right, .. } = *view;
+    let step = ((lower_right.re - upper_left.re) / bounds.0 as f64,
+                (upper_left.im - lower_right.im) / bounds.1 as f64);
+    let first = Reference::new(upper_left, step,
+                               (bounds.0 as f64 / 2.0, bounds.1 as f64 / 2.0), limit);
+    (0..bounds.0 * bounds.1)
+        .map(|index| {
+            let pixel = (index % bounds.0, index / bounds.0);
+            match first.iterate(first.delta(pixel, step), limit, false) {
+                Outcome::Escaped(count) => Some(count),
+                Outcome::Stayed | Outcome::Glitched => None
+            }
+        })
+        .collect()
+}
+
+//The glitched pixels in the regions they touch each other in (diagonally
+//too), the largest first
+fn regions(glitched : &[usize], bounds : (usize, usize)) -> Vec<Vec<usize>> {
+    let mut left = vec![false; bounds.0 * bounds.1];
+    for &index in glitched {
+        left[index] = true;
+    }
+    let mut regions = Vec::new();
+    for &start in glitched {
+        if !left[start] {
+            continue;
+        }
+        left[start] = false;
+        let mut region = vec![start];
+        let mut next = 0;
+        while next < region.len() {
+            let (column, row) = (region[next] % bounds.0, region[next] / bounds.0);
+            next += 1;
+            for row in row.saturating_sub(1)..(row + 2).min(bounds.1) {
+                for column in column.saturating_sub(1)..(column + 2).min(bounds.0) {
+                    let index = column + bounds.0 * row;
+                    if left[index] {
+                        left[index] = false;
+                        region.push(index);
+                    }
+                }
+            }
+        }
+        regions.push(region);
+    }
+    regions.sort_by_key(|region| std::cmp::Reverse(region.len()));
+    regions
+}
+
+//The pixel of `region` nearest its middle, which needn't be one of its
+//pixels itself
+fn middle(region : &[usize], bounds : (usize, usize)) -> usize {
+    let sums = region.iter().fold((0.0, 0.0), |(column, row), &index| {
+        (column + (index % bounds.0) as f64, row + (index / bounds.0) as f64)
+    });
+    let middle = (sums.0 / region.len() as f64, sums.1 / region.len() as f64);
+    let distance = |index : usize| {
+        let (column, row) = ((index % bounds.0) as f64 - middle.0,
+                             (index / bounds.0) as f64 - middle.1);
+        column * column + row * row
+    };
+    region.iter().copied()
+        .min_by(|&a, &b| distance(a).total_cmp(&distance(b)))
+        .unwrap_or(region[0])
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Outcome {
+    Escaped(u32),
+    Stayed,
+    Glitched
+}
+
+pub struct Reference {
+    //The (fractional) pixel the orbit starts from
+    pixel : (f64, f64),
+    //Its point, and the last Z of the orbit in full
+    c : (Double, Double),
+    z : (Double, Double),
+    //Z(0) = 0 up to the limit, or up to where the orbit escaped
+    orbit : Vec<Complex<f64>>
+}
+
+impl Reference {
+    //The orbit of the point `pixel` is at, `step` being the size of a pixel.
+    //The point is upper_left + pixel * step worked out in double-double, so
+    //that it is exactly where the pixels' differences are measured from.
+    fn new(upper_left : Complex<f64>, step : (f64, f64), pixel : (f64, f64), limit : u32)
+        -> Reference
+    {
+        let c_re = Double::from(upper_left.re) + Double::product(pixel.0, step.0);
+        let c_im = Double::from(upper_left.im) + Double::product(-pixel.1, step.1);
+        let zero = Double::from(0.0);
+        let mut reference = Reference { pixel, c : (c_re, c_im), z : (zero, zero),
+                                        orbit : vec![Complex { re : 0.0, im : 0.0 }] };
+        reference.extend(limit);
+        reference
+    }
+
+    fn escaped(&self) -> bool {
+        self.orbit.last().is_some_and(|z| z.norm_sqr() > 4.0)
+    }
+
+    //The orbit taken on to `limit` iterations, unless it has escaped
+    fn extend(&mut self, limit : u32) {
+        let from = self.orbit.len() as u32 - 1;
+        if self.escaped() || from >= limit {
+            return;
+        }
+        let (c_re, c_im) = self.c;
+        let orbit = std::mem::take(&mut self.orbit);
+        let iterated = in_stretches(from..limit, (self.z, orbit),
+                                    move |((mut re, mut im), mut orbit), stretch| {
+            for _ in stretch {
+                let square_re = re * re - im * im;
+                let twice_re_im = (re * im).doubled();
+                re = square_re + c_re;
+                im = twice_re_im + c_im;
+
+                let z = Complex { re : re.hi, im : im.hi };
+                orbit.push(z);
+                if z.norm_sqr() > 4.0 {
+                    return Break(orbit);
+                }
+            }
+            Continue(((re, im), orbit))
+        });
+        match iterated {
+            Break(orbit) => self.orbit = orbit,
+            Continue((z, orbit)) => {
+                self.orbit = orbit;
+                self.z = z;
+            }
+        }
+    }
+
+    //The same reference for a view with the upper left corner and pixels
+    //of `upper_left` and `step`, taken on to `limit`, if its point is in the
+    //`bounds` of that view
+    fn moved(mut self, upper_left : Complex<f64>, step : (f64, f64), bounds : (usize, usize),
+             limit : u32)
+        -> Option<Reference>
+    {
+        let pixel = ((self.c.0 - Double::from(upper_left.re)) / Double::from(step.0),
+                     (Double::from(upper_left.im) - self.c.1) / Double::from(step.1));
+        let pixel = (pixel.0.hi, pixel.1.hi);
+        let inside = |at : f64, across : usize| (0.0..=across as f64).contains(&at);
+        if !inside(pixel.0, bounds.0) || !inside(pixel.1, bounds.1) {
+            return None;
+        }
+        self.pixel = pixel;
+        self.extend(limit);
+        Some(self)
+    }
+
+    fn delta(&self, pixel : (usize, usize), step : (f64, f64)) -> Complex<f64> {
+        Complex {
+            re : (pixel.0 as f64 - self.pixel.0) * step.0,
+            im : -(pixel.1 as f64 - self.pixel.1) * step.1
+        }
+    }
+
+    //Same counting as escape_z() : the count is one less than the number
+    //of iterations it took z to leave the radius 2 circle. Unless `checked`,
+    //glitches go unnoticed and a pixel outliving the reference takes its
+    //count.
+    fn iterate(&self, dc : Complex<f64>, limit : u32, checked : bool) -> Outcome {
+        let orbit = &self.orbit;
+        let dz = Complex { re : 0.0, im : 0.0 };
+        in_stretches(0..limit, dz, move |mut dz, stretch| {
+            for i in stretch.start as usize..stretch.end as usize {
+                if i + 1 >= orbit.len() {
+                    //The reference escaped before this pixel did, there is
+                    //nothing left to measure from
+                    return Break(match checked {
+                        true => Outcome::Glitched,
+                        false => Outcome::Escaped(orbit.len() as u32 - 2)
+                    });
+                }
+                dz = (orbit[i] + orbit[i]) * dz + dz * dz + dc;
+                let z = orbit[i + 1] + dz;
+                if z.norm_sqr() > 4.0 {
+                    return Break(Outcome::Escaped(i as u32));
+                }
+                if checked && z.norm_sqr() < GLITCH_TOLERANCE * orbit[i + 1].norm_sqr() {
+                    return Break(Outcome::Glitched);
+                }
+            }
+            Continue(dz)
+        }).break_value().unwrap_or(Outcome::Stayed)
+    }
+}
+
+//<********From a fixed point reference*********>//
+//For --perturbation : the same differences under --precision fixed128 or
+//big:BITS, whose views are laid out around their center (see big.rs), at
+//zooms where double-doubles can't hold the reference either. The one orbit
+//of the center is iterated in BigInts to the precision's bits, and stored
+//as f64 for every pixel to read, which is as much of it as the differences
+//need. Each pixel's dc is then its offset from the center, an f64 however
+//deep the zoom (down to about 1e-300, where f64 runs out of exponent).
+//
+//Instead of picking new references for the pixels that glitch, a pixel is
+//rebased (Zhuoran's method) : once its z comes closer to 0 than its dz is
+//large, or the reference has escaped and there is no more of it, its whole
+//z becomes the difference from the start of the reference orbit, dz = z
+//against Z(0) = 0, and it goes on iterating from there. That keeps dz the
+//small part of z, so no glitch survives it and the one orbit is enough.
+
+//The escape counts of the pixels of `view`, whose corners are offsets from
+//`center`, as big::counts() gives them but for the edge of the set, the
+//orbit of the center kept in `cache` if there is one
+pub fn deep_counts(renderer : Option<&Renderer>, budget : Option<&Budget>, view : &View,
+                   center : &Point, limit : u32, cache : Option<&Cache>)
+    -> Vec<Option<u32>>
+{
+    let orbit = match cache {
+        Some(cache) => cache.orbit(center, limit),
+        None => big::orbit(center, limit)
+    };
+    verbose!("Reference orbit of {} iterations at {}", orbit.len() - 1, center.show());
+    let series = fitted(&orbit, &probes(view), limit);
+    info!("Series approximation : every pixel starts at iteration {} of {}", series.skip,
+          limit);
+    let bounds = view.bounds;
+    let mut counts = vec![None;bounds.0 * bounds.1];
+    render_bands_within(renderer, budget, &mut counts, bounds, |band, top| {
+        for row in 0..rows_of(band.len(), top, bounds) {
+            for column in 0..bounds.0 {
+                let dc = pixel_to_point(view, (column, top + row));
+                band[column + bounds.0 * row] =
+                    rebased(&orbit, dc, (series.skip, series.at(dc)), limit);
+            }
+        }
+    });
+    counts
+}
+
+//The iterations deep_counts() skips over for the pixels of `view`
+#[cfg(test)]
+pub fn skipped(view : &View, center : &Point, limit : u32) -> usize {
+    fitted(&big::orbit(center, limit), &probes(view), limit).skip
+}
+
+//The count of the point `dc` from iteration `from.0` on, where its
+//difference from `orbit` is `from.1`, the same counting as escape_time()
+fn rebased(orbit : &[Complex<f64>], dc : Complex<f64>, from : (usize, Complex<f64>),
+           limit : u32)
+    -> Option<u32>
+{
+    in_stretches(from.0 as u32..limit, from, move |(mut at, mut dz), stretch| {
+        for i in stretch {
+            dz = (orbit[at] + orbit[at]) * dz + dz * dz + dc;
+            at += 1;
+            let z = orbit[at] + dz;
+            if z.norm_sqr() > 4.0 {
+                return Break(i);
+            }
+            if z.norm_sqr() < dz.norm_sqr() || at + 1 == orbit.len() {
+                dz = z;
+                at = 0;
+            }
+        }
+        Continue((at, dz))
+    }).break_value()
+}
+
+//<********Series approximation*********>//
+//Deep in, every pixel's orbit follows the reference's for thousands of
+//iterations, and all that while dz is very nearly the same polynomial in
+//dc for all of them, the iteration of dz with the powers of dc gathered :
+//
+//    dz(n) = A(n) dc + B(n) dc^2 + C(n) dc^3
+//    A(n+1) = 2 Z(n) A(n) + 1
+//    B(n+1) = 2 Z(n) B(n) + A(n)^2
+//    C(n+1) = 2 Z(n) C(n) + 2 A(n) B(n)
+//
+//from A, B and C all 0. The coefficients are worked out once along the
+//reference orbit, as far as the polynomial still gives the dz of the
+//probes (the corners and the middles of the edges, the pixels furthest
+//from the center, where it is worst) to within SERIES_TOLERANCE of
+//iterating them, and every pixel then starts its iteration from there with
+//its dz from the polynomial. As a guard the probes are counted to the end
+//with the skip and without it, and the skip is halved until they agree.
+
+//How far off the probes' dz may be from the polynomial relative to dz, a
+//pixel being moved by that much of its offset from the center at most
+const SERIES_TOLERANCE : f64 = 1e-6;
+
+struct Series {
+    //The iteration the pixels start at
+    skip : usize,
+    //A, B and C there
+    coefficients : [Complex<f64>; 3]
+}
+
+impl Series {
+    fn at(&self, dc : Complex<f64>) -> Complex<f64> {
+        let [a, b, c] = self.coefficients;
+        ((c * dc + b) * dc + a) * dc
+    }
+}
+
+//The offsets of the pixels the series is checked at
+fn probes(view : &View) -> Vec<Complex<f64>> {
+    let (width, height) = view.bounds;
+    [(0, 0), (width / 2, 0), (width, 0), (0, height / 2), (width, height / 2), (0, height),
+     (width / 2, height), (width, height)].iter()
+        .map(|&pixel| pixel_to_point(view, pixel))
+        .collect()
+}
+
+//The series along `orbit` as far as it holds for the probes, cut back to
+//where they count the same with it as without
+fn fitted(orbit : &[Complex<f64>], probes : &[Complex<f64>], limit : u32) -> Series {
+    let zero = Complex { re : 0.0, im : 0.0 };
+    let counts = probes.iter()
+        .map(|&dc| rebased(orbit, dc, (0, zero), limit))
+        .collect::<Vec<_>>();
+    let mut series = series(orbit, probes, orbit.len());
+    while series.skip > 0 {
+        let agree = probes.iter().zip(&counts)
+            .all(|(&dc, &count)| rebased(orbit, dc, (series.skip, series.at(dc)), limit)
+                 == count);
+        if agree {
+            break;
+        }
+        verbose!("Series approximation : the probes count differently skipping {} \
+                  iterations, trying {}", series.skip, series.skip / 2);
+        series = self::series(orbit, probes, series.skip / 2);
+    }
+    series
+}
+
+//The series along `orbit` as far as it holds for the probes, up to `most`
+//iterations
+fn series(orbit : &[Complex<f64>], probes : &[Complex<f64>], most : usize) -> Series {
+    let zero = Complex { re : 0.0, im : 0.0 };
+    let one = Complex { re : 1.0, im : 0.0 };
+    let mut series = Series { skip : 0, coefficients : [zero; 3] };
+    let [mut a, mut b, mut c] = series.coefficients;
+    let mut differences = vec![zero; probes.len()];
+    //The pixels have to have some of the orbit left to go on from
+    for n in 0..most.min(orbit.len().saturating_sub(2)) {
+        let twice = orbit[n] + orbit[n];
+        c = twice * c + (a * b + a * b);
+        b = twice * b + a * a;
+        a = twice * a + one;
+        let holds = differences.iter_mut().zip(probes).all(|(dz, &dc)| {
+            *dz = twice * *dz + *dz * *dz + dc;
+            let z = orbit[n + 1] + *dz;
+            let off = (((c * dc + b) * dc + a) * dc - *dz).norm_sqr();
+            //Nor may a probe escape in what is skipped
+            z.norm_sqr() <= 4.0 && off <= SERIES_TOLERANCE * SERIES_TOLERANCE * dz.norm_sqr()
+        });
+        if !holds {
+            break;
+        }
+        series = Series { skip : n + 1, coefficients : [a, b, c] };
+    }
+    series
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Precision;
+    use crate::{render_counts, Arithmetic, ComplexArithmetic, Real};
+
+    const SIZE : (usize, usize) = (64, 48);
+    //Only as far as f64 can tell, on the edge of the set a few pixels go
+    //either way
+    const OFF : f64 = 0.01;
+
+    fn around(center : Complex<f64>, width : f64) -> View {
+        let half = Complex { re : width / 2.0, im : width * SIZE.1 as f64 / SIZE.0 as f64 / 2.0 };
+        View { bounds : SIZE, upper_left : center - half.conj(),
+               lower_right : center + half.conj(), rotation : None }
+    }
+
+    //The count of every pixel the plain way, iterated in F
+    fn direct<F : Real>(view : &View, limit : u32) -> Vec<Option<u32>> {
+        (0..SIZE.0 * SIZE.1)
+            .map(|index| (index % SIZE.0, index / SIZE.0))
+            .map(|pixel| ComplexArithmetic::<F>::escape_time(pixel_to_point(view, pixel), limit))
+            .collect()
+    }
+
+    fn differing(a : &[Option<u32>], b : &[Option<u32>]) -> usize {
+        a.iter().zip(b).filter(|(a, b)| a != b).count()
+    }
+
+    fn distinct(counts : &[Option<u32>]) -> usize {
+        let mut counts = counts.to_vec();
+        counts.sort();
+        counts.dedup();
+        counts.len()
+    }
+
+    #[test]
+    fn the_whole_set_counts_as_it_does_directly() {
+        let view = View { bounds : SIZE, upper_left : Complex { re : -2.0, im : 1.2 },
+                          lower_right : Complex { re : 1.0, im : -1.2 }, rotation : None };
+        let expected = direct::<f64>(&view, 255);
+        let (counts, _) = counts(None, None, &view, 255, None);
+        let off = differing(&counts, &expected);
+        assert!(off as f64 <= OFF * counts.len() as f64, "{} of {} differ", off, counts.len());
+    }
+
+    #[test]
+    fn a_zoom_counts_as_it_does_directly() {
+        //Against double-doubles : 1e-10 across, f64 rounds the plain
+        //iteration of a tenth of the pixels off their counts
+        for &(center, width, limit) in &[
+            (Complex { re : -0.743643887037151, im : 0.131825904205330 }, 1e-10, 2000),
+            (Complex { re : -0.75, im : 0.1 }, 1e-3, 1000),
+            (Complex { re : -0.10109636384562216, im : 0.9562865108091415 }, 1e-6, 1000)
+        ] {
+            let view = around(center, width);
+            let expected = direct::<Double>(&view, limit);
+            let (counts, _) = counts(None, None, &view, limit, None);
+            let off = differing(&counts, &expected);
+            assert!(distinct(&expected) >= 20, "no structure {} across", width);
+            assert!(off as f64 <= OFF * counts.len() as f64, "{} across : {} of {} differ",
+                    width, off, counts.len());
+        }
+    }
+
+    #[test]
+    fn perturbation_agrees_with_big_1e_30_across() {
+        let perturbed = big::tests::centered(Precision::Big(160), 1e-30, true);
+        let counts = render_counts(&perturbed, None);
+        let expected = render_counts(&big::tests::centered(Precision::Big(160), 1e-30, false),
+                                     None);
+        let off = differing(&counts, &expected);
+        assert!(distinct(&counts) >= big::tests::STRUCTURED, "{} different counts",
+                distinct(&counts));
+        assert!(off as f64 <= OFF * counts.len() as f64, "{} of {} differ", off, counts.len());
+    }
+
+    #[test]
+    fn the_series_skips_iterations_1e_30_across() {
+        let perturbed = big::tests::centered(Precision::Big(160), 1e-30, true);
+        let origin = perturbed.origin.as_ref().unwrap();
+        assert!(skipped(&perturbed.view, origin, perturbed.limit) > 0);
+    }
+
+    //<********Glitches*********>//
+    //A view whose center escapes after 44 iterations, a third of its pixels
+    //outliving it in one region : unchecked they all take its count, a flat
+    //blob, more than GLITCHED of the pixels wrong
+    const GLITCH_CENTER : Complex<f64> = Complex { re : -1.62917, im : -0.0203968 };
+    const GLITCH_WIDTH : f64 = 1e-6;
+    const GLITCHED : f64 = 0.1;
+
+    #[test]
+    fn the_first_reference_glitches_in_a_blob_unchecked() {
+        let view = around(GLITCH_CENTER, GLITCH_WIDTH);
+        let expected = direct::<f64>(&view, 2000);
+        let off = differing(&unchecked_counts(&view, 2000), &expected);
+        assert!(off as f64 > GLITCHED * expected.len() as f64, "only {} of {} differ", off,
+                expected.len());
+    }
+
+    #[test]
+    fn corrected_counts_match_the_direct_ones() {
+        let view = around(GLITCH_CENTER, GLITCH_WIDTH);
+        let expected = direct::<f64>(&view, 2000);
+        let (counts, glitches) = counts(None, None, &view, 2000, None);
+        let off = differing(&counts, &expected);
+        assert!(glitches.first.len() as f64 > GLITCHED * expected.len() as f64,
+                "{} glitched", glitches.first.len());
+        assert!(glitches.passes > 0 && glitches.fixed > 0, "{} fixed in {} passes",
+                glitches.fixed, glitches.passes);
+        assert!(off as f64 <= OFF * counts.len() as f64, "{} of {} differ, {} of {} glitched \
+                fixed in {} passes", off, counts.len(), glitches.fixed, glitches.first.len(),
+                glitches.passes);
+    }
+
+    #[test]
+    fn regions_are_the_pixels_that_touch_largest_first() {
+        //On a 6x4 grid :
+        //    x . . . . .
+        //    . x . . x x
+        //    . . . . . x
+        //    x x x . . .
+        let glitched = [0, 7, 10, 11, 17, 18, 19, 20];
+        let regions = regions(&glitched, (6, 4)).into_iter()
+            .map(|mut region| { region.sort(); region })
+            .collect::<Vec<_>>();
+        //Diagonal neighbours touch, and the two of three keep their order
+        assert_eq!(regions, [vec![10, 11, 17], vec![18, 19, 20], vec![0, 7]]);
+        assert_eq!(middle(&regions[1], (6, 4)), 19);
+        assert_eq!(middle(&regions[0], (6, 4)), 11);
+    }
+}
@@ -0,0 +1,253 @@
+//<***************Dry runs*****************>//
+//What a render or an animation is going to do, worked out from its Config
+//without computing a single pixel, for --dry-run. The plan prints as
+//`key  value` lines, or with --json as a JSON object with the same keys,
+//in the same order every time.
+
+use num::Complex;
+use std::path::{Path, PathBuf};
+
+use crate::cli::{Exterior, Format, Mode, Options, Schedule};
+use crate::config::{frame_view, suggest_iterations, Config, View};
+use crate::json::Json;
+use crate::{band_layout, estimated_memory, optimal_bands, SCHEDULER};
+
+//At most this many frames of an animation are listed one by one
+const LISTED_FRAMES : usize = 10;
+
+pub fn for_render(options : &Options, config : &Config, estimate : Option<f64>) -> Json {
+    let mut fields = vec![
+        ("command", Json::String("render".to_string())),
+        ("output", Json::String(options.output.as_ref()
+            .map(|output| output.display().to_string()).unwrap_or_default())),
+        ("format", Json::String(match options.format {
+            Format::Png => "png",
+            Format::Csv => "csv"
+        }.to_string()))
+    ];
+    fields.extend(view_fields(&config.view));
+    //Under --precision big the corners and center are offsets from this
+    if let Some(ref origin) = config.origin {
+        fields.push(("offsets_from", Json::String(origin.show())));
+    }
+    fields.extend(render_fields(options, config));
+    fields.push(estimate_field(estimate));
+    Json::Object(fields)
+}
+
+//Per frame the output file and the view, only the first LISTED_FRAMES
+//frames are listed and the rest are counted
+pub fn for_animate(options : &Options, config : &Config, manifest : &Path,
+                   frame_filename : fn(&Path, usize) -> PathBuf, estimate : Option<f64>)
+    -> Json
+{
+    let pattern = options.output.clone().unwrap_or_default();
+    let frames = (0..options.frames.min(LISTED_FRAMES))
+        .map(|frame| {
+            let view = frame_view(&config.view, options, frame);
+            let julia = options.julia_path.map(|path| path.at(frame, options.frames));
+            Json::Object(vec![
+                ("frame", Json::Integer(frame as u64)),
+                ("output", Json::String(frame_filename(&pattern, frame).display()
+                                         .to_string())),
+                ("center", point(view.center())),
+                ("width", Json::Number(view.width())),
+                ("zoom", Json::Number(view.zoom())),
+                ("iterations", Json::Integer(suggest_iterations(
+                    config.limit, options.zoom_at(frame), options.iteration_scale) as u64)),
+                ("julia", julia.map_or(Json::Null, point))
+            ])
+        })
+        .collect();
+
+    let mut fields = vec![
+        ("command", Json::String("animate".to_string())),
+        ("output", Json::String(pattern.display().to_string())),
+        ("manifest", Json::String(manifest.display().to_string())),
+        ("frame_count", Json::Integer(options.frames as u64)),
+        ("zoom_per_frame", Json::Number(options.frame_zoom())),
+        ("pan_per_frame", options.pan_per_frame.map_or(Json::Null, |(columns, rows)| {
+            Json::Array(vec![Json::Number(columns), Json::Number(rows)])
+        })),
+        ("iteration_scale", Json::Number(options.iteration_scale))
+    ];
+    fields.extend(view_fields(&config.view));
+    //The frames' centers too
+    if let Some(ref origin) = config.origin {
+        fields.push(("offsets_from", Json::String(origin.show())));
+    }
+    fields.extend(render_fields(options, config));
+    fields.push(("frames", Json::Array(frames)));
+    fields.push(("frames_not_listed",
+                 Json::Integer(options.frames.saturating_sub(LISTED_FRAMES) as u64)));
+    fields.push(estimate_field(estimate));
+    Json::Object(fields)
+}
+
+//How long the whole run should take, if --estimate asked for it
+fn estimate_field(estimate : Option<f64>) -> (&'static str, Json) {
+    ("estimated_seconds", estimate.map_or(Json::Null, Json::Number))
+}
+
+fn point(point : Complex<f64>) -> Json {
+    Json::Object(vec![("re", Json::Number(point.re)), ("im", Json::Number(point.im))])
+}
+
+fn view_fields(view : &View) -> Vec<(&'static str, Json)> {
+    vec![
+        ("size", Json::Object(vec![
+            ("width", Json::Integer(view.bounds.0 as u64)),
+            ("height", Json::Integer(view.bounds.1 as u64))
+        ])),
+        ("upper_left", point(view.upper_left)),
+        ("lower_right", point(view.lower_right)),
+        ("center", point(view.center())),
+        ("width", Json::Number(view.width())),
+        ("zoom", Json::Number(view.zoom())),
+        ("rotate_degrees", Json::Number(view.rotation
+            .map_or(0.0, |rotation| rotation.degrees)))
+    ]
+}
+
+//How the view gets iterated, coloured and split between threads
+fn render_fields(options : &Options, config : &Config) -> Vec<(&'static str, Json)> {
+    let limit = match (config.diff_limits, config.adaptive, config.limit_map) {
+        (Some((low, high)), _, _) => Json::Object(vec![
+            ("kind", Json::String("difference".to_string())),
+            ("low", Json::Integer(low as u64)),
+            ("high", Json::Integer(high as u64))
+        ]),
+        (None, Some((threshold, max_limit)), _) => Json::Object(vec![
+            ("kind", Json::String("adaptive".to_string())),
+            ("start", Json::Integer(255.min(max_limit) as u64)),
+            ("max", Json::Integer(max_limit as u64)),
+            ("threshold", Json::Number(threshold))
+        ]),
+        (None, None, Some((center_limit, edge_limit))) => Json::Object(vec![
+            ("kind", Json::String("radial".to_string())),
+            ("center", Json::Integer(center_limit as u64)),
+            ("edge", Json::Integer(edge_limit as u64))
+        ]),
+        (None, None, None) => Json::Object(vec![
+            ("kind", Json::String("fixed".to_string())),
+            ("iterations", Json::Integer(config.limit as u64))
+        ])
+    };
+
+    let threads = options.thread_count();
+    let height = config.view.bounds.1;
+    let bands = match options.schedule {
+        Schedule::Bands => band_layout(config.view.bounds,
+                                       optimal_bands(config.view.bounds, threads))
+            .into_iter()
+            .map(|(top, rows)| Json::Object(vec![
+                ("top", Json::Integer(top as u64)),
+                ("rows", Json::Integer(rows as u64))
+            ]))
+            .collect(),
+        //Each thread's rows, every `step`th from `top` on
+        Schedule::Interleaved => {
+            let step = threads.clamp(1, height.max(1));
+            (0..step)
+                .map(|top| Json::Object(vec![
+                    ("top", Json::Integer(top as u64)),
+                    ("rows", Json::Integer(((height + step - 1 - top) / step) as u64)),
+                    ("step", Json::Integer(step as u64))
+                ]))
+                .collect()
+        }
+        //Which thread gets which rows is only known once they are rendered,
+        //or once the pre-pass has been
+        Schedule::Dynamic | Schedule::CostBalanced => Vec::new()
+    };
+    let memory = match options.format {
+        Format::Png => estimated_memory(config),
+        Format::Csv => config.view.bounds.0 * config.view.bounds.1
+            * std::mem::size_of::<Option<u32>>()
+    };
+
+    vec![
+        ("julia", config.julia.map_or(Json::Null, point)),
+        ("mode", Json::String(config.mode.name().to_string())),
+        //As --precision auto settled it
+        ("precision", Json::String(config.precision.name())),
+        ("blend", config.blend.map_or(Json::Null, |blend| Json::String(blend.name()))),
+        ("band_width", match config.mode {
+            Mode::Bands => Json::Integer(config.band_width as u64),
+            _ => Json::Null
+        }),
+        ("limit", limit),
+        ("palette", config.palette.as_ref()
+            .map_or(Json::Null, |palette| Json::String(palette.spec().to_string()))),
+        ("chromatic", config.chromatic
+            .map_or(Json::Null, |offset| Json::Integer(offset as u64))),
+        ("gamma", Json::Number(config.gray.gamma)),
+        ("interior_value", Json::Integer(config.gray.interior as u64)),
+        ("exterior", Json::String(match config.gray.exterior {
+            Exterior::LightToDark => "light-to-dark",
+            Exterior::DarkToLight => "dark-to-light"
+        }.to_string())),
+        ("auto_exposure", config.gray.exposure.map_or(Json::Null, |(low, high)| {
+            Json::Object(vec![("low_percentile", Json::Number(low)),
+                              ("high_percentile", Json::Number(high))])
+        })),
+        ("time_budget_ms", config.time_budget
+            .map_or(Json::Null, |budget| Json::Integer(budget.as_millis() as u64))),
+        ("preview_stride", Json::Integer(config.preview_stride as u64)),
+        ("threads", Json::Integer(threads as u64)),
+        ("scheduler", Json::String(SCHEDULER.to_string())),
+        ("schedule", Json::String(options.schedule.name().to_string())),
+        ("chunk_rows", match options.schedule {
+            Schedule::Dynamic => Json::Integer(options.chunk_rows as u64),
+            _ => Json::Null
+        }),
+        ("bands", Json::Array(bands)),
+        ("estimated_memory_bytes", Json::Integer(memory as u64))
+    ]
+}
+
+pub fn print(plan : &Json, json : bool) {
+    if json {
+        println!("{}", plan.to_json());
+        return;
+    }
+    for line in lines(plan) {
+        println!("{}", line);
+    }
+}
+
+//The `key  value` lines of the plan, an array as its length and then an
+//indented line per item
+pub fn lines(plan : &Json) -> Vec<String> {
+    let fields = match *plan {
+        Json::Object(ref fields) => fields,
+        _ => return vec![text(plan)]
+    };
+    let mut lines = Vec::new();
+    for (key, value) in fields {
+        match *value {
+            Json::Array(ref items) => {
+                lines.push(format!("{:<24}{}", key, items.len()));
+                for item in items {
+                    lines.push(format!("  - {}", text(item)));
+                }
+            }
+            _ => lines.push(format!("{:<24}{}", key, text(value)))
+        }
+    }
+    lines
+}
+
+//On one line, objects as `key value, key value`
+fn text(value : &Json) -> String {
+    match *value {
+        Json::Null => "none".to_string(),
+        Json::String(ref text) => text.clone(),
+        Json::Array(ref items) => items.iter()
+            .map(text).collect::<Vec<_>>().join(", "),
+        Json::Object(ref fields) => fields.iter()
+            .map(|(key, value)| format!("{} {}", key, text(value)))
+            .collect::<Vec<_>>().join(", "),
+        _ => value.to_json()
+    }
+}
@@ -0,0 +1,144 @@
+//<***************Named views*****************>//
+//Well known places in the set, so --preset seahorse-valley can stand in
+//for looking the coordinates up again. A preset gives a center, a width
+//and an iteration limit that shows the place off; anything given in a
+//config file or on the command line takes precedence over it.
+//
+//Presets of your own go in ~/.config/mandelbrot/presets.toml (or under
+//$XDG_CONFIG_HOME), one [section] per preset :
+//
+//    [my-spiral]
+//    center = "-0.7746806106,0.1374168856"
+//    width = 0.0001
+//    iterations = 2000
+//
+//A user preset with the same name as a built-in one replaces it.
+
+use num::Complex;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::parse_complex;
+use crate::toml::{self, Value};
+
+pub struct Preset {
+    pub name : String,
+    pub center : Complex<f64>,
+    pub width : f64,
+    pub iterations : u32
+}
+
+//(name, center, width, iterations)
+const BUILTINS : &[(&str, (f64, f64), f64, u32)] = &[
+    ("full", (-0.75, 0.0), 3.5, 255),
+    ("seahorse-valley", (-0.75, 0.1), 0.1, 500),
+    ("seahorse-deep", (-0.743643887037151, 0.131825904205330), 0.00002, 5000),
+    ("elephant-valley", (0.3, 0.0), 0.1, 500),
+    ("triple-spiral", (-0.088, 0.654), 0.02, 1000),
+    ("quad-spiral", (0.274, 0.482), 0.005, 1500),
+    ("scepter-valley", (-1.36, 0.0), 0.05, 1000),
+    ("misiurewicz", (-0.1011, 0.9563), 0.005, 2000),
+    ("feigenbaum", (-1.401155189, 0.0), 0.001, 5000),
+    ("mini-mandelbrot", (-1.75488, 0.0), 0.04, 1000),
+    ("antenna-tip", (-2.0, 0.0), 0.05, 500),
+    ("lightning", (-0.170337, -1.06506), 0.005, 1500)
+];
+
+//The built-in presets with the user's merged over them
+pub fn all() -> Result<Vec<Preset>, String> {
+    let mut presets = BUILTINS.iter()
+        .map(|&(name, (re, im), width, iterations)| Preset {
+            name : name.to_string(),
+            center : Complex { re, im },
+            width,
+            iterations
+        })
+        .collect::<Vec<_>>();
+
+    if let Some(path) = user_file().filter(|path| path.exists()) {
+        for preset in read_user_file(&path)? {
+            match presets.iter().position(|builtin| builtin.name == preset.name) {
+                Some(index) => presets[index] = preset,
+                None => presets.push(preset)
+            }
+        }
+    }
+    Ok(presets)
+}
+
+pub fn find(name : &str) -> Result<Preset, String> {
+    let presets = all()?;
+    let names = presets.iter().map(|preset| preset.name.clone()).collect::<Vec<_>>();
+    presets.into_iter()
+        .find(|preset| preset.name == name)
+        .ok_or_else(|| format!("unknown preset '{}', expected one of {} \
+                                (see --list-presets)", name, names.join(", ")))
+}
+
+pub fn user_file() -> Option<PathBuf> {
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME")
+                 .map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config.join("mandelbrot").join("presets.toml"))
+}
+
+//A preset from the user's file as its keys are read
+struct Partial {
+    name : String,
+    center : Option<Complex<f64>>,
+    width : Option<f64>,
+    iterations : u32
+}
+
+fn read_user_file(path : &PathBuf) -> Result<Vec<Preset>, String> {
+    let shown = path.display();
+    let text = std::fs::read_to_string(path)
+        .map_err(|error| format!("error reading presets {} : {}", shown, error))?;
+    let entries = toml::parse(&text).map_err(|message| format!("{} : {}", shown, message))?;
+
+    let mut partial : Vec<Partial> = Vec::new();
+    for entry in entries {
+        let name = entry.section.clone().ok_or_else(|| {
+            format!("{} line {} : '{}' has to be under the [name] of a preset",
+                    shown, entry.line, entry.key)
+        })?;
+        if partial.last().is_none_or(|last| last.name != name) {
+            partial.push(Partial { name, center : None, width : None, iterations : 255 });
+        }
+        let preset = partial.last_mut().unwrap();
+
+        let text = match entry.value {
+            Value::String(ref text) | Value::Number(ref text) => text.as_str(),
+            Value::Boolean(_) => ""
+        };
+        let bad = |what : &str| format!("{} line {} : {} : expected {}, got '{}'",
+                                        shown, entry.line, entry.key, what, text);
+        match entry.key.as_str() {
+            "center" => preset.center = Some(parse_complex(text)
+                .map_err(|_| bad("a point RE,IM"))?),
+            "width" => preset.width = Some(f64::from_str(text).ok()
+                .filter(|&width| width > 0.0)
+                .ok_or_else(|| bad("a positive number"))?),
+            "iterations" => preset.iterations = u32::from_str(text).ok()
+                .filter(|&iterations| iterations > 0)
+                .ok_or_else(|| bad("a positive whole number"))?,
+            _ => return Err(format!("{} line {} : unknown key '{}', a preset has a \
+                                     center, width and iterations", shown, entry.line,
+                                    entry.key))
+        }
+    }
+
+    partial.into_iter()
+        .map(|preset| match (preset.center, preset.width) {
+            (Some(center), Some(width)) => Ok(Preset {
+                name : preset.name,
+                center,
+                width,
+                iterations : preset.iterations
+            }),
+            _ => Err(format!("{} : preset [{}] needs both a center and a width",
+                             shown, preset.name))
+        })
+        .collect()
+}
@@ -0,0 +1,256 @@
+//<***************Progress*****************>//
+//A report every REPORT_EVERY while a long render is going : how far it has
+//got, how many rows a second and how long it should take yet. Reports go
+//to stderr, or to the --progress-file, in one of two forms :
+//
+//    human  a line, written over each time on a terminal and added to
+//           otherwise. Renders quicker than REPORT_EVERY say nothing, and
+//           --quiet none at all.
+//    json   a JSON object a line, for programs watching the run, whatever
+//           --quiet says. Human readable reports are never mixed in.
+//
+//The JSON objects all have a "phase", each phase its own fields (a field
+//that isn't known is null) :
+//
+//    start   "unit" (image, frame or tile), "units", "rows_total" : the
+//            rows a unit takes counting every pass over it
+//    render  "rows_done", "rows_total", "elapsed_ms", "eta_ms", "unit_index"
+//            (from 0) : every REPORT_EVERY from the start on
+//    encode  "output" : a PNG about to be encoded and written
+//    write   "output" : written
+//    done    "unit", "unit_index", "units" : a frame or tile finished
+//    finish  "rows_done", "elapsed_ms" : the end of the run
+//
+//The renders count the rows they finish into ROWS, a relaxed add per row
+//and nothing in the loops over pixels, and a reporter thread started for
+//the command reads it. Every render counts, so a command rendering several
+//images side by side (render --jobs) goes without.
+
+use crate::json::Json;
+use crate::log::{self, Level};
+
+use std::fs::File;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+const REPORT_EVERY : Duration = Duration::from_secs(1);
+//How often the reporter checks whether it should stop
+const POLL : Duration = Duration::from_millis(50);
+
+static ROWS : AtomicUsize = AtomicUsize::new(0);
+static FORGONE : AtomicBool = AtomicBool::new(false);
+
+//Where reports go and in which form, set once from the command line
+struct Output {
+    json : bool,
+    //None for stderr
+    file : Option<File>
+}
+
+static OUTPUT : Mutex<Output> = Mutex::new(Output { json : false, file : None });
+
+//--progress and --progress-file
+pub fn configure(json : bool, file : Option<&Path>) -> Result<(), String> {
+    let file = match file {
+        Some(path) => Some(File::create(path).map_err(|error| {
+            format!("error creating {} : {}", path.display(), error)
+        })?),
+        None => None
+    };
+    *OUTPUT.lock().unwrap() = Output { json, file };
+    Ok(())
+}
+
+//No reports from here on
+pub fn forgo() {
+    FORGONE.store(true, Ordering::Relaxed);
+}
+
+//Called as rows are finished
+pub fn rows_done(rows : usize) {
+    ROWS.fetch_add(rows, Ordering::Relaxed);
+}
+
+fn json() -> bool {
+    OUTPUT.lock().unwrap().json && !FORGONE.load(Ordering::Relaxed)
+}
+
+//Writes a report, human readable ones written over the last on a terminal
+fn report(line : &str, terminal : bool) {
+    let mut output = OUTPUT.lock().unwrap();
+    let _ = match output.file {
+        Some(ref mut file) => writeln!(file, "{}", line).and_then(|_| file.flush()),
+        //Left at the start of the line, for the next report or whatever
+        //else gets printed to write over it
+        None if terminal => write!(std::io::stderr(), "\x1b[K{}\r", line),
+        None => writeln!(std::io::stderr(), "{}", line)
+    };
+}
+
+//A line of JSON progress, with --progress json
+fn emit(phase : &str, mut fields : Vec<(&'static str, Json)>) {
+    fields.insert(0, ("phase", Json::String(phase.to_string())));
+    report(&Json::Object(fields).to_json(), false);
+}
+
+//The PNG `output` is about to be encoded, and has been written
+pub fn encoding(output : &Path) {
+    if json() {
+        emit("encode", vec![("output", Json::String(output.display().to_string()))]);
+    }
+}
+
+pub fn written(output : &Path) {
+    if json() {
+        emit("write", vec![("output", Json::String(output.display().to_string()))]);
+    }
+}
+
+//Unit `index` of `units` (a frame, a tile) is finished
+pub fn unit_done(unit : &str, index : usize, units : usize) {
+    if json() {
+        emit("done", vec![
+            ("unit", Json::String(unit.to_string())),
+            ("unit_index", Json::Integer(index as u64)),
+            ("units", Json::Integer(units as u64))
+        ]);
+    }
+}
+
+//The reporter thread, stopped when this is dropped
+pub struct Progress {
+    stop : Arc<AtomicBool>,
+    thread : Option<JoinHandle<()>>
+}
+
+impl Progress {
+    //Reports on `units` images (frames, tiles) of `unit`, each of them
+    //`rows` rows to render counting every pass over it, None when that
+    //isn't known beforehand. None if nothing is to be reported.
+    pub fn start(unit : &'static str, rows : Option<usize>, units : usize)
+        -> Option<Progress>
+    {
+        let json = json();
+        if FORGONE.load(Ordering::Relaxed) || !json && !log::enabled(Level::Normal) {
+            return None;
+        }
+        ROWS.store(0, Ordering::Relaxed);
+        if json {
+            emit("start", vec![
+                ("unit", Json::String(unit.to_string())),
+                ("units", Json::Integer(units as u64)),
+                ("rows_total", rows.map_or(Json::Null, |rows| Json::Integer(rows as u64)))
+            ]);
+        }
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let terminal = OUTPUT.lock().unwrap().file.is_none()
+            && std::io::stderr().is_terminal();
+        let thread = thread::spawn(move || {
+            let started = Instant::now();
+            let mut reported = started;
+            while !stopped.load(Ordering::Relaxed) {
+                thread::sleep(POLL);
+                if reported.elapsed() < REPORT_EVERY {
+                    continue;
+                }
+                reported = Instant::now();
+                let tick = Tick::new(rows, units, ROWS.load(Ordering::Relaxed),
+                                     started.elapsed().as_secs_f64());
+                if json {
+                    emit("render", tick.fields());
+                } else {
+                    report(&tick.line(unit), terminal);
+                }
+            }
+            if json {
+                emit("finish", vec![
+                    ("rows_done", Json::Integer(ROWS.load(Ordering::Relaxed) as u64)),
+                    ("elapsed_ms", Json::Integer(started.elapsed().as_millis() as u64))
+                ]);
+            } else if terminal && reported != started {
+                eprint!("\x1b[K");
+            }
+        });
+        Some(Progress { stop, thread : Some(thread) })
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+//Where the run has got to, the rows of a unit and the total of them None
+//when they aren't known
+struct Tick {
+    done : usize,
+    rows : Option<usize>,
+    total : Option<usize>,
+    seconds : f64,
+    rate : f64
+}
+
+impl Tick {
+    fn new(rows : Option<usize>, units : usize, done : usize, seconds : f64) -> Tick {
+        let rows = rows.filter(|&rows| rows > 0);
+        let total = rows.map(|rows| rows * units.max(1));
+        //A render can take more passes than it was counted for (perturbation
+        //going over glitched pixels again), it stays just short of done
+        let done = total.map_or(done, |total| done.min(total.saturating_sub(1)));
+        Tick { done, rows, total, seconds, rate : done as f64 / seconds.max(1e-9) }
+    }
+
+    fn seconds_left(&self) -> Option<f64> {
+        self.total.map(|total| (total - self.done) as f64 / self.rate.max(1e-9))
+    }
+
+    fn fields(&self) -> Vec<(&'static str, Json)> {
+        let integer = |value : Option<usize>| value.map_or(Json::Null, |value| {
+            Json::Integer(value as u64)
+        });
+        vec![
+            ("rows_done", Json::Integer(self.done as u64)),
+            ("rows_total", integer(self.total)),
+            ("elapsed_ms", Json::Integer((self.seconds * 1000.0) as u64)),
+            ("eta_ms", self.seconds_left()
+                .map_or(Json::Null, |left| Json::Integer((left * 1000.0) as u64))),
+            ("unit_index", integer(self.rows.map(|rows| self.done / rows)))
+        ]
+    }
+
+    //`42% (frame 3 of 30 is 60% done), 1200 rows/s, 35s to go`, or for an
+    //unknown number of rows `5000 rows, 1200 rows/s`
+    fn line(&self, unit : &str) -> String {
+        let (rows, total, left) = match (self.rows, self.total, self.seconds_left()) {
+            (Some(rows), Some(total), Some(left)) => (rows, total, left),
+            _ => return format!("{} rows, {:.0} rows/s", self.done, self.rate)
+        };
+        let overall = format!("{}%", 100 * self.done / total);
+        let units = total / rows;
+        let image = match units {
+            1 => overall,
+            _ => format!("{} ({} {} of {} is {}% done)", overall, unit, self.done / rows + 1,
+                         units, 100 * (self.done % rows) / rows)
+        };
+        format!("{}, {:.0} rows/s, {} to go", image, self.rate, duration(left))
+    }
+}
+
+//`1h02m`, `3m05s` or `12s`
+fn duration(seconds : f64) -> String {
+    let seconds = seconds.round().min(u64::MAX as f64) as u64;
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m{:02}s", seconds / 60, seconds % 60),
+        _ => format!("{}h{:02}m", seconds / 3600, seconds / 60 % 60)
+    }
+}
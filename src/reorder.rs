@@ -0,0 +1,179 @@
+//<***************Frames rendered side by side, taken in order*****************>//
+//animate --parallel-frames N has up to N frames rendering at the same time,
+//finishing in whatever order they finish, while the frame files, the
+//manifest and the progress reports have to go in frame order. A Reorder
+//hands the frames out to the threads rendering them, holds the ones that
+//come back early, and gives them up to the one thread taking them in order.
+//
+//A frame is only handed out while it is fewer than `ahead` frames past the
+//one the taker waits for, so that however slow that one frame is, no more
+//than `ahead` frames are ever rendered and not yet taken : the bound on the
+//memory the frames take.
+
+use std::collections::BTreeMap;
+use std::sync::{Condvar, Mutex};
+
+pub struct Reorder<T> {
+    count : usize,
+    ahead : usize,
+    state : Mutex<State<T>>,
+    //Notified whenever any of the state changes
+    changed : Condvar
+}
+
+struct State<T> {
+    //The next frame to hand out, and the next to be taken
+    handed : usize,
+    taken : usize,
+    //Frames back, waiting for the ones before them
+    back : BTreeMap<usize, T>,
+    //No more frames are handed out
+    closed : bool
+}
+
+impl<T> Reorder<T> {
+    //Frames 0 to `count`, no more than `ahead` (at least 1) of them out at a
+    //time
+    pub fn new(count : usize, ahead : usize) -> Reorder<T> {
+        Reorder {
+            count,
+            ahead : ahead.max(1),
+            state : Mutex::new(State { handed : 0, taken : 0, back : BTreeMap::new(),
+                                       closed : false }),
+            changed : Condvar::new()
+        }
+    }
+
+    //The next frame to render, waiting until it is few enough frames ahead,
+    //None once they have all been handed out or close() was called
+    pub fn next(&self) -> Option<usize> {
+        let mut state = self.state.lock().unwrap();
+        while !state.closed && state.handed < self.count
+            && state.handed >= state.taken + self.ahead
+        {
+            state = self.changed.wait(state).unwrap();
+        }
+        if state.closed || state.handed == self.count {
+            return None;
+        }
+        state.handed += 1;
+        self.changed.notify_all();
+        Some(state.handed - 1)
+    }
+
+    //A frame next() handed out, rendered
+    pub fn put(&self, frame : usize, item : T) {
+        self.state.lock().unwrap().back.insert(frame, item);
+        self.changed.notify_all();
+    }
+
+    //The next frame in order, waiting for it, None once every frame handed
+    //out has been taken and no more will be
+    pub fn take(&self) -> Option<(usize, T)> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            let taken = state.taken;
+            if let Some(item) = state.back.remove(&taken) {
+                state.taken += 1;
+                self.changed.notify_all();
+                return Some((taken, item));
+            }
+            if taken == state.handed && (state.closed || taken == self.count) {
+                return None;
+            }
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+
+    //No frames are handed out from here on, the ones out already still come
+    //back and can be taken
+    pub fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.changed.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    //Runs `threads` threads putting back what next() hands them after
+    //`work`, while this one takes them
+    fn taken_in_order<F>(reorder : &Reorder<usize>, threads : usize, work : F) -> Vec<usize>
+        where F : Fn(usize) + Sync
+    {
+        let work = &work;
+        let mut taken = Vec::new();
+        crossbeam::scope(|spawner| {
+            for _ in 0..threads {
+                spawner.spawn(move || while let Some(frame) = reorder.next() {
+                    work(frame);
+                    reorder.put(frame, frame * 10);
+                });
+            }
+            while let Some((frame, item)) = reorder.take() {
+                assert_eq!(item, frame * 10);
+                taken.push(frame);
+            }
+        });
+        taken
+    }
+
+    #[test]
+    fn every_frame_is_taken_once_and_in_order() {
+        for count in [0, 1, 7, 40] {
+            for (threads, ahead) in [(1, 1), (3, 3), (4, 2), (2, 8)] {
+                let reorder = Reorder::new(count, ahead);
+                //The later frames quicker, so that they come back first
+                let taken = taken_in_order(&reorder, threads, |frame| {
+                    thread::sleep(Duration::from_micros(((count - frame) * 50) as u64));
+                });
+                assert_eq!(taken, (0..count).collect::<Vec<_>>(),
+                           "{} frames on {} threads, {} ahead", count, threads, ahead);
+            }
+        }
+    }
+
+    #[test]
+    fn no_more_than_ahead_frames_are_out() {
+        let reorder = Reorder::new(10, 3);
+        for frame in 0..3 {
+            assert_eq!(reorder.next(), Some(frame));
+        }
+        let handed = AtomicUsize::new(0);
+        crossbeam::scope(|spawner| {
+            spawner.spawn(|| {
+                assert_eq!(reorder.next(), Some(3));
+                handed.store(1, Ordering::SeqCst);
+            });
+            //Frames back but not taken are still out
+            thread::sleep(Duration::from_millis(20));
+            reorder.put(1, 1);
+            thread::sleep(Duration::from_millis(20));
+            assert_eq!(handed.load(Ordering::SeqCst), 0);
+            reorder.put(0, 0);
+            assert_eq!(reorder.take(), Some((0, 0)));
+        });
+        assert_eq!(handed.into_inner(), 1);
+    }
+
+    #[test]
+    fn closing_stops_handing_out_but_not_taking() {
+        let reorder = Reorder::new(10, 4);
+        let frames = (0..3).map(|_| reorder.next().unwrap()).collect::<Vec<_>>();
+        assert_eq!(frames, [0, 1, 2]);
+        reorder.close();
+        assert_eq!(reorder.next(), None);
+        //The frames out come back and are taken, out of order as they are
+        reorder.put(2, 2);
+        reorder.put(0, 0);
+        assert_eq!(reorder.take(), Some((0, 0)));
+        reorder.put(1, 1);
+        assert_eq!(reorder.take(), Some((1, 1)));
+        assert_eq!(reorder.take(), Some((2, 2)));
+        assert_eq!(reorder.take(), None);
+    }
+}
@@ -0,0 +1,32 @@
+//<***************Seeded random numbers*****************>//
+//SplitMix64 : tiny, fast and good enough for sampling points, and the
+//same seed always gives the same sequence on every platform, so a run
+//with --seed can be repeated exactly.
+
+pub struct Rng {
+    state : u64
+}
+
+impl Rng {
+    pub fn new(seed : u64) -> Rng {
+        Rng { state : seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    //Uniform in [0, 1), from the top 53 bits
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    //Uniform in [low, high)
+    pub fn range(&mut self, low : f64, high : f64) -> f64 {
+        low + (high - low) * self.next_f64()
+    }
+}
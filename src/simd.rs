@@ -0,0 +1,308 @@
+//<***************Escape times in SIMD lanes*****************>//
+//For --simd : the loop of ScalarArithmetic run on a row of points a few at
+//a time, one point to a lane of a vector register. With AVX2 (x86_64, if
+//the CPU has it, checked as the program runs) that is 4 points in f64 or 8
+//in f32, with NEON (aarch64, which always has it) 2 or 4, and anywhere else
+//none : the row goes through the scalar loop as it would without --simd.
+//
+//Every lane goes through the same multiplies, subtractions and additions
+//in the same order as the scalar loop, and nothing is fused into a
+//multiply-add, so the counts come out identical bit for bit. A lane that
+//escapes has its count taken there and goes on iterating unseen (its z
+//soon turns infinite, then NaN, neither of which matters), until every
+//lane of the group has escaped or the limit is reached. Whatever is left
+//over at the end of the row after the last whole group goes the scalar way.
+//
+//The loop is written once for each instruction set, as a macro that the
+//f64 and the f32 instructions are put into.
+
+use num::Complex;
+
+use crate::cli::Precision;
+use crate::{Arithmetic, Real, ScalarArithmetic};
+
+//How many points a group takes on this CPU, 1 without SIMD (and for
+//double-doubles and fixed point, which no instructions take)
+#[cfg(target_arch = "x86_64")]
+pub fn lanes(precision : Precision) -> usize {
+    match (is_x86_feature_detected!("avx2"), precision) {
+        (true, Precision::F64) => 4,
+        (true, Precision::F32) => 8,
+        _ => 1
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+pub fn lanes(precision : Precision) -> usize {
+    match precision {
+        Precision::F64 => 2,
+        Precision::F32 => 4,
+        _ => 1
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub fn lanes(_ : Precision) -> usize {
+    1
+}
+
+//The instructions the groups are iterated with, for the log
+pub fn name() -> &'static str {
+    match lanes(Precision::F64) {
+        4 => "AVX2",
+        2 => "NEON",
+        _ => "none"
+    }
+}
+
+//escape_time() of every one of `points` into `counts`
+pub fn escape_row<F : Real>(points : &[Complex<F>], limit : u32, counts : &mut [Option<u32>]) {
+    assert_eq!(points.len(), counts.len(), "points and escape counts differ in size");
+    let grouped = F::in_groups(points, limit, counts);
+    for (count, &point) in counts[grouped..].iter_mut().zip(&points[grouped..]) {
+        *count = ScalarArithmetic::<F>::escape_time(point, limit);
+    }
+}
+
+//The counts of the whole groups at the start of `points`, handing back how
+//many points they took
+#[cfg(target_arch = "x86_64")]
+pub fn in_groups_f64(points : &[Complex<f64>], limit : u32, counts : &mut [Option<u32>])
+    -> usize
+{
+    if !is_x86_feature_detected!("avx2") {
+        return 0;
+    }
+    //Safe, the CPU has been checked for AVX2
+    unsafe { avx2::in_groups_f64(points, limit, counts) }
+}
+
+#[cfg(target_arch = "x86_64")]
+pub fn in_groups_f32(points : &[Complex<f32>], limit : u32, counts : &mut [Option<u32>])
+    -> usize
+{
+    if !is_x86_feature_detected!("avx2") {
+        return 0;
+    }
+    unsafe { avx2::in_groups_f32(points, limit, counts) }
+}
+
+#[cfg(target_arch = "aarch64")]
+pub fn in_groups_f64(points : &[Complex<f64>], limit : u32, counts : &mut [Option<u32>])
+    -> usize
+{
+    //Safe, every aarch64 CPU has NEON
+    unsafe { neon::in_groups_f64(points, limit, counts) }
+}
+
+#[cfg(target_arch = "aarch64")]
+pub fn in_groups_f32(points : &[Complex<f32>], limit : u32, counts : &mut [Option<u32>])
+    -> usize
+{
+    unsafe { neon::in_groups_f32(points, limit, counts) }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub fn in_groups_f64(_ : &[Complex<f64>], _ : u32, _ : &mut [Option<u32>]) -> usize {
+    0
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub fn in_groups_f32(_ : &[Complex<f32>], _ : u32, _ : &mut [Option<u32>]) -> usize {
+    0
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use num::Complex;
+    use std::arch::x86_64::*;
+    use std::ops::ControlFlow::{Break, Continue};
+
+    macro_rules! in_groups {
+        ($name : ident, $real : ty, $lanes : expr, $load : ident, $splat : ident,
+         $mul : ident, $sub : ident, $add : ident, $compare : ident, $mask : ident) => {
+            #[target_feature(enable = "avx2")]
+            pub unsafe fn $name(points : &[Complex<$real>], limit : u32,
+                                counts : &mut [Option<u32>])
+                -> usize
+            {
+                let four = $splat(4.0);
+                let groups = points.len() / $lanes;
+                let chunks = points.chunks_exact($lanes).zip(counts.chunks_exact_mut($lanes));
+                for (group, counts) in chunks {
+                    let (mut c_re, mut c_im) = ([0.0; $lanes], [0.0; $lanes]);
+                    for (lane, point) in group.iter().enumerate() {
+                        c_re[lane] = point.re;
+                        c_im[lane] = point.im;
+                    }
+                    let (c_re, c_im) = ($load(c_re.as_ptr()), $load(c_im.as_ptr()));
+                    let z = ($splat(0.0), $splat(0.0));
+                    counts.fill(None);
+                    //And a bit per lane that has escaped
+                    let start = (z, 0, counts);
+                    let _ = crate::in_stretches(0..limit, start, move |state, stretch| {
+                        let ((mut re, mut im), mut escaped, counts) = state;
+                        for i in stretch {
+                            let square_re = $sub($mul(re, re), $mul(im, im));
+                            let square_im = $add($mul(re, im), $mul(im, re));
+                            re = $add(square_re, c_re);
+                            im = $add(square_im, c_im);
+                            let norm = $add($mul(re, re), $mul(im, im));
+                            let over = $mask($compare::<_CMP_GT_OQ>(norm, four));
+                            let now = over & !escaped;
+                            if now != 0 {
+                                for (lane, count) in counts.iter_mut().enumerate() {
+                                    if now >> lane & 1 == 1 {
+                                        *count = Some(i);
+                                    }
+                                }
+                                escaped |= now;
+                                if escaped == (1 << $lanes) - 1 {
+                                    return Break(());
+                                }
+                            }
+                        }
+                        Continue(((re, im), escaped, counts))
+                    });
+                }
+                groups * $lanes
+            }
+        }
+    }
+
+    in_groups!(in_groups_f64, f64, 4, _mm256_loadu_pd, _mm256_set1_pd,
+               _mm256_mul_pd, _mm256_sub_pd, _mm256_add_pd, _mm256_cmp_pd, _mm256_movemask_pd);
+    in_groups!(in_groups_f32, f32, 8, _mm256_loadu_ps, _mm256_set1_ps,
+               _mm256_mul_ps, _mm256_sub_ps, _mm256_add_ps, _mm256_cmp_ps, _mm256_movemask_ps);
+}
+
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use num::Complex;
+    use std::arch::aarch64::*;
+    use std::ops::ControlFlow::{Break, Continue};
+
+    macro_rules! in_groups {
+        ($name : ident, $real : ty, $lanes : expr, $bits : ty, $load : ident, $splat : ident,
+         $mul : ident, $sub : ident, $add : ident, $greater : ident, $store : ident) => {
+            #[target_feature(enable = "neon")]
+            pub unsafe fn $name(points : &[Complex<$real>], limit : u32,
+                                counts : &mut [Option<u32>])
+                -> usize
+            {
+                let four = $splat(4.0);
+                let groups = points.len() / $lanes;
+                let chunks = points.chunks_exact($lanes).zip(counts.chunks_exact_mut($lanes));
+                for (group, counts) in chunks {
+                    let (mut c_re, mut c_im) = ([0.0; $lanes], [0.0; $lanes]);
+                    for (lane, point) in group.iter().enumerate() {
+                        c_re[lane] = point.re;
+                        c_im[lane] = point.im;
+                    }
+                    let (c_re, c_im) = ($load(c_re.as_ptr()), $load(c_im.as_ptr()));
+                    let z = ($splat(0.0), $splat(0.0));
+                    counts.fill(None);
+                    let start = (z, [false; $lanes], counts);
+                    let _ = crate::in_stretches(0..limit, start, move |state, stretch| {
+                        let ((mut re, mut im), mut escaped, counts) = state;
+                        let mut over : [$bits; $lanes] = [0; $lanes];
+                        for i in stretch {
+                            let square_re = $sub($mul(re, re), $mul(im, im));
+                            let square_im = $add($mul(re, im), $mul(im, re));
+                            re = $add(square_re, c_re);
+                            im = $add(square_im, c_im);
+                            let norm = $add($mul(re, re), $mul(im, im));
+                            $store(over.as_mut_ptr(), $greater(norm, four));
+                            let lanes = counts.iter_mut().zip(&mut escaped).zip(&over);
+                            for ((count, escaped), &over) in lanes {
+                                if over != 0 && !*escaped {
+                                    *count = Some(i);
+                                    *escaped = true;
+                                }
+                            }
+                            if escaped == [true; $lanes] {
+                                return Break(());
+                            }
+                        }
+                        Continue(((re, im), escaped, counts))
+                    });
+                }
+                groups * $lanes
+            }
+        }
+    }
+
+    in_groups!(in_groups_f64, f64, 2, u64, vld1q_f64, vdupq_n_f64,
+               vmulq_f64, vsubq_f64, vaddq_f64, vcgtq_f64, vst1q_u64);
+    in_groups!(in_groups_f32, f32, 4, u32, vld1q_f32, vdupq_n_f32,
+               vmulq_f32, vsubq_f32, vaddq_f32, vcgtq_f32, vst1q_u32);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //A grid over the whole set and around it, with the points that sit on
+    //its edges (the cusp, the tip, the boundary circle) in among them
+    fn grid<F : Real>() -> Vec<Complex<F>> {
+        let mut points = (0..41)
+            .flat_map(|row| (0..61).map(move |column| Complex {
+                re : -2.25 + 3.0 * column as f64 / 60.0,
+                im : 1.25 - 2.5 * row as f64 / 40.0
+            }))
+            .collect::<Vec<_>>();
+        points.extend([Complex { re : 0.25, im : 0.0 }, Complex { re : -2.0, im : 0.0 },
+                       Complex { re : 2.0, im : 0.0 }, Complex { re : -0.75, im : 0.1 },
+                       Complex { re : 0.0, im : 1.0 }, Complex { re : 1e3, im : -1e3 }]);
+        points.into_iter().map(F::complex).collect()
+    }
+
+    fn one_at_a_time<F : Real>(points : &[Complex<F>], limit : u32) -> Vec<Option<u32>> {
+        points.iter().map(|&point| ScalarArithmetic::<F>::escape_time(point, limit)).collect()
+    }
+
+    fn rows_match_scalar<F : Real>(precision : Precision) {
+        let points = grid::<F>();
+        let lanes = lanes(precision);
+        for limit in [1, 2, 3, 64, 500] {
+            let expected = one_at_a_time(&points, limit);
+            //Every length up to a few groups, so that every leftover after
+            //the last whole group comes up, and the whole grid at once
+            let lengths = (0..=3 * lanes + 1).chain([points.len()]);
+            for length in lengths {
+                for start in (0..points.len() - length).step_by(97).chain([0]) {
+                    let row = &points[start..start + length];
+                    let mut counts = vec![Some(u32::MAX); length];
+                    escape_row(row, limit, &mut counts);
+                    assert!(counts == expected[start..start + length],
+                            "{} lanes, limit {}, {} points from {}", lanes, limit, length,
+                            start);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn f64_rows_match_the_scalar_loop() {
+        rows_match_scalar::<f64>(Precision::F64);
+    }
+
+    #[test]
+    fn f32_rows_match_the_scalar_loop() {
+        rows_match_scalar::<f32>(Precision::F32);
+    }
+
+    #[test]
+    fn whole_groups_go_through_the_lanes() {
+        let points = grid::<f64>();
+        let lanes = lanes(Precision::F64);
+        for length in 0..=3 * lanes + 1 {
+            let mut counts = vec![None; length];
+            let grouped = f64::in_groups(&points[..length], 255, &mut counts);
+            match lanes {
+                1 => assert_eq!(grouped, 0),
+                _ => assert_eq!(grouped, length - length % lanes)
+            }
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//<***************Timing statistics*****************>//
+//Where the time of a run goes, for tuning --threads and --schedule : how
+//long every band took (each piece of the window a thread was handed, a
+//single row with interleaved rows or on rayon's threads), and how long each
+//stage of the run took :
+//
+//    parse     the command line, the config and the view
+//    iterate   working out the escape counts, everything a render does but
+//              the colouring
+//    colorize  turning the counts into colours, where that is a pass of its
+//              own (plain gray is mapped pixel by pixel as it is iterated)
+//    encode    the PNGs
+//    write     writing them out
+//
+//A stage adds up over the run, every frame of an animation into the same
+//stages. With -v the numbers are printed at the end of the run, and with
+//--stats FILE written to FILE as a JSON object :
+//
+//    {"threads":8,"scheduler":"crossbeam","schedule":"bands",
+//     "stages_ms":{"parse":1.2,"iterate":830.5,"colorize":0,"encode":41.3,"write":0.4},
+//     "bands":{"count":32,"min_ms":2.1,"median_ms":18.7,"max_ms":96.3,
+//              "slowest":{"top":240,"rows":15,"ms":96.3}},
+//     "band_times":[{"top":0,"rows":15,"ms":2.1},...]}
+//
+//"band_times" are in the order the bands finished in, and "bands" has null
+//fields when nothing was rendered. Nothing is collected unless -v or
+//--stats asks for it, and then a band costs two Instant::now() and a push
+//under a lock, nothing per pixel.
+
+use crate::json::Json;
+
+use std::cell::Cell;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const STAGES : [&str; 5] = ["parse", "iterate", "colorize", "encode", "write"];
+
+static ENABLED : AtomicBool = AtomicBool::new(false);
+//(top row, rows, time) of every band
+static BANDS : Mutex<Vec<(usize, usize, Duration)>> = Mutex::new(Vec::new());
+static SPENT : Mutex<[Duration; 5]> = Mutex::new([Duration::ZERO; 5]);
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+//When a band was started, None if nothing is being collected
+pub fn band_started() -> Option<Instant> {
+    if enabled() { Some(Instant::now()) } else { None }
+}
+
+pub fn band_done(started : Option<Instant>, top : usize, rows : usize) {
+    if let Some(started) = started {
+        BANDS.lock().unwrap().push((top, rows, started.elapsed()));
+    }
+}
+
+//Adds `spent` to the stage `name`, one of STAGES
+pub fn add(name : &str, spent : Duration) {
+    if !enabled() {
+        return;
+    }
+    let index = STAGES.iter().position(|&stage| stage == name)
+        .expect("no such stage");
+    SPENT.lock().unwrap()[index] += spent;
+}
+
+//Adds the time since `started`
+pub fn stage(name : &str, started : Instant) {
+    add(name, started.elapsed());
+}
+
+//Runs `work` and adds the time it took to `name`
+pub fn time<T>(name : &str, work : impl FnOnce() -> T) -> T {
+    let started = Instant::now();
+    let result = work();
+    stage(name, started);
+    result
+}
+
+//What stage `name` has taken so far
+pub fn spent(name : &str) -> Duration {
+    STAGES.iter().position(|&stage| stage == name)
+        .map_or(Duration::ZERO, |index| SPENT.lock().unwrap()[index])
+}
+
+fn milliseconds(duration : Duration) -> Json {
+    Json::Number(duration.as_secs_f64() * 1000.0)
+}
+
+//The JSON object at the top of the file, `fields` (the threads and so on)
+//first
+pub fn to_json(fields : Vec<(&'static str, Json)>) -> Json {
+    let spent = *SPENT.lock().unwrap();
+    let bands = BANDS.lock().unwrap();
+    let mut times = bands.iter().map(|&(_, _, time)| time).collect::<Vec<_>>();
+    times.sort();
+    let slowest = bands.iter().max_by_key(|&&(_, _, time)| time);
+    let band = |&(top, rows, time) : &(usize, usize, Duration)| Json::Object(vec![
+        ("top", Json::Integer(top as u64)),
+        ("rows", Json::Integer(rows as u64)),
+        ("ms", milliseconds(time))
+    ]);
+    let timed = |time : Option<&Duration>| time.copied().map_or(Json::Null, milliseconds);
+
+    let mut object = fields;
+    object.push(("stages_ms", Json::Object(STAGES.iter().zip(spent)
+        .map(|(&name, spent)| (name, milliseconds(spent)))
+        .collect())));
+    object.push(("bands", Json::Object(vec![
+        ("count", Json::Integer(times.len() as u64)),
+        ("min_ms", timed(times.first())),
+        ("median_ms", timed(times.get(times.len() / 2))),
+        ("max_ms", timed(times.last())),
+        ("slowest", slowest.map_or(Json::Null, band))
+    ])));
+    object.push(("band_times", Json::Array(bands.iter().map(band).collect())));
+    Json::Object(object)
+}
+
+//The -v summary, a line for the bands and one for the stages
+pub fn lines() -> Vec<String> {
+    let spent = *SPENT.lock().unwrap();
+    let bands = BANDS.lock().unwrap();
+    let ms = |time : Duration| time.as_secs_f64() * 1000.0;
+    let mut times = bands.iter().map(|&(_, _, time)| time).collect::<Vec<_>>();
+    times.sort();
+    let bands_line = match bands.iter().max_by_key(|&&(_, _, time)| time) {
+        Some(&(top, rows, _)) => format!(
+            "Bands : {} of them, {:.1}ms min, {:.1}ms median, {:.1}ms max, the slowest \
+             rows {} to {}", times.len(), ms(times[0]), ms(times[times.len() / 2]),
+            ms(times[times.len() - 1]), top, top + rows),
+        None => "Bands : none rendered".to_string()
+    };
+    let stages = STAGES.iter().zip(spent)
+        .map(|(name, spent)| format!("{} {:.1}ms", name, ms(spent)))
+        .collect::<Vec<_>>();
+    vec![bands_line, format!("Stages : {}", stages.join(", "))]
+}
+
+//--stats FILE
+pub fn write(path : &Path, fields : Vec<(&'static str, Json)>) -> Result<(), String> {
+    std::fs::write(path, format!("{}\n", to_json(fields).to_json()))
+        .map_err(|error| format!("error writing {} : {}", path.display(), error))
+}
+
+//<***************Iteration counts*****************>//
+//For --report-iterations : how many times the escape-time loop went round
+//over the whole run, a pixel's count plus one for a point that escaped and
+//the limit for one that didn't. The renders only count when built around
+//Counted arithmetic, which adds to the thread's own ITERATED with no
+//atomics at all, and each finished band moves that into ITERATIONS.
+thread_local! {
+    static ITERATED : Cell<u64> = const { Cell::new(0) };
+}
+
+static ITERATIONS : AtomicU64 = AtomicU64::new(0);
+
+pub fn iterated(iterations : u64) {
+    ITERATED.with(|iterated| iterated.set(iterated.get() + iterations));
+}
+
+//At the end of a band, on the thread that rendered it
+pub fn band_iterated() {
+    let iterations = ITERATED.with(|iterated| iterated.replace(0));
+    if iterations > 0 {
+        ITERATIONS.fetch_add(iterations, Ordering::Relaxed);
+    }
+}
+
+pub fn iterations() -> u64 {
+    ITERATIONS.load(Ordering::Relaxed)
+}
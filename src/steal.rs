@@ -0,0 +1,189 @@
+//<***************Work stealing*****************>//
+//The scheduler the renders share their work out through. Every thread has
+//a deque of tasks of its own, which it takes from the front of and pushes
+//any work it finds along the way onto (a band that is split up, say); a
+//thread whose deque is empty steals from the back of another's. Threads
+//mostly keep to their own deques, rather than all of them going through
+//one shared queue, and a big task split up by one thread is shared out as
+//soon as the others run dry.
+//
+//The deques are crossbeam's Chase-Lev deques, the same crossbeam the
+//threads are spawned with.
+
+use crossbeam::sync::chase_lev::{self, Steal, Stealer, Worker};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+pub struct Stealing<T> {
+    //The deques no thread has taken in hand yet, with their place among
+    //`stealers`
+    workers : Mutex<Vec<(usize, Worker<T>)>>,
+    stealers : Vec<Stealer<T>>,
+    //Tasks pushed and not yet done, once it is 0 there is nothing left to
+    //steal or to be pushed
+    pending : AtomicUsize
+}
+
+//What a task gets to push more work with
+pub struct Local<'a, T> {
+    worker : &'a mut Worker<T>,
+    pending : &'a AtomicUsize
+}
+
+impl<'a, T> Local<'a, T> {
+    pub fn push(&mut self, task : T) {
+        self.pending.fetch_add(1, Ordering::SeqCst);
+        self.worker.push(task);
+    }
+}
+
+impl<T : Send> Stealing<T> {
+    //`tasks` shared out between `threads` deques in runs, the first tasks
+    //to the first deque and so on, so that one thread's tasks lie together
+    pub fn new(tasks : Vec<T>, threads : usize) -> Stealing<T> {
+        let threads = threads.max(1);
+        let (mut workers, stealers) : (Vec<_>, Vec<_>) = (0..threads)
+            .map(|_| chase_lev::deque())
+            .unzip();
+        let count = tasks.len();
+        //Pushed back to front, the front being where the worker takes from
+        for (i, task) in tasks.into_iter().enumerate().rev() {
+            workers[i * threads / count.max(1)].push(task);
+        }
+        Stealing {
+            workers : Mutex::new(workers.into_iter().enumerate().collect()),
+            stealers,
+            pending : AtomicUsize::new(count)
+        }
+    }
+
+    //How many threads there are deques for, each of them should call work()
+    pub fn threads(&self) -> usize {
+        self.stealers.len()
+    }
+
+    //Runs `work` on tasks until there are none left anywhere, for one of
+    //threads() threads. A task `work` pushes is done before the call returns.
+    pub fn work<F>(&self, mut work : F)
+        where F : FnMut(T, &mut Local<T>)
+    {
+        let (index, mut worker) = self.workers.lock().unwrap().pop()
+            .expect("more threads working than Stealing::new() was given");
+        loop {
+            let task = worker.try_pop().or_else(|| self.steal(index));
+            match task {
+                Some(task) => {
+                    work(task, &mut Local { worker : &mut worker, pending : &self.pending });
+                    self.pending.fetch_sub(1, Ordering::SeqCst);
+                }
+                None if self.pending.load(Ordering::SeqCst) == 0 => return,
+                //Another thread is still working on something that may yet
+                //push more
+                None => thread::yield_now()
+            }
+        }
+    }
+
+    //A task off the back of another thread's deque, trying each of them in
+    //turn starting with the one after `index`
+    fn steal(&self, index : usize) -> Option<T> {
+        let threads = self.stealers.len();
+        for offset in 1..threads {
+            let stealer = &self.stealers[(index + offset) % threads];
+            loop {
+                match stealer.steal() {
+                    Steal::Data(task) => return Some(task),
+                    Steal::Empty => break,
+                    //Lost a race for the last task, it may have more
+                    Steal::Abort => continue
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Runs `stealing` on all its threads at once, each task going through
+    //`work` on whichever thread gets to it
+    fn on_every_thread<T, F>(stealing : &Stealing<T>, work : F)
+        where T : Send, F : Fn(T, &mut Local<T>) + Sync
+    {
+        let work = &work;
+        crossbeam::scope(|spawner| {
+            for _ in 0..stealing.threads() {
+                spawner.spawn(move || stealing.work(work));
+            }
+        });
+    }
+
+    #[test]
+    fn every_task_is_taken_exactly_once() {
+        for tasks in 0..40 {
+            for threads in 1..=6 {
+                let taken = (0..tasks).map(|_| AtomicUsize::new(0)).collect::<Vec<_>>();
+                let stealing = Stealing::new((0..tasks).collect(), threads);
+                assert_eq!(stealing.threads(), threads);
+                on_every_thread(&stealing, |task : usize, _| {
+                    taken[task].fetch_add(1, Ordering::SeqCst);
+                });
+                let times = taken.iter().map(|count| count.load(Ordering::SeqCst))
+                    .collect::<Vec<_>>();
+                assert!(times.iter().all(|&times| times == 1),
+                        "{} tasks on {} threads taken {:?} times", tasks, threads, times);
+            }
+        }
+    }
+
+    #[test]
+    fn pushed_tasks_are_taken_exactly_once_before_work_returns() {
+        //Each task is a range of items, split in two until it is one item,
+        //the way the renders split their bands
+        const ITEMS : usize = 1000;
+        for threads in 1..=6 {
+            for _ in 0..5 {
+                let taken = (0..ITEMS).map(|_| AtomicUsize::new(0)).collect::<Vec<_>>();
+                let stealing = Stealing::new(vec![(0, ITEMS / 3), (ITEMS / 3, ITEMS)],
+                                             threads);
+                on_every_thread(&stealing, |(from, to), local| {
+                    if to - from > 1 {
+                        let middle = from + (to - from) / 2;
+                        local.push((from, middle));
+                        local.push((middle, to));
+                    } else {
+                        taken[from].fetch_add(1, Ordering::SeqCst);
+                    }
+                });
+                assert_eq!(stealing.pending.load(Ordering::SeqCst), 0);
+                let misses = (0..ITEMS)
+                    .filter(|&item| taken[item].load(Ordering::SeqCst) != 1)
+                    .collect::<Vec<_>>();
+                assert!(misses.is_empty(), "{} threads : items {:?} not taken once",
+                        threads, misses);
+            }
+        }
+    }
+
+    #[test]
+    fn no_thread_is_left_without_a_deque() {
+        //Fewer tasks than threads still gives every thread a deque to work
+        //from, an empty one
+        let stealing = Stealing::new(vec![1], 4);
+        assert_eq!(stealing.threads(), 4);
+        let sum = AtomicUsize::new(0);
+        on_every_thread(&stealing, |task, _| { sum.fetch_add(task, Ordering::SeqCst); });
+        assert_eq!(sum.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "more threads working")]
+    fn working_on_more_threads_than_deques_panics() {
+        let stealing = Stealing::new(vec![1, 2], 1);
+        stealing.work(|_, _| {});
+        stealing.work(|_, _| {});
+    }
+}
@@ -0,0 +1,166 @@
+//<***************Text in images*****************>//
+//A blocky 3x5 font, every dot of it `scale` pixels square, for the labels
+//of palette swatches and the captions of --title. Each character takes up
+//4 dots across (one of them the gap to the next) and 7 down with the space
+//between lines. Lowercase letters come out as capitals, anything the font
+//doesn't have as '?'.
+
+use crate::Image;
+
+use image::ColorType;
+
+//Printable ASCII from ' ' to '_', the rows of each character three bits
+//wide
+const FIRST : u8 = b' ';
+const GLYPHS : [[u8; 5]; 64] = [
+    [0b000, 0b000, 0b000, 0b000, 0b000], //' '
+    [0b010, 0b010, 0b010, 0b000, 0b010], //'!'
+    [0b101, 0b101, 0b000, 0b000, 0b000], //'"'
+    [0b101, 0b111, 0b101, 0b111, 0b101], //'#'
+    [0b011, 0b110, 0b010, 0b011, 0b110], //'$'
+    [0b101, 0b001, 0b010, 0b100, 0b101], //'%'
+    [0b010, 0b101, 0b010, 0b101, 0b011], //'&'
+    [0b010, 0b010, 0b000, 0b000, 0b000], //'\''
+    [0b001, 0b010, 0b010, 0b010, 0b001], //'('
+    [0b100, 0b010, 0b010, 0b010, 0b100], //')'
+    [0b000, 0b101, 0b010, 0b101, 0b000], //'*'
+    [0b000, 0b010, 0b111, 0b010, 0b000], //'+'
+    [0b000, 0b000, 0b000, 0b010, 0b100], //','
+    [0b000, 0b000, 0b111, 0b000, 0b000], //'-'
+    [0b000, 0b000, 0b000, 0b000, 0b010], //'.'
+    [0b001, 0b001, 0b010, 0b100, 0b100], //'/'
+    [0b111, 0b101, 0b101, 0b101, 0b111], //'0'
+    [0b010, 0b110, 0b010, 0b010, 0b111], //'1'
+    [0b111, 0b001, 0b111, 0b100, 0b111], //'2'
+    [0b111, 0b001, 0b111, 0b001, 0b111], //'3'
+    [0b101, 0b101, 0b111, 0b001, 0b001], //'4'
+    [0b111, 0b100, 0b111, 0b001, 0b111], //'5'
+    [0b111, 0b100, 0b111, 0b101, 0b111], //'6'
+    [0b111, 0b001, 0b001, 0b001, 0b001], //'7'
+    [0b111, 0b101, 0b111, 0b101, 0b111], //'8'
+    [0b111, 0b101, 0b111, 0b001, 0b111], //'9'
+    [0b000, 0b010, 0b000, 0b010, 0b000], //':'
+    [0b000, 0b010, 0b000, 0b010, 0b100], //';'
+    [0b001, 0b010, 0b100, 0b010, 0b001], //'<'
+    [0b000, 0b111, 0b000, 0b111, 0b000], //'='
+    [0b100, 0b010, 0b001, 0b010, 0b100], //'>'
+    [0b110, 0b001, 0b010, 0b000, 0b010], //'?'
+    [0b010, 0b101, 0b111, 0b100, 0b011], //'@'
+    [0b010, 0b101, 0b111, 0b101, 0b101], //'A'
+    [0b110, 0b101, 0b110, 0b101, 0b110], //'B'
+    [0b011, 0b100, 0b100, 0b100, 0b011], //'C'
+    [0b110, 0b101, 0b101, 0b101, 0b110], //'D'
+    [0b111, 0b100, 0b110, 0b100, 0b111], //'E'
+    [0b111, 0b100, 0b110, 0b100, 0b100], //'F'
+    [0b011, 0b100, 0b101, 0b101, 0b011], //'G'
+    [0b101, 0b101, 0b111, 0b101, 0b101], //'H'
+    [0b111, 0b010, 0b010, 0b010, 0b111], //'I'
+    [0b001, 0b001, 0b001, 0b101, 0b010], //'J'
+    [0b101, 0b101, 0b110, 0b101, 0b101], //'K'
+    [0b100, 0b100, 0b100, 0b100, 0b111], //'L'
+    [0b101, 0b111, 0b111, 0b101, 0b101], //'M'
+    [0b110, 0b101, 0b101, 0b101, 0b101], //'N'
+    [0b010, 0b101, 0b101, 0b101, 0b010], //'O'
+    [0b110, 0b101, 0b110, 0b100, 0b100], //'P'
+    [0b010, 0b101, 0b101, 0b110, 0b011], //'Q'
+    [0b110, 0b101, 0b110, 0b101, 0b101], //'R'
+    [0b011, 0b100, 0b010, 0b001, 0b110], //'S'
+    [0b111, 0b010, 0b010, 0b010, 0b010], //'T'
+    [0b101, 0b101, 0b101, 0b101, 0b111], //'U'
+    [0b101, 0b101, 0b101, 0b101, 0b010], //'V'
+    [0b101, 0b101, 0b111, 0b111, 0b101], //'W'
+    [0b101, 0b101, 0b010, 0b101, 0b101], //'X'
+    [0b101, 0b101, 0b010, 0b010, 0b010], //'Y'
+    [0b111, 0b001, 0b010, 0b100, 0b111], //'Z'
+    [0b011, 0b010, 0b010, 0b010, 0b011], //'['
+    [0b100, 0b100, 0b010, 0b001, 0b001], //'\\'
+    [0b110, 0b010, 0b010, 0b010, 0b110], //']'
+    [0b010, 0b101, 0b000, 0b000, 0b000], //'^'
+    [0b000, 0b000, 0b000, 0b000, 0b111]  //'_'
+];
+
+fn glyph(c : char) -> [u8; 5] {
+    let c = c.to_ascii_uppercase();
+    match c as u32 {
+        code @ 32..=95 => GLYPHS[(code as u8 - FIRST) as usize],
+        _ => GLYPHS[(b'?' - FIRST) as usize]
+    }
+}
+
+//How many pixels across `text` comes out
+pub fn width(text : &str, scale : usize) -> usize {
+    (text.chars().count() * 4 * scale).saturating_sub(scale)
+}
+
+//Draws `text` in `color` into the RGB `pixels` of an image `image_width`
+//across, with its top left corner at `at`
+pub fn draw(pixels : &mut [u8], image_width : usize, text : &str, at : (usize, usize),
+            scale : usize, color : [u8; 3])
+{
+    for (place, c) in text.chars().enumerate() {
+        for (y, bits) in glyph(c).iter().enumerate() {
+            for x in 0..3 {
+                if bits & (0b100 >> x) == 0 {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        let column = at.0 + (place * 4 + x) * scale + dx;
+                        let row = at.1 + y * scale + dy;
+                        let index = (row * image_width + column) * 3;
+                        pixels[index..index + 3].copy_from_slice(&color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+//<********--title*************************>//
+//The caption goes in rows added under the picture, which keeps the size it
+//was asked for. A line per piece of text, each centered, in black or white
+//whichever shows up better on `border`.
+pub struct Caption {
+    pub title : Option<String>,
+    pub coordinates : bool,
+    pub border : [u8; 3],
+    //The height of a capital in pixels, a multiple of the font's 5 dots,
+    //None for a 40th of the image's width
+    pub font_size : Option<usize>
+}
+
+//`image` with `lines` under it
+pub fn captioned(image : Image, lines : &[String], caption : &Caption) -> Image {
+    let (width, height) = image.bounds;
+    let scale = caption.font_size.map_or(width / 200, |size| size / 5).max(1);
+    let lines = lines.iter().map(|line| fitted(line, width, scale)).collect::<Vec<_>>();
+    let strip = lines.len() * 7 * scale + 3 * scale;
+
+    let mut pixels = match image.color {
+        ColorType::Gray(8) => image.pixels.iter().flat_map(|&gray| [gray; 3]).collect(),
+        _ => image.pixels
+    };
+    pixels.extend((0..width * strip).flat_map(|_| caption.border));
+    let [r, g, b] = caption.border;
+    let light = 0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64 > 127.5;
+    let ink = if light { [0, 0, 0] } else { [255, 255, 255] };
+    for (i, line) in lines.iter().enumerate() {
+        let left = (width - self::width(line, scale)) / 2;
+        draw(&mut pixels, width, line, (left, height + 2 * scale + i * 7 * scale), scale,
+             ink);
+    }
+    Image { pixels, bounds : (width, height + strip), color : ColorType::RGB(8) }
+}
+
+//`line` with as many characters taken off the end as it takes to fit in
+//`width` pixels with a margin either side
+fn fitted(line : &str, width : usize, scale : usize) -> String {
+    let room = width.saturating_sub(4 * scale);
+    if self::width(line, scale) <= room {
+        return line.to_string();
+    }
+    let fits = (room + scale) / (4 * scale);
+    warning!("'{}' doesn't fit across the image at --font-size {}, cutting it down to \
+              {} characters", line, scale * 5, fits);
+    line.chars().take(fits).collect()
+}
@@ -0,0 +1,155 @@
+//<***************A small subset of TOML*****************>//
+//Just enough TOML for --config files : `[section]` headers and
+//`key = value` lines, where a value is a string ("basic" or 'literal'),
+//a number or a boolean, with # starting a comment. `[[section]]` starts
+//the next table of an array of tables, as in a --jobs file. Arrays of
+//values, inline tables and multi-line strings aren't needed by anything the
+//options hold, so they are reported as errors like any other line that
+//doesn't parse.
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    String(String),
+    //Numbers are kept as written, the option they are for parses them
+    Number(String),
+    Boolean(bool)
+}
+
+//A `key = value` line, along with the section it appeared under
+#[derive(Clone)]
+pub struct Entry {
+    pub line : usize,
+    pub section : Option<String>,
+    //Under a [[section]] header, which of the tables of that name (from 0)
+    pub item : Option<usize>,
+    pub key : String,
+    pub value : Value
+}
+
+pub fn parse(text : &str) -> Result<Vec<Entry>, String> {
+    let mut entries = Vec::new();
+    let mut section = None;
+    let mut item = None;
+    //How many [[section]] tables there have been of each name
+    let mut items : Vec<(String, usize)> = Vec::new();
+    for (index, raw) in text.lines().enumerate() {
+        let line = index + 1;
+        let content = raw.trim();
+        if content.is_empty() || content.starts_with('#') {
+            continue;
+        }
+
+        if content.starts_with("[[") {
+            let header = strip_comment(content);
+            if !header.ends_with("]]") || header.len() < 5 {
+                return Err(format!("line {} : malformed table header '{}'",
+                                   line, content));
+            }
+            let name = header[2..header.len() - 2].trim().to_string();
+            let count = match items.iter_mut().find(|(seen, _)| *seen == name) {
+                Some((_, count)) => { *count += 1; *count }
+                None => { items.push((name.clone(), 0)); 0 }
+            };
+            section = Some(name);
+            item = Some(count);
+            continue;
+        }
+        if content.starts_with('[') {
+            let header = strip_comment(content);
+            if !header.ends_with(']') || header.len() < 3 {
+                return Err(format!("line {} : malformed section header '{}'",
+                                   line, content));
+            }
+            section = Some(header[1..header.len() - 1].trim().to_string());
+            item = None;
+            continue;
+        }
+
+        let equals = content.find('=')
+            .ok_or_else(|| format!("line {} : expected key = value, got '{}'",
+                                   line, content))?;
+        let key = content[..equals].trim();
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric()
+                                                  || c == '-' || c == '_') {
+            return Err(format!("line {} : malformed key '{}'", line, key));
+        }
+        let value = parse_value(content[equals + 1..].trim())
+            .map_err(|message| format!("line {} : {} : {}", line, key, message))?;
+        entries.push(Entry { line, section : section.clone(), item, key : key.to_string(),
+                             value });
+    }
+    Ok(entries)
+}
+
+fn parse_value(s : &str) -> Result<Value, String> {
+    if let Some(rest) = s.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = rest.chars();
+        loop {
+            match chars.next() {
+                None => return Err("unterminated string".to_string()),
+                Some('"') => break,
+                Some('\\') => value.push(match chars.next() {
+                    Some('"') => '"',
+                    Some('\\') => '\\',
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    other => return Err(format!("unsupported escape \\{}",
+                                                other.map(String::from)
+                                                     .unwrap_or_default()))
+                }),
+                Some(c) => value.push(c)
+            }
+        }
+        expect_end(chars.as_str())?;
+        return Ok(Value::String(value));
+    }
+    if let Some(rest) = s.strip_prefix('\'') {
+        let end = rest.find('\'').ok_or_else(|| "unterminated string".to_string())?;
+        expect_end(&rest[end + 1..])?;
+        return Ok(Value::String(rest[..end].to_string()));
+    }
+
+    let bare = strip_comment(s);
+    match bare {
+        "true" => Ok(Value::Boolean(true)),
+        "false" => Ok(Value::Boolean(false)),
+        "" => Err("missing value".to_string()),
+        _ if bare.chars().all(|c| c.is_ascii_digit() || "+-._eE".contains(c)) =>
+            Ok(Value::Number(bare.replace('_', ""))),
+        _ => Err(format!("expected a string, number or boolean, got '{}' \
+                          (strings need quotes)", bare))
+    }
+}
+
+//Only a comment may follow a value
+fn expect_end(rest : &str) -> Result<(), String> {
+    if strip_comment(rest).is_empty() {
+        Ok(())
+    } else {
+        Err(format!("unexpected '{}' after the value", rest.trim()))
+    }
+}
+
+fn strip_comment(s : &str) -> &str {
+    match s.find('#') {
+        Some(index) => s[..index].trim(),
+        None => s.trim()
+    }
+}
+
+//`s` as a basic string that parse() reads back unchanged
+pub fn quote(s : &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c)
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,258 @@
+//<***************The files a render writes alongside its image*****************>//
+//--progress-file and --stats, run through the program itself : both are
+//process wide, so the unit tests rendering next to each other would get in
+//their way. So are the presets, which read the user's file of them, and
+//the subcommands, their exit codes and the jobs as a whole.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+//A directory of its own for each test, under the temp directory
+fn scratch(test : &str) -> PathBuf {
+    let directory = std::env::temp_dir()
+        .join(format!("mandelbrot-files-{}-{}", std::process::id(), test));
+    let _ = std::fs::remove_dir_all(&directory);
+    std::fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+//The program, with the directory of a test as its config directory so that
+//none of the user's presets or bookmarks get in
+fn program(directory : &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_mandelbrot_plot"));
+    command.env("XDG_CONFIG_HOME", directory);
+    command
+}
+
+//Renders the whole set at 64x48 on 3 threads, so that there are a few bands,
+//with `args` on top
+fn render(directory : &Path, args : &[&str]) {
+    let view = ["--upper-left", "-2,1.2", "--lower-right", "1,-1.2", "--fit", "stretch"];
+    render_view(directory, &[&view, args].concat());
+}
+
+//Renders out.png at 64x48 on 3 threads, the view as `args` give it
+fn render_view(directory : &Path, args : &[&str]) {
+    let status = program(directory)
+        .args(["--size", "64x48", "--threads", "3", "--quiet", "--output"])
+        .arg(directory.join("out.png"))
+        .args(args)
+        .status()
+        .unwrap();
+    assert!(status.success(), "{}", status);
+}
+
+//The exit code of the program run with `args` in `directory`, and what it
+//printed on stderr
+fn exit_code(directory : &Path, args : &[&str]) -> (i32, String) {
+    let output = program(directory).current_dir(directory).args(args).output().unwrap();
+    (output.status.code().unwrap(), String::from_utf8_lossy(&output.stderr).into_owned())
+}
+
+//What the program prints on stdout with `args`, which have to succeed
+fn output(directory : &Path, args : &[&str]) -> String {
+    let output = program(directory).args(args).output().unwrap();
+    assert!(output.status.success(), "{:?} : {}", args,
+            String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+//The number after "key": in `text`, the first one
+fn number(text : &str, key : &str) -> f64 {
+    let at = text.find(&format!("\"{}\":", key)).unwrap_or_else(|| panic!("no {} in {}", key,
+                                                                           text));
+    let rest = &text[at + key.len() + 3..];
+    let end = rest.find([',', '}']).unwrap_or(rest.len());
+    rest[..end].parse().unwrap()
+}
+
+#[test]
+fn progress_json_reports_every_phase() {
+    let directory = scratch("progress");
+    let file = directory.join("progress.jsonl");
+    render(&directory, &["--progress", "json", "--progress-file", file.to_str().unwrap()]);
+    let text = std::fs::read_to_string(&file).unwrap();
+    //Every line an object with a phase
+    let phases = text.lines()
+        .map(|line| {
+            assert!(line.starts_with('{') && line.ends_with('}'), "{}", line);
+            let at = line.find("\"phase\":\"").unwrap_or_else(|| panic!("no phase : {}", line));
+            let phase = &line[at + 9..];
+            phase[..phase.find('"').unwrap()].to_string()
+        })
+        .collect::<Vec<_>>();
+    for phase in ["start", "encode", "write", "finish"] {
+        assert!(phases.iter().any(|seen| seen == phase), "no {} in {:?}", phase, phases);
+    }
+    assert_eq!(number(&text, "rows_total"), 48.0);
+    assert_eq!(number(&text, "rows_done"), 48.0);
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn stats_have_every_stage_and_band() {
+    let directory = scratch("stats");
+    let file = directory.join("stats.json");
+    render(&directory, &["--stats", file.to_str().unwrap()]);
+    let text = std::fs::read_to_string(&file).unwrap();
+    for key in ["threads", "scheduler", "schedule", "stages_ms", "parse", "iterate", "colorize",
+                "encode", "write", "bands", "count", "min_ms", "median_ms", "max_ms", "slowest",
+                "band_times"] {
+        assert!(text.contains(&format!("\"{}\":", key)), "no \"{}\" in {}", key, text);
+    }
+    assert_eq!(number(&text, "threads"), 3.0);
+    for stage in ["iterate", "encode"] {
+        assert!(number(&text, stage) > 0.0, "no time spent in {} : {}", stage, text);
+    }
+    //All the rows, in the bands the timings list
+    let bands = &text[text.find("\"band_times\":").unwrap()..];
+    let rows = bands.split("\"rows\":").skip(1)
+        .map(|rest| rest[..rest.find([',', '}']).unwrap()].parse::<usize>().unwrap())
+        .sum::<usize>();
+    assert_eq!(rows, 48, "{}", text);
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+//The plan --dry-run prints for `preset`, as its (key, value) lines
+fn preset_plan(directory : &Path, preset : &str) -> Vec<(String, String)> {
+    let out = directory.join("plan.png");
+    output(directory, &["--preset", preset, "--size", "64x48", "--dry-run", "--output",
+                        out.to_str().unwrap()])
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .map(|(key, value)| (key.to_string(), value.trim().to_string()))
+        .collect()
+}
+
+fn planned(plan : &[(String, String)], key : &str) -> String {
+    plan.iter().find(|(planned, _)| planned == key)
+        .unwrap_or_else(|| panic!("no {} in {:?}", key, plan)).1.clone()
+}
+
+#[test]
+fn every_builtin_preset_renders() {
+    let directory = scratch("presets");
+    let listed = output(&directory, &["--list-presets"]);
+    let names = listed.lines()
+        .map(|line| line.split_whitespace().next().unwrap())
+        .collect::<Vec<_>>();
+    assert!(names.len() >= 12 && names.contains(&"seahorse-valley"), "{}", listed);
+    for name in names {
+        let plan = preset_plan(&directory, name);
+        let line = listed.lines().find(|line| line.starts_with(name)).unwrap();
+        let iterations = planned(&plan, "limit").replace("kind fixed, ", "");
+        assert!(line.ends_with(&iterations), "{} : {:?}", line, plan);
+        render_view(&directory, &["--preset", name, "--force"]);
+        let png = std::fs::read(directory.join("out.png")).unwrap();
+        assert!(png.starts_with(b"\x89PNG"), "{} didn't render", name);
+    }
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn a_user_preset_replaces_the_builtin_one() {
+    let directory = scratch("user-presets");
+    let builtin = preset_plan(&directory, "full");
+    assert_eq!(planned(&builtin, "center"), "re -0.75, im 0");
+    assert_eq!(planned(&builtin, "width"), "3.5");
+
+    std::fs::create_dir_all(directory.join("mandelbrot")).unwrap();
+    std::fs::write(directory.join("mandelbrot").join("presets.toml"),
+                   "[full]\ncenter = \"-1,0.25\"\nwidth = 0.5\niterations = 100\n\n\
+                    [mine]\ncenter = \"0.3,0\"\nwidth = 0.1\n").unwrap();
+    let user = preset_plan(&directory, "full");
+    assert_eq!(planned(&user, "center"), "re -1, im 0.25");
+    assert_eq!(planned(&user, "width"), "0.5");
+    assert_eq!(planned(&user, "limit"), "kind fixed, iterations 100");
+    //Listed once, in the built-in's place, with the user's own after them
+    let listed = output(&directory, &["--list-presets"]);
+    let names = listed.lines()
+        .map(|line| line.split_whitespace().next().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(names.iter().filter(|&&name| name == "full").count(), 1);
+    assert_eq!((names[0], names[names.len() - 1]), ("full", "mine"));
+    render_view(&directory, &["--preset", "full"]);
+    assert!(directory.join("out.png").exists());
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn every_subcommand_runs() {
+    let directory = scratch("subcommands");
+    let run = |args : &[&str]| {
+        let (code, stderr) = exit_code(&directory, args);
+        assert_eq!(code, 0, "{:?} : {}", args, stderr);
+    };
+    let view = ["--size", "32x24", "--center", "-0.5,0", "--width", "3", "--quiet"];
+    run(&[&["render", "--output", "render.png"], &view[..]].concat());
+    run(&[&["animate", "--output", "frame_{}.png", "--frames", "3"], &view[..]].concat());
+    run(&["probe", "--point", "-0.5,0.5", "--quiet"]);
+    run(&["stitch", "--output", "stitched.png", "--columns", "2", "frame_0000.png",
+          "frame_0001.png", "--quiet"]);
+    run(&["verify-manifest", "manifest.txt"]);
+    for file in ["render.png", "frame_0002.png", "manifest.txt", "stitched.png"] {
+        assert!(directory.join(file).exists(), "no {}", file);
+    }
+    //Without a subcommand it is render
+    run(&[&["--output", "plain.png"], &view[..]].concat());
+    assert_eq!(std::fs::read(directory.join("plain.png")).unwrap(),
+               std::fs::read(directory.join("render.png")).unwrap());
+
+    let (code, stderr) = exit_code(&directory, &["frobnicate", "--size", "32x24"]);
+    assert_eq!(code, 2, "{}", stderr);
+    assert!(stderr.contains("unknown command 'frobnicate'"), "{}", stderr);
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn failures_exit_with_their_codes() {
+    let directory = scratch("exit-codes");
+    //2 for a command line that can't be read
+    for args in [&["--bogus"][..], &["--size", "big", "--output", "out.png"],
+                 &["render", "--iterations"]] {
+        let (code, stderr) = exit_code(&directory, args);
+        assert_eq!(code, 2, "{:?} : {}", args, stderr);
+    }
+    //3 for one that reads but asks for something that can't be rendered
+    let (code, stderr) = exit_code(&directory, &["out.png", "32x24", "-1,1", "-2,2"]);
+    assert_eq!(code, 3, "{}", stderr);
+    assert!(stderr.contains("--swap-corners"), "{}", stderr);
+    //4 for a file that can't be written, under what is a file rather than a
+    //directory (permissions alone don't stop root)
+    std::fs::write(directory.join("file"), "").unwrap();
+    let (code, stderr) = exit_code(&directory, &["--output", "file/out.png", "--size", "32x24",
+                                                 "--center", "-0.5,0", "--width", "3"]);
+    assert_eq!(code, 4, "{}", stderr);
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn force_goes_for_every_job_or_one() {
+    let directory = scratch("jobs-force");
+    let jobs = directory.join("jobs.toml");
+    let write_jobs = |force : &str| std::fs::write(&jobs, format!(
+        "[defaults]\nsize = \"32x24\"\ncenter = \"-0.5,0\"\nwidth = 3\n\n\
+         [[job]]\nname = \"a\"\noutput = \"a.png\"\n{}\n\
+         [[job]]\nname = \"b\"\noutput = \"b.png\"\n", force)).unwrap();
+    let run = |args : &[&str]| exit_code(&directory, &[&["--jobs", "jobs.toml"], args]
+                                         .concat());
+    write_jobs("");
+    assert_eq!(run(&[]).0, 0);
+    //Both are there now, and neither is written over without --force
+    let (code, stderr) = run(&[]);
+    assert_eq!(code, 3, "{}", stderr);
+    assert!(stderr.contains("2 of 2 jobs failed"), "{}", stderr);
+    let (code, stderr) = run(&["--force"]);
+    assert_eq!(code, 0, "{}", stderr);
+    assert_eq!(run(&["--skip-existing"]).0, 0);
+    //A job saying force on its own is written over, the other isn't
+    write_jobs("force = true\n");
+    let (code, stderr) = run(&[]);
+    assert_eq!(code, 3, "{}", stderr);
+    assert!(stderr.contains("1 of 2 jobs failed") && stderr.contains("b.png already exists"),
+            "{}", stderr);
+    //What the jobs say is still theirs alone
+    let (code, stderr) = run(&["--iterations", "10"]);
+    assert_eq!(code, 2, "{}", stderr);
+    let _ = std::fs::remove_dir_all(&directory);
+}